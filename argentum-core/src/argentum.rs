@@ -1,36 +1,675 @@
 //! Wrapper struct to conviniently abstract the inner workings.
 
-use crate::{bus::Bus, cpu::Cpu, joypad::ArgentumKey};
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, VecDeque},
+    vec::Vec,
+};
+use core::ops::RangeInclusive;
 
-/// T-cycles to execute per frame.
-const CYCLES_PER_FRAME: u32 = 70224;
+use crate::{
+    audio::{AudioCallback, AudioFormat},
+    audio_log::AudioLog,
+    bus::{Bus, InvalidBootRom},
+    cartridge::{
+        CartridgeInfo, EmptySlot, Mapper, MapperRegistry, RamError, Rom, UnsupportedCartridge,
+    },
+    cheats::CheatCode,
+    compat_palette::DmgPalettes,
+    cpu::{Cpu, CpuState},
+    debugger::{
+        self, BankedAddress, CpuRegisters, CpuUsage, Debugger, DisassembledInstruction, Flags,
+        HookAction, OpcodeHistogram, PcHook, RomBankStats, TraceCallback, TraceFilter,
+    },
+    determinism::{self, DeterminismMode},
+    event::ArgentumEvent,
+    hle_boot::HleBoot,
+    joypad::{ArgentumKey, MAX_PLAYERS},
+    peripheral::Peripheral,
+    ppu::{FrameInfo, IndexedImage, RawFrame, ScanlineRegisters},
+    ram_diff::{RamChange, RamDiff},
+    serial::LinkPartner,
+    state::{Snapshot, StateError, StateReader, StateWriter},
+    util::timed,
+};
 
+/// Events that aren't polled are dropped, oldest first, once there
+/// are this many. Keeps the queue from allocating while running.
+const MAX_EVENTS: usize = 16;
+
+#[cfg(feature = "perf-counters")]
+use crate::perf::PerfCounters;
+
+/// An emulated Game Boy.
+///
+/// Everything emulation needs is allocated when the instance is
+/// created, `execute_frame` and `step` don't allocate after that.
+/// Frontends with real-time constraints can rely on it as long as
+/// traces and the RAM diff are off and their hooks don't allocate.
+/// Methods that return owned data, like `save_state`, do allocate.
 pub struct Argentum {
     bus: Bus,
     cpu: Cpu,
+    debugger: Debugger,
+
+    /// Events that haven't been polled yet.
+    events: VecDeque<ArgentumEvent>,
+
+    /// Which host inputs may influence the emulated state.
+    determinism: DeterminismMode,
+
+    /// Callbacks run when the CPU reaches their address.
+    hooks: BTreeMap<BankedAddress, PcHook>,
+
+    /// How the CPU spent the current frame so far.
+    frame_cpu_usage: CpuUsage,
+
+    /// How the CPU spent the last complete frame.
+    last_cpu_usage: CpuUsage,
+
+    /// The boot sequence being shown instead of running a boot ROM.
+    hle_boot: Option<HleBoot>,
 }
 
 impl Argentum {
-    /// Create a new `Argentum` instance.
-    pub fn new(rom: &[u8], callback: Box<dyn Fn(&[f32])>, save_file: Option<Vec<u8>>) -> Self {
+    /// Create a new `Argentum` instance, with a copy of the ROM.
+    /// Fails if no mapper handles the cartridge type.
+    pub fn new(
+        rom: &[u8],
+        callback: AudioCallback,
+        save_file: Option<Vec<u8>>,
+    ) -> Result<Self, UnsupportedCartridge> {
+        Self::from_rom(Rom::from(rom), callback, save_file)
+    }
+
+    /// Create a new `Argentum` instance reading the ROM from
+    /// the given `Rom`, see `RomSource` for where it can come from.
+    pub fn from_rom(
+        rom: Rom,
+        callback: AudioCallback,
+        save_file: Option<Vec<u8>>,
+    ) -> Result<Self, UnsupportedCartridge> {
+        Self::with_registry(rom, callback, save_file, &MapperRegistry::default())
+    }
+
+    /// Create a new `Argentum` instance, picking the cartridge
+    /// mapper from the given registry.
+    pub fn with_registry(
+        rom: Rom,
+        callback: AudioCallback,
+        save_file: Option<Vec<u8>>,
+        registry: &MapperRegistry,
+    ) -> Result<Self, UnsupportedCartridge> {
+        let cartridge = registry
+            .create(rom, save_file)
+            .ok_or(UnsupportedCartridge)?;
+
+        Ok(Self::with_cartridge(cartridge, callback))
+    }
+
+    /// Create a new `Argentum` instance with nothing in the cartridge
+    /// slot. The bootrom scrolls down a logo made of the 0xFF bytes read
+    /// from the empty slot, fails the logo check and hangs like on
+    /// hardware. A cartridge can be put in with `insert_cartridge`.
+    pub fn without_cartridge(callback: AudioCallback) -> Self {
+        let mut argentum = Self::with_cartridge(Box::new(EmptySlot), callback);
+
+        argentum.set_header_check(true);
+        argentum
+    }
+
+    fn with_cartridge(cartridge: Box<dyn Mapper>, callback: AudioCallback) -> Self {
         Self {
-            bus: Bus::new(rom, callback, save_file),
+            bus: Bus::new(cartridge, callback),
             cpu: Cpu::new(),
+            debugger: Debugger::new(),
+            events: VecDeque::with_capacity(MAX_EVENTS),
+            determinism: DeterminismMode::Relaxed,
+            hooks: BTreeMap::new(),
+            frame_cpu_usage: CpuUsage::default(),
+            last_cpu_usage: CpuUsage::default(),
+            hle_boot: None,
         }
     }
 
-    /// Execute a frame's worth of instructions.
+    /// Execute instructions until the PPU completes a frame.
+    ///
+    /// Returns early if a breakpoint is hit, calling this again
+    /// resumes execution where it stopped.
     pub fn execute_frame(&mut self) {
-        let mut cycles = 0;
+        if self.hle_boot.is_some() {
+            self.execute_hle_boot_frame();
+
+            return;
+        }
+
+        while !self.bus.ppu.take_frame_complete() {
+            if self.debugger.is_active() && self.cpu.state == CpuState::Running {
+                if let Some(addr) = self.debugger.before_instruction(&self.cpu, &self.bus) {
+                    self.push_event(ArgentumEvent::BreakpointHit(addr));
+
+                    return;
+                }
+            }
+
+            self.execute_instruction();
+        }
+
+        self.last_cpu_usage = core::mem::take(&mut self.frame_cpu_usage);
+        self.bus.apply_cheats();
+
+        #[cfg(feature = "perf-counters")]
+        {
+            self.bus.perf.frames += 1;
+        }
+
+        self.bus.ppu.decay_vram_heat();
+    }
+
+    /// Execute a single instruction, ignoring breakpoints.
+    pub fn step(&mut self) {
+        // There are no instructions to step through while booting.
+        if self.hle_boot.take().is_some() {
+            self.skip_bootrom();
+        }
+
+        if self.debugger.is_active() && self.cpu.state == CpuState::Running {
+            self.debugger.before_instruction(&self.cpu, &self.bus);
+        }
+
+        self.execute_instruction();
+
+        // Stepping doesn't finish frames, the next call to
+        // `execute_frame` runs until the following one.
+        self.bus.ppu.take_frame_complete();
+    }
 
-        while cycles <= CYCLES_PER_FRAME {
-            cycles += self.cpu.execute_next(&mut self.bus);
+    /// Run the CPU `multiplier` times faster than the rest of the
+    /// system, which can get rid of slowdown in some games.
+    ///
+    /// This is not accurate and can break games that rely on
+    /// cycle timing. Only multipliers of 1, 2 and 4 are supported.
+    pub fn set_overclock(&mut self, multiplier: u8) {
+        assert!(
+            matches!(multiplier, 1 | 2 | 4),
+            "unsupported overclock multiplier"
+        );
+
+        self.bus.overclock = multiplier;
+    }
+
+    /// Check if the CPU is overclocked. Overclocked runs are not
+    /// accurate, and must not be used for anything that expects
+    /// to be reproduced on stock hardware, such as replays.
+    pub fn is_overclocked(&self) -> bool {
+        self.bus.overclock != 1
+    }
+
+    /// Execute the next instruction and report the CPU locking up.
+    fn execute_instruction(&mut self) {
+        if !self.hooks.is_empty() && self.cpu.state == CpuState::Running && self.run_hook() {
+            return;
+        }
+
+        if self.bus.ram_diff.is_some() {
+            let pc = self.resolve_address(self.cpu.reg.pc);
+
+            if let Some(ram_diff) = self.bus.ram_diff.as_mut() {
+                ram_diff.pc = pc;
+            }
+        }
+
+        let was_locked = self.cpu.state == CpuState::Locked;
+        let was_running = self.cpu.state == CpuState::Running;
+
+        let cycles = timed!(self.bus.perf.total, self.cpu.execute_next(&mut self.bus));
+
+        #[cfg(feature = "perf-counters")]
+        {
+            self.bus.perf.instructions += 1;
+        }
+
+        if was_running {
+            self.frame_cpu_usage.busy_cycles += cycles;
+        } else {
+            self.frame_cpu_usage.halted_cycles += cycles;
+        }
+
+        if !was_locked && self.cpu.state == CpuState::Locked {
+            // The illegal opcode was the last byte fetched.
+            let addr = self.cpu.reg.pc.wrapping_sub(1);
+
+            self.push_event(ArgentumEvent::CpuLocked {
+                opcode: self.bus.peek_byte(addr),
+                address: self.resolve_address(addr),
+            });
+        }
+
+        // Hang like the boot ROM does, the CPU is never handed the game.
+        if core::mem::take(&mut self.bus.boot_failed) {
+            self.cpu.state = CpuState::Locked;
+            self.push_event(ArgentumEvent::BootFailed);
+        }
+    }
+
+    /// Run the hook at PC, if there is one. Returns true
+    /// if the hook replaced the instruction at PC.
+    fn run_hook(&mut self) -> bool {
+        let addr = self.resolve_address(self.cpu.reg.pc);
+
+        // The hook is taken out while it runs, so it can borrow the instance.
+        let mut hook = match self.hooks.remove(&addr) {
+            Some(hook) => hook,
+            None => return false,
+        };
+
+        let action = hook(self);
+
+        self.hooks.entry(addr).or_insert(hook);
+
+        match action {
+            HookAction::Continue => false,
+
+            HookAction::Return => {
+                let sp = self.cpu.reg.sp;
+                let lo = self.bus.read_byte(sp, false);
+                let hi = self.bus.read_byte(sp.wrapping_add(1), false);
+
+                self.cpu.reg.sp = sp.wrapping_add(2);
+                self.cpu.reg.pc = u16::from_le_bytes([lo, hi]);
+
+                // The other components aren't ticked,
+                // the routine takes no time at all.
+                true
+            }
         }
     }
 
-    /// Get a reference to the framebuffer.
-    pub fn get_framebuffer(&self) -> &[u8] {
-        self.bus.ppu.front_framebuffer.as_ref()
+    /// Call `hook` whenever the CPU is about to execute the instruction
+    /// at the given address, replacing any hook already there.
+    ///
+    /// Useful to replace routines with Rust code or to instrument them.
+    /// Hooks run before the instruction, but after breakpoints.
+    pub fn add_pc_hook(&mut self, addr: BankedAddress, hook: PcHook) {
+        self.hooks.insert(addr, hook);
+    }
+
+    pub fn remove_pc_hook(&mut self, addr: BankedAddress) {
+        self.hooks.remove(&addr);
+    }
+
+    /// Take a snapshot of all RAM, and start recording
+    /// which instructions change it.
+    pub fn start_ram_diff(&mut self) {
+        self.bus.ram_diff = Some(RamDiff::new(self.bus.ram_contents()));
+    }
+
+    /// Get the RAM that changed since `start_ram_diff` was called,
+    /// or `None` if it wasn't. Recording goes on until stopped.
+    pub fn ram_diff(&self) -> Option<Vec<RamChange>> {
+        self.bus
+            .ram_diff
+            .as_ref()
+            .map(|ram_diff| ram_diff.report(&self.bus.ram_contents()))
+    }
+
+    /// Stop recording changes to RAM and discard the snapshot.
+    pub fn stop_ram_diff(&mut self) {
+        self.bus.ram_diff = None;
+    }
+
+    /// Start logging every write to the APU's registers, with the
+    /// T-cycle it happened on. The log starts with the current values
+    /// of the registers that can be read back.
+    pub fn start_audio_log(&mut self) {
+        let apu = &self.bus.apu;
+
+        self.bus.audio_log = Some(AudioLog::new(|addr| apu.read_byte(addr)));
+    }
+
+    /// Stop logging writes to the APU's registers, returning the log
+    /// or `None` if logging wasn't started.
+    pub fn stop_audio_log(&mut self) -> Option<AudioLog> {
+        self.bus.audio_log.take()
+    }
+
+    /// Compare the RAM in a save state of this game with the current
+    /// RAM, for moments that are hard to catch with `start_ram_diff`.
+    /// `before` is the value in the save state, the instructions that
+    /// changed a byte aren't known. The current state is kept.
+    pub fn ram_changes_since_state(&mut self, state: &[u8]) -> Result<Vec<RamChange>, StateError> {
+        let current = self.save_state();
+        let hle_boot = self.hle_boot.take();
+
+        let before = self.load_state(state).map(|()| self.bus.ram_contents());
+
+        self.rollback(&current);
+        self.hle_boot = hle_boot;
+
+        Ok(RamDiff::new(before?).report(&self.bus.ram_contents()))
+    }
+
+    /// Queue an event, dropping the oldest one if the queue is full.
+    fn push_event(&mut self, event: ArgentumEvent) {
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+
+        self.events.push_back(event);
+    }
+
+    /// Get the oldest event that hasn't been polled yet.
+    pub fn poll_event(&mut self) -> Option<ArgentumEvent> {
+        self.events.pop_front()
+    }
+
+    /// Stop execution when the CPU is about to execute
+    /// the instruction at the given address.
+    ///
+    /// `execute_frame` returns early and reports a
+    /// `ArgentumEvent::BreakpointHit` when this happens.
+    pub fn add_breakpoint(&mut self, addr: BankedAddress) {
+        self.debugger.add_breakpoint(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: BankedAddress) {
+        self.debugger.remove_breakpoint(addr);
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.debugger.clear_breakpoints();
+    }
+
+    /// Get a copy of the CPU's registers.
+    pub fn registers(&self) -> CpuRegisters {
+        CpuRegisters::capture(&self.cpu)
+    }
+
+    /// Overwrite the CPU's registers.
+    pub fn set_registers(&mut self, registers: CpuRegisters) {
+        registers.restore(&mut self.cpu);
+    }
+
+    /// Get the flag register.
+    pub fn flags(&self) -> Flags {
+        self.cpu.reg.f.bits().into()
+    }
+
+    /// Overwrite the flag register.
+    pub fn set_flags(&mut self, flags: Flags) {
+        let mut registers = self.registers();
+
+        registers.f = flags;
+        self.set_registers(registers);
+    }
+
+    /// Qualify an address with the bank that is currently mapped there.
+    pub fn resolve_address(&self, addr: u16) -> BankedAddress {
+        BankedAddress::new(self.bus.bank_at(addr), addr)
+    }
+
+    /// Disassemble `count` instructions starting at the given address.
+    pub fn disassemble(&self, mut addr: u16, count: usize) -> Vec<DisassembledInstruction> {
+        let mut instructions = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let instruction = debugger::disassemble(&self.bus, addr);
+
+            addr = addr.wrapping_add(instruction.bytes.len() as u16);
+            instructions.push(instruction);
+        }
+
+        instructions
+    }
+
+    /// Read a byte the way the CPU sees it, without any side effects.
+    pub fn peek_byte(&self, addr: u16) -> u8 {
+        self.bus.peek_byte(addr)
+    }
+
+    /// Write a byte the way the CPU would, without letting time pass.
+    pub fn poke_byte(&mut self, addr: u16, value: u8) {
+        self.bus.write_byte(addr, value, false);
+    }
+
+    /// Set a callback that receives a line of trace
+    /// for every executed instruction.
+    pub fn set_trace_callback(&mut self, callback: Option<TraceCallback>) {
+        self.debugger.set_trace_callback(callback);
+    }
+
+    /// Choose which instructions are traced, see `TraceFilter`.
+    pub fn set_trace_filter(&mut self, filter: TraceFilter) {
+        self.debugger.set_trace_filter(filter);
+    }
+
+    /// Enable or disable counting how often each instruction is executed.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.debugger.set_profiling(enabled);
+    }
+
+    /// Get the host time spent in every subsystem since the counters
+    /// were last reset, to find out what makes emulation slow.
+    #[cfg(feature = "perf-counters")]
+    pub fn perf_counters(&self) -> PerfCounters {
+        self.bus.perf
+    }
+
+    #[cfg(feature = "perf-counters")]
+    pub fn reset_perf_counters(&mut self) {
+        self.bus.perf = PerfCounters::default();
+    }
+
+    /// Get the collected profile, hottest instruction first.
+    pub fn profile_report(&self) -> Vec<(BankedAddress, u64)> {
+        self.debugger.profile_report()
+    }
+
+    /// Enable or disable counting how often each opcode is executed.
+    pub fn set_opcode_histogram(&mut self, enabled: bool) {
+        self.debugger.set_opcode_histogram(enabled);
+    }
+
+    /// Get the opcode counts, if counting is enabled.
+    pub fn opcode_histogram(&self) -> Option<&OpcodeHistogram> {
+        self.debugger.opcode_histogram()
+    }
+
+    /// Enable or disable counting reads per ROM bank, this discards
+    /// the collected counts. Shows which banks are hot, and which
+    /// ones the bank switching code never reaches.
+    pub fn set_rom_bank_stats(&mut self, enabled: bool) {
+        self.bus.rom_bank_stats = if enabled {
+            Some(RomBankStats::new())
+        } else {
+            None
+        };
+    }
+
+    /// Get the reads per ROM bank, if counting is enabled.
+    pub fn rom_bank_stats(&self) -> Option<&RomBankStats> {
+        self.bus.rom_bank_stats.as_ref()
+    }
+
+    /// Get how many cycles of the last complete frame the CPU spent
+    /// executing code and how many it spent halted. Games that halt
+    /// until VBlank show how much of the frame they need.
+    pub fn cpu_usage(&self) -> CpuUsage {
+        self.last_cpu_usage
+    }
+
+    /// Get a reference to the framebuffer, along with its layout.
+    ///
+    /// Frontends shouldn't assume the size of the LCD, the frame
+    /// can grow in the future, for example to fit SGB borders.
+    pub fn get_framebuffer(&self) -> (&[u8], FrameInfo) {
+        (self.bus.ppu.front_framebuffer(), self.bus.ppu.frame_info())
+    }
+
+    /// Get the index of the framebuffer holding the last complete
+    /// frame, 0 or 1. The PPU draws to the other one and swaps them
+    /// when a frame is completed, so a change means a new frame.
+    pub fn front_buffer_index(&self) -> usize {
+        self.bus.ppu.front_buffer
+    }
+
+    /// Leave applying the colours to the frontend, which can do it on
+    /// another thread while the next frame is emulated. While enabled
+    /// frames are only available through `raw_frame`, the frame of
+    /// `get_framebuffer` isn't updated anymore.
+    pub fn set_deferred_colours(&mut self, enabled: bool) {
+        self.bus.ppu.set_deferred_colours(enabled);
+    }
+
+    /// Get the last complete frame without the colours applied, see
+    /// `set_deferred_colours`. It has no pixels unless it's enabled.
+    pub fn raw_frame(&self) -> RawFrame {
+        self.bus.ppu.raw_frame()
+    }
+
+    /// Get the rendering registers every scanline of the last complete
+    /// frame was drawn with, top to bottom. Shows how a game changes
+    /// them mid-frame for raster effects.
+    pub fn scanline_registers(&self) -> &[ScanlineRegisters] {
+        &self.bus.ppu.front_scanline_log
+    }
+
+    /// Draw every tile in VRAM with the first background palette,
+    /// 16 tiles per row. Useful to rip graphics.
+    pub fn tile_sheet(&self) -> IndexedImage {
+        self.bus.ppu.tile_sheet()
+    }
+
+    /// Draw the whole background map with the active palettes.
+    pub fn bg_map(&self) -> IndexedImage {
+        self.bus.ppu.bg_map()
+    }
+
+    /// Enable or disable tracking which tiles are written to.
+    pub fn set_vram_heatmap(&mut self, enabled: bool) {
+        self.bus.ppu.set_vram_heatmap(enabled);
+    }
+
+    /// Get how recently every tile was written to, one value per tile
+    /// in the order of `tile_sheet`. 255 means the tile was written to
+    /// this frame, the value drops a bit every frame after that.
+    pub fn vram_heatmap(&self) -> Option<&[u8]> {
+        self.bus.ppu.vram_heatmap()
+    }
+
+    /// Reset the system as if it was power cycled.
+    ///
+    /// The cartridge stays inserted and keeps its RAM, debugger
+    /// settings, the overclock and the DMG palette are kept too.
+    pub fn reset(&mut self) {
+        self.bus.reset();
+        self.cpu = Cpu::new();
+        self.frame_cpu_usage = CpuUsage::default();
+        self.hle_boot = None;
+        self.events.clear();
+    }
+
+    /// Plug a link cable partner into the serial port, or unplug it
+    /// with `None`. Without a partner, transfers using the internal
+    /// clock receive 0xFF and ones using the external clock never
+    /// complete, so games don't mistake it for a connection.
+    pub fn set_link_partner(&mut self, partner: Option<Box<dyn LinkPartner>>) {
+        self.bus.serial.partner = partner;
+    }
+
+    /// Attach a device to the bus, which sees every access to the given
+    /// addresses. Peripherals attached to the same address see accesses
+    /// in the order they were attached. They stay attached across resets.
+    pub fn attach_peripheral(
+        &mut self,
+        addresses: RangeInclusive<u16>,
+        peripheral: Box<dyn Peripheral>,
+    ) {
+        self.bus.peripherals.push((addresses, peripheral));
+    }
+
+    /// Detach all the devices attached by `attach_peripheral`.
+    pub fn detach_peripherals(&mut self) {
+        self.bus.peripherals.clear();
+    }
+
+    /// Set the colours the four shades are drawn with in DMG mode,
+    /// as 0xRRGGBB values from lightest to darkest.
+    pub fn set_dmg_palette(&mut self, palette: [u32; 4]) {
+        self.bus.ppu.dmg_palettes = DmgPalettes::uniform(palette);
+    }
+
+    /// Set the colours of the background and both object
+    /// palettes separately, used in DMG mode.
+    pub fn set_dmg_palettes(&mut self, palettes: DmgPalettes) {
+        self.bus.ppu.dmg_palettes = palettes;
+    }
+
+    /// Get the palettes the CGB boot ROM would colour the inserted game
+    /// with, chosen by its title. See `DmgPalettes::for_buttons` for the
+    /// palettes that can be picked by hand.
+    pub fn cgb_compat_palettes(&self) -> DmgPalettes {
+        DmgPalettes::for_cartridge(self.bus.cartridge.as_ref())
+    }
+
+    /// Replace the enabled cheat codes. Game Genie codes apply to
+    /// every ROM read, GameShark codes are written after every frame.
+    pub fn set_cheats(&mut self, cheats: &[CheatCode]) {
+        self.bus.cheats = cheats.to_vec();
+    }
+
+    /// Read the cartridge header, and check it the
+    /// way the boot ROM of a real Game Boy does.
+    pub fn cartridge_info(&self) -> CartridgeInfo {
+        CartridgeInfo::read(self.bus.cartridge.as_ref())
+    }
+
+    /// Set whether the boot ROM refuses to start games with a bad
+    /// logo or header checksum, as on a real Game Boy. Off by default,
+    /// so bad dumps and homebrew without the logo still run. Doesn't
+    /// apply when the boot ROM is skipped or replaced by `hle_boot`.
+    pub fn set_header_check(&mut self, enabled: bool) {
+        self.bus.check_header = enabled;
+    }
+
+    /// Run a dump of a real boot ROM instead of the built in one, it is
+    /// kept across resets. The boot ROM has to be the one of the system
+    /// being emulated, 256 bytes for the DMG and 2304 for the CGB.
+    pub fn set_boot_rom(&mut self, boot_rom: &[u8]) -> Result<(), InvalidBootRom> {
+        let expected = self.bus.boot_rom_size();
+
+        if boot_rom.len() != expected {
+            return Err(InvalidBootRom {
+                expected,
+                actual: boot_rom.len(),
+            });
+        }
+
+        self.bus.boot_rom = Some(boot_rom.to_vec());
+
+        Ok(())
+    }
+
+    /// Show the boot sequence without running a boot ROM, the
+    /// logo from the cartridge header scrolls down and the chime
+    /// plays. The game starts as if `skip_bootrom` was called.
+    pub fn hle_boot(&mut self) {
+        self.hle_boot = Some(HleBoot::new(&mut self.bus));
+    }
+
+    /// Run the system for a frame of the boot sequence,
+    /// starting the game once it's over.
+    fn execute_hle_boot_frame(&mut self) {
+        while !self.bus.ppu.take_frame_complete() {
+            self.bus.tick();
+        }
+
+        if let Some(boot) = self.hle_boot.as_mut() {
+            if boot.next_frame(&mut self.bus) {
+                self.hle_boot = None;
+                self.skip_bootrom();
+            }
+        }
     }
 
     pub fn skip_bootrom(&mut self) {
@@ -38,22 +677,328 @@ impl Argentum {
         self.bus.skip_bootrom();
     }
 
+    /// Set how many audio samples are generated per second, 48000 by
+    /// default. This has to match the rate the frontend plays them at.
+    pub fn set_audio_sample_rate(&mut self, rate: u32) {
+        self.bus.apu.set_sample_rate(rate);
+    }
+
+    /// Set the format the audio callback gets samples in,
+    /// `AudioFormat::F32` by default.
+    pub fn set_audio_format(&mut self, format: AudioFormat) {
+        self.bus.apu.set_format(format);
+    }
+
+    /// Generate `ratio` times as many audio samples as usual.
+    ///
+    /// Frontends can use this to keep their audio queue from
+    /// running dry or growing, see `argentum_frontend_utils::RateControl`.
+    /// Ignored in `DeterminismMode::Strict`.
+    pub fn set_audio_rate_ratio(&mut self, ratio: f64) {
+        if self.determinism == DeterminismMode::Relaxed {
+            self.bus.apu.set_sample_rate_ratio(ratio);
+        }
+    }
+
+    /// Set which host inputs may influence the emulated state.
+    pub fn set_determinism_mode(&mut self, mode: DeterminismMode) {
+        if mode == DeterminismMode::Strict {
+            self.bus.apu.set_sample_rate_ratio(1.0);
+        }
+
+        self.determinism = mode;
+    }
+
+    pub fn determinism_mode(&self) -> DeterminismMode {
+        self.determinism
+    }
+
+    /// Hash the state of the whole system, two instances in the
+    /// same state have the same hash. Useful to detect desyncs.
+    pub fn state_hash(&self) -> u64 {
+        determinism::hash_state(&self.save_state())
+    }
+
+    /// Hash the last complete frame, two instances showing the
+    /// same picture with the same palette have the same hash.
+    pub fn frame_hash(&self) -> u64 {
+        determinism::hash_state(self.bus.ppu.front_framebuffer())
+    }
+
     /// Redirects to joypad interface.
     pub fn key_down(&mut self, key: ArgentumKey) {
-        self.bus.joypad.key_down(key);
+        self.bus.joypad.key_down(0, key);
     }
 
     /// Redirects to joypad interface.
     pub fn key_up(&mut self, key: ArgentumKey) {
-        self.bus.joypad.key_up(key);
+        self.bus.joypad.key_up(0, key);
+    }
+
+    /// Press a key on the controller of the given player, from 0 to 3.
+    ///
+    /// Only the first player's controller is read by games, the
+    /// others are there for SGB multiplayer.
+    pub fn key_down_player(&mut self, player: u8, key: ArgentumKey) {
+        assert!((player as usize) < MAX_PLAYERS, "invalid player index");
+
+        self.bus.joypad.key_down(player as usize, key);
+    }
+
+    /// Release a key on the controller of the given player, from 0 to 3.
+    pub fn key_up_player(&mut self, player: u8, key: ArgentumKey) {
+        assert!((player as usize) < MAX_PLAYERS, "invalid player index");
+
+        self.bus.joypad.key_up(player as usize, key);
+    }
+
+    /// Set which keys are held on the first player's controller
+    /// all at once, as `ArgentumKey` values OR'd together.
+    ///
+    /// Useful to apply recorded input before every frame, keys
+    /// missing from `keys` are released.
+    pub fn set_keys(&mut self, keys: u8) {
+        self.bus.joypad.set_keys(0, keys);
+    }
+
+    /// Get the keys held on the first player's controller,
+    /// as `ArgentumKey` values OR'd together.
+    pub fn keys(&self) -> u8 {
+        self.bus.joypad.keys(0)
+    }
+
+    /// Serialize the state of the whole system.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+
+        w.write_header();
+        self.save_components(&mut w);
+
+        w.finish()
+    }
+
+    /// Save the state of the whole system into a buffer, replacing
+    /// what it held. Once the buffer has grown to the size of a
+    /// state, saving doesn't allocate it again, which makes this
+    /// cheap enough to call every frame, for run-ahead or rewinding.
+    pub fn save_state_into(&self, buffer: &mut Vec<u8>) {
+        let mut w = StateWriter::with_buffer(core::mem::take(buffer));
+
+        w.write_header();
+        self.save_components(&mut w);
+
+        *buffer = w.finish();
+    }
+
+    /// Take a snapshot of the whole system, replacing the one
+    /// `snapshot` held. Once a snapshot has been taken into it,
+    /// taking another doesn't allocate, so rewinding and run-ahead
+    /// can take one every frame.
+    pub fn take_snapshot(&self, snapshot: &mut Snapshot) {
+        let mut w = StateWriter::with_buffer(core::mem::take(&mut snapshot.buffer));
+
+        self.save_components(&mut w);
+
+        snapshot.buffer = w.finish();
+    }
+
+    /// Go back to a snapshot this system took with `take_snapshot`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if nothing was taken into the snapshot,
+    /// or it was taken by a system running another game.
+    pub fn restore_snapshot(&mut self, snapshot: &Snapshot) {
+        self.load_components(&mut StateReader::new(&snapshot.buffer))
+            .expect("failed to restore a snapshot");
+    }
+
+    /// Go back to a state this system saved earlier with `save_state`
+    /// or `save_state_into`. Unlike `load_state` no backup is made
+    /// first, which makes this the cheap way to undo frames.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the state is invalid, a state from another
+    /// source should be loaded with `load_state` instead.
+    pub fn rollback(&mut self, state: &[u8]) {
+        self.apply_state(state)
+            .expect("failed to roll back to a saved state");
+    }
+
+    /// Restore the state of the whole system from a buffer
+    /// created by `save_state`. The system is left untouched
+    /// if the state can't be loaded.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let backup = self.save_state();
+
+        self.apply_state(state).inspect_err(|_| {
+            self.apply_state(&backup)
+                .expect("failed to restore state after a failed load");
+        })
+    }
+
+    fn apply_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut r = StateReader::new(state);
+
+        r.read_header()?;
+
+        self.load_components(&mut r)
+    }
+
+    /// Serialize the components, the part save states and snapshots share.
+    fn save_components(&self, w: &mut StateWriter) {
+        self.cpu.save_state(w);
+        self.bus.save_state(w);
+    }
+
+    fn load_components(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        // States saved during the boot sequence
+        // continue with the boot ROM instead.
+        self.hle_boot = None;
+
+        self.cpu.load_state(r)?;
+        self.bus.load_state(r)
+    }
+
+    /// Pull the cartridge out while the system keeps running, reads from
+    /// the empty slot return 0xFF. Returns the cartridge's RAM, so the
+    /// game can be saved.
+    pub fn eject_cartridge(&mut self) -> Option<Vec<u8>> {
+        let cartridge = core::mem::replace(&mut self.bus.cartridge, Box::new(EmptySlot));
+
+        cartridge.dump_ram()
+    }
+
+    /// Put a cartridge into the slot while the system keeps running,
+    /// the way games were swapped for the classic cart swap tricks.
+    /// The system stays in DMG or CGB mode, whatever the new game wants.
+    pub fn insert_cartridge(
+        &mut self,
+        rom: &[u8],
+        save_file: Option<Vec<u8>>,
+    ) -> Result<(), UnsupportedCartridge> {
+        self.bus.cartridge = MapperRegistry::default()
+            .create(Rom::from(rom), save_file)
+            .ok_or(UnsupportedCartridge)?;
+
+        Ok(())
+    }
+
+    /// Swap the cartridge for another one, see `eject_cartridge`
+    /// and `insert_cartridge`. Returns the RAM of the old cartridge.
+    /// If the new ROM isn't supported the old cartridge stays in.
+    pub fn swap_cartridge(
+        &mut self,
+        rom: &[u8],
+        save_file: Option<Vec<u8>>,
+    ) -> Result<Option<Vec<u8>>, UnsupportedCartridge> {
+        let cartridge = MapperRegistry::default()
+            .create(Rom::from(rom), save_file)
+            .ok_or(UnsupportedCartridge)?;
+
+        let old_cartridge = core::mem::replace(&mut self.bus.cartridge, cartridge);
+
+        Ok(old_cartridge.dump_ram())
+    }
+
+    /// Get a copy of the cartridge RAM, even if it
+    /// isn't battery backed. Useful for save editing.
+    pub fn export_ram(&self) -> Option<Vec<u8>> {
+        self.bus.cartridge.dump_ram()
+    }
+
+    /// Overwrite the cartridge RAM with a dump created by `export_ram`,
+    /// or a save editor. The dump must be exactly as large as the RAM.
+    pub fn import_ram(&mut self, ram: &[u8]) -> Result<(), RamError> {
+        let expected = self.bus.cartridge.dump_ram().ok_or(RamError::NoRam)?.len();
+
+        if ram.len() != expected {
+            return Err(RamError::SizeMismatch {
+                expected,
+                actual: ram.len(),
+            });
+        }
+
+        if self.bus.cartridge.load_ram(ram) {
+            Ok(())
+        } else {
+            Err(RamError::NoRam)
+        }
     }
 
     /// Dump the SRAM and get a copy.
     pub fn get_ram_dump(&self) -> Option<Vec<u8>> {
-        if !([0x03, 0x0F, 0x10, 0x13, 0x1B, 0x1E].contains(&self.bus.cartridge.read_byte(0x0147))) {
+        if !([0x03, 0x06, 0x09, 0x0F, 0x10, 0x13, 0x1B, 0x1E]
+            .contains(&self.bus.cartridge.read_rom(0x0147)))
+        {
             return None;
         }
 
         self.bus.cartridge.dump_ram()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rom;
+
+    /// An endless loop, JR -2.
+    const PROGRAM: [u8; 2] = [0x18, 0xFE];
+
+    #[test]
+    fn corrupt_states_roll_back() {
+        let mut gb =
+            Argentum::new(&test_rom::with_program(&PROGRAM), Box::new(|_| {}), None).unwrap();
+
+        gb.skip_bootrom();
+
+        let mut state = gb.save_state();
+
+        gb.execute_frame();
+
+        let current = gb.save_state();
+
+        // The WRAM bank comes after the header, the CPU, both
+        // RAMs and the IF, IE and boot ROM registers.
+        let mut cpu = StateWriter::new();
+        gb.cpu.save_state(&mut cpu);
+
+        let wram_bank = 6 + cpu.finish().len() + 0x8000 + 0x7F + 3;
+
+        assert_eq!(state[wram_bank], 1);
+
+        for bank in [0, 8] {
+            state[wram_bank] = bank;
+
+            assert_eq!(gb.load_state(&state), Err(StateError::OutOfRange));
+            assert_eq!(gb.save_state(), current);
+        }
+    }
+
+    #[test]
+    fn states_of_another_cartridge_roll_back() {
+        let rom = test_rom::with_program(&PROGRAM);
+
+        // MBC1 with 8 KB of RAM and a battery.
+        let mut mbc1_rom = rom.clone();
+        mbc1_rom[0x0147] = 0x03;
+        mbc1_rom[0x0149] = 0x02;
+
+        let mut gb = Argentum::new(&rom, Box::new(|_| {}), None).unwrap();
+        let mut mbc1 = Argentum::new(&mbc1_rom, Box::new(|_| {}), None).unwrap();
+
+        gb.skip_bootrom();
+        mbc1.skip_bootrom();
+        mbc1.execute_frame();
+
+        let current = mbc1.save_state();
+
+        assert_eq!(
+            mbc1.load_state(&gb.save_state()),
+            Err(StateError::CartridgeMismatch)
+        );
+        assert_eq!(mbc1.save_state(), current);
+    }
+}
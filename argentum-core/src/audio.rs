@@ -1,10 +1,47 @@
+use crate::state::{StateError, StateReader, StateWriter};
+use alloc::boxed::Box;
+
 /// The rate at which samples are consumed by the audio
-/// driver.
+/// driver, unless the frontend sets another one.
 pub const SAMPLE_RATE: usize = 48000;
 
 /// The size of the audio sample buffer.
 pub const BUFFER_SIZE: usize = 1024;
 
+/// A mixed sample, computed with integer math with the `fixed-point`
+/// feature, for targets without an FPU.
+#[cfg(not(feature = "fixed-point"))]
+type Sample = f32;
+
+#[cfg(feature = "fixed-point")]
+type Sample = i16;
+
+/// The formats the APU can hand samples to the audio callback in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioFormat {
+    /// Interleaved stereo 32-bit floats, the default.
+    F32,
+
+    /// Interleaved stereo 16-bit integers.
+    I16,
+
+    /// 32-bit floats in separate buffers for the left and right side.
+    Planar,
+}
+
+/// A full buffer of samples, in the format set with
+/// `Argentum::set_audio_format`. Interleaved samples
+/// start with the left side.
+#[derive(Clone, Copy, Debug)]
+pub enum AudioSamples<'a> {
+    F32(&'a [f32]),
+    I16(&'a [i16]),
+    Planar { left: &'a [f32], right: &'a [f32] },
+}
+
+/// Called with every full buffer of samples.
+pub type AudioCallback = Box<dyn Fn(AudioSamples<'_>)>;
+
 /// The rate at which the CPU is ticked.
 pub const CPU_CLOCK: usize = 4194304;
 
@@ -16,21 +53,132 @@ const WAVE_DUTY: [[u8; 8]; 4] = [
     [0, 1, 1, 1, 1, 1, 1, 0], // 75%
 ];
 
+/// Get the volume of a playing channel after a write to its NRx2,
+/// "zombie mode". Some music engines rely on it to change the volume
+/// without retriggering, which would restart the note.
+///
+/// This is what most DMG and CGB revisions do, the exact behaviour
+/// differs between them.
+fn zombie_volume(volume: u8, period: u8, is_incrementing: bool, stopped: bool, value: u8) -> u8 {
+    let mut volume = volume;
+
+    if period == 0 && !stopped {
+        volume += 1;
+    } else if !is_incrementing {
+        volume += 2;
+    }
+
+    // Switching direction mirrors the volume.
+    if is_incrementing != ((value & 0x08) != 0) {
+        volume = 16u8.wrapping_sub(volume);
+    }
+
+    volume & 0x0F
+}
+
+/// Update a length counter for a write to NRx4, `max` being the length
+/// the channel reloads with, 64 or 256. Returns the new counter and
+/// whether the write expired it, which disables the channel.
+///
+/// If the frame sequencer's next step doesn't clock the length counters,
+/// enabling the length counter clocks it once right away, and triggering
+/// with an expired counter reloads it with `max - 1` if it's enabled.
+fn write_length(
+    counter: u16,
+    max: u16,
+    was_enabled: bool,
+    value: u8,
+    extra_clock: bool,
+) -> (u16, bool) {
+    let enabled = (value & 0x40) != 0;
+    let trigger = (value & 0x80) != 0;
+
+    let mut counter = counter;
+    let mut expired = false;
+
+    if extra_clock && !was_enabled && enabled && counter > 0 {
+        counter -= 1;
+
+        // Triggering reloads the counter right after.
+        expired = counter == 0 && !trigger;
+    }
+
+    if trigger && counter == 0 {
+        counter = if extra_clock && enabled { max - 1 } else { max };
+    }
+
+    (counter, expired)
+}
+
+/// Step the noise channel's LFSR, in 7-bit mode if `short` is set.
+///
+/// The XNOR of the lowest two bits is shifted in at bit 14, and in
+/// 7-bit mode at bit 6 too. Both widths share the register, so switching
+/// mid-run carries on from whatever the lower bits hold. If the lower
+/// 7 bits are all ones when switching to 7-bit mode, the XNOR keeps
+/// shifting ones in and the channel locks up until the width changes
+/// back or it's triggered.
+fn step_lfsr(lfsr: u16, short: bool) -> u16 {
+    let result = !(lfsr ^ (lfsr >> 1)) & 0b01;
+    let mut lfsr = (lfsr >> 1) | (result << 14);
+
+    if short {
+        lfsr = (lfsr & !(1 << 6)) | (result << 6);
+    }
+
+    lfsr
+}
+
+/// Convert a mixed sample to a 32-bit float.
+#[cfg(not(feature = "fixed-point"))]
+fn to_f32(sample: Sample) -> f32 {
+    sample
+}
+
+/// Convert a mixed sample to a 32-bit float.
+#[cfg(feature = "fixed-point")]
+fn to_f32(sample: Sample) -> f32 {
+    sample as f32 / 32768.0
+}
+
+/// Convert a mixed sample to a 16-bit integer.
+#[cfg(not(feature = "fixed-point"))]
+fn to_i16(sample: Sample) -> i16 {
+    (sample * i16::MAX as f32) as i16
+}
+
+/// Convert a mixed sample to a 16-bit integer.
+#[cfg(feature = "fixed-point")]
+fn to_i16(sample: Sample) -> i16 {
+    sample
+}
+
 pub trait Channel {
     /// Read a byte from the specified address.
     fn read_byte(&self, addr: u16) -> u8;
 
-    /// Write a byte to the specified address.
+    /// Write a byte to the specified address, other than NRx4.
     fn write_byte(&mut self, addr: u16, value: u8);
 
+    /// Write to NRx4, `extra_length_clock` tells if the frame
+    /// sequencer's next step doesn't clock the length counter.
+    fn write_control(&mut self, value: u8, extra_length_clock: bool);
+
     /// Tick the channel by 4 T-cycles.
     fn tick_channel(&mut self);
 
-    /// Get the current amplitude of the channel.
-    fn get_amplitude(&self) -> f32;
+    /// Get the output of the channel between 0 and 15,
+    /// or `None` while it's silent.
+    fn get_output(&self) -> Option<u8>;
 
     /// Step the length timer of the channel.
     fn step_length(&mut self);
+
+    /// Serialize the channel's state.
+    fn save_state(&self, w: &mut StateWriter);
+
+    /// Restore the channel's state.
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError>;
 }
 
 pub struct Apu {
@@ -58,20 +206,31 @@ pub struct Apu {
     /// Implementation of the noise wave channel.
     channel_four: ChannelFour,
 
-    /// Used to clock FS and sample generation.
-    sample_clock: u32,
+    /// T-cycles since the last sample was generated.
+    sample_clock: f64,
 
-    /// The audio buffer which contains 32-bit float samples.
-    pub buffer: Box<[f32; BUFFER_SIZE]>,
+    /// The samples generated per second, `SAMPLE_RATE` by default.
+    sample_rate: f64,
 
-    /// The position we are currently in the audio buffer.
-    pub buffer_position: usize,
+    /// T-cycles between two samples. This is fractional so that
+    /// exactly `sample_rate` samples are generated per second,
+    /// frontends can nudge it to keep their audio queue filled.
+    sample_period: f64,
+
+    /// The format samples are handed to the callback in.
+    format: AudioFormat,
 
-    /// Indicates if the sample buffer is full.
-    pub is_buffer_full: bool,
+    /// The audio buffer for the 32-bit float formats.
+    buffer: Box<[f32; BUFFER_SIZE]>,
+
+    /// The audio buffer for `AudioFormat::I16`.
+    buffer_i16: Box<[i16; BUFFER_SIZE]>,
+
+    /// The position we are currently in the audio buffer.
+    buffer_position: usize,
 
     /// Audio callback which is called when the sample buffer is full.
-    callback: Box<dyn Fn(&[f32])>,
+    callback: AudioCallback,
 
     /// The position the FS is currently in.
     frame_sequencer_position: u8,
@@ -85,7 +244,7 @@ pub struct Apu {
 
 impl Apu {
     /// Create a new `Apu` instance.
-    pub fn new(callback: Box<dyn Fn(&[f32])>) -> Self {
+    pub fn new(callback: AudioCallback, cgb_mode: bool) -> Self {
         Self {
             left_volume: 0,
             right_volume: 0,
@@ -93,12 +252,18 @@ impl Apu {
             apu_enabled: false,
             channel_one: ChannelOne::default(),
             channel_two: ChannelTwo::default(),
-            channel_three: ChannelThree::default(),
+            channel_three: ChannelThree {
+                cgb_mode,
+                ..ChannelThree::default()
+            },
             channel_four: ChannelFour::default(),
-            sample_clock: 0,
-            buffer: Box::new([0.0; 1024]),
+            sample_clock: 0.0,
+            sample_rate: SAMPLE_RATE as f64,
+            sample_period: CPU_CLOCK as f64 / SAMPLE_RATE as f64,
+            format: AudioFormat::F32,
+            buffer: Box::new([0.0; BUFFER_SIZE]),
+            buffer_i16: Box::new([0; BUFFER_SIZE]),
             buffer_position: 0,
-            is_buffer_full: false,
             callback,
             frame_sequencer_position: 0,
             left_vin: false,
@@ -110,9 +275,8 @@ impl Apu {
     pub fn tick(&mut self, cycles: u32) {
         for _ in 0..cycles {
             // This clock is incremented every T-cycle.
-            // This is used to clock the frame sequencer and
-            // to generate samples.
-            self.sample_clock = self.sample_clock.wrapping_add(1);
+            // This is used to generate samples.
+            self.sample_clock += 1.0;
 
             // Tick all the connected channels.
             self.channel_one.tick_channel();
@@ -120,106 +284,221 @@ impl Apu {
             self.channel_three.tick_channel();
             self.channel_four.tick_channel();
 
-            // Tick the frame sequencer. It generates clocks for the length,
-            // envelope and sweep functions.
-            if self.sample_clock % 8192 == 0 {
-                self.sample_clock = 0;
-
-                match self.frame_sequencer_position {
-                    0 => {
-                        self.channel_one.step_length();
-                        self.channel_two.step_length();
-                        self.channel_three.step_length();
-                        self.channel_four.step_length();
-                    }
+            // Each (CPU CLOCK / SAMPLE RATE) cycles one sample is generated
+            // and pushed to the buffer.
+            if self.sample_clock >= self.sample_period {
+                self.sample_clock -= self.sample_period;
 
-                    2 => {
-                        self.channel_one.step_length();
-                        self.channel_two.step_length();
-                        self.channel_three.step_length();
-                        self.channel_four.step_length();
-                        self.channel_one.step_sweep();
-                    }
+                let left = self.mix(self.nr51 >> 4, self.left_volume);
+                let right = self.mix(self.nr51 & 0x0F, self.right_volume);
 
-                    4 => {
-                        self.channel_one.step_length();
-                        self.channel_two.step_length();
-                        self.channel_three.step_length();
-                        self.channel_four.step_length();
-                    }
+                self.push_samples(left, right);
+            }
 
-                    6 => {
-                        self.channel_one.step_length();
-                        self.channel_two.step_length();
-                        self.channel_three.step_length();
-                        self.channel_four.step_length();
-                        self.channel_one.step_sweep();
-                    }
+            // Checks if the buffer is full and calls the provided callback.
+            if self.buffer_position >= BUFFER_SIZE {
+                let samples = match self.format {
+                    AudioFormat::F32 => AudioSamples::F32(self.buffer.as_ref()),
+                    AudioFormat::I16 => AudioSamples::I16(self.buffer_i16.as_ref()),
+
+                    AudioFormat::Planar => {
+                        let (left, right) = self.buffer.split_at(BUFFER_SIZE / 2);
 
-                    7 => {
-                        self.channel_one.step_volume();
-                        self.channel_two.step_volume();
-                        self.channel_four.step_volume();
+                        AudioSamples::Planar { left, right }
                     }
+                };
 
-                    _ => {}
-                }
+                (self.callback)(samples);
 
-                self.frame_sequencer_position = (self.frame_sequencer_position + 1) & 7;
+                // Reset the buffer position.
+                self.buffer_position = 0;
             }
+        }
+    }
 
-            // Each (CPU CLOCK / SAMPLE RATE) cycles one sample is generated
-            // and pushed to the buffer.
-            if self.sample_clock % ((CPU_CLOCK / SAMPLE_RATE) as u32) == 0 {
-                self.buffer[self.buffer_position] = (self.left_volume as f32 / 7.0)
-                    * ((if (self.nr51 & 0x80) != 0 {
-                        self.channel_four.get_amplitude()
-                    } else {
-                        0.0
-                    } + if (self.nr51 & 0x40) != 0 {
-                        self.channel_three.get_amplitude()
-                    } else {
-                        0.0
-                    } + if (self.nr51 & 0x20) != 0 {
-                        self.channel_two.get_amplitude()
-                    } else {
-                        0.0
-                    } + if (self.nr51 & 0x10) != 0 {
-                        self.channel_one.get_amplitude()
-                    } else {
-                        0.0
-                    }) / 4.0);
+    /// Mix the channels enabled on one side, `enabled` holds their
+    /// bits from NR51 and `volume` is the side's volume from NR50.
+    #[cfg(not(feature = "fixed-point"))]
+    fn mix(&self, enabled: u8, volume: u8) -> Sample {
+        let sum: f32 = self
+            .channel_outputs(enabled)
+            .map(|output| output.map_or(0.0, |output| (output as f32 / 7.5) - 1.0))
+            .sum();
 
-                self.buffer[self.buffer_position + 1] = (self.right_volume as f32 / 7.0)
-                    * ((if (self.nr51 & 0x08) != 0 {
-                        self.channel_four.get_amplitude()
-                    } else {
-                        0.0
-                    } + if (self.nr51 & 0x04) != 0 {
-                        self.channel_three.get_amplitude()
-                    } else {
-                        0.0
-                    } + if (self.nr51 & 0x02) != 0 {
-                        self.channel_two.get_amplitude()
-                    } else {
-                        0.0
-                    } + if (self.nr51 & 0x01) != 0 {
-                        self.channel_one.get_amplitude()
-                    } else {
-                        0.0
-                    }) / 4.0);
+        (volume as f32 / 7.0) * (sum / 4.0)
+    }
+
+    /// Mix the channels enabled on one side, `enabled` holds their
+    /// bits from NR51 and `volume` is the side's volume from NR50.
+    #[cfg(feature = "fixed-point")]
+    fn mix(&self, enabled: u8, volume: u8) -> Sample {
+        // Each channel contributes between -15 and 15.
+        let sum: i32 = self
+            .channel_outputs(enabled)
+            .map(|output| output.map_or(0, |output| (output as i32 * 2) - 15))
+            .sum();
+
+        (sum * volume as i32 * i16::MAX as i32 / (15 * 4 * 7)) as i16
+    }
+
+    /// Get the outputs of the channels whose bits are set in `enabled`.
+    fn channel_outputs(&self, enabled: u8) -> impl Iterator<Item = Option<u8>> {
+        let outputs = [
+            self.channel_one.get_output(),
+            self.channel_two.get_output(),
+            self.channel_three.get_output(),
+            self.channel_four.get_output(),
+        ];
+
+        (0..4)
+            .filter(move |channel| (enabled & (1 << channel)) != 0)
+            .map(move |channel| outputs[channel])
+    }
 
-                self.buffer_position += 2;
+    /// Write a sample for each side to the buffer of the current format.
+    fn push_samples(&mut self, left: Sample, right: Sample) {
+        let position = self.buffer_position;
+
+        match self.format {
+            AudioFormat::F32 => {
+                self.buffer[position] = to_f32(left);
+                self.buffer[position + 1] = to_f32(right);
             }
 
-            // Checks if the buffer is full and calls the provided callback.
-            if self.buffer_position >= BUFFER_SIZE {
-                (self.callback)(self.buffer.as_ref());
+            AudioFormat::I16 => {
+                self.buffer_i16[position] = to_i16(left);
+                self.buffer_i16[position + 1] = to_i16(right);
+            }
 
-                // Reset the buffer position.
-                self.buffer_position = 0;
+            AudioFormat::Planar => {
+                self.buffer[position / 2] = to_f32(left);
+                self.buffer[(BUFFER_SIZE + position) / 2] = to_f32(right);
+            }
+        }
+
+        self.buffer_position += 2;
+    }
+
+    /// Reset the APU to its power on state,
+    /// keeping the callback, sample rate and format.
+    pub fn reset(&mut self) {
+        let callback = core::mem::replace(&mut self.callback, Box::new(|_| {}));
+        let sample_rate = self.sample_rate;
+        let sample_period = self.sample_period;
+        let format = self.format;
+
+        *self = Self::new(callback, self.channel_three.cgb_mode);
+        self.sample_rate = sample_rate;
+        self.sample_period = sample_period;
+        self.format = format;
+    }
+
+    /// Set the format samples are handed to the callback in.
+    /// Samples in the buffer that wasn't full yet are dropped.
+    pub fn set_format(&mut self, format: AudioFormat) {
+        self.format = format;
+        self.buffer_position = 0;
+    }
+
+    /// Set the samples generated per second, to match the audio driver.
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.sample_rate = rate as f64;
+        self.sample_period = CPU_CLOCK as f64 / self.sample_rate;
+    }
+
+    /// Generate `ratio` times as many samples as usual.
+    ///
+    /// Used for dynamic rate control, the ratio should
+    /// stay very close to 1.0 to not be audible.
+    pub fn set_sample_rate_ratio(&mut self, ratio: f64) {
+        self.sample_period = CPU_CLOCK as f64 / (self.sample_rate * ratio);
+    }
+
+    /// Clock the frame sequencer, which generates clocks for the
+    /// length, envelope and sweep functions.
+    ///
+    /// This is driven by falling edges of bit 4 of DIV
+    /// (bit 5 in double speed mode), not a counter of its own.
+    pub fn clock_frame_sequencer(&mut self) {
+        if !self.apu_enabled {
+            return;
+        }
+
+        match self.frame_sequencer_position {
+            0 => {
+                self.channel_one.step_length();
+                self.channel_two.step_length();
+                self.channel_three.step_length();
+                self.channel_four.step_length();
+            }
+
+            2 => {
+                self.channel_one.step_length();
+                self.channel_two.step_length();
+                self.channel_three.step_length();
+                self.channel_four.step_length();
+                self.channel_one.step_sweep();
+            }
+
+            4 => {
+                self.channel_one.step_length();
+                self.channel_two.step_length();
+                self.channel_three.step_length();
+                self.channel_four.step_length();
+            }
+
+            6 => {
+                self.channel_one.step_length();
+                self.channel_two.step_length();
+                self.channel_three.step_length();
+                self.channel_four.step_length();
+                self.channel_one.step_sweep();
             }
+
+            7 => {
+                self.channel_one.step_volume();
+                self.channel_two.step_volume();
+                self.channel_four.step_volume();
+            }
+
+            _ => {}
         }
+
+        self.frame_sequencer_position = (self.frame_sequencer_position + 1) & 7;
+    }
+
+    /// Serialize the APU's state.
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.left_volume);
+        w.write_u8(self.right_volume);
+        w.write_u8(self.nr51);
+        w.write_bool(self.apu_enabled);
+        w.write_bool(self.left_vin);
+        w.write_bool(self.right_vin);
+        w.write_u8(self.frame_sequencer_position);
+        w.write_f64(self.sample_clock);
+
+        self.channel_one.save_state(w);
+        self.channel_two.save_state(w);
+        self.channel_three.save_state(w);
+        self.channel_four.save_state(w);
+    }
+
+    /// Restore the APU's state.
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.left_volume = r.read_u8()?;
+        self.right_volume = r.read_u8()?;
+        self.nr51 = r.read_u8()?;
+        self.apu_enabled = r.read_bool()?;
+        self.left_vin = r.read_bool()?;
+        self.right_vin = r.read_bool()?;
+        self.frame_sequencer_position = r.read_u8_in(0..=7)?;
+        self.sample_clock = r.read_f64()?;
+
+        self.channel_one.load_state(r)?;
+        self.channel_two.load_state(r)?;
+        self.channel_three.load_state(r)?;
+        self.channel_four.load_state(r)
     }
 
     /// Read a byte from the given address.
@@ -304,23 +583,38 @@ impl Apu {
 
                     self.frame_sequencer_position = 0;
 
+                    // Powering on is the only thing that resets the duty
+                    // positions, the first note after it starts at step 0
+                    // and stays there for a whole period.
                     self.channel_one.wave_position = 0;
                     self.channel_two.wave_position = 0;
                     self.channel_three.wave_position = 0;
                 }
             }
 
+            // NRx4 writes can clock the length counters.
+            0xFF14 | 0xFF19 | 0xFF1E | 0xFF23 => {
+                let extra_length_clock = (self.frame_sequencer_position & 1) != 0;
+
+                match addr {
+                    0xFF14 => self.channel_one.write_control(value, extra_length_clock),
+                    0xFF19 => self.channel_two.write_control(value, extra_length_clock),
+                    0xFF1E => self.channel_three.write_control(value, extra_length_clock),
+                    _ => self.channel_four.write_control(value, extra_length_clock),
+                }
+            }
+
             // Channel 1 IO registers.
-            0xFF10..=0xFF14 => self.channel_one.write_byte(addr, value),
+            0xFF10..=0xFF13 => self.channel_one.write_byte(addr, value),
 
             // Channel 2 IO registers.
-            0xFF15..=0xFF19 => self.channel_two.write_byte(addr, value),
+            0xFF15..=0xFF18 => self.channel_two.write_byte(addr, value),
 
             // Channel 3 IO registers + Wave RAM.
-            0xFF1A..=0xFF1E | 0xFF30..=0xFF3F => self.channel_three.write_byte(addr, value),
+            0xFF1A..=0xFF1D | 0xFF30..=0xFF3F => self.channel_three.write_byte(addr, value),
 
             // Channel 4 IO registers.
-            0xFF1F..=0xFF23 => self.channel_four.write_byte(addr, value),
+            0xFF1F..=0xFF22 => self.channel_four.write_byte(addr, value),
 
             _ => unreachable!(),
         }
@@ -396,6 +690,10 @@ pub struct ChannelOne {
 
     /// The current volume of the channel.
     current_volume: u8,
+
+    /// Set once the envelope reaches 0 or 15, it stops
+    /// changing the volume until the channel is triggered.
+    envelope_stopped: bool,
 }
 
 impl ChannelOne {
@@ -406,7 +704,7 @@ impl ChannelOne {
                 self.period_timer -= 1;
             }
 
-            if self.period_timer == 0 {
+            if self.period_timer == 0 && !self.envelope_stopped {
                 self.period_timer = self.period;
 
                 if (self.current_volume < 0xF && self.is_incrementing)
@@ -417,6 +715,8 @@ impl ChannelOne {
                     } else {
                         self.current_volume -= 1;
                     }
+                } else {
+                    self.envelope_stopped = true;
                 }
             }
         }
@@ -464,9 +764,64 @@ impl ChannelOne {
 
         new_frequency
     }
+
+    /// Reload the frequency timer on a trigger. Only the upper bits
+    /// are reloaded, the lower two keep counting, and the duty position
+    /// carries on where it was, so the new note starts mid-wave.
+    fn reload_frequency_timer(&mut self) {
+        self.frequency_timer = ((2048 - self.frequency) * 4) | (self.frequency_timer & 0b11);
+    }
 }
 
 impl Channel for ChannelOne {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.dac_enabled);
+        w.write_bool(self.channel_enabled);
+        w.write_u16(self.frequency_timer);
+        w.write_u8(self.wave_position as u8);
+        w.write_u8(self.sweep_period);
+        w.write_bool(self.sweep_is_decrementing);
+        w.write_u8(self.sweep_amount);
+        w.write_u8(self.sweep_period_timer);
+        w.write_bool(self.sweep_enabled);
+        w.write_u16(self.shadow_frequency);
+        w.write_u8(self.duty_pattern);
+        w.write_u8(self.length_counter);
+        w.write_u16(self.frequency);
+        w.write_bool(self.length_enabled);
+        w.write_u8(self.initial_volume);
+        w.write_bool(self.is_incrementing);
+        w.write_u8(self.period);
+        w.write_u8(self.period_timer);
+        w.write_u8(self.current_volume);
+        w.write_bool(self.envelope_stopped);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.dac_enabled = r.read_bool()?;
+        self.channel_enabled = r.read_bool()?;
+        self.frequency_timer = r.read_u16()?;
+        self.wave_position = r.read_u8_in(0..=7)? as usize;
+        self.sweep_period = r.read_u8()?;
+        self.sweep_is_decrementing = r.read_bool()?;
+        self.sweep_amount = r.read_u8()?;
+        self.sweep_period_timer = r.read_u8()?;
+        self.sweep_enabled = r.read_bool()?;
+        self.shadow_frequency = r.read_u16()?;
+        self.duty_pattern = r.read_u8_in(0..=3)?;
+        self.length_counter = r.read_u8()?;
+        self.frequency = r.read_u16()?;
+        self.length_enabled = r.read_bool()?;
+        self.initial_volume = r.read_u8()?;
+        self.is_incrementing = r.read_bool()?;
+        self.period = r.read_u8()?;
+        self.period_timer = r.read_u8()?;
+        self.current_volume = r.read_u8()?;
+        self.envelope_stopped = r.read_bool()?;
+
+        Ok(())
+    }
+
     fn read_byte(&self, addr: u16) -> u8 {
         match addr {
             0xFF10 => {
@@ -513,6 +868,17 @@ impl Channel for ChannelOne {
                 self.length_counter = 64 - (value & 0b0011_1111);
             }
             0xFF12 => {
+                // Writes while the channel plays nudge the volume.
+                if self.channel_enabled {
+                    self.current_volume = zombie_volume(
+                        self.current_volume,
+                        self.period,
+                        self.is_incrementing,
+                        self.envelope_stopped,
+                        value,
+                    );
+                }
+
                 // Update the envelope function parameters.
                 self.is_incrementing = (value & 0x08) != 0;
                 self.initial_volume = value >> 4;
@@ -532,46 +898,56 @@ impl Channel for ChannelOne {
                 self.frequency = (self.frequency & 0x0700) | value as u16;
             }
 
-            0xFF14 => {
-                // Update frequency with the upper three bits.
-                self.frequency = (self.frequency & 0xFF) | (((value & 0x07) as u16) << 8);
+            _ => unreachable!(),
+        }
+    }
 
-                self.length_enabled = ((value >> 6) & 0x01) != 0;
+    fn write_control(&mut self, value: u8, extra_length_clock: bool) {
+        // Update frequency with the upper three bits.
+        self.frequency = (self.frequency & 0xFF) | (((value & 0x07) as u16) << 8);
 
-                // If length counter is zero reload it with 64.
-                if self.length_counter == 0 {
-                    self.length_counter = 64;
-                }
+        let (length_counter, expired) = write_length(
+            self.length_counter as u16,
+            64,
+            self.length_enabled,
+            value,
+            extra_length_clock,
+        );
 
-                // Restart the channel iff DAC is enabled and trigger is set.
-                let trigger = (value >> 7) != 0;
+        self.length_counter = length_counter as u8;
+        self.length_enabled = ((value >> 6) & 0x01) != 0;
 
-                if trigger && self.dac_enabled {
-                    self.channel_enabled = true;
+        if expired {
+            self.channel_enabled = false;
+        }
 
-                    // Trigger the envelope function.
-                    self.period_timer = self.period;
-                    self.current_volume = self.initial_volume;
+        // Restart the channel iff DAC is enabled and trigger is set.
+        let trigger = (value >> 7) != 0;
 
-                    // Trigger the sweep function.
-                    self.shadow_frequency = self.frequency;
+        if trigger && self.dac_enabled {
+            self.channel_enabled = true;
+            self.reload_frequency_timer();
 
-                    // Sweep period of 0 is treated as 8 for some reason.
-                    self.sweep_period_timer = if self.sweep_period > 0 {
-                        self.sweep_period
-                    } else {
-                        8
-                    };
+            // Trigger the envelope function.
+            self.period_timer = self.period;
+            self.current_volume = self.initial_volume;
+            self.envelope_stopped = false;
 
-                    self.sweep_enabled = self.sweep_period > 0 || self.sweep_amount > 0;
+            // Trigger the sweep function.
+            self.shadow_frequency = self.frequency;
 
-                    if self.sweep_amount > 0 {
-                        self.calculate_frequency();
-                    }
-                }
-            }
+            // Sweep period of 0 is treated as 8 for some reason.
+            self.sweep_period_timer = if self.sweep_period > 0 {
+                self.sweep_period
+            } else {
+                8
+            };
 
-            _ => unreachable!(),
+            self.sweep_enabled = self.sweep_period > 0 || self.sweep_amount > 0;
+
+            if self.sweep_amount > 0 {
+                self.calculate_frequency();
+            }
         }
     }
 
@@ -590,16 +966,13 @@ impl Channel for ChannelOne {
         self.frequency_timer -= 1;
     }
 
-    /// Get the current amplitude of the channel.
-    /// The only possible values of this are 0 or 1.
-    fn get_amplitude(&self) -> f32 {
+    /// Get the output of the channel, the volume
+    /// while the duty is high and 0 otherwise.
+    fn get_output(&self) -> Option<u8> {
         if self.dac_enabled && self.channel_enabled {
-            let input = WAVE_DUTY[self.duty_pattern as usize][self.wave_position] as f32
-                * self.current_volume as f32;
-
-            (input / 7.5) - 1.0
+            Some(WAVE_DUTY[self.duty_pattern as usize][self.wave_position] * self.current_volume)
         } else {
-            0.0
+            None
         }
     }
 
@@ -664,6 +1037,10 @@ pub struct ChannelTwo {
 
     /// The current volume of the channel.
     current_volume: u8,
+
+    /// Set once the envelope reaches 0 or 15, it stops
+    /// changing the volume until the channel is triggered.
+    envelope_stopped: bool,
 }
 
 impl ChannelTwo {
@@ -674,7 +1051,7 @@ impl ChannelTwo {
                 self.period_timer -= 1;
             }
 
-            if self.period_timer == 0 {
+            if self.period_timer == 0 && !self.envelope_stopped {
                 self.period_timer = self.period;
 
                 if (self.current_volume < 0xF && self.is_incrementing)
@@ -685,13 +1062,57 @@ impl ChannelTwo {
                     } else {
                         self.current_volume -= 1;
                     }
+                } else {
+                    self.envelope_stopped = true;
                 }
             }
         }
     }
+
+    /// Reload the frequency timer on a trigger, keeping its lower
+    /// two bits and the duty position, like channel one does.
+    fn reload_frequency_timer(&mut self) {
+        self.frequency_timer = ((2048 - self.frequency) * 4) | (self.frequency_timer & 0b11);
+    }
 }
 
 impl Channel for ChannelTwo {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.dac_enabled);
+        w.write_bool(self.channel_enabled);
+        w.write_u16(self.frequency_timer);
+        w.write_u8(self.wave_position as u8);
+        w.write_u8(self.duty_pattern);
+        w.write_u8(self.length_counter);
+        w.write_u16(self.frequency);
+        w.write_bool(self.length_enabled);
+        w.write_u8(self.initial_volume);
+        w.write_bool(self.is_incrementing);
+        w.write_u8(self.period);
+        w.write_u8(self.period_timer);
+        w.write_u8(self.current_volume);
+        w.write_bool(self.envelope_stopped);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.dac_enabled = r.read_bool()?;
+        self.channel_enabled = r.read_bool()?;
+        self.frequency_timer = r.read_u16()?;
+        self.wave_position = r.read_u8_in(0..=7)? as usize;
+        self.duty_pattern = r.read_u8_in(0..=3)?;
+        self.length_counter = r.read_u8()?;
+        self.frequency = r.read_u16()?;
+        self.length_enabled = r.read_bool()?;
+        self.initial_volume = r.read_u8()?;
+        self.is_incrementing = r.read_bool()?;
+        self.period = r.read_u8()?;
+        self.period_timer = r.read_u8()?;
+        self.current_volume = r.read_u8()?;
+        self.envelope_stopped = r.read_bool()?;
+
+        Ok(())
+    }
+
     fn read_byte(&self, addr: u16) -> u8 {
         match addr {
             // There is no NR10 register.
@@ -730,6 +1151,17 @@ impl Channel for ChannelTwo {
             }
 
             0xFF17 => {
+                // Writes while the channel plays nudge the volume.
+                if self.channel_enabled {
+                    self.current_volume = zombie_volume(
+                        self.current_volume,
+                        self.period,
+                        self.is_incrementing,
+                        self.envelope_stopped,
+                        value,
+                    );
+                }
+
                 // Update the envelope function parameters.
                 self.is_incrementing = (value & 0x08) != 0;
                 self.initial_volume = value >> 4;
@@ -749,30 +1181,40 @@ impl Channel for ChannelTwo {
                 self.frequency = (self.frequency & 0x0700) | value as u16;
             }
 
-            0xFF19 => {
-                // Update frequency with the upper three bits.
-                self.frequency = (self.frequency & 0xFF) | (((value & 0x07) as u16) << 8);
+            _ => unreachable!(),
+        }
+    }
 
-                self.length_enabled = ((value >> 6) & 0x01) != 0;
+    fn write_control(&mut self, value: u8, extra_length_clock: bool) {
+        // Update frequency with the upper three bits.
+        self.frequency = (self.frequency & 0xFF) | (((value & 0x07) as u16) << 8);
 
-                // If length counter is zero reload it with 64.
-                if self.length_counter == 0 {
-                    self.length_counter = 64;
-                }
+        let (length_counter, expired) = write_length(
+            self.length_counter as u16,
+            64,
+            self.length_enabled,
+            value,
+            extra_length_clock,
+        );
 
-                // Restart the channel iff DAC is enabled and trigger is set.
-                let trigger = (value >> 7) != 0;
+        self.length_counter = length_counter as u8;
+        self.length_enabled = ((value >> 6) & 0x01) != 0;
 
-                if trigger && self.dac_enabled {
-                    self.channel_enabled = true;
+        if expired {
+            self.channel_enabled = false;
+        }
 
-                    // Envelope is triggered.
-                    self.period_timer = self.period;
-                    self.current_volume = self.initial_volume;
-                }
-            }
+        // Restart the channel iff DAC is enabled and trigger is set.
+        let trigger = (value >> 7) != 0;
 
-            _ => unreachable!(),
+        if trigger && self.dac_enabled {
+            self.channel_enabled = true;
+            self.reload_frequency_timer();
+
+            // Envelope is triggered.
+            self.period_timer = self.period;
+            self.current_volume = self.initial_volume;
+            self.envelope_stopped = false;
         }
     }
 
@@ -791,16 +1233,13 @@ impl Channel for ChannelTwo {
         self.frequency_timer -= 1;
     }
 
-    /// Get the current amplitude of the channel.
-    /// The only possible values of this are 0 or 1.
-    fn get_amplitude(&self) -> f32 {
+    /// Get the output of the channel, the volume
+    /// while the duty is high and 0 otherwise.
+    fn get_output(&self) -> Option<u8> {
         if self.dac_enabled && self.channel_enabled {
-            let input = WAVE_DUTY[self.duty_pattern as usize][self.wave_position] as f32
-                * self.current_volume as f32;
-
-            (input / 7.5) - 1.0
+            Some(WAVE_DUTY[self.duty_pattern as usize][self.wave_position] * self.current_volume)
         } else {
-            0.0
+            None
         }
     }
 
@@ -856,9 +1295,85 @@ pub struct ChannelThree {
 
     /// Arbitrary 32 4-bit samples.
     wave_ram: Box<[u8; 0x10]>,
+
+    /// The sample last read from wave RAM, which is what's played.
+    /// Triggering doesn't refill it, so the old sample plays until
+    /// the next read.
+    sample_buffer: u8,
+
+    /// T-cycles since wave RAM was last read, saturating.
+    cycles_since_read: u8,
+
+    /// The CGB's wave channel lacks the DMG's wave RAM quirks.
+    cgb_mode: bool,
+}
+
+impl ChannelThree {
+    /// Get the index of the wave RAM byte the CPU accesses at an address.
+    ///
+    /// While the channel plays, the CPU accesses the byte the channel
+    /// reads instead. The DMG only allows this on the cycle the channel
+    /// reads the byte, at other times reads see 0xFF and writes are lost.
+    fn wave_ram_index(&self, addr: u16) -> Option<usize> {
+        if !self.channel_enabled {
+            Some((addr - 0xFF30) as usize)
+        } else if self.cgb_mode || self.cycles_since_read < 2 {
+            Some(self.wave_position / 2)
+        } else {
+            None
+        }
+    }
+
+    /// Retriggering the DMG's wave channel just as it reads wave RAM
+    /// corrupts the first bytes of wave RAM. The byte about to be read is
+    /// copied to the first byte if it's one of the first four, otherwise
+    /// the four bytes it is part of are copied to the first four.
+    fn corrupt_wave_ram(&mut self) {
+        let index = ((self.wave_position + 1) & 31) / 2;
+
+        if index < 4 {
+            self.wave_ram[0] = self.wave_ram[index];
+        } else {
+            let start = index & !0b11;
+
+            self.wave_ram.copy_within(start..start + 4, 0);
+        }
+    }
 }
 
 impl Channel for ChannelThree {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.dac_enabled);
+        w.write_bool(self.channel_enabled);
+        w.write_u16(self.frequency_timer);
+        w.write_u8(self.wave_position as u8);
+        w.write_u16(self.length_counter);
+        w.write_u8(self.output_level);
+        w.write_u8(self.volume_shift);
+        w.write_u16(self.frequency);
+        w.write_bool(self.length_enabled);
+        w.write_bytes(self.wave_ram.as_ref());
+        w.write_u8(self.sample_buffer);
+        w.write_u8(self.cycles_since_read);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.dac_enabled = r.read_bool()?;
+        self.channel_enabled = r.read_bool()?;
+        self.frequency_timer = r.read_u16()?;
+        self.wave_position = r.read_u8_in(0..=31)? as usize;
+        self.length_counter = r.read_u16()?;
+        self.output_level = r.read_u8()?;
+        self.volume_shift = r.read_u8_in(0..=4)?;
+        self.frequency = r.read_u16()?;
+        self.length_enabled = r.read_bool()?;
+        r.read_into(self.wave_ram.as_mut())?;
+        self.sample_buffer = r.read_u8()?;
+        self.cycles_since_read = r.read_u8()?;
+
+        Ok(())
+    }
+
     fn read_byte(&self, addr: u16) -> u8 {
         match addr {
             0xFF1A => ((self.dac_enabled as u8) << 7) | 0x7F,
@@ -867,7 +1382,10 @@ impl Channel for ChannelThree {
             0xFF1D => 0xFF,
             0xFF1E => ((self.length_enabled as u8) << 6) | 0b1011_1111,
 
-            0xFF30..=0xFF3F => self.wave_ram[(addr - 0xFF30) as usize],
+            0xFF30..=0xFF3F => match self.wave_ram_index(addr) {
+                Some(index) => self.wave_ram[index],
+                None => 0xFF,
+            },
 
             _ => unreachable!(),
         }
@@ -905,33 +1423,60 @@ impl Channel for ChannelThree {
                 self.frequency = (self.frequency & 0x0700) | value as u16;
             }
 
-            0xFF1E => {
-                // Update frequency with the upper three bits.
-                self.frequency = (self.frequency & 0xFF) | (((value & 0x07) as u16) << 8);
+            0xFF30..=0xFF3F => {
+                if let Some(index) = self.wave_ram_index(addr) {
+                    self.wave_ram[index] = value;
+                }
+            }
 
-                self.length_enabled = ((value >> 6) & 0x01) != 0;
+            _ => unreachable!(),
+        }
+    }
 
-                // If length counter is zero reload it with 64.
-                if self.length_counter == 0 {
-                    self.length_counter = 256;
-                }
+    fn write_control(&mut self, value: u8, extra_length_clock: bool) {
+        // Update frequency with the upper three bits.
+        self.frequency = (self.frequency & 0xFF) | (((value & 0x07) as u16) << 8);
 
-                // Restart the channel iff DAC is enabled and trigger is set.
-                let trigger = (value >> 7) != 0;
+        let (length_counter, expired) = write_length(
+            self.length_counter,
+            256,
+            self.length_enabled,
+            value,
+            extra_length_clock,
+        );
 
-                if trigger && self.dac_enabled {
-                    self.channel_enabled = true;
-                }
-            }
+        self.length_counter = length_counter;
+        self.length_enabled = ((value >> 6) & 0x01) != 0;
 
-            0xFF30..=0xFF3F => self.wave_ram[(addr - 0xFF30) as usize] = value,
+        if expired {
+            self.channel_enabled = false;
+        }
 
-            _ => unreachable!(),
+        // Restart the channel iff DAC is enabled and trigger is set.
+        let trigger = (value >> 7) != 0;
+
+        if trigger && self.channel_enabled && !self.cgb_mode && self.frequency_timer < 2 {
+            self.corrupt_wave_ram();
+        }
+
+        if trigger && self.dac_enabled {
+            self.channel_enabled = true;
+
+            // The first sample is read after a short delay, from
+            // position 1, position 0 is skipped until the wave wraps.
+            self.wave_position = 0;
+            self.frequency_timer = (2048 - self.frequency) * 2 + 6;
         }
     }
 
     /// Tick the channel by one T-cycle.
     fn tick_channel(&mut self) {
+        if !self.channel_enabled {
+            return;
+        }
+
+        self.cycles_since_read = self.cycles_since_read.saturating_add(1);
+
         // If the frequency timer decrement to 0, it is reloaded with the formula
         // `(2048 - frequency) * 2` and wave position is advanced by one.
         if self.frequency_timer == 0 {
@@ -940,21 +1485,28 @@ impl Channel for ChannelThree {
             // Wave position is wrapped, so when the position is >32 it's
             // wrapped back to 0.
             self.wave_position = (self.wave_position + 1) & 31;
+
+            // The upper nibble of each byte is played first.
+            let byte = self.wave_ram[self.wave_position / 2];
+
+            self.sample_buffer = if (self.wave_position & 1) == 0 {
+                byte >> 4
+            } else {
+                byte & 0x0F
+            };
+
+            self.cycles_since_read = 0;
         }
 
         self.frequency_timer -= 1;
     }
 
-    /// Get the current amplitude of the channel.
-    fn get_amplitude(&self) -> f32 {
+    /// Get the output of the channel.
+    fn get_output(&self) -> Option<u8> {
         if self.dac_enabled {
-            let sample = ((self.wave_ram[self.wave_position / 2])
-                >> (if (self.wave_position & 1) != 0 { 4 } else { 0 }))
-                & 0x0F;
-
-            (((sample >> self.volume_shift) as f32) / 7.5) - 1.0
+            Some(self.sample_buffer >> self.volume_shift)
         } else {
-            0.0
+            None
         }
     }
 
@@ -980,13 +1532,14 @@ pub struct ChannelFour {
     /// This can be only affected by the `length` parameter.
     channel_enabled: bool,
 
-    /// This is equal to `(2048 - frequency) * 2`
+    /// This is equal to `divisor << clock_shift`, up to 112 << 13.
     /// This timer is decremented every T-cycle.
-    /// When this timer reaches 0, wave generation is stepped, and
+    /// When this timer reaches 0, the LFSR is stepped, and
     /// it is reloaded.
-    frequency_timer: u16,
+    frequency_timer: u32,
 
     /// The linear feedback shift register (LFSR) generates a pseudo-random bit sequence.
+    /// Bit 0 is played, a set bit outputs the volume.
     lfsr: u16,
 
     /// The sound length counter. If this is >0 and bit 6 in NR24 is set
@@ -1016,6 +1569,10 @@ pub struct ChannelFour {
 
     /// The current volume of the channel.
     current_volume: u8,
+
+    /// Set once the envelope reaches 0 or 15, it stops
+    /// changing the volume until the channel is triggered.
+    envelope_stopped: bool,
 }
 
 impl ChannelFour {
@@ -1026,7 +1583,7 @@ impl ChannelFour {
                 self.period_timer -= 1;
             }
 
-            if self.period_timer == 0 {
+            if self.period_timer == 0 && !self.envelope_stopped {
                 self.period_timer = self.period;
 
                 if (self.current_volume < 0xF && self.is_incrementing)
@@ -1037,6 +1594,8 @@ impl ChannelFour {
                     } else {
                         self.current_volume -= 1;
                     }
+                } else {
+                    self.envelope_stopped = true;
                 }
             }
         }
@@ -1044,6 +1603,40 @@ impl ChannelFour {
 }
 
 impl Channel for ChannelFour {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write_bool(self.dac_enabled);
+        w.write_bool(self.channel_enabled);
+        w.write_u32(self.frequency_timer);
+        w.write_u16(self.lfsr);
+        w.write_u8(self.length_counter);
+        w.write_u8(self.nr43);
+        w.write_bool(self.length_enabled);
+        w.write_u8(self.initial_volume);
+        w.write_bool(self.is_incrementing);
+        w.write_u8(self.period);
+        w.write_u8(self.period_timer);
+        w.write_u8(self.current_volume);
+        w.write_bool(self.envelope_stopped);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.dac_enabled = r.read_bool()?;
+        self.channel_enabled = r.read_bool()?;
+        self.frequency_timer = r.read_u32()?;
+        self.lfsr = r.read_u16()?;
+        self.length_counter = r.read_u8()?;
+        self.nr43 = r.read_u8()?;
+        self.length_enabled = r.read_bool()?;
+        self.initial_volume = r.read_u8()?;
+        self.is_incrementing = r.read_bool()?;
+        self.period = r.read_u8()?;
+        self.period_timer = r.read_u8()?;
+        self.current_volume = r.read_u8()?;
+        self.envelope_stopped = r.read_bool()?;
+
+        Ok(())
+    }
+
     fn read_byte(&self, addr: u16) -> u8 {
         match addr {
             // NR40 does not exist.
@@ -1075,6 +1668,17 @@ impl Channel for ChannelFour {
             }
 
             0xFF21 => {
+                // Writes while the channel plays nudge the volume.
+                if self.channel_enabled {
+                    self.current_volume = zombie_volume(
+                        self.current_volume,
+                        self.period,
+                        self.is_incrementing,
+                        self.envelope_stopped,
+                        value,
+                    );
+                }
+
                 // Update the envelope function parameters.
                 self.is_incrementing = (value & 0x08) != 0;
                 self.initial_volume = value >> 4;
@@ -1091,67 +1695,74 @@ impl Channel for ChannelFour {
 
             0xFF22 => self.nr43 = value,
 
-            0xFF23 => {
-                self.length_enabled = ((value >> 6) & 0x01) != 0;
+            _ => unreachable!(),
+        }
+    }
 
-                // If length counter is zero reload it with 64.
-                if self.length_counter == 0 {
-                    self.length_counter = 64;
-                }
+    fn write_control(&mut self, value: u8, extra_length_clock: bool) {
+        let (length_counter, expired) = write_length(
+            self.length_counter as u16,
+            64,
+            self.length_enabled,
+            value,
+            extra_length_clock,
+        );
 
-                // Restart the channel iff DAC is enabled and trigger is set.
-                let trigger = (value >> 7) != 0;
+        self.length_counter = length_counter as u8;
+        self.length_enabled = ((value >> 6) & 0x01) != 0;
 
-                if trigger && self.dac_enabled {
-                    self.channel_enabled = true;
-                }
+        if expired {
+            self.channel_enabled = false;
+        }
 
-                if trigger {
-                    // On trigger event all bits of LFSR are turned on.
-                    self.lfsr = 0x7FFF;
+        // Restart the channel iff DAC is enabled and trigger is set.
+        let trigger = (value >> 7) != 0;
 
-                    // Envelope is triggered.
-                    self.period_timer = self.period;
-                    self.current_volume = self.initial_volume;
-                }
-            }
+        if trigger && self.dac_enabled {
+            self.channel_enabled = true;
+        }
 
-            _ => unreachable!(),
+        if trigger {
+            // On trigger event all bits of LFSR are cleared.
+            self.lfsr = 0;
+
+            // Envelope is triggered.
+            self.period_timer = self.period;
+            self.current_volume = self.initial_volume;
+            self.envelope_stopped = false;
         }
     }
 
     fn tick_channel(&mut self) {
+        let clock_shift = self.nr43 >> 4;
+
+        // With a clock shift of 14 or 15 the LFSR isn't clocked at all.
+        if clock_shift >= 14 {
+            return;
+        }
+
         // If the frequency timer decrement to 0, it is reloaded with the formula
         // `divisor_code << clock_shift` and wave position is advanced by one.
         if self.frequency_timer == 0 {
-            let divisor_code = (self.nr43 & 0x07) as u16;
+            let divisor_code = (self.nr43 & 0x07) as u32;
 
             self.frequency_timer = (if divisor_code == 0 {
                 8
             } else {
                 divisor_code << 4
-            }) << ((self.nr43 >> 4) as u32);
-
-            let xor_result = (self.lfsr & 0b01) ^ ((self.lfsr & 0b10) >> 1);
-
-            self.lfsr = (self.lfsr >> 1) | (xor_result << 14);
+            }) << clock_shift;
 
-            if ((self.nr43 >> 3) & 0b01) != 0 {
-                self.lfsr &= !(1 << 6);
-                self.lfsr |= xor_result << 6;
-            }
+            self.lfsr = step_lfsr(self.lfsr, ((self.nr43 >> 3) & 0b01) != 0);
         }
 
         self.frequency_timer -= 1;
     }
 
-    fn get_amplitude(&self) -> f32 {
+    fn get_output(&self) -> Option<u8> {
         if self.dac_enabled && self.channel_enabled {
-            let input = (!self.lfsr & 0b01) as f32 * self.current_volume as f32;
-
-            (input / 7.5) - 1.0
+            Some((self.lfsr & 0b01) as u8 * self.current_volume)
         } else {
-            0.0
+            None
         }
     }
 
@@ -1166,3 +1777,200 @@ impl Channel for ChannelFour {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A noise channel playing at the given volume, with the
+    /// envelope set up by `nr42`.
+    fn playing_noise_channel(nr42: u8) -> ChannelFour {
+        let mut channel = ChannelFour::default();
+
+        channel.write_byte(0xFF21, nr42);
+        channel.write_control(0x80, false);
+
+        channel
+    }
+
+    #[test]
+    fn zombie_volume_increments() {
+        // A period of 0 on a running envelope adds 1.
+        assert_eq!(zombie_volume(5, 0, true, false, 0x08), 6);
+
+        // Otherwise decreasing envelopes add 2,
+        assert_eq!(zombie_volume(5, 3, false, false, 0x00), 7);
+        assert_eq!(zombie_volume(5, 0, false, true, 0x00), 7);
+
+        // and increasing ones are left alone.
+        assert_eq!(zombie_volume(5, 3, true, false, 0x08), 5);
+        assert_eq!(zombie_volume(5, 0, true, true, 0x08), 5);
+    }
+
+    #[test]
+    fn zombie_volume_wraps_around() {
+        assert_eq!(zombie_volume(15, 0, true, false, 0x08), 0);
+        assert_eq!(zombie_volume(14, 3, false, false, 0x00), 0);
+    }
+
+    #[test]
+    fn zombie_volume_mirrors_on_direction_flip() {
+        // Decreasing to increasing, 16 - (5 + 2).
+        assert_eq!(zombie_volume(5, 3, false, false, 0x08), 9);
+
+        // Increasing to decreasing, 16 - 5.
+        assert_eq!(zombie_volume(5, 3, true, false, 0x00), 11);
+
+        // Decreasing to increasing with a period of 0, 16 - (5 + 1).
+        assert_eq!(zombie_volume(5, 0, false, false, 0x08), 10);
+    }
+
+    #[test]
+    fn nrx2_writes_nudge_a_playing_channel() {
+        let mut channel = playing_noise_channel(0x50);
+
+        assert_eq!(channel.current_volume, 5);
+
+        // Still decreasing, with a period of 0 now.
+        channel.write_byte(0xFF21, 0x50);
+        assert_eq!(channel.current_volume, 6);
+
+        // The initial volume isn't applied until the next trigger.
+        channel.write_byte(0xFF21, 0xF0);
+        assert_eq!(channel.current_volume, 7);
+    }
+
+    #[test]
+    fn nrx2_writes_leave_a_silent_channel_alone() {
+        let mut channel = ChannelFour {
+            current_volume: 5,
+            ..Default::default()
+        };
+
+        channel.write_byte(0xFF21, 0x58);
+
+        assert_eq!(channel.current_volume, 5);
+    }
+
+    #[test]
+    fn nrx2_writes_with_the_dac_off_disable_the_channel() {
+        let mut channel = playing_noise_channel(0x50);
+
+        // The direction bit alone keeps the DAC on.
+        channel.write_byte(0xFF21, 0x08);
+        assert!(channel.channel_enabled);
+
+        channel.write_byte(0xFF21, 0x00);
+        assert!(!channel.channel_enabled);
+        assert_eq!(channel.get_output(), None);
+
+        // Triggering doesn't start the channel without the DAC.
+        channel.write_control(0x80, false);
+        assert!(!channel.channel_enabled);
+    }
+
+    /// Step the LFSR from 0, as after a trigger, and
+    /// collect the bits it plays, the first in bit 31.
+    fn lfsr_output(short: bool) -> u32 {
+        let mut lfsr = 0;
+
+        (0..32).fold(0, |output, _| {
+            lfsr = step_lfsr(lfsr, short);
+
+            (output << 1) | (lfsr & 0x01) as u32
+        })
+    }
+
+    /// Find the period of the LFSR, once it has settled into it.
+    fn lfsr_period(mut lfsr: u16, short: bool) -> usize {
+        for _ in 0..0x8000 {
+            lfsr = step_lfsr(lfsr, short);
+        }
+
+        let start = lfsr;
+
+        (1..=0x8000)
+            .find(|_| {
+                lfsr = step_lfsr(lfsr, short);
+                lfsr == start
+            })
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn lfsr_15_bit_sequence() {
+        assert_eq!(
+            lfsr_output(false),
+            0b0000_0000_0000_0011_1111_1111_1111_0111
+        );
+        assert_eq!(lfsr_period(0, false), 32767);
+    }
+
+    #[test]
+    fn lfsr_7_bit_sequence() {
+        assert_eq!(lfsr_output(true), 0b0000_0011_1111_0111_1100_1111_0101_1100);
+        assert_eq!(lfsr_period(0, true), 127);
+    }
+
+    #[test]
+    fn lfsr_width_switch_carries_on_from_the_lower_bits() {
+        let mut lfsr = 0;
+
+        for _ in 0..1000 {
+            lfsr = step_lfsr(lfsr, false);
+        }
+
+        // The lower 7 bits pick up right where they are.
+        let switched = step_lfsr(lfsr, true);
+
+        assert_eq!(switched & 0x3F, (lfsr >> 1) & 0x3F);
+        assert_eq!(lfsr_period(lfsr, true), 127);
+    }
+
+    #[test]
+    fn lfsr_locks_up_with_the_lower_bits_set() {
+        let mut lfsr = 0x007F;
+
+        for _ in 0..1000 {
+            lfsr = step_lfsr(lfsr, true);
+
+            assert_eq!(lfsr & 0x7F, 0x7F);
+        }
+
+        // Switching back to 15-bit mode shifts the zeroes
+        // in the upper bits down, which unlocks it.
+        let mut lfsr = 0x007F;
+
+        for _ in 0..15 {
+            lfsr = step_lfsr(lfsr, false);
+        }
+
+        assert_ne!(lfsr & 0x7F, 0x7F);
+    }
+
+    #[test]
+    fn clock_shift_14_and_15_stop_the_lfsr() {
+        for &nr43 in [0xE0, 0xF7].iter() {
+            let mut channel = playing_noise_channel(0xF0);
+
+            channel.write_byte(0xFF22, nr43);
+
+            for _ in 0..0x10000 {
+                channel.tick_channel();
+            }
+
+            assert_eq!(channel.lfsr, 0);
+        }
+
+        // The longest period that still clocks it, too long for 16 bits.
+        let mut channel = playing_noise_channel(0xF0);
+
+        channel.write_byte(0xFF22, 0xD7);
+
+        for _ in 0..(112 << 13) + 1 {
+            channel.tick_channel();
+        }
+
+        assert_ne!(channel.lfsr, 0);
+    }
+}
@@ -0,0 +1,62 @@
+//! Logging writes to the APU's registers, so soundtracks can be
+//! ripped to formats chip music tools read, like VGM.
+
+use alloc::vec::Vec;
+
+/// The registers whose value reads back exactly as written,
+/// which the log starts with. NR52 comes first, the APU ignores
+/// writes to the other registers while it's off.
+const READABLE_REGISTERS: [u16; 9] = [
+    0xFF26, 0xFF24, 0xFF25, 0xFF10, 0xFF12, 0xFF17, 0xFF1A, 0xFF21, 0xFF22,
+];
+
+/// A write to one of the APU's registers or wave RAM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AudioRegisterWrite {
+    /// T-cycles since logging started.
+    pub cycle: u64,
+
+    pub addr: u16,
+    pub value: u8,
+}
+
+/// The APU register writes since `Argentum::start_audio_log`.
+#[derive(Clone, Debug, Default)]
+pub struct AudioLog {
+    /// The writes, oldest first.
+    pub writes: Vec<AudioRegisterWrite>,
+
+    /// T-cycles since logging started. These are counted at the normal
+    /// speed clock of 4194304 Hz, in double speed mode as well.
+    pub cycles: u64,
+}
+
+impl AudioLog {
+    /// Create a new `AudioLog` instance, starting with the current
+    /// values of the registers that can be read back and wave RAM.
+    pub(crate) fn new(read_byte: impl Fn(u16) -> u8) -> Self {
+        let mut log = Self::default();
+
+        for &addr in READABLE_REGISTERS.iter() {
+            log.record(addr, read_byte(addr));
+        }
+
+        for addr in 0xFF30..=0xFF3F {
+            log.record(addr, read_byte(addr));
+        }
+
+        log
+    }
+
+    pub(crate) fn record(&mut self, addr: u16, value: u8) {
+        self.writes.push(AudioRegisterWrite {
+            cycle: self.cycles,
+            addr,
+            value,
+        });
+    }
+
+    pub(crate) fn tick(&mut self, cycles: u32) {
+        self.cycles += cycles as u64;
+    }
+}
@@ -1,6 +1,29 @@
-use std::{cell::RefCell, rc::Rc};
-
-use crate::{audio::Apu, cartridge::*, joypad::Joypad, ppu::Ppu, timer::Timer};
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use core::{
+    cell::RefCell,
+    fmt::{self, Display, Formatter},
+    ops::RangeInclusive,
+};
+
+use crate::{
+    audio::{Apu, AudioCallback},
+    audio_log::AudioLog,
+    cartridge::{CartridgeInfo, Mapper},
+    cheats::CheatCode,
+    clock::Speed,
+    debugger::{BankedAddress, RomBankStats},
+    joypad::Joypad,
+    peripheral::Peripheral,
+    ppu::Ppu,
+    ram_diff::RamDiff,
+    serial::Serial,
+    state::{StateError, StateReader, StateWriter},
+    timer::Timer,
+    util::{res_bit, timed},
+};
+
+#[cfg(feature = "perf-counters")]
+use crate::perf::PerfCounters;
 
 /// This is a custom bootrom for DMG
 /// made by LIJI.
@@ -8,12 +31,33 @@ const DMG_BOOT_ROM: &[u8] = include_bytes!("bootrom/dmg_boot.bin");
 
 /// This is a custom bootrom for CGB
 /// made by LIJI.
-const CGB_BOOT_ROM: &[u8] = include_bytes!("bootrom/cgb_boot.bin");
+pub(crate) const CGB_BOOT_ROM: &[u8] = include_bytes!("bootrom/cgb_boot.bin");
+
+/// The error returned for boot ROM dumps that aren't
+/// as large as the boot ROM of the system being emulated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidBootRom {
+    pub expected: usize,
+    pub actual: usize,
+}
+
+impl Display for InvalidBootRom {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the boot ROM is {} bytes, it should be {} bytes",
+            self.actual, self.expected
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidBootRom {}
 
 /// Implementation of the Game Boy memory bus.
 pub(crate) struct Bus {
     // The inserted cartridge.
-    pub cartridge: Box<dyn Cartridge>,
+    pub cartridge: Box<dyn Mapper>,
 
     // 8 KB of Work RAM.
     pub work_ram: Box<[u8; 0x8000]>,
@@ -36,6 +80,10 @@ pub(crate) struct Bus {
     /// The Game Boy joypad subsystem.
     pub joypad: Joypad,
 
+    /// The Game Boy serial port.
+    /// Contains SB and SC registers.
+    pub serial: Serial,
+
     /// $FF0F - IF register. (Set bits here to request interrupts).
     pub if_reg: Rc<RefCell<u8>>,
 
@@ -45,12 +93,31 @@ pub(crate) struct Bus {
     /// $FF50 - BOOT register. Set to non-zero value to un-map bootrom.
     pub boot_reg: u8,
 
+    /// A dump of a real boot ROM, run instead of the built in one.
+    pub boot_rom: Option<Vec<u8>>,
+
     /// Is CGB mode enabled or not.
     pub cgb_mode: bool,
 
     /// SVBK - WRAM Bank.
     pub wram_bank: usize,
 
+    /// $FF46 - DMA, the high byte of the OAM DMA source.
+    pub oam_dma_reg: u8,
+
+    /// Signals whether OAM DMA is currently active.
+    pub oam_dma_active: bool,
+
+    /// M-cycles left until the OAM DMA copies its first byte.
+    pub oam_dma_delay: u8,
+
+    /// Set while the OAM DMA is copying bytes, and while a
+    /// transfer restarted during the copy is being set up.
+    pub oam_dma_blocking: bool,
+
+    /// The index of the next byte the OAM DMA copies.
+    pub oam_dma_index: u8,
+
     /// $FF51 - HDMA1
     pub dma_src_high: u8,
 
@@ -78,21 +145,158 @@ pub(crate) struct Bus {
     /// The HDMA destination where to transfer the next byte.
     pub hdma_dst: u16,
 
-    /// $FF4D - KEY1.
+    /// CPU M-cycles the CPU still has to pause for,
+    /// while a VRAM DMA copies the bytes.
+    pub dma_stall: u32,
+
+    /// $FF4D - KEY1, the current speed
+    /// in bit 7 and the prepare bit in bit 0.
     pub speed_reg: u8,
+
+    /// How many CPU M-cycles make up one M-cycle of
+    /// the other components, 1 when not overclocked.
+    pub overclock: u8,
+
+    /// CPU M-cycles executed since the components were last ticked.
+    overclock_cycles: u8,
+
+    /// The enabled cheat codes.
+    pub cheats: Vec<CheatCode>,
+
+    /// Tracks writes to RAM, if a snapshot was taken.
+    pub ram_diff: Option<RamDiff>,
+
+    /// Counts reads per ROM bank, if enabled.
+    pub rom_bank_stats: Option<RomBankStats>,
+
+    /// Logs writes to the APU's registers, if logging was started.
+    pub audio_log: Option<AudioLog>,
+
+    /// Where the host time went.
+    #[cfg(feature = "perf-counters")]
+    pub perf: PerfCounters,
+
+    /// Whether the boot ROM refuses to start games
+    /// with a bad logo or header checksum.
+    pub check_header: bool,
+
+    /// Set when the boot ROM refused to start the game.
+    pub boot_failed: bool,
+
+    /// Devices attached from outside the core, with the addresses
+    /// they see. Kept across resets, and not part of save states.
+    pub peripherals: Vec<(RangeInclusive<u16>, Box<dyn Peripheral>)>,
 }
 
-impl Bus {
-    /// Create a new `Bus` instance.
-    pub fn new(rom: &[u8], callback: Box<dyn Fn(&[f32])>, save_file: Option<Vec<u8>>) -> Self {
-        let cartridge: Box<dyn Cartridge> = match rom[0x0147] {
-            0x00 => Box::new(RomOnly::new(rom)),
-            0x01..=0x03 => Box::new(Mbc1::new(rom)),
-            0x0F..=0x13 => Box::new(Mbc3::new(rom, save_file)),
-            0x19..=0x1E => Box::new(Mbc5::new(rom)),
-            _ => panic!("unsupported cartridge type"),
+/// The memory system the CPU runs against.
+///
+/// `Bus` implements it with the Game Boy's memory map, other
+/// implementations can plug in custom memory maps and peripherals.
+pub trait MemoryBus {
+    /// Read a byte, the rest of the system runs for one M cycle.
+    fn read(&mut self, addr: u16) -> u8;
+
+    /// Write a byte, the rest of the system runs for one M cycle.
+    fn write(&mut self, addr: u16, value: u8);
+
+    /// Run the rest of the system for one M cycle, without an access.
+    fn tick(&mut self);
+
+    /// Get the interrupts that are both enabled and requested,
+    /// IE & IF. Bit 0 is VBlank, bit 4 is the joypad.
+    fn pending_interrupts(&self) -> u8;
+
+    /// Clear the interrupt's request flag, as it's dispatched.
+    fn acknowledge_interrupt(&mut self, bit: u8);
+
+    /// Get the joypad input lines of P1, a cleared bit means a selected
+    /// key is held. STOP waits for one of them to go low.
+    fn joypad_lines(&self) -> u8 {
+        0x0F
+    }
+
+    /// Take the M-cycles the CPU has to pause for since the
+    /// last call, while a VRAM DMA was copying bytes.
+    fn take_dma_stall(&mut self) -> u32 {
+        0
+    }
+
+    /// Switch between normal and double speed, if a switch was
+    /// prepared through KEY1. Called by STOP, returns true if
+    /// the speed changed.
+    fn switch_speed(&mut self) -> bool {
+        false
+    }
+
+    /// Check if the CPU clock runs at double speed,
+    /// which KEY1 keeps track of.
+    fn double_speed(&self) -> bool {
+        false
+    }
+}
+
+impl MemoryBus for Bus {
+    fn read(&mut self, addr: u16) -> u8 {
+        if self.oam_dma_blocks(addr) {
+            self.tick();
+
+            return 0xFF;
+        }
+
+        self.read_byte(addr, true)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if self.oam_dma_blocks(addr) {
+            self.tick();
+
+            return;
+        }
+
+        self.write_byte(addr, value, true);
+    }
+
+    fn tick(&mut self) {
+        Bus::tick(self);
+    }
+
+    fn pending_interrupts(&self) -> u8 {
+        self.ie_reg & *self.if_reg.borrow() & 0x1F
+    }
+
+    fn acknowledge_interrupt(&mut self, bit: u8) {
+        res_bit!(self.if_reg.borrow_mut(), bit);
+    }
+
+    fn joypad_lines(&self) -> u8 {
+        self.joypad.input_lines()
+    }
+
+    fn take_dma_stall(&mut self) -> u32 {
+        core::mem::take(&mut self.dma_stall)
+    }
+
+    fn switch_speed(&mut self) -> bool {
+        if !self.cgb_mode || (self.speed_reg & 0x01) == 0 {
+            return false;
+        }
+
+        self.speed_reg = match self.speed() {
+            Speed::Normal => 0b1000_0000,
+            Speed::Double => 0b0000_0000,
         };
 
+        true
+    }
+
+    fn double_speed(&self) -> bool {
+        self.speed() == Speed::Double
+    }
+}
+
+impl Bus {
+    /// Create a new `Bus` instance.
+    pub fn new(cartridge: Box<dyn Mapper>, callback: AudioCallback) -> Self {
         let if_reg = Rc::new(RefCell::new(0));
         let cgb_mode = cartridge.has_cgb_support();
 
@@ -102,13 +306,19 @@ impl Bus {
             high_ram: Box::new([0; 0x7F]),
             timer: Timer::new(Rc::clone(&if_reg)),
             ppu: Ppu::new(Rc::clone(&if_reg), cgb_mode),
-            apu: Apu::new(callback),
+            apu: Apu::new(callback, cgb_mode),
             joypad: Joypad::new(Rc::clone(&if_reg)),
+            serial: Serial::new(Rc::clone(&if_reg), cgb_mode),
             ie_reg: 0,
             if_reg,
             boot_reg: 0,
             cgb_mode,
             wram_bank: 1,
+            oam_dma_reg: 0xFF,
+            oam_dma_active: false,
+            oam_dma_delay: 0,
+            oam_dma_blocking: false,
+            oam_dma_index: 0,
             dma_src_high: 0,
             dma_src_low: 0,
             dma_dst_high: 0,
@@ -118,32 +328,177 @@ impl Bus {
             hdma_len: 0,
             hdma_dst: 0,
             hdma_src: 0,
+            dma_stall: 0,
             speed_reg: 0,
+            overclock: 1,
+            overclock_cycles: 0,
+            cheats: Vec::new(),
+            ram_diff: None,
+            rom_bank_stats: None,
+            audio_log: None,
+            #[cfg(feature = "perf-counters")]
+            perf: PerfCounters::default(),
+            check_header: false,
+            boot_failed: false,
+            boot_rom: None,
+            peripherals: Vec::new(),
         }
     }
 
+    /// Reset everything but the cartridge to its power on state.
+    /// Settings like the overclock are kept.
+    pub fn reset(&mut self) {
+        let if_reg = Rc::new(RefCell::new(0));
+
+        *self.work_ram = [0; 0x8000];
+        *self.high_ram = [0; 0x7F];
+        self.timer = Timer::new(Rc::clone(&if_reg));
+        self.ppu.reset(Rc::clone(&if_reg));
+        self.apu.reset();
+        self.joypad = Joypad::new(Rc::clone(&if_reg));
+        self.serial.reset(Rc::clone(&if_reg));
+        self.ie_reg = 0;
+        self.if_reg = if_reg;
+        self.boot_reg = 0;
+        self.boot_failed = false;
+        self.wram_bank = 1;
+        self.oam_dma_reg = 0xFF;
+        self.oam_dma_active = false;
+        self.oam_dma_delay = 0;
+        self.oam_dma_blocking = false;
+        self.oam_dma_index = 0;
+        self.dma_src_high = 0;
+        self.dma_src_low = 0;
+        self.dma_dst_high = 0;
+        self.dma_dst_low = 0;
+        self.dma_control = 0;
+        self.hdma_active = false;
+        self.hdma_len = 0;
+        self.hdma_dst = 0;
+        self.hdma_src = 0;
+        self.dma_stall = 0;
+        self.speed_reg = 0;
+        self.overclock_cycles = 0;
+    }
+
+    /// Serialize the state of the bus and
+    /// all the components attached to it.
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(self.work_ram.as_ref());
+        w.write_bytes(self.high_ram.as_ref());
+
+        w.write_u8(*self.if_reg.borrow());
+        w.write_u8(self.ie_reg);
+        w.write_u8(self.boot_reg);
+        w.write_u8(self.wram_bank as u8);
+        w.write_u8(self.speed_reg);
+
+        w.write_u8(self.oam_dma_reg);
+        w.write_bool(self.oam_dma_active);
+        w.write_u8(self.oam_dma_delay);
+        w.write_bool(self.oam_dma_blocking);
+        w.write_u8(self.oam_dma_index);
+        w.write_u8(self.dma_src_high);
+        w.write_u8(self.dma_src_low);
+        w.write_u8(self.dma_dst_high);
+        w.write_u8(self.dma_dst_low);
+        w.write_u8(self.dma_control);
+        w.write_bool(self.hdma_active);
+        w.write_u16(self.hdma_len);
+        w.write_u16(self.hdma_src);
+        w.write_u16(self.hdma_dst);
+        w.write_u32(self.dma_stall);
+
+        self.timer.save_state(w);
+        self.ppu.save_state(w);
+        self.apu.save_state(w);
+        self.joypad.save_state(w);
+        self.serial.save_state(w);
+
+        w.write_blob(|state| self.cartridge.save_state(state));
+    }
+
+    /// Restore the state of the bus and
+    /// all the components attached to it.
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        r.read_into(self.work_ram.as_mut())?;
+        r.read_into(self.high_ram.as_mut())?;
+
+        *self.if_reg.borrow_mut() = r.read_u8()?;
+        self.ie_reg = r.read_u8()?;
+        self.boot_reg = r.read_u8()?;
+        self.wram_bank = r.read_u8_in(1..=7)? as usize;
+        self.speed_reg = r.read_u8()?;
+
+        self.oam_dma_reg = r.read_u8()?;
+        self.oam_dma_active = r.read_bool()?;
+        self.oam_dma_delay = r.read_u8()?;
+        self.oam_dma_blocking = r.read_bool()?;
+        self.oam_dma_index = r.read_u8_in(0..=0xA0)?;
+        self.dma_src_high = r.read_u8()?;
+        self.dma_src_low = r.read_u8()?;
+        self.dma_dst_high = r.read_u8()?;
+        self.dma_dst_low = r.read_u8()?;
+        self.dma_control = r.read_u8()?;
+        self.hdma_active = r.read_bool()?;
+        self.hdma_len = r.read_u16()?;
+        self.hdma_src = r.read_u16()?;
+        self.hdma_dst = r.read_u16()?;
+        self.dma_stall = r.read_u32()?;
+
+        self.timer.load_state(r)?;
+        self.ppu.load_state(r)?;
+        self.apu.load_state(r)?;
+        self.joypad.load_state(r)?;
+        self.serial.load_state(r)?;
+
+        self.cartridge.load_state(r.read_blob()?)
+    }
+
     /// Read a byte from the given address.
     /// Tick the components if specified.
     pub fn read_byte(&mut self, addr: u16, tick: bool) -> u8 {
-        let value = match addr {
-            0x0000..=0x00FF if self.boot_reg == 0 => {
-                if self.cgb_mode {
-                    CGB_BOOT_ROM[addr as usize]
-                } else {
-                    DMG_BOOT_ROM[addr as usize]
-                }
+        let value = self.peek_byte(addr);
+
+        if self.rom_bank_stats.is_some() && addr < 0x8000 && !self.boot_rom_mapped(addr) {
+            let bank = self.bank_at(addr);
+
+            if let Some(stats) = self.rom_bank_stats.as_mut() {
+                stats.record(bank);
             }
+        }
+
+        let value = if self.peripherals.is_empty() {
+            value
+        } else {
+            self.read_peripherals(addr, value)
+        };
+
+        if tick {
+            self.tick();
+        }
 
-            0x0200..=0x08FF if self.boot_reg == 0 && self.cgb_mode => CGB_BOOT_ROM[addr as usize],
+        value
+    }
+
+    /// Read a byte from the given address without
+    /// ticking any of the components.
+    pub fn peek_byte(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x00FF if self.boot_reg == 0 => self.boot_rom()[addr as usize],
+
+            0x0200..=0x08FF if self.boot_reg == 0 && self.cgb_mode => {
+                self.boot_rom()[addr as usize]
+            }
 
             // ROM Banks.
-            0x0000..=0x7FFF => self.cartridge.read_byte(addr),
+            0x0000..=0x7FFF => self.read_rom(addr),
 
             // Video RAM, rerouted to PPU.
             0x8000..=0x9FFF => self.ppu.read_byte(addr),
 
             // External RAM
-            0xA000..=0xBFFF => self.cartridge.read_byte(addr),
+            0xA000..=0xBFFF => self.cartridge.read_ram(addr),
 
             // Work RAM.
             0xC000..=0xCFFF => self.work_ram[(addr - 0xC000) as usize],
@@ -169,6 +524,9 @@ impl Bus {
             // P1 - JOYP register.
             0xFF00 => self.joypad.read_byte(addr),
 
+            // SB and SC registers.
+            0xFF01..=0xFF02 => self.serial.read_byte(addr),
+
             // DIV, TIMA and co.
             0xFF04..=0xFF07 => self.timer.read_byte(addr),
 
@@ -184,9 +542,10 @@ impl Bus {
             }
 
             // DMA transfer request.
-            0xFF46 => 0xFF,
+            0xFF46 => self.oam_dma_reg,
 
-            0xFF4D => self.speed_reg,
+            // The unused bits read as 1.
+            0xFF4D => 0b0111_1110 | self.speed_reg,
 
             0xFF50 => {
                 if self.boot_reg != 0 {
@@ -196,9 +555,13 @@ impl Bus {
                 }
             }
 
-            // HDMA5.
+            // HDMA5. Bit 7 is cleared while a HBlank DMA is
+            // active, the rest are the blocks left minus one.
             0xFF55 if self.cgb_mode => self.dma_control,
 
+            // OPRI.
+            0xFF6C if self.cgb_mode => self.ppu.read_byte(addr),
+
             // SVBK.
             0xFF70 if self.cgb_mode => self.wram_bank as u8,
 
@@ -209,30 +572,135 @@ impl Bus {
             0xFFFF => self.ie_reg,
 
             _ => 0xFF,
-        };
+        }
+    }
 
-        if tick {
-            self.tick();
+    /// Check if the boot ROM is mapped over the cartridge at the address.
+    fn boot_rom_mapped(&self, addr: u16) -> bool {
+        self.boot_reg == 0 && (addr < 0x0100 || (self.cgb_mode && (0x0200..0x0900).contains(&addr)))
+    }
+
+    /// Get the bank currently mapped at the given address.
+    pub fn bank_at(&self, addr: u16) -> u16 {
+        match addr {
+            0x0000..=0x00FF if self.boot_reg == 0 => 0,
+            0x0200..=0x08FF if self.boot_reg == 0 && self.cgb_mode => 0,
+            0x0000..=0x7FFF => self.cartridge.rom_bank(addr),
+            0x8000..=0x9FFF => self.ppu.vram_bank(),
+            0xA000..=0xBFFF => self.cartridge.ram_bank(),
+            0xD000..=0xDFFF if self.cgb_mode => self.wram_bank as u16,
+            0xD000..=0xDFFF => 1,
+
+            _ => 0,
         }
+    }
 
-        value
+    /// Get every byte of RAM with its address, cartridge RAM
+    /// first, then all work RAM banks and high RAM.
+    pub fn ram_contents(&self) -> Vec<(BankedAddress, u8)> {
+        let mut contents = Vec::new();
+
+        if let Some(ram) = self.cartridge.dump_ram() {
+            for (i, &value) in ram.iter().enumerate() {
+                let address = BankedAddress::new((i / 0x2000) as u16, 0xA000 + (i % 0x2000) as u16);
+
+                contents.push((address, value));
+            }
+        }
+
+        let wram_banks = if self.cgb_mode { 8 } else { 2 };
+
+        for (i, &value) in self.work_ram[..wram_banks * 0x1000].iter().enumerate() {
+            let bank = i / 0x1000;
+            let base = if bank == 0 { 0xC000 } else { 0xD000 };
+
+            contents.push((
+                BankedAddress::new(bank as u16, base + (i % 0x1000) as u16),
+                value,
+            ));
+        }
+
+        for (i, &value) in self.high_ram.iter().enumerate() {
+            contents.push((BankedAddress::new(0, 0xFF80 + i as u16), value));
+        }
+
+        contents
     }
 
-    /// Write a byte to the given address.
+    /// Write a byte to the given address, recording
+    /// changes to RAM if a snapshot was taken.
     /// Tick the components if specified.
     pub fn write_byte(&mut self, addr: u16, value: u8, tick: bool) {
+        // Echo RAM mirrors work RAM.
+        let ram_addr = match addr {
+            0xE000..=0xFDFF => addr - 0x2000,
+            _ => addr,
+        };
+
+        if self.ram_diff.is_none() || !matches!(ram_addr, 0xA000..=0xDFFF | 0xFF80..=0xFFFE) {
+            self.write_memory(addr, value, tick);
+
+            return;
+        }
+
+        let before = self.peek_byte(ram_addr);
+
+        self.write_memory(addr, value, tick);
+
+        if self.peek_byte(ram_addr) != before {
+            let address = BankedAddress::new(self.bank_at(ram_addr), ram_addr);
+
+            if let Some(ram_diff) = self.ram_diff.as_mut() {
+                ram_diff.record(address);
+            }
+        }
+    }
+
+    /// Let the peripherals at the address see a read.
+    fn read_peripherals(&mut self, addr: u16, value: u8) -> u8 {
+        self.peripherals
+            .iter_mut()
+            .filter(|(addresses, _)| addresses.contains(&addr))
+            .fold(value, |value, (_, peripheral)| peripheral.read(addr, value))
+    }
+
+    /// Let the peripherals at the address see a write, returning
+    /// the value to write, or `None` if one of them took it.
+    fn write_peripherals(&mut self, addr: u16, mut value: u8) -> Option<u8> {
+        for (addresses, peripheral) in self.peripherals.iter_mut() {
+            if addresses.contains(&addr) {
+                value = peripheral.write(addr, value)?;
+            }
+        }
+
+        Some(value)
+    }
+
+    fn write_memory(&mut self, addr: u16, value: u8, tick: bool) {
+        let value = match self.write_peripherals(addr, value) {
+            Some(value) => value,
+
+            None => {
+                if tick {
+                    self.tick();
+                }
+
+                return;
+            }
+        };
+
         match addr {
             // First 256 bytes map to bootrom.
             0x0000..=0x00FF if self.boot_reg == 0 => {}
 
             // ROM Banks.
-            0x0000..=0x7FFF => self.cartridge.write_byte(addr, value),
+            0x0000..=0x7FFF => self.cartridge.write_rom(addr, value),
 
             // Video RAM, rerouted to PPU.
             0x8000..=0x9FFF => self.ppu.write_byte(addr, value),
 
             // External RAM
-            0xA000..=0xBFFF => self.cartridge.write_byte(addr, value),
+            0xA000..=0xBFFF => self.cartridge.write_ram(addr, value),
 
             // Work RAM.
             0xC000..=0xCFFF => self.work_ram[(addr - 0xC000) as usize] = value,
@@ -258,36 +726,63 @@ impl Bus {
             // P1 - JOYP register.
             0xFF00 => self.joypad.write_byte(addr, value),
 
+            // SB and SC registers.
+            0xFF01..=0xFF02 => self.serial.write_byte(addr, value),
+
             // DIV, TIMA and co.
-            0xFF04..=0xFF07 => self.timer.write_byte(addr, value),
+            // Resetting DIV while the bit the frame sequencer is
+            // watching is set counts as a falling edge.
+            0xFF04 => {
+                if self.timer.div_apu_bit(self.speed()) {
+                    self.apu.clock_frame_sequencer();
+                }
+
+                self.timer.write_byte(addr, value);
+            }
+
+            0xFF05..=0xFF07 => self.timer.write_byte(addr, value),
 
             // IF register.
             0xFF0F => *self.if_reg.borrow_mut() = value,
 
             // APU's IO registers.
-            0xFF10..=0xFF26 | 0xFF30..=0xFF3F => self.apu.write_byte(addr, value),
+            0xFF10..=0xFF26 | 0xFF30..=0xFF3F => {
+                if let Some(audio_log) = self.audio_log.as_mut() {
+                    audio_log.record(addr, value);
+                }
+
+                self.apu.write_byte(addr, value);
+            }
 
             // PPU's IO registers.
             0xFF40..=0xFF45 | 0xFF47..=0xFF4B | 0xFF4F | 0xFF68 | 0xFF69..=0xFF6B => {
                 self.ppu.write_byte(addr, value);
             }
 
-            // DMA transfer request.
+            // DMA transfer request. The bytes are copied one per
+            // M-cycle after a cycle of setup, see `tick_oam_dma`.
+            // Writing again restarts the transfer, the bus
+            // stays blocked while the new one is set up.
             0xFF46 => {
-                let source = (value as u16) * 0x100;
-
-                for i in 0..0xA0 {
-                    let byte = self.read_byte(source + i, false);
-
-                    self.write_byte(0xFE00 + i, byte, false);
-                }
+                self.oam_dma_reg = value;
+                self.oam_dma_active = true;
+                self.oam_dma_delay = 2;
+                self.oam_dma_index = 0;
             }
 
-            0xFF4D => self.speed_reg = value & 0b0000_0001,
-
-            // BOOT register.
-            0xFF50 => {
-                if self.boot_reg == 0 {
+            // Only the prepare bit is writable, the current
+            // speed is kept until STOP switches it.
+            0xFF4D => self.speed_reg = (self.speed_reg & 0b1000_0000) | (value & 0b0000_0001),
+
+            // BOOT register, only writable until the boot ROM is unmapped.
+            // The real boot ROM hangs before handing off when the header is bad.
+            0xFF50 if self.boot_reg == 0 => {
+                if value != 0
+                    && self.check_header
+                    && !CartridgeInfo::read(self.cartridge.as_ref()).passes_boot_checks()
+                {
+                    self.boot_failed = true;
+                } else {
                     self.boot_reg = value;
                 }
             }
@@ -319,11 +814,12 @@ impl Bus {
 
                 // Source and destination addresses of the DMA.
                 let src = ((self.dma_src_high as u16) << 8) | ((self.dma_src_low & 0xF0) as u16);
-                let dst = ((self.dma_dst_high as u16) << 8) | ((self.dma_dst_low & 0xF0) as u16);
+                let dst =
+                    (((self.dma_dst_high & 0x1F) as u16) << 8) | ((self.dma_dst_low & 0xF0) as u16);
 
                 // Check if the DMA is a GDMA or a HDMA.
                 if (value & 0x80) != 0 {
-                    self.dma_control = value;
+                    self.dma_control = value & 0x7F;
 
                     self.hdma_len = len;
                     self.hdma_dst = dst;
@@ -332,24 +828,25 @@ impl Bus {
                 } else {
                     // If HDMA was activated earlier and top bit is 0 it means
                     // instead of GDMA the ROM wants to cancel the earlier DMA.
+                    // The blocks that are left can still be read.
                     if self.hdma_active {
                         self.hdma_active = false;
-                        self.dma_control = 0xFF;
+                        self.dma_control |= 0x80;
                         return;
                     }
 
-                    // We copy all the bytes instantly and not worry about
-                    // timings currently.
-                    for i in 0..len {
-                        let value = self.read_byte(src + i, false);
-
-                        self.write_byte(dst + i, value, false);
+                    // The bytes are copied at once, the CPU
+                    // pauses for as long as the copy takes.
+                    for block in (0..len).step_by(0x10) {
+                        self.copy_dma_block(src.wrapping_add(block), dst + block);
                     }
 
                     self.dma_control = 0xFF;
                 }
             }
 
+            0xFF6C if self.cgb_mode => self.ppu.write_byte(addr, value),
+
             0xFF70 if self.cgb_mode => {
                 let bank = (value & 0b111) as usize;
 
@@ -370,6 +867,58 @@ impl Bus {
         }
     }
 
+    /// Read from ROM, with Game Genie codes applied.
+    fn read_rom(&self, addr: u16) -> u8 {
+        let value = self.cartridge.read_rom(addr);
+
+        for cheat in self.cheats.iter() {
+            if let CheatCode::GameGenie {
+                address,
+                value: patched,
+                compare,
+            } = *cheat
+            {
+                if address == addr && compare.is_none_or(|compare| compare == value) {
+                    return patched;
+                }
+            }
+        }
+
+        value
+    }
+
+    /// Write the values of GameShark codes to memory, done once per
+    /// frame. Writes to ROM would change the MBC's registers,
+    /// so those are skipped.
+    pub fn apply_cheats(&mut self) {
+        for i in 0..self.cheats.len() {
+            if let CheatCode::GameShark { address, value } = self.cheats[i] {
+                if address >= 0x8000 {
+                    self.write_byte(address, value, false);
+                }
+            }
+        }
+    }
+
+    /// Get the boot ROM of the system being emulated.
+    fn boot_rom(&self) -> &[u8] {
+        match &self.boot_rom {
+            Some(boot_rom) => boot_rom,
+            None if self.cgb_mode => CGB_BOOT_ROM,
+            None => DMG_BOOT_ROM,
+        }
+    }
+
+    /// Get how large the boot ROM of the system being emulated is, the
+    /// CGB's is mapped around the cartridge header at 0x0100 - 0x01FF.
+    pub fn boot_rom_size(&self) -> usize {
+        if self.cgb_mode {
+            0x900
+        } else {
+            0x100
+        }
+    }
+
     /// Skip the bootrom, and initialize all the registers.
     pub fn skip_bootrom(&mut self) {
         self.write_byte(0xFF40, 0x91, false);
@@ -380,42 +929,126 @@ impl Bus {
         self.boot_reg = 1;
     }
 
-    /// Check if we are in double speed mode.
-    pub fn is_double_speed(&self) -> bool {
-        (self.speed_reg & 0b1000_0000) != 0
+    /// Get the speed of the CPU clock.
+    pub fn speed(&self) -> Speed {
+        if (self.speed_reg & 0b1000_0000) != 0 {
+            Speed::Double
+        } else {
+            Speed::Normal
+        }
     }
 
     /// Tick the components on the Bus.
     pub fn tick(&mut self) {
-        let cycles = 4 >> (self.is_double_speed() as u8);
+        // When overclocked, the components only see every
+        // `overclock`th M-cycle of the CPU.
+        self.overclock_cycles += 1;
+
+        if self.overclock_cycles < self.overclock {
+            return;
+        }
+
+        self.overclock_cycles = 0;
+
+        let speed = self.speed();
+
+        // The CPU clock domain, ticked once per M-cycle
+        // which is twice as fast in double speed mode.
+        if self.oam_dma_active {
+            self.tick_oam_dma();
+        }
+
+        if self.timer.tick(speed) {
+            self.apu.clock_frame_sequencer();
+        }
+
+        self.serial.tick();
+        self.cartridge.tick_rtc();
+
+        for (_, peripheral) in self.peripherals.iter_mut() {
+            *self.if_reg.borrow_mut() |= peripheral.tick() & 0x1F;
+        }
+
+        // The system clock domain, ticked by the T-cycles that passed
+        // so it keeps the same speed in double speed mode.
+        let cycles = speed.system_cycles(1);
+
+        if let Some(audio_log) = self.audio_log.as_mut() {
+            audio_log.tick(cycles);
+        }
 
-        self.timer.tick();
-        self.apu.tick(cycles);
+        timed!(self.perf.apu, self.apu.tick(cycles));
 
-        let entered_hblank = self.ppu.tick(cycles);
+        let entered_hblank = timed!(self.perf.ppu, self.ppu.tick(cycles));
 
         // If we entered HBlank and HDMA is active perform
         // a transfer of 0x10 bytes.
         if entered_hblank && self.hdma_active {
-            for i in 0..0x10 {
-                let byte = self.read_byte(self.hdma_src + i, false);
-
-                // The destination is always in VRAM.
-                self.ppu
-                    .write_byte(((self.hdma_dst + i) & 0x1FFF) + 0x8000, byte);
-            }
+            self.copy_dma_block(self.hdma_src, self.hdma_dst);
 
             self.hdma_len -= 0x10;
-            self.hdma_src += 0x10;
+            self.hdma_src = self.hdma_src.wrapping_add(0x10);
             self.hdma_dst += 0x10;
 
-            self.dma_control -= 1;
-
             // Switch off HDMA if all bytes are transferred.
             if self.hdma_len == 0 {
                 self.dma_control = 0xFF;
                 self.hdma_active = false;
+            } else {
+                self.dma_control -= 1;
             }
         }
     }
+
+    /// Copy the next byte of an OAM DMA, or wait
+    /// out the cycle of setup before the first.
+    fn tick_oam_dma(&mut self) {
+        if self.oam_dma_delay > 0 {
+            self.oam_dma_delay -= 1;
+
+            return;
+        }
+
+        self.oam_dma_blocking = true;
+
+        let source = ((self.oam_dma_reg as u16) << 8) | self.oam_dma_index as u16;
+
+        // Sources past work RAM read from echo RAM.
+        let source = if source >= 0xE000 {
+            source - 0x2000
+        } else {
+            source
+        };
+
+        let byte = self.read_byte(source, false);
+
+        self.ppu
+            .write_byte(0xFE00 + self.oam_dma_index as u16, byte);
+
+        self.oam_dma_index += 1;
+
+        if self.oam_dma_index == 0xA0 {
+            self.oam_dma_active = false;
+            self.oam_dma_blocking = false;
+        }
+    }
+
+    /// Check if an OAM DMA keeps the CPU from accessing the
+    /// address, only the IO registers and high RAM are free
+    /// while the bytes are copied.
+    fn oam_dma_blocks(&self, addr: u16) -> bool {
+        self.oam_dma_blocking && addr < 0xFF00
+    }
+
+    /// Copy a block of 0x10 bytes for a GDMA or HDMA.
+    fn copy_dma_block(&mut self, src: u16, dst: u16) {
+        for i in 0..0x10 {
+            let byte = self.read_byte(src.wrapping_add(i), false);
+
+            // The destination is always in VRAM.
+            self.ppu.write_byte(((dst + i) & 0x1FFF) + 0x8000, byte);
+        }
+
+        self.dma_stall += self.speed().dma_block_cycles();
+    }
 }
@@ -0,0 +1,191 @@
+//! Replaying timed bus accesses against a fresh system, so hardware
+//! quirks found with test ROMs can be checked without a ROM.
+//!
+//! A script has one command per line, `#` starts a comment. Addresses
+//! and values are hexadecimal, cycles are decimal M-cycles.
+//!
+//! ```text
+//! cgb                 # Run in CGB mode, has to come first.
+//! write FF40 00       # Write a byte, which takes one M-cycle.
+//! read FF41           # Read a byte, which takes one M-cycle.
+//! read FF44 = 00      # Read a byte and check its value.
+//! wait 114            # Let M-cycles pass.
+//! ```
+//!
+//! The system starts in the state the bootrom leaves it in,
+//! with an empty cartridge without RAM.
+
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use crate::{
+    bus::Bus,
+    cartridge::{Rom, RomOnly},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Command {
+    Write { address: u16, value: u8 },
+    Read { address: u16, expected: Option<u8> },
+    Wait(u32),
+}
+
+/// A parsed bus script.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BusScript {
+    cgb_mode: bool,
+
+    /// The commands, with the line they are on.
+    commands: Vec<(usize, Command)>,
+}
+
+/// The error returned for scripts with a line that isn't a valid command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseBusScriptError {
+    /// The line, starting at 1.
+    pub line: usize,
+}
+
+impl Display for ParseBusScriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} is not a valid bus script command", self.line)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseBusScriptError {}
+
+/// A read that didn't return the expected value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BusScriptFailure {
+    /// The line of the read, starting at 1.
+    pub line: usize,
+
+    pub address: u16,
+    pub expected: u8,
+    pub actual: u8,
+}
+
+impl Display for BusScriptFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: read {:02X} from {:04X}, expected {:02X}",
+            self.line, self.actual, self.address, self.expected
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BusScriptFailure {}
+
+fn parse_command(words: &[&str]) -> Option<Command> {
+    let hex_u8 = |word: &str| u8::from_str_radix(word, 16).ok();
+    let hex_u16 = |word: &str| u16::from_str_radix(word, 16).ok();
+
+    match *words {
+        ["write", address, value] => Some(Command::Write {
+            address: hex_u16(address)?,
+            value: hex_u8(value)?,
+        }),
+
+        ["read", address] => Some(Command::Read {
+            address: hex_u16(address)?,
+            expected: None,
+        }),
+
+        ["read", address, "=", expected] => Some(Command::Read {
+            address: hex_u16(address)?,
+            expected: Some(hex_u8(expected)?),
+        }),
+
+        ["wait", cycles] => cycles.parse().ok().map(Command::Wait),
+
+        _ => None,
+    }
+}
+
+impl FromStr for BusScript {
+    type Err = ParseBusScriptError;
+
+    fn from_str(script: &str) -> Result<Self, Self::Err> {
+        let mut cgb_mode = false;
+        let mut commands = Vec::new();
+
+        for (index, line) in script.lines().enumerate() {
+            let line_number = index + 1;
+            let code = line.split('#').next().unwrap_or_default();
+            let words: Vec<&str> = code.split_whitespace().collect();
+
+            if words.is_empty() {
+                continue;
+            }
+
+            // The mode can't change once the system is running.
+            if words == ["cgb"] && commands.is_empty() {
+                cgb_mode = true;
+
+                continue;
+            }
+
+            let command = parse_command(&words).ok_or(ParseBusScriptError { line: line_number })?;
+
+            commands.push((line_number, command));
+        }
+
+        Ok(Self { cgb_mode, commands })
+    }
+}
+
+impl BusScript {
+    /// Run the script against a fresh system, stopping
+    /// at the first read that returns an unexpected value.
+    pub fn run(&self) -> Result<(), BusScriptFailure> {
+        let mut rom = vec![0; 0x8000];
+
+        if self.cgb_mode {
+            rom[0x0143] = 0x80;
+        }
+
+        let mut bus = Bus::new(
+            Box::new(RomOnly::new(Rom::from(rom), None)),
+            Box::new(|_| {}),
+        );
+
+        bus.skip_bootrom();
+
+        for &(line, command) in self.commands.iter() {
+            match command {
+                Command::Write { address, value } => bus.write_byte(address, value, true),
+
+                Command::Read { address, expected } => {
+                    let actual = bus.read_byte(address, true);
+
+                    match expected {
+                        Some(expected) if expected != actual => {
+                            return Err(BusScriptFailure {
+                                line,
+                                address,
+                                expected,
+                                actual,
+                            });
+                        }
+
+                        _ => {}
+                    }
+                }
+
+                Command::Wait(cycles) => {
+                    for _ in 0..cycles {
+                        bus.tick();
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,444 +1,408 @@
+//! Cartridge mappers and the registry used to construct them.
+
+mod empty_slot;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod rom;
+mod rom_only;
+mod wisdom_tree;
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::fmt::{self, Display, Formatter};
+
+use crate::state::StateError;
+
+pub use self::{
+    empty_slot::EmptySlot,
+    mbc1::Mbc1,
+    mbc2::Mbc2,
+    mbc3::Mbc3,
+    mbc5::Mbc5,
+    rom::{Rom, RomSource},
+    rom_only::RomOnly,
+    wisdom_tree::WisdomTree,
+};
+
 /// RAM Size corresponding to indices
 /// in cartridge headers.
-const RAM_SIZES: [usize; 6] = [0x0000, 0x0000, 0x2000, 0x8000, 0x20000, 0x10000];
+pub(crate) const RAM_SIZES: [usize; 6] = [0x0000, 0x0000, 0x2000, 0x8000, 0x20000, 0x10000];
+
+/// Trait implemented by all cartridge mappers.
+///
+/// The bus routes 0x0000 - 0x7FFF to the ROM methods and
+/// 0xA000 - 0xBFFF to the RAM methods, addresses are passed
+/// through unchanged.
+pub trait Mapper {
+    /// Get the name of the mapper chip, as in "MBC1".
+    fn name(&self) -> &'static str {
+        "Custom"
+    }
 
-/// Trait implemented by all cartridges.
-pub trait Cartridge {
-    /// Return the title of the game.
-    fn game_title(&self) -> String;
+    /// Read a byte from the ROM area.
+    fn read_rom(&self, addr: u16) -> u8;
 
-    /// Read a byte from the given address.
-    fn read_byte(&self, addr: u16) -> u8;
+    /// Write a byte to the ROM area.
+    /// This is how games talk to the mapper's registers.
+    fn write_rom(&mut self, addr: u16, value: u8);
 
-    /// Write a byte to the given address.
-    fn write_byte(&mut self, addr: u16, value: u8);
+    /// Read a byte from the external RAM area.
+    fn read_ram(&self, addr: u16) -> u8;
 
-    /// Detects whether the game is a CGB game.
-    fn has_cgb_support(&self) -> bool {
-        let cgb_flag_byte = self.read_byte(0x0143);
+    /// Write a byte to the external RAM area.
+    fn write_ram(&mut self, addr: u16, value: u8);
 
-        // A game supports CGB functions if the upper bit is set.
-        (cgb_flag_byte & 0x80) != 0
-    }
+    /// Tick the cartridge's real time clock by 1 M-cycle.
+    /// Mappers without a RTC can ignore this.
+    fn tick_rtc(&mut self) {}
 
-    fn dump_ram(&self) -> Option<Vec<u8>>;
-}
+    /// Serialize the mapper's registers and RAM, appending them to
+    /// `state`. Save states are taken every frame for run-ahead,
+    /// so this shouldn't allocate anything else.
+    fn save_state(&self, state: &mut Vec<u8>);
 
-/// Cartridge with just two ROM banks.
-pub struct RomOnly {
-    /// Two ROM banks each of 4KB.
-    rom: Vec<u8>,
-}
+    /// Restore the mapper's registers and RAM from what `save_state`
+    /// appended. States that don't fit this cartridge, like those of
+    /// another game, are rejected with `StateError::CartridgeMismatch`.
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError>;
+
+    /// Dump the external RAM, if there is any.
+    fn dump_ram(&self) -> Option<Vec<u8>>;
 
-impl RomOnly {
-    /// Create a new `RomOnly` instance.
-    pub fn new(rom: &[u8]) -> Self {
-        Self { rom: rom.to_vec() }
+    /// Overwrite the external RAM, `ram` is as large as the
+    /// dump. Mappers that can't do this return false.
+    fn load_ram(&mut self, _ram: &[u8]) -> bool {
+        false
     }
-}
 
-impl Cartridge for RomOnly {
-    fn game_title(&self) -> String {
-        if self.has_cgb_support() {
-            String::from_utf8_lossy(&self.rom[0x134..=0x013E]).into()
+    /// Get the ROM bank currently mapped at the given address.
+    fn rom_bank(&self, addr: u16) -> u16 {
+        if addr < 0x4000 {
+            0
         } else {
-            String::from_utf8_lossy(&self.rom[0x134..=0x0143]).into()
+            1
         }
     }
 
-    fn read_byte(&self, addr: u16) -> u8 {
-        self.rom[addr as usize]
+    /// Get the RAM bank currently mapped to 0xA000 - 0xBFFF.
+    fn ram_bank(&self) -> u16 {
+        0
     }
 
-    fn write_byte(&mut self, _: u16, _: u8) {}
-
-    fn dump_ram(&self) -> Option<Vec<u8>> {
-        None
+    /// Return the title of the game.
+    fn game_title(&self) -> String {
+        header_title(|addr| self.read_rom(addr), self.has_cgb_support())
     }
-}
-
-/// Cartridge with the MBC1 chip.
-/// Max 16 MBit ROM and 256 KBit RAM.
-pub struct Mbc1 {
-    /// ROM with a maximum size of 16 MBit.
-    rom: Vec<u8>,
-
-    /// RAM with a maximum size of 256Kbit.
-    ram: Vec<u8>,
 
-    /// RAM gate register.
-    /// Used to enable access to the external RAM.
-    ram_enabled: bool,
-
-    /// ROM bank register (lower).
-    /// Stores the lower 5 bits of the ROM bank.
-    /// The lower 5 bits cannot contain an zero bit pattern.
-    rom_bank_lower: u8,
-
-    /// ROM bank register (upper).
-    /// Stores the upper 2 bits of the ROM bank.
-    rom_bank_upper: u8,
+    /// Detects whether the game is a CGB game.
+    fn has_cgb_support(&self) -> bool {
+        let cgb_flag_byte = self.read_rom(0x0143);
 
-    /// The banking mode currently in use.
-    banking_mode: bool,
+        // A game supports CGB functions if the upper bit is set.
+        (cgb_flag_byte & 0x80) != 0
+    }
+}
 
-    /// The number of ROM banks in the cartridge.
-    rom_banks: usize,
+/// Read the title from the header.
+fn header_title(read_rom: impl Fn(u16) -> u8, cgb_support: bool) -> String {
+    // CGB games use the last bytes of the title for
+    // the manufacturer code and the CGB flag.
+    let end = if cgb_support { 0x013E } else { 0x0143 };
 
-    /// The number of RAM banks in the cartridge.
-    ram_banks: usize,
-}
+    let title = (0x0134..=end).map(read_rom).collect::<Vec<u8>>();
 
-impl Mbc1 {
-    /// Create a new `Mbc1` instance.
-    pub fn new(rom: &[u8]) -> Self {
-        Self {
-            rom: rom.to_vec(),
-            ram: vec![0u8; RAM_SIZES[rom[0x0149] as usize]],
-            ram_enabled: false,
-            rom_bank_lower: 1,
-            rom_bank_upper: 0,
-            banking_mode: false,
-            rom_banks: 2 * 2usize.pow(rom[0x0148] as u32),
-            ram_banks: (RAM_SIZES[rom[0x0149] as usize] >> 13) as usize,
-        }
-    }
+    String::from_utf8_lossy(&title).into()
 }
 
-impl Cartridge for Mbc1 {
-    fn game_title(&self) -> String {
-        if self.has_cgb_support() {
-            String::from_utf8_lossy(&self.rom[0x134..=0x013E]).into()
-        } else {
-            String::from_utf8_lossy(&self.rom[0x134..=0x0143]).into()
-        }
-    }
+/// The logo every cartridge header has to contain.
+const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
 
-    fn read_byte(&self, addr: u16) -> u8 {
-        match addr {
-            0x0000..=0x3FFF => {
-                let bank = if self.banking_mode {
-                    (self.rom_bank_upper as usize) << 5
-                } else {
-                    0
-                } % self.rom_banks;
+/// What the cartridge header says, and whether it passes
+/// the checks the boot ROM of a real Game Boy makes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CartridgeInfo {
+    pub title: String,
+    pub cgb_support: bool,
 
-                let addr = (bank * 0x4000) + addr as usize;
+    /// The game only runs on a CGB.
+    pub cgb_only: bool,
 
-                self.rom[addr]
-            }
+    /// The game uses SGB functions.
+    pub sgb_support: bool,
 
-            0x4000..=0x7FFF => {
-                let bank = ((self.rom_bank_lower as usize) | ((self.rom_bank_upper as usize) << 5))
-                    % self.rom_banks;
+    /// The cartridge type at 0x0147, which says what mapper
+    /// and extra hardware the cartridge has.
+    pub cartridge_type: u8,
 
-                let addr = (bank * 0x4000) + (addr as usize - 0x4000);
+    /// The ROM and RAM sizes in bytes, `None`
+    /// if the header holds an unknown size.
+    pub rom_size: Option<usize>,
+    pub ram_size: Option<usize>,
 
-                self.rom[addr]
-            }
+    /// The version number of the game.
+    pub version: u8,
 
-            0xA000..=0xBFFF if self.ram_enabled => {
-                let bank = if self.banking_mode {
-                    self.rom_bank_upper as usize
-                } else {
-                    0
-                } % self.ram_banks;
+    /// The header holds the Nintendo logo.
+    pub logo_valid: bool,
 
-                let addr = (bank * 0x2000) + (addr as usize - 0xA000);
+    /// The header checksum at 0x014D.
+    pub header_checksum: u8,
 
-                self.ram[addr]
-            }
+    /// The header checksum at 0x014D matches the header.
+    pub header_checksum_valid: bool,
 
-            _ => 0xFF,
-        }
-    }
+    /// The checksum of the whole ROM at 0x014E, which
+    /// nothing checks. See `global_checksum_valid`.
+    pub global_checksum: u16,
+}
 
-    fn write_byte(&mut self, addr: u16, value: u8) {
-        match addr {
-            0x0000..=0x1FFF => {
-                self.ram_enabled = (value & 0x0F) == 0b1010;
-            }
-
-            0x2000..=0x3FFF => {
-                if (value & 0b11111) == 0 {
-                    self.rom_bank_lower = 1;
-                } else {
-                    self.rom_bank_lower = value & 0b11111;
-                }
-            }
-
-            0x4000..=0x5FFF => {
-                self.rom_bank_upper = value & 0b11;
-            }
-
-            0x6000..=0x7FFF => {
-                self.banking_mode = (value & 0b1) != 0;
-            }
-
-            0xA000..=0xBFFF if self.ram_enabled => {
-                let bank = if self.banking_mode {
-                    self.rom_bank_upper as usize
-                } else {
-                    0
-                } % self.ram_banks;
-
-                let addr = (bank * 0x2000) + (addr as usize - 0xA000);
-
-                self.ram[addr] = value;
-            }
-
-            _ => {}
-        }
+impl CartridgeInfo {
+    /// Read the header of the inserted cartridge.
+    pub(crate) fn read(cartridge: &dyn Mapper) -> Self {
+        Self::from_header(
+            |addr| cartridge.read_rom(addr),
+            cartridge.game_title(),
+            cartridge.has_cgb_support(),
+        )
     }
 
-    fn dump_ram(&self) -> Option<Vec<u8>> {
-        if !self.ram.is_empty() {
-            Some(self.ram.clone())
-        } else {
-            None
+    /// Read the header of a ROM without inserting it, which works for
+    /// cartridge types no mapper supports too. Returns `None` if the
+    /// ROM is too small to have a header.
+    pub fn parse(rom: &[u8]) -> Option<Self> {
+        if rom.len() < 0x0150 {
+            return None;
         }
-    }
-}
-
-/// Cartridge with the MBC3 chip.
-/// Max 16 Mbit ROM and 256 KBit RAM.
-pub struct Mbc3 {
-    /// ROM with a maximum size of 16 MBit.
-    rom: Vec<u8>,
-
-    /// RAM with a maximum size of 256 Kbit.
-    ram: Vec<u8>,
-
-    /// RAM gate register.
-    /// Used to enable access to the external RAM and timer.
-    ram_enabled: bool,
 
-    /// ROM Bank register.
-    /// Used to store the selected ROM bank.
-    rom_bank: u8,
+        let read_rom = |addr: u16| rom[addr as usize];
+        let cgb_support = (read_rom(0x0143) & 0x80) != 0;
 
-    /// RAMB register.
-    /// Used to store the 4 bits of the RAM bank in use.
-    ram_bank: u8,
+        Some(Self::from_header(
+            read_rom,
+            header_title(read_rom, cgb_support),
+            cgb_support,
+        ))
+    }
 
-    /// The number of ROM banks in the cartridge.
-    rom_banks: usize,
+    fn from_header(read_rom: impl Fn(u16) -> u8, title: String, cgb_support: bool) -> Self {
+        let logo_valid = NINTENDO_LOGO
+            .iter()
+            .zip(0x0104..)
+            .all(|(&byte, addr)| read_rom(addr) == byte);
 
-    /// The number of RAM banks in the cartridge.
-    ram_banks: usize,
-}
+        let checksum = (0x0134..=0x014C).fold(0u8, |checksum, addr| {
+            checksum.wrapping_sub(read_rom(addr)).wrapping_sub(1)
+        });
 
-impl Mbc3 {
-    /// Create a new `Mbc3` instance.
-    pub fn new(rom: &[u8], save_file: Option<Vec<u8>>) -> Self {
-        let mut ram = vec![0u8; RAM_SIZES[rom[0x0149] as usize]];
-
-        if !ram.is_empty() {
-            if let Some(ram_save) = save_file {
-                if ram.len() == ram_save.len() {
-                    ram.copy_from_slice(&ram_save);
-                }
-            }
-        }
+        let rom_size = match read_rom(0x0148) {
+            size @ 0x00..=0x08 => Some(0x8000 << size),
+            _ => None,
+        };
 
         Self {
-            rom: rom.to_vec(),
-            ram,
-            ram_enabled: false,
-            rom_bank: 1,
-            ram_bank: 0,
-            rom_banks: 2 * 2usize.pow(rom[0x0148] as u32),
-            ram_banks: (RAM_SIZES[rom[0x0149] as usize] >> 13) as usize,
+            title,
+            cgb_support,
+            cgb_only: cgb_support && read_rom(0x0143) == 0xC0,
+
+            // The SGB flag is ignored unless the old
+            // licensee code says to use the new one.
+            sgb_support: read_rom(0x0146) == 0x03 && read_rom(0x014B) == 0x33,
+
+            cartridge_type: read_rom(0x0147),
+            rom_size,
+            ram_size: RAM_SIZES.get(read_rom(0x0149) as usize).copied(),
+            version: read_rom(0x014C),
+            logo_valid,
+            header_checksum: read_rom(0x014D),
+            header_checksum_valid: checksum == read_rom(0x014D),
+            global_checksum: u16::from_be_bytes([read_rom(0x014E), read_rom(0x014F)]),
         }
     }
-}
 
-impl Cartridge for Mbc3 {
-    fn game_title(&self) -> String {
-        if self.has_cgb_support() {
-            String::from_utf8_lossy(&self.rom[0x134..=0x013E]).into()
-        } else {
-            String::from_utf8_lossy(&self.rom[0x134..=0x0143]).into()
-        }
+    /// Check the global checksum against the whole ROM, the sum
+    /// of all its bytes but the two of the checksum itself.
+    pub fn global_checksum_valid(&self, rom: &[u8]) -> bool {
+        let checksum = rom
+            .iter()
+            .enumerate()
+            .filter(|&(addr, _)| addr != 0x014E && addr != 0x014F)
+            .fold(0u16, |checksum, (_, &byte)| {
+                checksum.wrapping_add(byte as u16)
+            });
+
+        checksum == self.global_checksum
     }
 
-    fn read_byte(&self, addr: u16) -> u8 {
-        match addr {
-            0x0000..=0x3FFF => self.rom[addr as usize],
-
-            0x4000..=0x7FFF => {
-                let bank = self.rom_bank as usize % self.rom_banks;
-
-                let addr = (bank * 0x4000) + (addr as usize - 0x4000);
-
-                self.rom[addr]
-            }
-
-            0xA000..=0xBFFF if self.ram_enabled => {
-                let addr =
-                    (0x2000 * (self.ram_bank as usize % self.ram_banks)) + (addr as usize - 0xA000);
-
-                self.ram[addr]
-            }
-
-            _ => 0xFF,
-        }
+    /// Get what the cartridge type stands for, as in
+    /// "MBC1+RAM+BATTERY". `None` if the type is unknown.
+    pub fn cartridge_type_name(&self) -> Option<&'static str> {
+        let name = match self.cartridge_type {
+            0x00 => "ROM ONLY",
+            0x01 => "MBC1",
+            0x02 => "MBC1+RAM",
+            0x03 => "MBC1+RAM+BATTERY",
+            0x05 => "MBC2",
+            0x06 => "MBC2+BATTERY",
+            0x08 => "ROM+RAM",
+            0x09 => "ROM+RAM+BATTERY",
+            0x0B => "MMM01",
+            0x0C => "MMM01+RAM",
+            0x0D => "MMM01+RAM+BATTERY",
+            0x0F => "MBC3+TIMER+BATTERY",
+            0x10 => "MBC3+TIMER+RAM+BATTERY",
+            0x11 => "MBC3",
+            0x12 => "MBC3+RAM",
+            0x13 => "MBC3+RAM+BATTERY",
+            0x19 => "MBC5",
+            0x1A => "MBC5+RAM",
+            0x1B => "MBC5+RAM+BATTERY",
+            0x1C => "MBC5+RUMBLE",
+            0x1D => "MBC5+RUMBLE+RAM",
+            0x1E => "MBC5+RUMBLE+RAM+BATTERY",
+            0x20 => "MBC6",
+            0x22 => "MBC7+SENSOR+RUMBLE+RAM+BATTERY",
+            0xFC => "POCKET CAMERA",
+            0xFD => "BANDAI TAMA5",
+            0xFE => "HuC3",
+            0xFF => "HuC1+RAM+BATTERY",
+            _ => return None,
+        };
+
+        Some(name)
     }
 
-    fn write_byte(&mut self, addr: u16, value: u8) {
-        match addr {
-            0x0000..=0x1FFF => {
-                self.ram_enabled = (value & 0x0F) == 0b1010;
-            }
-
-            0x2000..=0x3FFF => {
-                self.rom_bank = if (value & 0b0111_1111) == 0 {
-                    1
-                } else {
-                    value & 0b0111_1111
-                };
-            }
-
-            0x4000..=0x5FFF => {
-                self.ram_bank = value & 0b11;
-            }
+    /// Check if a real Game Boy would start the game,
+    /// bad dumps usually fail this.
+    pub fn passes_boot_checks(&self) -> bool {
+        self.logo_valid && self.header_checksum_valid
+    }
+}
 
-            0xA000..=0xBFFF if self.ram_enabled => {
-                let addr =
-                    (0x2000 * (self.ram_bank as usize % self.ram_banks)) + (addr as usize - 0xA000);
+/// Errors that can occur while importing cartridge RAM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RamError {
+    /// The cartridge has no RAM, or the mapper can't import it.
+    NoRam,
 
-                self.ram[addr] = value;
-            }
+    /// The dump isn't as large as the cartridge's RAM.
+    SizeMismatch { expected: usize, actual: usize },
+}
 
-            _ => {}
-        }
-    }
+impl Display for RamError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoRam => write!(f, "the cartridge has no RAM that can be imported"),
 
-    fn dump_ram(&self) -> Option<Vec<u8>> {
-        if !self.ram.is_empty() {
-            Some(self.ram.clone())
-        } else {
-            None
+            Self::SizeMismatch { expected, actual } => write!(
+                f,
+                "the cartridge has {} bytes of RAM, but the dump is {} bytes",
+                expected, actual
+            ),
         }
     }
 }
 
-/// Cartridge with the MBC5 chip.
-/// Max 64 Mbit ROM and 1 MBit RAM.
-pub struct Mbc5 {
-    /// ROM with a maximum size of 64 MBit.
-    rom: Vec<u8>,
-
-    /// RAM with a maximum size of 1 Mbit.
-    ram: Vec<u8>,
+#[cfg(feature = "std")]
+impl std::error::Error for RamError {}
 
-    /// RAM gate register.
-    /// Used to enable access to the external RAM.
-    ram_enabled: bool,
+/// The error returned for ROMs no registered mapper handles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnsupportedCartridge;
 
-    /// ROMB0 register.
-    /// Used to store the lower 8 bits of the ROM bank.
-    rom_bank_lower: u8,
+impl Display for UnsupportedCartridge {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "unsupported cartridge type")
+    }
+}
 
-    /// ROMB1 register.
-    /// Used to store the upper 1 bit of the ROM bank.
-    rom_bank_upper: u8,
+#[cfg(feature = "std")]
+impl std::error::Error for UnsupportedCartridge {}
 
-    /// RAMB register.
-    /// Used to store the 4 bits of the RAM bank in use.
-    ram_bank: u8,
+/// Checks whether a ROM should be handled by a mapper.
+type MapperDetector = Box<dyn Fn(&[u8]) -> bool>;
 
-    /// The number of ROM banks in the cartridge.
-    rom_banks: usize,
+/// Constructs a mapper from a ROM and an optional save file.
+type MapperConstructor = Box<dyn Fn(Rom, Option<Vec<u8>>) -> Box<dyn Mapper>>;
 
-    /// The number of RAM banks in the cartridge.
-    ram_banks: usize,
+/// Registry of all the mappers the emulator knows about.
+///
+/// Mappers are picked by looking at the ROM header, the most
+/// recently registered mapper that accepts a ROM wins, which lets
+/// custom mappers override the built-in ones.
+pub struct MapperRegistry {
+    entries: Vec<(MapperDetector, MapperConstructor)>,
 }
 
-impl Mbc5 {
-    /// Create a new `Mbc5` instance.
-    pub fn new(rom: &[u8]) -> Self {
+impl MapperRegistry {
+    /// Create an empty `MapperRegistry` instance.
+    pub fn new() -> Self {
         Self {
-            rom: rom.to_vec(),
-            ram: vec![0u8; RAM_SIZES[rom[0x0149] as usize]],
-            ram_enabled: false,
-            rom_bank_lower: 1,
-            rom_bank_upper: 0,
-            ram_bank: 0,
-            rom_banks: 2 * 2usize.pow(rom[0x0148] as u32),
-            ram_banks: (RAM_SIZES[rom[0x0149] as usize] >> 13) as usize,
+            entries: Vec::new(),
         }
     }
-}
 
-impl Cartridge for Mbc5 {
-    fn game_title(&self) -> String {
-        if self.has_cgb_support() {
-            String::from_utf8_lossy(&self.rom[0x134..=0x013E]).into()
-        } else {
-            String::from_utf8_lossy(&self.rom[0x134..=0x0143]).into()
-        }
+    /// Register a mapper.
+    ///
+    /// `detect` is called with the complete ROM, the header
+    /// lives at 0x0100 - 0x014F.
+    pub fn register<D, C>(&mut self, detect: D, construct: C)
+    where
+        D: Fn(&[u8]) -> bool + 'static,
+        C: Fn(Rom, Option<Vec<u8>>) -> Box<dyn Mapper> + 'static,
+    {
+        self.entries.push((Box::new(detect), Box::new(construct)));
     }
 
-    fn read_byte(&self, addr: u16) -> u8 {
-        match addr {
-            0x0000..=0x3FFF => self.rom[addr as usize],
-
-            0x4000..=0x7FFF => {
-                let mut bank =
-                    ((self.rom_bank_upper as usize) << 8) | (self.rom_bank_lower as usize);
-
-                bank %= self.rom_banks;
-
-                let addr = (bank * 0x4000) + (addr as usize - 0x4000);
-
-                self.rom[addr]
-            }
-
-            0xA000..=0xBFFF if self.ram_enabled => {
-                let addr =
-                    (0x2000 * (self.ram_bank as usize % self.ram_banks)) + (addr as usize - 0xA000);
-
-                self.ram[addr]
-            }
-
-            _ => 0xFF,
-        }
+    /// Register a mapper for the given cartridge types (0x0147).
+    pub fn register_types<C>(&mut self, types: &'static [u8], construct: C)
+    where
+        C: Fn(Rom, Option<Vec<u8>>) -> Box<dyn Mapper> + 'static,
+    {
+        self.register(move |rom| types.contains(&rom[0x0147]), construct);
     }
 
-    fn write_byte(&mut self, addr: u16, value: u8) {
-        match addr {
-            0x0000..=0x1FFF => {
-                self.ram_enabled = value == 0b0000_1010;
-            }
-
-            0x2000..=0x2FFF => {
-                self.rom_bank_lower = value;
-            }
-
-            0x3000..=0x3FFF => {
-                self.rom_bank_upper = value & 0b1;
-            }
-
-            0x4000..=0x5FFF => {
-                self.ram_bank = value & 0b1111;
-            }
-
-            0xA000..=0xBFFF if self.ram_enabled => {
-                let addr = (0x2000 * self.ram_bank as usize) + (addr as usize - 0xA000);
-
-                self.ram[addr] = value;
-            }
-
-            _ => {}
+    /// Construct a mapper for the given ROM.
+    /// Returns `None` if no registered mapper supports it.
+    pub fn create(&self, rom: Rom, save_file: Option<Vec<u8>>) -> Option<Box<dyn Mapper>> {
+        if rom.len() < 0x0150 {
+            return None;
         }
+
+        self.entries
+            .iter()
+            .rev()
+            .find(|(detect, _)| detect(&rom))
+            .map(|(_, construct)| construct(rom, save_file))
     }
+}
 
-    fn dump_ram(&self) -> Option<Vec<u8>> {
-        if !self.ram.is_empty() {
-            Some(self.ram.clone())
-        } else {
-            None
-        }
+impl Default for MapperRegistry {
+    /// Create a `MapperRegistry` with all the built-in mappers.
+    fn default() -> Self {
+        let mut registry = Self::new();
+
+        registry.register_types(&[0x00, 0x08, 0x09], |rom, save_file| {
+            Box::new(RomOnly::new(rom, save_file))
+        });
+        registry.register(WisdomTree::detect, |rom, _| Box::new(WisdomTree::new(rom)));
+        registry.register_types(&[0x01, 0x02, 0x03], |rom, save_file| {
+            Box::new(Mbc1::new(rom, save_file))
+        });
+        registry.register_types(&[0x05, 0x06], |rom, save_file| {
+            Box::new(Mbc2::new(rom, save_file))
+        });
+        registry.register_types(&[0x0F, 0x10, 0x11, 0x12, 0x13], |rom, save_file| {
+            Box::new(Mbc3::new(rom, save_file))
+        });
+        registry.register_types(&[0x19, 0x1A, 0x1B, 0x1C, 0x1D, 0x1E], |rom, save_file| {
+            Box::new(Mbc5::new(rom, save_file))
+        });
+
+        registry
     }
 }
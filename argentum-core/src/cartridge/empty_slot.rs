@@ -0,0 +1,47 @@
+use super::Mapper;
+use crate::state::StateError;
+use alloc::vec::Vec;
+
+/// The cartridge slot with no cartridge in it.
+///
+/// Nothing drives the data lines, so every read returns
+/// 0xFF and writes go nowhere.
+pub struct EmptySlot;
+
+impl Mapper for EmptySlot {
+    fn name(&self) -> &'static str {
+        "Empty slot"
+    }
+
+    fn read_rom(&self, _: u16) -> u8 {
+        0xFF
+    }
+
+    fn write_rom(&mut self, _: u16, _: u8) {}
+
+    fn read_ram(&self, _: u16) -> u8 {
+        0xFF
+    }
+
+    fn write_ram(&mut self, _: u16, _: u8) {}
+
+    fn save_state(&self, _: &mut Vec<u8>) {}
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        if state.is_empty() {
+            Ok(())
+        } else {
+            Err(StateError::CartridgeMismatch)
+        }
+    }
+
+    fn dump_ram(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    // The CGB flag reads as set, but without a
+    // cartridge the system starts in DMG mode.
+    fn has_cgb_support(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,391 @@
+use super::{Mapper, Rom, RAM_SIZES};
+use crate::state::StateError;
+use alloc::{vec, vec::Vec};
+
+/// Cartridge with the MBC1 chip.
+/// Max 16 MBit ROM and 256 KBit RAM.
+pub struct Mbc1 {
+    /// ROM with a maximum size of 16 MBit.
+    rom: Rom,
+
+    /// RAM with a maximum size of 256Kbit.
+    ram: Vec<u8>,
+
+    /// RAM gate register.
+    /// Used to enable access to the external RAM.
+    ram_enabled: bool,
+
+    /// ROM bank register (lower).
+    /// Stores the lower 5 bits of the ROM bank.
+    /// The lower 5 bits cannot contain an zero bit pattern.
+    rom_bank_lower: u8,
+
+    /// ROM bank register (upper).
+    /// Stores the upper 2 bits of the ROM bank.
+    rom_bank_upper: u8,
+
+    /// The banking mode currently in use.
+    banking_mode: bool,
+
+    /// The number of ROM banks in the cartridge.
+    rom_banks: usize,
+
+    /// The number of RAM banks in the cartridge.
+    ram_banks: usize,
+
+    /// Is this a MBC1M multicart.
+    /// Multicarts only wire up 4 bits of the lower ROM bank
+    /// register, so the upper bank bits are shifted by 4 instead of 5.
+    multicart: bool,
+}
+
+impl Mbc1 {
+    /// Create a new `Mbc1` instance.
+    pub fn new(rom: Rom, save_file: Option<Vec<u8>>) -> Self {
+        // Large ROMs are indexed with the upper bank bits, so we
+        // go by the actual size of the ROM instead of trusting the header.
+        let rom_banks = (rom.len() / 0x4000).max(2);
+
+        let mut ram = vec![0u8; RAM_SIZES[rom[0x0149] as usize]];
+
+        if let Some(ram_save) = save_file {
+            if ram.len() == ram_save.len() {
+                ram.copy_from_slice(&ram_save);
+            }
+        }
+
+        Self {
+            ram,
+            ram_enabled: false,
+            rom_bank_lower: 1,
+            rom_bank_upper: 0,
+            banking_mode: false,
+            rom_banks,
+            ram_banks: RAM_SIZES[rom[0x0149] as usize] >> 13,
+            multicart: Self::is_multicart(&rom),
+            rom,
+        }
+    }
+
+    /// Detect a MBC1M multicart.
+    ///
+    /// Multicarts are 8 MBit carts containing multiple games, each
+    /// game starts at a multiple of bank 0x10 and has its own
+    /// Nintendo logo in the header.
+    fn is_multicart(rom: &[u8]) -> bool {
+        if rom.len() != 0x100000 {
+            return false;
+        }
+
+        let logo = &rom[0x0104..0x0134];
+
+        (1..4).any(|game| {
+            let offset = game * 0x40000;
+
+            &rom[offset + 0x0104..offset + 0x0134] == logo
+        })
+    }
+
+    /// Get the amount of bits the upper ROM bank register
+    /// is shifted by.
+    fn upper_bank_shift(&self) -> usize {
+        if self.multicart {
+            4
+        } else {
+            5
+        }
+    }
+
+    /// Get the ROM bank currently mapped to 0x0000 - 0x3FFF.
+    ///
+    /// In mode 1 the upper bank bits also apply to this region,
+    /// which lets large ROMs map banks 0x20, 0x40 and 0x60 here.
+    fn lower_rom_bank(&self) -> usize {
+        if self.banking_mode {
+            ((self.rom_bank_upper as usize) << self.upper_bank_shift()) % self.rom_banks
+        } else {
+            0
+        }
+    }
+
+    /// Get the ROM bank currently mapped to 0x4000 - 0x7FFF.
+    fn upper_rom_bank(&self) -> usize {
+        let lower = if self.multicart {
+            self.rom_bank_lower & 0x0F
+        } else {
+            self.rom_bank_lower
+        } as usize;
+
+        (lower | ((self.rom_bank_upper as usize) << self.upper_bank_shift())) % self.rom_banks
+    }
+
+    /// Get the offset into RAM for the given address, if the
+    /// cartridge has any RAM.
+    ///
+    /// In mode 1 the upper bank bits select the RAM bank, carts
+    /// with a single RAM bank simply mirror it.
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        if self.ram_banks == 0 {
+            return None;
+        }
+
+        let bank = if self.banking_mode {
+            self.rom_bank_upper as usize
+        } else {
+            0
+        } % self.ram_banks;
+
+        Some((bank * 0x2000) + (addr as usize - 0xA000))
+    }
+}
+
+impl Mapper for Mbc1 {
+    fn name(&self) -> &'static str {
+        "MBC1"
+    }
+
+    fn read_rom(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => {
+                let addr = (self.lower_rom_bank() * 0x4000) + addr as usize;
+
+                self.rom[addr]
+            }
+
+            0x4000..=0x7FFF => {
+                let addr = (self.upper_rom_bank() * 0x4000) + (addr as usize - 0x4000);
+
+                self.rom[addr]
+            }
+
+            _ => 0xFF,
+        }
+    }
+
+    fn write_rom(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = (value & 0x0F) == 0b1010;
+            }
+
+            0x2000..=0x3FFF => {
+                if (value & 0b11111) == 0 {
+                    self.rom_bank_lower = 1;
+                } else {
+                    self.rom_bank_lower = value & 0b11111;
+                }
+            }
+
+            0x4000..=0x5FFF => {
+                self.rom_bank_upper = value & 0b11;
+            }
+
+            0x6000..=0x7FFF => {
+                self.banking_mode = (value & 0b1) != 0;
+            }
+
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+
+        match self.ram_offset(addr) {
+            Some(offset) => self.ram[offset],
+            None => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, addr: u16, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+
+        if let Some(offset) = self.ram_offset(addr) {
+            self.ram[offset] = value;
+        }
+    }
+
+    fn save_state(&self, state: &mut Vec<u8>) {
+        state.extend_from_slice(&[
+            self.ram_enabled as u8,
+            self.rom_bank_lower,
+            self.rom_bank_upper,
+            self.banking_mode as u8,
+        ]);
+        state.extend_from_slice(&self.ram);
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        match state {
+            [ram_enabled, rom_bank_lower, rom_bank_upper, banking_mode, ram @ ..]
+                if ram.len() == self.ram.len() =>
+            {
+                self.ram_enabled = *ram_enabled != 0;
+                self.rom_bank_lower = *rom_bank_lower;
+                self.rom_bank_upper = *rom_bank_upper;
+                self.banking_mode = *banking_mode != 0;
+                self.ram.copy_from_slice(ram);
+
+                Ok(())
+            }
+
+            _ => Err(StateError::CartridgeMismatch),
+        }
+    }
+
+    fn dump_ram(&self) -> Option<Vec<u8>> {
+        if !self.ram.is_empty() {
+            Some(self.ram.clone())
+        } else {
+            None
+        }
+    }
+
+    fn load_ram(&mut self, ram: &[u8]) -> bool {
+        self.ram.copy_from_slice(ram);
+
+        true
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        if addr < 0x4000 {
+            self.lower_rom_bank() as u16
+        } else {
+            self.upper_rom_bank() as u16
+        }
+    }
+
+    fn ram_bank(&self) -> u16 {
+        self.ram_offset(0xA000)
+            .map_or(0, |offset| (offset / 0x2000) as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a ROM of `banks` banks, each starting with its number.
+    fn banked_rom(banks: usize, ram_size: u8) -> Vec<u8> {
+        let mut rom = vec![0u8; banks * 0x4000];
+
+        for bank in 0..banks {
+            rom[bank * 0x4000] = bank as u8;
+        }
+
+        rom[0x0147] = 0x03;
+        rom[0x0149] = ram_size;
+        rom
+    }
+
+    /// Build a MBC1M multicart, with the same logo
+    /// at the start of all four games.
+    fn multicart_rom() -> Vec<u8> {
+        let mut rom = banked_rom(64, 0);
+
+        for game in 0..4 {
+            let offset = game * 0x40000;
+
+            rom[offset + 0x0104..offset + 0x0134].copy_from_slice(&[0xCE; 48]);
+        }
+
+        rom
+    }
+
+    fn mbc1(rom: Vec<u8>) -> Mbc1 {
+        Mbc1::new(Rom::from(rom), None)
+    }
+
+    #[test]
+    fn zero_lower_bank_maps_to_the_next_bank() {
+        let mut mbc = mbc1(banked_rom(128, 0));
+
+        for (upper, bank) in [(0, 0x01), (1, 0x21), (2, 0x41), (3, 0x61)].iter() {
+            mbc.write_rom(0x4000, *upper);
+            mbc.write_rom(0x2000, 0x00);
+
+            assert_eq!(mbc.read_rom(0x4000), *bank);
+        }
+    }
+
+    #[test]
+    fn mode_1_maps_upper_bits_to_the_lower_window() {
+        let mut mbc = mbc1(banked_rom(128, 0));
+
+        for (upper, bank) in [(1, 0x20), (2, 0x40), (3, 0x60)].iter() {
+            mbc.write_rom(0x4000, *upper);
+
+            mbc.write_rom(0x6000, 0x00);
+            assert_eq!(mbc.read_rom(0x0000), 0x00);
+
+            mbc.write_rom(0x6000, 0x01);
+            assert_eq!(mbc.read_rom(0x0000), *bank);
+            assert_eq!(mbc.rom_bank(0x0000), *bank as u16);
+        }
+    }
+
+    #[test]
+    fn mode_1_lower_window_wraps_on_small_roms() {
+        let mut mbc = mbc1(banked_rom(32, 0));
+
+        mbc.write_rom(0x4000, 0x01);
+        mbc.write_rom(0x6000, 0x01);
+
+        assert_eq!(mbc.read_rom(0x0000), 0x00);
+    }
+
+    #[test]
+    fn multicart_is_detected_by_the_logo() {
+        assert!(mbc1(multicart_rom()).multicart);
+
+        // A 8 MBit game with only its own logo.
+        let mut rom = banked_rom(64, 0);
+
+        rom[0x0104..0x0134].copy_from_slice(&[0xCE; 48]);
+        assert!(!mbc1(rom).multicart);
+    }
+
+    #[test]
+    fn multicart_wires_4_bits_of_the_lower_bank() {
+        let mut mbc = mbc1(multicart_rom());
+
+        mbc.write_rom(0x4000, 0x01);
+        mbc.write_rom(0x2000, 0x1F);
+        assert_eq!(mbc.read_rom(0x4000), 0x1F);
+
+        mbc.write_rom(0x2000, 0x10);
+        assert_eq!(mbc.read_rom(0x4000), 0x10);
+
+        mbc.write_rom(0x4000, 0x03);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(0x0000), 0x30);
+    }
+
+    #[test]
+    fn ram_reads_open_bus_without_ram() {
+        let mut mbc = mbc1(banked_rom(4, 0));
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(0xA000, 0x12);
+
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+        assert_eq!(mbc.read_ram(0xBFFF), 0xFF);
+        assert_eq!(mbc.dump_ram(), None);
+    }
+
+    #[test]
+    fn ram_is_only_accessible_when_enabled() {
+        let mut mbc = mbc1(banked_rom(4, 0x02));
+
+        mbc.write_ram(0xA000, 0x12);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(0xA000, 0x12);
+        assert_eq!(mbc.read_ram(0xA000), 0x12);
+    }
+}
@@ -0,0 +1,137 @@
+use super::{Mapper, Rom};
+use crate::state::StateError;
+use alloc::{vec, vec::Vec};
+
+/// Cartridge with the MBC2 chip.
+/// Max 2 MBit ROM and 512 x 4 bits of RAM built into the MBC.
+pub struct Mbc2 {
+    /// ROM with a maximum size of 2 MBit.
+    rom: Rom,
+
+    /// The built-in RAM, only the lower 4 bits of each byte exist.
+    ram: Vec<u8>,
+
+    /// RAM gate register.
+    /// Used to enable access to the built-in RAM.
+    ram_enabled: bool,
+
+    /// ROM bank register.
+    /// Stores the 4 bits of the ROM bank, which can't be zero.
+    rom_bank: u8,
+
+    /// The number of ROM banks in the cartridge.
+    rom_banks: usize,
+}
+
+impl Mbc2 {
+    /// Create a new `Mbc2` instance.
+    pub fn new(rom: Rom, save_file: Option<Vec<u8>>) -> Self {
+        let mut ram = vec![0u8; 0x0200];
+
+        if let Some(ram_save) = save_file {
+            if ram.len() == ram_save.len() {
+                ram.copy_from_slice(&ram_save);
+            }
+        }
+
+        Self {
+            ram,
+            ram_enabled: false,
+            rom_bank: 1,
+            rom_banks: (rom.len() / 0x4000).max(2),
+            rom,
+        }
+    }
+}
+
+impl Mapper for Mbc2 {
+    fn name(&self) -> &'static str {
+        "MBC2"
+    }
+
+    fn read_rom(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => self.rom[addr as usize],
+
+            0x4000..=0x7FFF => {
+                let bank = self.rom_bank as usize % self.rom_banks;
+                let addr = (bank * 0x4000) + (addr as usize - 0x4000);
+
+                self.rom[addr]
+            }
+
+            _ => 0xFF,
+        }
+    }
+
+    fn write_rom(&mut self, addr: u16, value: u8) {
+        // Both registers share 0x0000 - 0x3FFF,
+        // bit 8 of the address selects between them.
+        if addr > 0x3FFF {
+            return;
+        }
+
+        if (addr & 0x0100) == 0 {
+            self.ram_enabled = (value & 0x0F) == 0b1010;
+        } else if (value & 0x0F) == 0 {
+            self.rom_bank = 1;
+        } else {
+            self.rom_bank = value & 0x0F;
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+
+        // The RAM is mirrored through 0xA000 - 0xBFFF,
+        // and the upper 4 bits are open bus.
+        self.ram[(addr as usize - 0xA000) & 0x01FF] | 0xF0
+    }
+
+    fn write_ram(&mut self, addr: u16, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+
+        self.ram[(addr as usize - 0xA000) & 0x01FF] = value & 0x0F;
+    }
+
+    fn save_state(&self, state: &mut Vec<u8>) {
+        state.extend_from_slice(&[self.ram_enabled as u8, self.rom_bank]);
+        state.extend_from_slice(&self.ram);
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        match state {
+            [ram_enabled, rom_bank, ram @ ..] if ram.len() == self.ram.len() => {
+                self.ram_enabled = *ram_enabled != 0;
+                self.rom_bank = *rom_bank;
+                self.ram.copy_from_slice(ram);
+
+                Ok(())
+            }
+
+            _ => Err(StateError::CartridgeMismatch),
+        }
+    }
+
+    fn dump_ram(&self) -> Option<Vec<u8>> {
+        Some(self.ram.clone())
+    }
+
+    fn load_ram(&mut self, ram: &[u8]) -> bool {
+        self.ram.copy_from_slice(ram);
+
+        true
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        if addr < 0x4000 {
+            0
+        } else {
+            (self.rom_bank as usize % self.rom_banks) as u16
+        }
+    }
+}
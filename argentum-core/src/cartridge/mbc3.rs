@@ -0,0 +1,166 @@
+use super::{Mapper, Rom, RAM_SIZES};
+use crate::state::StateError;
+use alloc::{vec, vec::Vec};
+
+/// Cartridge with the MBC3 chip.
+/// Max 16 Mbit ROM and 256 KBit RAM.
+pub struct Mbc3 {
+    /// ROM with a maximum size of 16 MBit.
+    rom: Rom,
+
+    /// RAM with a maximum size of 256 Kbit.
+    ram: Vec<u8>,
+
+    /// RAM gate register.
+    /// Used to enable access to the external RAM and timer.
+    ram_enabled: bool,
+
+    /// ROM Bank register.
+    /// Used to store the selected ROM bank.
+    rom_bank: u8,
+
+    /// RAMB register.
+    /// Used to store the 4 bits of the RAM bank in use.
+    ram_bank: u8,
+
+    /// The number of ROM banks in the cartridge.
+    rom_banks: usize,
+
+    /// The number of RAM banks in the cartridge.
+    ram_banks: usize,
+}
+
+impl Mbc3 {
+    /// Create a new `Mbc3` instance.
+    pub fn new(rom: Rom, save_file: Option<Vec<u8>>) -> Self {
+        let mut ram = vec![0u8; RAM_SIZES[rom[0x0149] as usize]];
+
+        if !ram.is_empty() {
+            if let Some(ram_save) = save_file {
+                if ram.len() == ram_save.len() {
+                    ram.copy_from_slice(&ram_save);
+                }
+            }
+        }
+
+        Self {
+            ram,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            rom_banks: 2 * 2usize.pow(rom[0x0148] as u32),
+            ram_banks: RAM_SIZES[rom[0x0149] as usize] >> 13,
+            rom,
+        }
+    }
+}
+
+impl Mapper for Mbc3 {
+    fn name(&self) -> &'static str {
+        "MBC3"
+    }
+
+    fn read_rom(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => self.rom[addr as usize],
+
+            0x4000..=0x7FFF => {
+                let bank = self.rom_bank as usize % self.rom_banks;
+
+                let addr = (bank * 0x4000) + (addr as usize - 0x4000);
+
+                self.rom[addr]
+            }
+
+            _ => 0xFF,
+        }
+    }
+
+    fn write_rom(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = (value & 0x0F) == 0b1010;
+            }
+
+            0x2000..=0x3FFF => {
+                self.rom_bank = if (value & 0b0111_1111) == 0 {
+                    1
+                } else {
+                    value & 0b0111_1111
+                };
+            }
+
+            0x4000..=0x5FFF => {
+                self.ram_bank = value & 0b11;
+            }
+
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if !self.ram_enabled || self.ram_banks == 0 {
+            return 0xFF;
+        }
+
+        let addr = (0x2000 * (self.ram_bank as usize % self.ram_banks)) + (addr as usize - 0xA000);
+
+        self.ram[addr]
+    }
+
+    fn write_ram(&mut self, addr: u16, value: u8) {
+        if !self.ram_enabled || self.ram_banks == 0 {
+            return;
+        }
+
+        let addr = (0x2000 * (self.ram_bank as usize % self.ram_banks)) + (addr as usize - 0xA000);
+
+        self.ram[addr] = value;
+    }
+
+    fn save_state(&self, state: &mut Vec<u8>) {
+        state.extend_from_slice(&[self.ram_enabled as u8, self.rom_bank, self.ram_bank]);
+        state.extend_from_slice(&self.ram);
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        match state {
+            [ram_enabled, rom_bank, ram_bank, ram @ ..] if ram.len() == self.ram.len() => {
+                self.ram_enabled = *ram_enabled != 0;
+                self.rom_bank = *rom_bank;
+                self.ram_bank = *ram_bank;
+                self.ram.copy_from_slice(ram);
+
+                Ok(())
+            }
+
+            _ => Err(StateError::CartridgeMismatch),
+        }
+    }
+
+    fn dump_ram(&self) -> Option<Vec<u8>> {
+        if !self.ram.is_empty() {
+            Some(self.ram.clone())
+        } else {
+            None
+        }
+    }
+
+    fn load_ram(&mut self, ram: &[u8]) -> bool {
+        self.ram.copy_from_slice(ram);
+
+        true
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        if addr < 0x4000 {
+            0
+        } else {
+            (self.rom_bank as usize % self.rom_banks) as u16
+        }
+    }
+
+    fn ram_bank(&self) -> u16 {
+        self.ram_bank as u16
+    }
+}
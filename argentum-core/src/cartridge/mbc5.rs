@@ -0,0 +1,190 @@
+use super::{Mapper, Rom, RAM_SIZES};
+use crate::state::StateError;
+use alloc::{vec, vec::Vec};
+
+/// Cartridge with the MBC5 chip.
+/// Max 64 Mbit ROM and 1 MBit RAM.
+pub struct Mbc5 {
+    /// ROM with a maximum size of 64 MBit.
+    rom: Rom,
+
+    /// RAM with a maximum size of 1 Mbit.
+    ram: Vec<u8>,
+
+    /// RAM gate register.
+    /// Used to enable access to the external RAM.
+    ram_enabled: bool,
+
+    /// ROMB0 register.
+    /// Used to store the lower 8 bits of the ROM bank.
+    rom_bank_lower: u8,
+
+    /// ROMB1 register.
+    /// Used to store the upper 1 bit of the ROM bank.
+    rom_bank_upper: u8,
+
+    /// RAMB register.
+    /// Used to store the 4 bits of the RAM bank in use.
+    ram_bank: u8,
+
+    /// The number of ROM banks in the cartridge.
+    rom_banks: usize,
+
+    /// The number of RAM banks in the cartridge.
+    ram_banks: usize,
+}
+
+impl Mbc5 {
+    /// Create a new `Mbc5` instance.
+    pub fn new(rom: Rom, save_file: Option<Vec<u8>>) -> Self {
+        // Go by the actual size of the ROM, a header claiming more
+        // banks than the ROM has would index past its end.
+        let rom_banks = (rom.len() / 0x4000).max(2);
+
+        let mut ram = vec![0u8; RAM_SIZES[rom[0x0149] as usize]];
+
+        if let Some(ram_save) = save_file {
+            if ram.len() == ram_save.len() {
+                ram.copy_from_slice(&ram_save);
+            }
+        }
+
+        Self {
+            ram,
+            ram_enabled: false,
+            rom_bank_lower: 1,
+            rom_bank_upper: 0,
+            ram_bank: 0,
+            rom_banks,
+            ram_banks: RAM_SIZES[rom[0x0149] as usize] >> 13,
+            rom,
+        }
+    }
+}
+
+impl Mapper for Mbc5 {
+    fn name(&self) -> &'static str {
+        "MBC5"
+    }
+
+    fn read_rom(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => self.rom[addr as usize],
+
+            0x4000..=0x7FFF => {
+                let mut bank =
+                    ((self.rom_bank_upper as usize) << 8) | (self.rom_bank_lower as usize);
+
+                bank %= self.rom_banks;
+
+                let addr = (bank * 0x4000) + (addr as usize - 0x4000);
+
+                self.rom[addr]
+            }
+
+            _ => 0xFF,
+        }
+    }
+
+    fn write_rom(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = value == 0b0000_1010;
+            }
+
+            0x2000..=0x2FFF => {
+                self.rom_bank_lower = value;
+            }
+
+            0x3000..=0x3FFF => {
+                self.rom_bank_upper = value & 0b1;
+            }
+
+            0x4000..=0x5FFF => {
+                self.ram_bank = value & 0b1111;
+            }
+
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if !self.ram_enabled || self.ram_banks == 0 {
+            return 0xFF;
+        }
+
+        let addr = (0x2000 * (self.ram_bank as usize % self.ram_banks)) + (addr as usize - 0xA000);
+
+        self.ram[addr]
+    }
+
+    fn write_ram(&mut self, addr: u16, value: u8) {
+        if !self.ram_enabled || self.ram_banks == 0 {
+            return;
+        }
+
+        let addr = (0x2000 * (self.ram_bank as usize % self.ram_banks)) + (addr as usize - 0xA000);
+
+        self.ram[addr] = value;
+    }
+
+    fn save_state(&self, state: &mut Vec<u8>) {
+        state.extend_from_slice(&[
+            self.ram_enabled as u8,
+            self.rom_bank_lower,
+            self.rom_bank_upper,
+            self.ram_bank,
+        ]);
+        state.extend_from_slice(&self.ram);
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        match state {
+            [ram_enabled, rom_bank_lower, rom_bank_upper, ram_bank, ram @ ..]
+                if ram.len() == self.ram.len() =>
+            {
+                self.ram_enabled = *ram_enabled != 0;
+                self.rom_bank_lower = *rom_bank_lower;
+                self.rom_bank_upper = *rom_bank_upper;
+                self.ram_bank = *ram_bank;
+                self.ram.copy_from_slice(ram);
+
+                Ok(())
+            }
+
+            _ => Err(StateError::CartridgeMismatch),
+        }
+    }
+
+    fn dump_ram(&self) -> Option<Vec<u8>> {
+        if !self.ram.is_empty() {
+            Some(self.ram.clone())
+        } else {
+            None
+        }
+    }
+
+    fn load_ram(&mut self, ram: &[u8]) -> bool {
+        self.ram.copy_from_slice(ram);
+
+        true
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        if addr < 0x4000 {
+            0
+        } else {
+            let bank = ((self.rom_bank_upper as usize) << 8) | (self.rom_bank_lower as usize);
+
+            (bank % self.rom_banks) as u16
+        }
+    }
+
+    fn ram_bank(&self) -> u16 {
+        if self.ram_banks == 0 {
+            0
+        } else {
+            (self.ram_bank as usize % self.ram_banks) as u16
+        }
+    }
+}
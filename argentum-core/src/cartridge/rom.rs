@@ -0,0 +1,65 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::ops::Deref;
+
+/// Anything that holds the bytes of a ROM.
+///
+/// Mappers read the ROM through this instead of keeping a copy of
+/// their own, so ROMs embedded with `include_bytes!` or memory mapped
+/// from a file are used in place.
+pub trait RomSource {
+    /// Get the whole ROM.
+    fn bytes(&self) -> &[u8];
+}
+
+impl RomSource for Vec<u8> {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl RomSource for Box<[u8]> {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl RomSource for &'static [u8] {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+/// The ROM of a cartridge, dereferences to its bytes.
+pub struct Rom {
+    source: Box<dyn RomSource>,
+}
+
+impl Rom {
+    /// Create a new `Rom` instance reading from the given source.
+    pub fn new(source: impl RomSource + 'static) -> Self {
+        Self {
+            source: Box::new(source),
+        }
+    }
+}
+
+/// Copies the ROM, `Rom::new` uses a `&'static [u8]` in place.
+impl From<&[u8]> for Rom {
+    fn from(rom: &[u8]) -> Self {
+        Self::new(rom.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Rom {
+    fn from(rom: Vec<u8>) -> Self {
+        Self::new(rom)
+    }
+}
+
+impl Deref for Rom {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.source.bytes()
+    }
+}
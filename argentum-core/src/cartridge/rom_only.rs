@@ -0,0 +1,94 @@
+use super::{Mapper, Rom, RAM_SIZES};
+use crate::state::StateError;
+use alloc::{vec, vec::Vec};
+
+/// Cartridge with just two ROM banks, and optionally
+/// up to 8 KB of RAM with no MBC in between.
+///
+/// The RAM variant is popular with homebrew, as it doesn't
+/// need any bank switching code.
+pub struct RomOnly {
+    /// Two ROM banks each of 16 KB.
+    rom: Rom,
+
+    /// RAM which is always enabled.
+    ram: Vec<u8>,
+}
+
+impl RomOnly {
+    /// Create a new `RomOnly` instance.
+    pub fn new(rom: Rom, save_file: Option<Vec<u8>>) -> Self {
+        let ram_size = RAM_SIZES
+            .get(rom[0x0149] as usize)
+            .copied()
+            .unwrap_or(0)
+            .min(0x2000);
+
+        let mut ram = vec![0u8; ram_size];
+
+        if let Some(ram_save) = save_file {
+            if ram.len() == ram_save.len() {
+                ram.copy_from_slice(&ram_save);
+            }
+        }
+
+        Self { ram, rom }
+    }
+}
+
+impl Mapper for RomOnly {
+    fn name(&self) -> &'static str {
+        "ROM only"
+    }
+
+    fn read_rom(&self, addr: u16) -> u8 {
+        self.rom.get(addr as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write_rom(&mut self, _: u16, _: u8) {}
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if self.ram.is_empty() {
+            return 0xFF;
+        }
+
+        self.ram[(addr as usize - 0xA000) % self.ram.len()]
+    }
+
+    fn write_ram(&mut self, addr: u16, value: u8) {
+        if self.ram.is_empty() {
+            return;
+        }
+
+        let len = self.ram.len();
+        self.ram[(addr as usize - 0xA000) % len] = value;
+    }
+
+    fn save_state(&self, state: &mut Vec<u8>) {
+        state.extend_from_slice(&self.ram);
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        if state.len() != self.ram.len() {
+            return Err(StateError::CartridgeMismatch);
+        }
+
+        self.ram.copy_from_slice(state);
+
+        Ok(())
+    }
+
+    fn dump_ram(&self) -> Option<Vec<u8>> {
+        if !self.ram.is_empty() {
+            Some(self.ram.clone())
+        } else {
+            None
+        }
+    }
+
+    fn load_ram(&mut self, ram: &[u8]) -> bool {
+        self.ram.copy_from_slice(ram);
+
+        true
+    }
+}
@@ -0,0 +1,92 @@
+use super::{Mapper, Rom};
+use crate::state::StateError;
+use alloc::vec::Vec;
+
+/// Cartridge with the Wisdom Tree mapper.
+///
+/// Switches the whole 32 KB ROM area at once, the bank
+/// number is taken from the lower 8 bits of the address
+/// written to, the written value is ignored.
+pub struct WisdomTree {
+    /// ROM which is switched in 32 KB banks.
+    rom: Rom,
+
+    /// The 32 KB bank currently mapped to 0x0000 - 0x7FFF.
+    rom_bank: u8,
+
+    /// The number of 32 KB banks in the cartridge.
+    rom_banks: usize,
+}
+
+impl WisdomTree {
+    /// Create a new `WisdomTree` instance.
+    pub fn new(rom: Rom) -> Self {
+        Self {
+            rom_bank: 0,
+            rom_banks: rom.len().div_ceil(0x8000).max(1),
+            rom,
+        }
+    }
+
+    /// Detect a Wisdom Tree cartridge.
+    ///
+    /// These carts claim to be ROM only in the header,
+    /// but are larger than 32 KB.
+    pub fn detect(rom: &[u8]) -> bool {
+        [0x00, 0xC0].contains(&rom[0x0147]) && rom.len() > 0x8000
+    }
+}
+
+impl Mapper for WisdomTree {
+    fn name(&self) -> &'static str {
+        "Wisdom Tree"
+    }
+
+    fn read_rom(&self, addr: u16) -> u8 {
+        let bank = self.rom_bank as usize % self.rom_banks;
+
+        self.rom
+            .get((bank * 0x8000) + addr as usize)
+            .copied()
+            .unwrap_or(0xFF)
+    }
+
+    fn write_rom(&mut self, addr: u16, _: u8) {
+        if let 0x0000..=0x3FFF = addr {
+            self.rom_bank = addr as u8;
+        }
+    }
+
+    fn read_ram(&self, _: u16) -> u8 {
+        0xFF
+    }
+
+    fn write_ram(&mut self, _: u16, _: u8) {}
+
+    fn save_state(&self, state: &mut Vec<u8>) {
+        state.push(self.rom_bank);
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        match state {
+            [rom_bank] => {
+                self.rom_bank = *rom_bank;
+
+                Ok(())
+            }
+
+            _ => Err(StateError::CartridgeMismatch),
+        }
+    }
+
+    fn dump_ram(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn rom_bank(&self, addr: u16) -> u16 {
+        // Report 16 KB banks like every other mapper.
+        let bank = (self.rom_bank as usize % self.rom_banks) * 2;
+
+        (bank + (addr >= 0x4000) as usize) as u16
+    }
+}
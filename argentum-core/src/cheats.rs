@@ -0,0 +1,88 @@
+//! GameShark and Game Genie cheat codes.
+
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+/// A decoded cheat code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheatCode {
+    /// Writes a value to RAM every frame. The codes look like
+    /// `01VVLLHH`, the RAM bank in the first byte is ignored.
+    GameShark { address: u16, value: u8 },
+
+    /// Replaces a value read from ROM. The codes look like
+    /// `VVA-AAA-CCC`, or `VVA-AAA` without the compare value.
+    GameGenie {
+        address: u16,
+        value: u8,
+
+        /// Only replace the value if the ROM holds this one,
+        /// so the code doesn't break other ROM banks.
+        compare: Option<u8>,
+    },
+}
+
+/// The error returned for codes that aren't valid
+/// GameShark or Game Genie codes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseCheatError;
+
+impl Display for ParseCheatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "not a valid GameShark or Game Genie code")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseCheatError {}
+
+impl FromStr for CheatCode {
+    type Err = ParseCheatError;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let digits = code
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_digit(16).map(|digit| digit as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or(ParseCheatError)?;
+
+        let byte = |i: usize| (digits[i] << 4) | digits[i + 1];
+
+        match digits.len() {
+            // GameShark codes have no dashes.
+            8 if !code.contains('-') => Ok(Self::GameShark {
+                address: u16::from_le_bytes([byte(4), byte(6)]),
+                value: byte(2),
+            }),
+
+            6 | 9 => {
+                // The highest nibble of the address is inverted.
+                let address = (((digits[5] ^ 0xF) as u16) << 12)
+                    | ((digits[2] as u16) << 8)
+                    | ((digits[3] as u16) << 4)
+                    | digits[4] as u16;
+
+                // The compare value is scrambled, the eighth digit is unused.
+                let compare = if digits.len() == 9 {
+                    let scrambled = (digits[6] << 4) | digits[8];
+
+                    Some(scrambled.rotate_right(2) ^ 0xBA)
+                } else {
+                    None
+                };
+
+                Ok(Self::GameGenie {
+                    address,
+                    value: byte(0),
+                    compare,
+                })
+            }
+
+            _ => Err(ParseCheatError),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! The clock domains of the system, and how CGB double speed
+//! mode affects them.
+//!
+//! The CPU, the timer and the serial port run off the CPU clock,
+//! and tick once every M-cycle. Double speed mode doubles this
+//! clock, so they run twice as fast.
+//!
+//! The PPU and the APU run off the 4 MiHz system clock, which double
+//! speed mode leaves alone. They are ticked by the T-cycles of the
+//! system clock that passed during one M-cycle of the CPU, so they
+//! keep running at the same speed.
+
+/// The speed of the CPU clock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Speed {
+    Normal,
+
+    /// CGB Mode Only, entered by a speed switch through KEY1.
+    Double,
+}
+
+impl Speed {
+    /// Get the T-cycles of the system clock
+    /// that pass during the given CPU M-cycles.
+    pub fn system_cycles(self, m_cycles: u32) -> u32 {
+        match self {
+            Speed::Normal => m_cycles * 4,
+            Speed::Double => m_cycles * 2,
+        }
+    }
+
+    /// Get the CPU M-cycles the CPU is paused for while a VRAM DMA
+    /// copies 16 bytes, which takes 32 T-cycles of the system clock.
+    pub fn dma_block_cycles(self) -> u32 {
+        match self {
+            Speed::Normal => 8,
+            Speed::Double => 16,
+        }
+    }
+
+    /// Get the bit of DIV whose falling edge clocks the APU's frame
+    /// sequencer. DIV counts in the CPU clock, so the bit moves up one
+    /// in double speed mode to keep the frame sequencer at 512 Hz.
+    pub fn div_apu_bit(self) -> u16 {
+        match self {
+            Speed::Normal => 12,
+            Speed::Double => 13,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_cycles_per_m_cycle() {
+        assert_eq!(Speed::Normal.system_cycles(1), 4);
+        assert_eq!(Speed::Double.system_cycles(1), 2);
+
+        // A frame is 70224 T-cycles in both speeds, double
+        // speed fits twice as many M-cycles in it.
+        assert_eq!(Speed::Normal.system_cycles(17556), 70224);
+        assert_eq!(Speed::Double.system_cycles(35112), 70224);
+    }
+
+    #[test]
+    fn vram_dma_takes_32_system_cycles() {
+        for &speed in [Speed::Normal, Speed::Double].iter() {
+            assert_eq!(speed.system_cycles(speed.dma_block_cycles()), 32);
+        }
+    }
+
+    #[test]
+    fn div_apu_bit_moves_up_in_double_speed() {
+        assert_eq!(Speed::Normal.div_apu_bit(), 12);
+        assert_eq!(Speed::Double.div_apu_bit(), 13);
+    }
+}
@@ -0,0 +1,148 @@
+//! The palettes the CGB boot ROM colours DMG games with.
+//!
+//! For games by Nintendo the boot ROM sums up the bytes of the title
+//! and looks the sum up in a table, some sums are shared by several
+//! games and are told apart by the fourth letter of the title. Other
+//! games get the default palettes. Holding a direction, optionally
+//! with A or B, while the logo is shown picks one of twelve palettes
+//! instead.
+//!
+//! The tables are read straight out of the bundled boot ROM.
+
+use crate::{bus::CGB_BOOT_ROM, cartridge::Mapper, joypad::ArgentumKey, ppu::scale_rgb};
+
+/// The title checksums the boot ROM knows.
+const TITLE_CHECKSUMS: usize = 0x0200;
+
+/// The number of title checksums.
+const CHECKSUM_COUNT: usize = 94;
+
+/// Checksums from this index on are told apart by the fourth letter.
+const FIRST_DUPLICATE: usize = 65;
+
+/// The palette combination of every title checksum, as offsets into
+/// the combination table. The top bit is a flag the boot ROM uses
+/// for its own purposes.
+const PALETTE_PER_CHECKSUM: usize = TITLE_CHECKSUMS + CHECKSUM_COUNT;
+
+/// The fourth letter of the titles with a shared checksum.
+const FOURTH_LETTERS: usize = PALETTE_PER_CHECKSUM + CHECKSUM_COUNT;
+
+/// Palette combinations, three bytes each holding the offsets of the
+/// OBJ0, OBJ1 and BG palettes into the palette table.
+const PALETTE_COMBINATIONS: usize = FOURTH_LETTERS + (CHECKSUM_COUNT - FIRST_DUPLICATE);
+
+/// Palettes, four little endian RGB555 colours each.
+const PALETTES: usize = 0x037E;
+
+/// The palette combination of every button combination, in
+/// the order of `BUTTON_DIRECTIONS` without a button, with A
+/// and with B.
+const PALETTE_PER_BUTTONS: usize = 0x047E;
+
+/// The directions in the order of the button combination table.
+const BUTTON_DIRECTIONS: [ArgentumKey; 4] = [
+    ArgentumKey::Right,
+    ArgentumKey::Left,
+    ArgentumKey::Up,
+    ArgentumKey::Down,
+];
+
+/// The colours a DMG game is drawn with, one set for the background
+/// and window and one for either object palette. The colours are
+/// 0xRRGGBB values from lightest to darkest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DmgPalettes {
+    pub bg: [u32; 4],
+    pub obj0: [u32; 4],
+    pub obj1: [u32; 4],
+}
+
+impl DmgPalettes {
+    /// Draw every layer with the same colours, like a DMG.
+    pub fn uniform(colours: [u32; 4]) -> Self {
+        Self {
+            bg: colours,
+            obj0: colours,
+            obj1: colours,
+        }
+    }
+
+    /// Get the palettes the CGB boot ROM picks when a direction is held,
+    /// optionally with A or B, while the logo is shown. Returns `None`
+    /// for other keys.
+    pub fn for_buttons(direction: ArgentumKey, button: Option<ArgentumKey>) -> Option<Self> {
+        let direction = BUTTON_DIRECTIONS.iter().position(|&key| key == direction)?;
+
+        let row = match button {
+            None => 0,
+            Some(ArgentumKey::ButtonA) => 1,
+            Some(ArgentumKey::ButtonB) => 2,
+            Some(_) => return None,
+        };
+
+        Some(Self::from_combination(
+            CGB_BOOT_ROM[PALETTE_PER_BUTTONS + row * 4 + direction],
+        ))
+    }
+
+    /// Get the palettes the CGB boot ROM picks for the inserted game.
+    pub(crate) fn for_cartridge(cartridge: &dyn Mapper) -> Self {
+        let licensee = cartridge.read_rom(0x014B);
+
+        // Only games by Nintendo are coloured in,
+        // the new licensee code is "01" for them.
+        let by_nintendo = match licensee {
+            0x01 => true,
+            0x33 => cartridge.read_rom(0x0144) == b'0' && cartridge.read_rom(0x0145) == b'1',
+            _ => false,
+        };
+
+        let index = if by_nintendo {
+            let checksum =
+                (0x0134..=0x0143).fold(0u8, |sum, addr| sum.wrapping_add(cartridge.read_rom(addr)));
+
+            let fourth_letter = cartridge.read_rom(0x0137);
+
+            (0..CHECKSUM_COUNT)
+                .find(|&index| {
+                    CGB_BOOT_ROM[TITLE_CHECKSUMS + index] == checksum
+                        && (index < FIRST_DUPLICATE
+                            || CGB_BOOT_ROM[FOURTH_LETTERS + index - FIRST_DUPLICATE]
+                                == fourth_letter)
+                })
+                .unwrap_or(0)
+        } else {
+            0
+        };
+
+        Self::from_combination(CGB_BOOT_ROM[PALETTE_PER_CHECKSUM + index])
+    }
+
+    /// Read the palette combination at the given offset.
+    fn from_combination(offset: u8) -> Self {
+        let combination = PALETTE_COMBINATIONS + (offset & 0x7F) as usize;
+
+        let palette = |index: usize| {
+            let start = PALETTES + CGB_BOOT_ROM[combination + index] as usize;
+            let mut colours = [0; 4];
+
+            for (i, colour) in colours.iter_mut().enumerate() {
+                let rgb555 = u16::from_le_bytes([
+                    CGB_BOOT_ROM[start + i * 2],
+                    CGB_BOOT_ROM[start + i * 2 + 1],
+                ]);
+
+                *colour = scale_rgb(rgb555);
+            }
+
+            colours
+        };
+
+        Self {
+            obj0: palette(0),
+            obj1: palette(1),
+            bg: palette(2),
+        }
+    }
+}
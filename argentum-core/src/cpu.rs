@@ -4,59 +4,86 @@ mod decode;
 mod instructions;
 mod registers;
 
-use std::fmt::{Display, Formatter, Result};
+use core::fmt::{self, Display, Formatter};
 
 use self::registers::Registers;
 use crate::{
-    bus::Bus,
-    util::{get_bit, res_bit},
+    bus::MemoryBus,
+    clock::Speed,
+    debugger::CpuRegisters,
+    state::{StateError, StateReader, StateWriter},
 };
 
 /// Enumerates all the states the CPU can be in.
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CpuState {
     Halted,
     Running,
+
+    /// Entered by STOP, only pressing a key in a
+    /// group selected through P1 wakes the CPU up.
+    Stopped,
+
+    /// Entered after executing an illegal opcode,
+    /// only a reset gets the CPU out of this state.
+    Locked,
 }
 
 /// Implementation of the Sharp SM83 CPU.
-pub(crate) struct Cpu {
+///
+/// `Argentum` runs it against the Game Boy's memory map, but it
+/// can run against anything implementing `MemoryBus`, such as a
+/// mock for unit tests.
+pub struct Cpu {
     /// All the registers associated with the CPU.
-    pub reg: Registers,
+    pub(crate) reg: Registers,
 
     /// The Interrupt Master Enable flag.
     /// Interrupts are serviced iff this flag is enabled.
-    pub ime: bool,
+    pub(crate) ime: bool,
 
     /// The state the CPU is in.
-    pub state: CpuState,
+    pub(crate) state: CpuState,
 
     /// The amount of cycles spent executing the current
     /// instruction.
-    pub cycles: u32,
+    pub(crate) cycles: u32,
+
+    /// Set by EI, IME is only enabled after the
+    /// instruction following EI is executed.
+    pub(crate) ime_pending: bool,
 
-    /// Are we currently in double speed mode?
-    pub is_double_speed: bool,
+    /// Set when HALT is executed with IME disabled while an
+    /// interrupt is pending. The next opcode fetch then
+    /// fails to increment PC.
+    pub(crate) halt_bug: bool,
 }
 
 // Formatting similar to Peach's (wheremyfoodat) logs.
 impl Display for Cpu {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        let reg_one = format!(
-            "A: {:02X} F: {:02X} B: {:02X} C: {:02X} D: {:02X}",
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Written straight to the formatter, so formatting doesn't allocate.
+        write!(
+            f,
+            "A: {:02X} F: {:02X} B: {:02X} C: {:02X} D: {:02X} ",
             self.reg.a,
             self.reg.f.bits(),
             self.reg.b,
             self.reg.c,
             self.reg.d
-        );
+        )?;
 
-        let reg_two = format!(
+        write!(
+            f,
             "E: {:02X} H: {:02X} L: {:02X} SP: {:04X} PC: 00:{:04X}",
             self.reg.e, self.reg.h, self.reg.l, self.reg.sp, self.reg.pc,
-        );
+        )
+    }
+}
 
-        write!(f, "{} {}", reg_one, reg_two)
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -68,29 +95,92 @@ impl Cpu {
             ime: false,
             state: CpuState::Running,
             cycles: 0,
-            is_double_speed: false,
+            ime_pending: false,
+            halt_bug: false,
         }
     }
 
-    /// Read a byte from the current PC address.
-    pub fn imm_byte(&mut self, bus: &mut Bus) -> u8 {
-        let value = bus.read_byte(self.reg.pc, true);
+    /// Get a copy of the registers.
+    pub fn registers(&self) -> CpuRegisters {
+        CpuRegisters::capture(self)
+    }
+
+    /// Overwrite the registers.
+    pub fn set_registers(&mut self, registers: CpuRegisters) {
+        registers.restore(self);
+    }
+
+    /// Get the state the CPU is in.
+    pub fn state(&self) -> CpuState {
+        self.state
+    }
 
+    /// Read a byte from the bus, this takes one M cycle.
+    ///
+    /// All memory accesses made by instructions go through this
+    /// and `write_cycle`, so that every access lands on the same
+    /// M cycle it would on hardware.
+    pub(crate) fn read_cycle(&mut self, bus: &mut impl MemoryBus, addr: u16) -> u8 {
         self.cycles += 4;
-        self.reg.pc = self.reg.pc.wrapping_add(1);
 
-        value
+        bus.read(addr)
+    }
+
+    /// Write a byte to the bus, this takes one M cycle.
+    pub(crate) fn write_cycle(&mut self, bus: &mut impl MemoryBus, addr: u16, value: u8) {
+        self.cycles += 4;
+
+        bus.write(addr, value);
     }
 
     /// Tick all components attached to the bus by one M cycle.
-    pub fn internal_cycle(&mut self, bus: &mut Bus) {
+    pub(crate) fn internal_cycle(&mut self, bus: &mut impl MemoryBus) {
         self.cycles += 4;
         bus.tick();
     }
 
+    /// Read a byte from the current PC address.
+    pub(crate) fn imm_byte(&mut self, bus: &mut impl MemoryBus) -> u8 {
+        let value = self.read_cycle(bus, self.reg.pc);
+
+        self.reg.pc = self.reg.pc.wrapping_add(1);
+
+        value
+    }
+
+    /// Read a little endian word from the current PC address.
+    pub(crate) fn imm_word(&mut self, bus: &mut impl MemoryBus) -> u16 {
+        let lower = self.imm_byte(bus);
+        let upper = self.imm_byte(bus);
+
+        u16::from_le_bytes([lower, upper])
+    }
+
+    /// Push a word onto the stack, upper byte first.
+    pub(crate) fn push_word(&mut self, bus: &mut impl MemoryBus, value: u16) {
+        let [lower, upper] = value.to_le_bytes();
+
+        self.reg.sp = self.reg.sp.wrapping_sub(1);
+        self.write_cycle(bus, self.reg.sp, upper);
+
+        self.reg.sp = self.reg.sp.wrapping_sub(1);
+        self.write_cycle(bus, self.reg.sp, lower);
+    }
+
+    /// Pop a word off the stack, lower byte first.
+    pub(crate) fn pop_word(&mut self, bus: &mut impl MemoryBus) -> u16 {
+        let lower = self.read_cycle(bus, self.reg.sp);
+        self.reg.sp = self.reg.sp.wrapping_add(1);
+
+        let upper = self.read_cycle(bus, self.reg.sp);
+        self.reg.sp = self.reg.sp.wrapping_add(1);
+
+        u16::from_le_bytes([lower, upper])
+    }
+
     /// Read a R16 by specifiying the group and its index.
     /// See wheremyfoodat's decoding opcode PDF.
-    pub fn read_r16<const GROUP: u8>(&mut self, r16: u8) -> u16 {
+    pub(crate) fn read_r16<const GROUP: u8>(&mut self, r16: u8) -> u16 {
         match GROUP {
             1 => match r16 {
                 0 => self.reg.get_bc(),
@@ -135,7 +225,7 @@ impl Cpu {
 
     /// Write a value to a R16 by specifiying the group and its index.
     /// See wheremyfoodat's decoding opcode PDF.
-    pub fn write_r16<const GROUP: u8>(&mut self, r16: u8, value: u16) {
+    pub(crate) fn write_r16<const GROUP: u8>(&mut self, r16: u8, value: u16) {
         match GROUP {
             1 => match r16 {
                 0 => self.reg.set_bc(value),
@@ -169,7 +259,7 @@ impl Cpu {
 
     /// Read a R8 by specifiying its index.
     /// See wheremyfoodat's decoding opcode PDF.
-    pub fn read_r8(&mut self, bus: &mut Bus, r8: u8) -> u8 {
+    pub(crate) fn read_r8(&mut self, bus: &mut impl MemoryBus, r8: u8) -> u8 {
         match r8 {
             0 => self.reg.b,
             1 => self.reg.c,
@@ -177,7 +267,7 @@ impl Cpu {
             3 => self.reg.e,
             4 => self.reg.h,
             5 => self.reg.l,
-            6 => bus.read_byte(self.reg.get_hl(), true),
+            6 => self.read_cycle(bus, self.reg.get_hl()),
             7 => self.reg.a,
 
             _ => unreachable!(),
@@ -186,7 +276,7 @@ impl Cpu {
 
     /// Write a value to R8 by specifiying its index.
     /// See wheremyfoodat's decoding opcode PDF.
-    pub fn write_r8(&mut self, bus: &mut Bus, r8: u8, value: u8) {
+    pub(crate) fn write_r8(&mut self, bus: &mut impl MemoryBus, r8: u8, value: u8) {
         match r8 {
             0 => self.reg.b = value,
             1 => self.reg.c = value,
@@ -194,13 +284,57 @@ impl Cpu {
             3 => self.reg.e = value,
             4 => self.reg.h = value,
             5 => self.reg.l = value,
-            6 => bus.write_byte(self.reg.get_hl(), value, true),
+            6 => self.write_cycle(bus, self.reg.get_hl(), value),
             7 => self.reg.a = value,
 
             _ => unreachable!(),
         }
     }
 
+    /// Serialize the CPU's state.
+    pub(crate) fn save_state(&self, w: &mut StateWriter) {
+        w.write_u16(self.reg.get_af());
+        w.write_u16(self.reg.get_bc());
+        w.write_u16(self.reg.get_de());
+        w.write_u16(self.reg.get_hl());
+        w.write_u16(self.reg.sp);
+        w.write_u16(self.reg.pc);
+
+        w.write_bool(self.ime);
+        w.write_bool(self.ime_pending);
+        w.write_bool(self.halt_bug);
+
+        w.write_u8(match self.state {
+            CpuState::Running => 0,
+            CpuState::Halted => 1,
+            CpuState::Locked => 2,
+            CpuState::Stopped => 3,
+        });
+    }
+
+    /// Restore the CPU's state.
+    pub(crate) fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.reg.set_af(r.read_u16()?);
+        self.reg.set_bc(r.read_u16()?);
+        self.reg.set_de(r.read_u16()?);
+        self.reg.set_hl(r.read_u16()?);
+        self.reg.sp = r.read_u16()?;
+        self.reg.pc = r.read_u16()?;
+
+        self.ime = r.read_bool()?;
+        self.ime_pending = r.read_bool()?;
+        self.halt_bug = r.read_bool()?;
+
+        self.state = match r.read_u8()? {
+            1 => CpuState::Halted,
+            2 => CpuState::Locked,
+            3 => CpuState::Stopped,
+            _ => CpuState::Running,
+        };
+
+        Ok(())
+    }
+
     /// Skips the bootrom, and initializes default values for
     /// registers.
     pub fn skip_bootrom(&mut self, cgb: bool) {
@@ -222,76 +356,220 @@ impl Cpu {
 
     /// Handle all pending interrupts.
     /// Only one interrupt is serviced at one time.
-    pub fn handle_interrupts(&mut self, bus: &mut Bus) {
-        let interrupts = bus.ie_reg & *bus.if_reg.borrow();
+    pub(crate) fn handle_interrupts(&mut self, bus: &mut impl MemoryBus) {
+        let interrupts = bus.pending_interrupts();
 
-        // If there are pending interrupts, CPU should be
-        // back up and running.
-        if interrupts != 0 {
-            self.state = CpuState::Running;
+        // A halted CPU first has to notice the interrupt and wake
+        // up, which is handled in `execute_next`. Interrupts can't
+        // wake up a locked CPU at all.
+        if self.state != CpuState::Running {
+            return;
         }
 
         // If IME is not enabled, we don't service the interrupt.
-        if !self.ime {
+        if !self.ime || interrupts == 0 {
             return;
         }
 
-        if interrupts != 0 {
-            for i in 0..5 {
-                if get_bit!(bus.ie_reg, i) && get_bit!(*bus.if_reg.borrow(), i) {
-                    // Disable the interrupt in IF.
-                    res_bit!(bus.if_reg.borrow_mut(), i);
+        // Disable IME.
+        self.ime = false;
 
-                    // Disable IME.
-                    self.ime = false;
+        // Two wait states are executed every ISR.
+        self.internal_cycle(bus);
+        self.internal_cycle(bus);
 
-                    // Two wait states are executed every ISR.
-                    self.internal_cycle(bus);
-                    self.internal_cycle(bus);
+        // Push PC onto the stack.
+        let [lower, upper] = self.reg.pc.to_le_bytes();
 
-                    // Push PC onto the stack.
-                    let [lower, upper] = self.reg.pc.to_le_bytes();
+        self.reg.sp = self.reg.sp.wrapping_sub(1);
+        self.write_cycle(bus, self.reg.sp, upper);
 
-                    self.reg.sp = self.reg.sp.wrapping_sub(1);
-                    bus.write_byte(self.reg.sp, upper, true);
+        // The interrupt to service is only picked after the upper
+        // byte of PC is pushed. If that push overwrote IE, the
+        // dispatch is cancelled and execution continues at 0x0000.
+        let interrupts = bus.pending_interrupts();
 
-                    self.reg.sp = self.reg.sp.wrapping_sub(1);
-                    bus.write_byte(self.reg.sp, lower, true);
+        self.reg.sp = self.reg.sp.wrapping_sub(1);
+        self.write_cycle(bus, self.reg.sp, lower);
 
-                    // 0x40 - VBLANK
-                    // 0x48 - LCD STAT
-                    // 0x50 - Timer
-                    // 0x58 - Serial
-                    // 0x60 - Joypad
-                    self.reg.pc = 0x40 + (0x08 * i);
-                    self.internal_cycle(bus);
+        if interrupts == 0 {
+            self.reg.pc = 0x0000;
+        } else {
+            let i = interrupts.trailing_zeros() as u16;
 
-                    // Service only one interrupt at a time.
-                    break;
-                }
-            }
+            // Disable the interrupt in IF.
+            bus.acknowledge_interrupt(i as u8);
+
+            // 0x40 - VBLANK
+            // 0x48 - LCD STAT
+            // 0x50 - Timer
+            // 0x58 - Serial
+            // 0x60 - Joypad
+            self.reg.pc = 0x40 + (0x08 * i);
         }
+
+        self.internal_cycle(bus);
     }
 
     /// Execute the next opcode, while checking for interrupts.
-    /// Return the amount of cycles it took to execute the instruction.
-    pub fn execute_next(&mut self, bus: &mut Bus) -> u32 {
+    /// Return the T-cycles of the system clock it took to execute the
+    /// instruction, which are halved in double speed mode.
+    pub fn execute_next(&mut self, bus: &mut impl MemoryBus) -> u32 {
         self.cycles = 0;
 
+        // The CPU is paused while a VRAM DMA copies bytes.
+        for _ in 0..bus.take_dma_stall() {
+            self.internal_cycle(bus);
+        }
+
+        // An EI executed by the previous instruction
+        // takes effect after this one.
+        let enable_ime = self.ime_pending;
+
         // Handle pending interrupts.
         self.handle_interrupts(bus);
 
-        // If the CPU is in HALT state, it just burns one M cycle.
-        if self.state == CpuState::Halted {
-            self.internal_cycle(bus);
-        } else {
-            // Fetch the opcode.
-            let opcode = self.imm_byte(bus);
+        match self.state {
+            // If the CPU is halted, it burns one M cycle and then checks
+            // for pending interrupts. After waking up, the interrupt is
+            // dispatched on the next call if IME is set, otherwise
+            // execution simply resumes after the HALT.
+            CpuState::Halted => {
+                self.internal_cycle(bus);
+
+                if bus.pending_interrupts() != 0 {
+                    self.state = CpuState::Running;
+                }
+            }
+
+            // A stopped CPU waits for one of the P1 input lines to
+            // go low, whether or not the joypad interrupt is enabled.
+            CpuState::Stopped => {
+                self.internal_cycle(bus);
+
+                if bus.joypad_lines() != 0x0F {
+                    self.state = CpuState::Running;
+                }
+            }
+
+            // A locked up CPU burns cycles forever.
+            CpuState::Locked => self.internal_cycle(bus),
 
-            // Decode and execute it.
-            self.decode_and_execute(bus, opcode);
+            CpuState::Running => {
+                // Fetch the opcode.
+                let opcode = if self.halt_bug {
+                    self.halt_bug = false;
+
+                    self.read_cycle(bus, self.reg.pc)
+                } else {
+                    self.imm_byte(bus)
+                };
+
+                // Decode and execute it.
+                self.decode_and_execute(bus, opcode);
+            }
+        }
+
+        // DI cancels a pending EI.
+        if enable_ime && self.ime_pending {
+            self.ime = true;
+            self.ime_pending = false;
         }
 
-        self.cycles >> (self.is_double_speed as u8)
+        let speed = if bus.double_speed() {
+            Speed::Double
+        } else {
+            Speed::Normal
+        };
+
+        speed.system_cycles(self.cycles / 4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{joypad::ArgentumKey, test_rom, Argentum};
+    use alloc::boxed::Box;
+
+    /// Selects the buttons, stops and writes 0x42 to 0xC000 after waking up.
+    #[rustfmt::skip]
+    const STOP_PROGRAM: [u8; 12] = [
+        0x3E, 0x10,       // LD A, 0x10
+        0xE0, 0x00,       // LDH (P1), A
+        0x10, 0x00,       // STOP
+        0x3E, 0x42,       // LD A, 0x42
+        0xEA, 0x00, 0xC0, // LD (0xC000), A
+        0x76,             // HALT
+    ];
+
+    /// Where the CPU stops, right after STOP.
+    const STOPPED_PC: u16 = 0x0156;
+
+    fn stopped_gb() -> Argentum {
+        let mut gb = Argentum::new(
+            &test_rom::with_program(&STOP_PROGRAM),
+            Box::new(|_| {}),
+            None,
+        )
+        .unwrap();
+
+        gb.skip_bootrom();
+        gb.execute_frame();
+        gb.execute_frame();
+
+        gb
+    }
+
+    #[test]
+    fn stop_waits_for_a_selected_key() {
+        let mut gb = stopped_gb();
+
+        assert_eq!(gb.registers().pc, STOPPED_PC);
+        assert_eq!(gb.peek_byte(0xC000), 0x00);
+
+        // The DPAD isn't selected, so it can't wake the CPU up.
+        gb.key_down(ArgentumKey::Up);
+        gb.execute_frame();
+
+        assert_eq!(gb.registers().pc, STOPPED_PC);
+        assert_eq!(gb.peek_byte(0xC000), 0x00);
+
+        gb.key_down(ArgentumKey::Start);
+        gb.execute_frame();
+
+        assert_eq!(gb.peek_byte(0xC000), 0x42);
+    }
+
+    /// Prepares a speed switch and stops twice, storing
+    /// KEY1 after each switch in 0xC000 and 0xC001.
+    #[rustfmt::skip]
+    const SPEED_SWITCH_PROGRAM: [u8; 23] = [
+        0x3E, 0x01,       // LD A, 0x01
+        0xE0, 0x4D,       // LDH (KEY1), A
+        0x10, 0x00,       // STOP
+        0xF0, 0x4D,       // LDH A, (KEY1)
+        0xEA, 0x00, 0xC0, // LD (0xC000), A
+        0x3E, 0x01,       // LD A, 0x01
+        0xE0, 0x4D,       // LDH (KEY1), A
+        0x10, 0x00,       // STOP
+        0xF0, 0x4D,       // LDH A, (KEY1)
+        0xEA, 0x01, 0xC0, // LD (0xC001), A
+        0x76,             // HALT
+    ];
+
+    #[test]
+    fn stop_toggles_the_speed_through_key1() {
+        let mut rom = test_rom::with_program(&SPEED_SWITCH_PROGRAM);
+
+        // CGB support.
+        rom[0x0143] = 0x80;
+
+        let mut gb = Argentum::new(&rom, Box::new(|_| {}), None).unwrap();
+
+        gb.skip_bootrom();
+        gb.execute_frame();
+
+        assert_eq!(gb.peek_byte(0xC000), 0xFE);
+        assert_eq!(gb.peek_byte(0xC001), 0x7E);
     }
 }
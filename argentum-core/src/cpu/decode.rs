@@ -1,11 +1,11 @@
 //! Method to decode opcodes and dispatch the correct methods.
 
 use super::{Cpu, CpuState};
-use crate::bus::Bus;
+use crate::bus::MemoryBus;
 
 impl Cpu {
     /// Decode the provided opcode and execute it.
-    pub fn decode_and_execute(&mut self, bus: &mut Bus, opcode: u8) {
+    pub(crate) fn decode_and_execute(&mut self, bus: &mut impl MemoryBus, opcode: u8) {
         match opcode {
             0x00 => self.nop(),
 
@@ -92,9 +92,9 @@ impl Cpu {
                 }
             }
 
-            0x76 => self.state = CpuState::Halted,
+            0x76 => self.halt(bus),
 
-            0x40..=0x7F if opcode != 0x76 => {
+            0x40..=0x7F => {
                 let src = opcode & 0x7;
                 let dst = (opcode >> 3) & 0x7;
 
@@ -158,31 +158,25 @@ impl Cpu {
             0xE2 => {
                 let address = (0xFF00u16).wrapping_add(self.reg.c as u16);
 
-                bus.write_byte(address, self.reg.a, true);
+                self.write_cycle(bus, address, self.reg.a);
             }
 
             0xEA => {
-                let lower = self.imm_byte(bus);
-                let upper = self.imm_byte(bus);
+                let address = self.imm_word(bus);
 
-                let address = u16::from_le_bytes([lower, upper]);
-
-                bus.write_byte(address, self.reg.a, true);
+                self.write_cycle(bus, address, self.reg.a);
             }
 
             0xF2 => {
                 let address = (0xFF00u16).wrapping_add(self.reg.c as u16);
 
-                self.reg.a = bus.read_byte(address, true);
+                self.reg.a = self.read_cycle(bus, address);
             }
 
             0xFA => {
-                let lower = self.imm_byte(bus);
-                let upper = self.imm_byte(bus);
-
-                let address = u16::from_le_bytes([lower, upper]);
+                let address = self.imm_word(bus);
 
-                self.reg.a = bus.read_byte(address, true);
+                self.reg.a = self.read_cycle(bus, address);
             }
 
             0xC3 => self.unconditional_jp(bus),
@@ -232,9 +226,12 @@ impl Cpu {
                 }
             }
 
-            0xF3 => self.ime = false,
+            0xF3 => {
+                self.ime = false;
+                self.ime_pending = false;
+            }
 
-            0xFB => self.ime = true,
+            0xFB => self.ime_pending = true,
 
             0xC4 | 0xCC | 0xD4 | 0xDC => {
                 let condition = (opcode >> 3) & 0x3;
@@ -271,20 +268,16 @@ impl Cpu {
             0xC7 | 0xD7 | 0xE7 | 0xF7 | 0xCF | 0xDF | 0xEF | 0xFF => {
                 let vec = (opcode & 0b0011_1000) as u16;
 
-                let [lower, upper] = self.reg.pc.to_le_bytes();
-
                 self.internal_cycle(bus);
-
-                self.reg.sp = self.reg.sp.wrapping_sub(1);
-                bus.write_byte(self.reg.sp, upper, true);
-
-                self.reg.sp = self.reg.sp.wrapping_sub(1);
-                bus.write_byte(self.reg.sp, lower, true);
+                self.push_word(bus, self.reg.pc);
 
                 self.reg.pc = vec;
             }
 
-            _ => {}
+            // Illegal opcodes lock up the CPU.
+            0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD => {
+                self.state = CpuState::Locked;
+            }
         }
     }
 }
@@ -1,5 +1,5 @@
-use super::{registers::Flags, Cpu};
-use crate::bus::Bus;
+use super::{registers::Flags, Cpu, CpuState};
+use crate::bus::MemoryBus;
 
 impl Cpu {
     /// Match condition according to,
@@ -19,66 +19,73 @@ impl Cpu {
     }
 
     /// NOP.
-    pub fn nop(&self) {}
+    pub(crate) fn nop(&self) {}
 
     /// LD (u16), SP.
-    pub fn ld_u16_sp(&mut self, bus: &mut Bus) {
-        let lower = self.imm_byte(bus);
-        let upper = self.imm_byte(bus);
-
-        let address = u16::from_le_bytes([lower, upper]);
+    pub(crate) fn ld_u16_sp(&mut self, bus: &mut impl MemoryBus) {
+        let address = self.imm_word(bus);
 
         let [sp_lower, sp_upper] = self.reg.sp.to_le_bytes();
 
-        bus.write_byte(address, sp_lower, true);
-        bus.write_byte(address + 1, sp_upper, true);
+        self.write_cycle(bus, address, sp_lower);
+        self.write_cycle(bus, address.wrapping_add(1), sp_upper);
     }
 
-    /// STOP.
-    pub fn stop(&mut self, bus: &mut Bus) {
-        if bus.cgb_mode && (bus.speed_reg & 0x01) != 0 {
-            if self.is_double_speed {
-                self.is_double_speed = false;
+    /// HALT.
+    pub(crate) fn halt(&mut self, bus: &mut impl MemoryBus) {
+        let interrupts = bus.pending_interrupts();
 
-                bus.speed_reg = 0b0111_1110;
-            } else {
-                self.is_double_speed = true;
+        if interrupts == 0 {
+            self.state = CpuState::Halted;
+        } else if self.ime_pending {
+            // With EI right before HALT the interrupt is dispatched,
+            // but returns to the HALT which is executed again.
+            self.reg.pc = self.reg.pc.wrapping_sub(1);
+        } else if !self.ime {
+            // HALT bug, the CPU doesn't halt and the
+            // next byte gets read twice.
+            self.halt_bug = true;
+        }
+    }
 
-                bus.speed_reg = 0b1111_1110;
-            }
+    /// STOP.
+    pub(crate) fn stop(&mut self, bus: &mut impl MemoryBus) {
+        if !bus.switch_speed() && bus.joypad_lines() == 0x0F {
+            // With a selected key already held STOP does
+            // nothing, the CPU would wake up right away.
+            self.state = CpuState::Stopped;
         }
 
-        self.reg.pc += 1;
+        self.reg.pc = self.reg.pc.wrapping_add(1);
     }
 
     /// JR (unconditional).
-    pub fn unconditional_jr(&mut self, bus: &mut Bus) {
-        let offset = self.imm_byte(bus) as i8 as i16;
+    pub(crate) fn unconditional_jr(&mut self, bus: &mut impl MemoryBus) {
+        let offset = self.imm_byte(bus) as i8 as u16;
 
-        self.reg.pc = (self.reg.pc as i16 + offset) as u16;
+        self.reg.pc = self.reg.pc.wrapping_add(offset);
         self.internal_cycle(bus);
     }
 
     /// JR (conditional).
-    pub fn conditional_jr(&mut self, bus: &mut Bus, condition: u8) {
-        let offset = self.imm_byte(bus) as i8 as i16;
+    pub(crate) fn conditional_jr(&mut self, bus: &mut impl MemoryBus, condition: u8) {
+        let offset = self.imm_byte(bus) as i8 as u16;
 
         if self.get_condition(condition) {
-            self.reg.pc = (self.reg.pc as i16 + offset) as u16;
+            self.reg.pc = self.reg.pc.wrapping_add(offset);
             self.internal_cycle(bus);
         }
     }
 
     /// LD R16, u16.
-    pub fn ld_r16_u16(&mut self, bus: &mut Bus, r16: u8) {
-        let lower = self.imm_byte(bus);
-        let upper = self.imm_byte(bus);
+    pub(crate) fn ld_r16_u16(&mut self, bus: &mut impl MemoryBus, r16: u8) {
+        let value = self.imm_word(bus);
 
-        self.write_r16::<1>(r16, u16::from_le_bytes([lower, upper]));
+        self.write_r16::<1>(r16, value);
     }
 
     /// ADD HL, R16.
-    pub fn add_hl_r16(&mut self, bus: &mut Bus, r16: u8) {
+    pub(crate) fn add_hl_r16(&mut self, bus: &mut impl MemoryBus, r16: u8) {
         let hl = self.reg.get_hl();
         let value = self.read_r16::<1>(r16);
 
@@ -93,21 +100,21 @@ impl Cpu {
     }
 
     /// LD (R16), A.
-    pub fn ld_r16_a(&mut self, bus: &mut Bus, r16: u8) {
+    pub(crate) fn ld_r16_a(&mut self, bus: &mut impl MemoryBus, r16: u8) {
         let addr = self.read_r16::<2>(r16);
 
-        bus.write_byte(addr, self.reg.a, true);
+        self.write_cycle(bus, addr, self.reg.a);
     }
 
     /// LD A, (R16).
-    pub fn ld_a_r16(&mut self, bus: &mut Bus, r16: u8) {
+    pub(crate) fn ld_a_r16(&mut self, bus: &mut impl MemoryBus, r16: u8) {
         let addr = self.read_r16::<2>(r16);
 
-        self.reg.a = bus.read_byte(addr, true);
+        self.reg.a = self.read_cycle(bus, addr);
     }
 
     /// INC R16.
-    pub fn inc_r16(&mut self, bus: &mut Bus, r16: u8) {
+    pub(crate) fn inc_r16(&mut self, bus: &mut impl MemoryBus, r16: u8) {
         let value = self.read_r16::<1>(r16);
 
         self.write_r16::<1>(r16, value.wrapping_add(1));
@@ -115,7 +122,7 @@ impl Cpu {
     }
 
     /// DEC R16.
-    pub fn dec_r16(&mut self, bus: &mut Bus, r16: u8) {
+    pub(crate) fn dec_r16(&mut self, bus: &mut impl MemoryBus, r16: u8) {
         let value = self.read_r16::<1>(r16);
 
         self.write_r16::<1>(r16, value.wrapping_sub(1));
@@ -123,7 +130,7 @@ impl Cpu {
     }
 
     /// INC R8.
-    pub fn inc_r8(&mut self, bus: &mut Bus, r8: u8) {
+    pub(crate) fn inc_r8(&mut self, bus: &mut impl MemoryBus, r8: u8) {
         let value = self.read_r8(bus, r8);
         let result = value.wrapping_add(1);
 
@@ -135,7 +142,7 @@ impl Cpu {
     }
 
     /// DEC R8.
-    pub fn dec_r8(&mut self, bus: &mut Bus, r8: u8) {
+    pub(crate) fn dec_r8(&mut self, bus: &mut impl MemoryBus, r8: u8) {
         let value = self.read_r8(bus, r8);
         let result = value.wrapping_sub(1);
 
@@ -147,14 +154,14 @@ impl Cpu {
     }
 
     /// LD R8, u8.
-    pub fn ld_r8_u8(&mut self, bus: &mut Bus, r8: u8) {
+    pub(crate) fn ld_r8_u8(&mut self, bus: &mut impl MemoryBus, r8: u8) {
         let value = self.imm_byte(bus);
 
         self.write_r8(bus, r8, value);
     }
 
     /// RLCA.
-    pub fn rlca(&mut self) {
+    pub(crate) fn rlca(&mut self) {
         let value = self.reg.a;
         let result = value.rotate_left(1);
 
@@ -167,7 +174,7 @@ impl Cpu {
     }
 
     /// RRCA.
-    pub fn rrca(&mut self) {
+    pub(crate) fn rrca(&mut self) {
         let value = self.reg.a;
         let result = value.rotate_right(1);
 
@@ -180,7 +187,7 @@ impl Cpu {
     }
 
     /// RLA.
-    pub fn rla(&mut self) {
+    pub(crate) fn rla(&mut self) {
         let value = self.reg.a;
         let carry = self.reg.get_flag(Flags::C) as u8;
         let result = (value << 1) | carry;
@@ -194,7 +201,7 @@ impl Cpu {
     }
 
     /// RRA.
-    pub fn rra(&mut self) {
+    pub(crate) fn rra(&mut self) {
         let value = self.reg.a;
         let carry = self.reg.get_flag(Flags::C) as u8;
         let result = (value >> 1) | (carry << 7);
@@ -208,7 +215,7 @@ impl Cpu {
     }
 
     /// DA A.
-    pub fn daa(&mut self) {
+    pub(crate) fn daa(&mut self) {
         let mut a = self.reg.a;
 
         if self.reg.get_flag(Flags::N) {
@@ -237,7 +244,7 @@ impl Cpu {
     }
 
     /// CPL.
-    pub fn cpl(&mut self) {
+    pub(crate) fn cpl(&mut self) {
         self.reg.a = !self.reg.a;
 
         self.reg.set_flag(Flags::N, true);
@@ -245,14 +252,14 @@ impl Cpu {
     }
 
     /// SCF.
-    pub fn scf(&mut self) {
+    pub(crate) fn scf(&mut self) {
         self.reg.set_flag(Flags::N, false);
         self.reg.set_flag(Flags::H, false);
         self.reg.set_flag(Flags::C, true);
     }
 
     /// CCF.
-    pub fn ccf(&mut self) {
+    pub(crate) fn ccf(&mut self) {
         let carry = self.reg.get_flag(Flags::C);
 
         self.reg.set_flag(Flags::N, false);
@@ -261,14 +268,14 @@ impl Cpu {
     }
 
     /// LD R8, R8.
-    pub fn ld_r8_r8(&mut self, bus: &mut Bus, src: u8, dst: u8) {
+    pub(crate) fn ld_r8_r8(&mut self, bus: &mut impl MemoryBus, src: u8, dst: u8) {
         let value = self.read_r8(bus, src);
 
         self.write_r8(bus, dst, value);
     }
 
     /// ADD A, R8.
-    pub fn add_r8(&mut self, value: u8) {
+    pub(crate) fn add_r8(&mut self, value: u8) {
         let a = self.reg.a;
         let result = a.wrapping_add(value);
 
@@ -282,7 +289,7 @@ impl Cpu {
     }
 
     /// ADC A, R8.
-    pub fn adc_r8(&mut self, value: u8) {
+    pub(crate) fn adc_r8(&mut self, value: u8) {
         let a = self.reg.a;
         let f = self.reg.get_flag(Flags::C) as u8;
         let result = a.wrapping_add(value).wrapping_add(f);
@@ -298,7 +305,7 @@ impl Cpu {
     }
 
     /// SUB A, R8.
-    pub fn sub_r8(&mut self, value: u8) {
+    pub(crate) fn sub_r8(&mut self, value: u8) {
         let a = self.reg.a;
         let result = self.reg.a.wrapping_sub(value);
 
@@ -311,7 +318,7 @@ impl Cpu {
     }
 
     /// SBC A, R8.
-    pub fn sbc_r8(&mut self, value: u8) {
+    pub(crate) fn sbc_r8(&mut self, value: u8) {
         let a = self.reg.a;
         let f = self.reg.get_flag(Flags::C) as u8;
         let result = a.wrapping_sub(value).wrapping_sub(f);
@@ -327,7 +334,7 @@ impl Cpu {
     }
 
     /// AND A, R8.
-    pub fn and_r8(&mut self, value: u8) {
+    pub(crate) fn and_r8(&mut self, value: u8) {
         self.reg.a &= value;
 
         self.reg.set_flag(Flags::Z, self.reg.a == 0);
@@ -337,7 +344,7 @@ impl Cpu {
     }
 
     /// XOR A, R8.
-    pub fn xor_r8(&mut self, value: u8) {
+    pub(crate) fn xor_r8(&mut self, value: u8) {
         self.reg.a ^= value;
 
         self.reg.set_flag(Flags::Z, self.reg.a == 0);
@@ -347,7 +354,7 @@ impl Cpu {
     }
 
     /// OR A, R8.
-    pub fn or_r8(&mut self, value: u8) {
+    pub(crate) fn or_r8(&mut self, value: u8) {
         self.reg.a |= value;
 
         self.reg.set_flag(Flags::Z, self.reg.a == 0);
@@ -357,7 +364,7 @@ impl Cpu {
     }
 
     /// CP A, R8.
-    pub fn cp_r8(&mut self, value: u8) {
+    pub(crate) fn cp_r8(&mut self, value: u8) {
         let result = self.reg.a.wrapping_sub(value);
 
         self.reg.set_flag(Flags::Z, result == 0);
@@ -369,21 +376,13 @@ impl Cpu {
     }
 
     /// RET (unconditional).
-    pub fn unconditional_ret(&mut self, bus: &mut Bus) {
-        let lower = bus.read_byte(self.reg.sp, true);
-        self.reg.sp += 1;
-
-        let upper = bus.read_byte(self.reg.sp, true);
-        self.reg.sp += 1;
-
-        let return_address = u16::from_le_bytes([lower, upper]);
-
-        self.reg.pc = return_address;
+    pub(crate) fn unconditional_ret(&mut self, bus: &mut impl MemoryBus) {
+        self.reg.pc = self.pop_word(bus);
         self.internal_cycle(bus);
     }
 
     /// RET (conditional).
-    pub fn conditional_ret(&mut self, bus: &mut Bus, condition: u8) {
+    pub(crate) fn conditional_ret(&mut self, bus: &mut impl MemoryBus, condition: u8) {
         self.internal_cycle(bus);
 
         if self.get_condition(condition) {
@@ -392,14 +391,14 @@ impl Cpu {
     }
 
     /// LD [FF00 + u8], A.
-    pub fn ld_io_u8_a(&mut self, bus: &mut Bus) {
+    pub(crate) fn ld_io_u8_a(&mut self, bus: &mut impl MemoryBus) {
         let offset = self.imm_byte(bus) as u16;
 
-        bus.write_byte(0xFF00u16.wrapping_add(offset), self.reg.a, true);
+        self.write_cycle(bus, 0xFF00u16.wrapping_add(offset), self.reg.a);
     }
 
     /// ADD SP, i8.
-    pub fn add_sp_i8(&mut self, bus: &mut Bus) {
+    pub(crate) fn add_sp_i8(&mut self, bus: &mut impl MemoryBus) {
         let offset = self.imm_byte(bus) as i8 as i16 as u16;
         let sp = self.reg.sp;
 
@@ -417,14 +416,14 @@ impl Cpu {
     }
 
     /// LD A, [FF00 + u8].
-    pub fn ld_a_io_u8(&mut self, bus: &mut Bus) {
+    pub(crate) fn ld_a_io_u8(&mut self, bus: &mut impl MemoryBus) {
         let offset = self.imm_byte(bus) as u16;
 
-        self.reg.a = bus.read_byte(0xFF00u16.wrapping_add(offset), true);
+        self.reg.a = self.read_cycle(bus, 0xFF00u16.wrapping_add(offset));
     }
 
     /// LD HL, SP + i8.
-    pub fn ld_hl_sp_i8(&mut self, bus: &mut Bus) {
+    pub(crate) fn ld_hl_sp_i8(&mut self, bus: &mut impl MemoryBus) {
         let offset = self.imm_byte(bus) as i8 as i16 as u16;
         let sp = self.reg.sp;
 
@@ -440,52 +439,38 @@ impl Cpu {
     }
 
     /// POP R16.
-    pub fn pop_r16(&mut self, bus: &mut Bus, r16: u8) {
-        let lower = bus.read_byte(self.reg.sp, true);
-        self.reg.sp = self.reg.sp.wrapping_add(1);
-
-        let upper = bus.read_byte(self.reg.sp, true);
-        self.reg.sp = self.reg.sp.wrapping_add(1);
-
-        let value = u16::from_le_bytes([lower, upper]);
+    pub(crate) fn pop_r16(&mut self, bus: &mut impl MemoryBus, r16: u8) {
+        let value = self.pop_word(bus);
 
         self.write_r16::<3>(r16, value);
     }
 
     /// RETI.
-    pub fn reti(&mut self, bus: &mut Bus) {
+    pub(crate) fn reti(&mut self, bus: &mut impl MemoryBus) {
         self.unconditional_ret(bus);
         self.ime = true;
     }
 
     /// JP HL.
-    pub fn jp_hl(&mut self) {
+    pub(crate) fn jp_hl(&mut self) {
         self.reg.pc = self.reg.get_hl();
     }
 
     /// LD SP, HL.
-    pub fn ld_sp_hl(&mut self, bus: &mut Bus) {
+    pub(crate) fn ld_sp_hl(&mut self, bus: &mut impl MemoryBus) {
         self.reg.sp = self.reg.get_hl();
         self.internal_cycle(bus);
     }
 
     /// JP (unconditional).
-    pub fn unconditional_jp(&mut self, bus: &mut Bus) {
-        let lower = self.imm_byte(bus);
-        let upper = self.imm_byte(bus);
-
-        let jump_address = u16::from_le_bytes([lower, upper]);
-
-        self.reg.pc = jump_address;
+    pub(crate) fn unconditional_jp(&mut self, bus: &mut impl MemoryBus) {
+        self.reg.pc = self.imm_word(bus);
         self.internal_cycle(bus);
     }
 
     /// JP (conditional).
-    pub fn conditional_jp(&mut self, bus: &mut Bus, condition: u8) {
-        let lower = self.imm_byte(bus);
-        let upper = self.imm_byte(bus);
-
-        let jump_address = u16::from_le_bytes([lower, upper]);
+    pub(crate) fn conditional_jp(&mut self, bus: &mut impl MemoryBus, condition: u8) {
+        let jump_address = self.imm_word(bus);
 
         if self.get_condition(condition) {
             self.reg.pc = jump_address;
@@ -494,7 +479,7 @@ impl Cpu {
     }
 
     /// RLC R8.
-    pub fn rlc_r8(&mut self, bus: &mut Bus, r8: u8) {
+    pub(crate) fn rlc_r8(&mut self, bus: &mut impl MemoryBus, r8: u8) {
         let value = self.read_r8(bus, r8);
         let result = value.rotate_left(1);
 
@@ -507,7 +492,7 @@ impl Cpu {
     }
 
     /// RRC R8.
-    pub fn rrc_r8(&mut self, bus: &mut Bus, r8: u8) {
+    pub(crate) fn rrc_r8(&mut self, bus: &mut impl MemoryBus, r8: u8) {
         let value = self.read_r8(bus, r8);
         let result = value.rotate_right(1);
 
@@ -520,7 +505,7 @@ impl Cpu {
     }
 
     /// RL R8.
-    pub fn rl_r8(&mut self, bus: &mut Bus, r8: u8) {
+    pub(crate) fn rl_r8(&mut self, bus: &mut impl MemoryBus, r8: u8) {
         let value = self.read_r8(bus, r8);
         let carry = self.reg.get_flag(Flags::C) as u8;
         let result = (value << 1) | carry;
@@ -534,7 +519,7 @@ impl Cpu {
     }
 
     /// RR r8.
-    pub fn rr_r8(&mut self, bus: &mut Bus, r8: u8) {
+    pub(crate) fn rr_r8(&mut self, bus: &mut impl MemoryBus, r8: u8) {
         let value = self.read_r8(bus, r8);
         let carry = self.reg.get_flag(Flags::C) as u8;
         let result = (value >> 1) | (carry << 7);
@@ -548,7 +533,7 @@ impl Cpu {
     }
 
     /// SLA R8.
-    pub fn sla_r8(&mut self, bus: &mut Bus, r8: u8) {
+    pub(crate) fn sla_r8(&mut self, bus: &mut impl MemoryBus, r8: u8) {
         let value = self.read_r8(bus, r8);
         let result = value << 1;
 
@@ -561,7 +546,7 @@ impl Cpu {
     }
 
     /// SRA R8.
-    pub fn sra_r8(&mut self, bus: &mut Bus, r8: u8) {
+    pub(crate) fn sra_r8(&mut self, bus: &mut impl MemoryBus, r8: u8) {
         let value = self.read_r8(bus, r8);
         let result = (value >> 1) | (value & 0x80);
 
@@ -574,9 +559,9 @@ impl Cpu {
     }
 
     /// SWAP R8.
-    pub fn swap_r8(&mut self, bus: &mut Bus, r8: u8) {
+    pub(crate) fn swap_r8(&mut self, bus: &mut impl MemoryBus, r8: u8) {
         let value = self.read_r8(bus, r8);
-        let result = (value << 4) | (value >> 4);
+        let result = value.rotate_right(4);
 
         self.write_r8(bus, r8, result);
 
@@ -587,7 +572,7 @@ impl Cpu {
     }
 
     // SRL R8.
-    pub fn srl_r8(&mut self, bus: &mut Bus, r8: u8) {
+    pub(crate) fn srl_r8(&mut self, bus: &mut impl MemoryBus, r8: u8) {
         let value = self.read_r8(bus, r8);
         let result = value >> 1;
 
@@ -600,7 +585,7 @@ impl Cpu {
     }
 
     // BIT bit, R8.
-    pub fn bit_r8(&mut self, bus: &mut Bus, r8: u8, bit: u8) {
+    pub(crate) fn bit_r8(&mut self, bus: &mut impl MemoryBus, r8: u8, bit: u8) {
         let value = self.read_r8(bus, r8) & (1 << bit);
 
         self.reg.set_flag(Flags::Z, value == 0);
@@ -609,7 +594,7 @@ impl Cpu {
     }
 
     // RES bit, R8.
-    pub fn res_r8(&mut self, bus: &mut Bus, r8: u8, bit: u8) {
+    pub(crate) fn res_r8(&mut self, bus: &mut impl MemoryBus, r8: u8, bit: u8) {
         let value = self.read_r8(bus, r8);
         let mask = !(1 << bit);
         let result = value & mask;
@@ -618,65 +603,39 @@ impl Cpu {
     }
 
     /// SET bit, R8.
-    pub fn set_r8(&mut self, bus: &mut Bus, r8: u8, bit: u8) {
+    pub(crate) fn set_r8(&mut self, bus: &mut impl MemoryBus, r8: u8, bit: u8) {
         let value = self.read_r8(bus, r8) | (1 << bit);
 
         self.write_r8(bus, r8, value);
     }
 
     /// CALL (conditional).
-    pub fn conditional_call(&mut self, bus: &mut Bus, condition: u8) {
-        let lower_imm = self.imm_byte(bus);
-        let upper_imm = self.imm_byte(bus);
-
-        let address = u16::from_le_bytes([lower_imm, upper_imm]);
+    pub(crate) fn conditional_call(&mut self, bus: &mut impl MemoryBus, condition: u8) {
+        let address = self.imm_word(bus);
 
         if self.get_condition(condition) {
             self.internal_cycle(bus);
-
-            let [lower, upper] = self.reg.pc.to_le_bytes();
-
-            self.reg.sp = self.reg.sp.wrapping_sub(1);
-            bus.write_byte(self.reg.sp, upper, true);
-
-            self.reg.sp = self.reg.sp.wrapping_sub(1);
-            bus.write_byte(self.reg.sp, lower, true);
+            self.push_word(bus, self.reg.pc);
 
             self.reg.pc = address;
         }
     }
 
     /// CALL (unconditional).
-    pub fn unconditional_call(&mut self, bus: &mut Bus) {
-        let lower_imm = self.imm_byte(bus);
-        let upper_imm = self.imm_byte(bus);
-
-        let address = u16::from_le_bytes([lower_imm, upper_imm]);
+    pub(crate) fn unconditional_call(&mut self, bus: &mut impl MemoryBus) {
+        let address = self.imm_word(bus);
 
         self.internal_cycle(bus);
-
-        let [lower, upper] = self.reg.pc.to_le_bytes();
-
-        self.reg.sp = self.reg.sp.wrapping_sub(1);
-        bus.write_byte(self.reg.sp, upper, true);
-
-        self.reg.sp = self.reg.sp.wrapping_sub(1);
-        bus.write_byte(self.reg.sp, lower, true);
+        self.push_word(bus, self.reg.pc);
 
         self.reg.pc = address;
     }
 
     /// PUSH R16.
-    pub fn push_r16(&mut self, bus: &mut Bus, r16: u8) {
+    pub(crate) fn push_r16(&mut self, bus: &mut impl MemoryBus, r16: u8) {
         let value = self.read_r16::<3>(r16);
-        let [lower, upper] = value.to_le_bytes();
 
         self.internal_cycle(bus);
-
-        self.reg.sp = self.reg.sp.wrapping_sub(1);
-        bus.write_byte(self.reg.sp, upper, true);
-
-        self.reg.sp = self.reg.sp.wrapping_sub(1);
-        bus.write_byte(self.reg.sp, lower, true);
+        self.push_word(bus, value);
     }
 }
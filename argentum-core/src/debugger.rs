@@ -0,0 +1,283 @@
+//! Debugging facilities.
+//!
+//! Flat 16-bit addresses are ambiguous once a game starts switching
+//! banks, so everything in here deals in `BankedAddress`es, which
+//! are resolved using the current state of the MBC.
+
+mod bank_stats;
+mod disasm;
+mod histogram;
+mod registers;
+mod trace_filter;
+mod usage;
+
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    format,
+    vec::Vec,
+};
+use core::{
+    fmt::{self, Display, Formatter},
+    num::ParseIntError,
+    str::FromStr,
+};
+
+pub(crate) use self::disasm::disassemble;
+use self::trace_filter::LoopDeduplicator;
+pub use self::{
+    bank_stats::RomBankStats,
+    disasm::DisassembledInstruction,
+    histogram::OpcodeHistogram,
+    registers::{CpuRegisters, Flags},
+    trace_filter::{AddressRange, TraceFilter},
+    usage::CpuUsage,
+};
+use crate::{argentum::Argentum, bus::Bus, cpu::Cpu};
+
+/// An address qualified with the bank that is mapped there.
+///
+/// Formatted as `BB:AAAA`, for example `03:4F20`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BankedAddress {
+    /// The bank the address belongs to.
+    pub bank: u16,
+
+    /// The 16-bit address as seen by the CPU.
+    pub addr: u16,
+}
+
+impl BankedAddress {
+    /// Create a new `BankedAddress` instance.
+    pub fn new(bank: u16, addr: u16) -> Self {
+        Self { bank, addr }
+    }
+}
+
+impl Display for BankedAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02X}:{:04X}", self.bank, self.addr)
+    }
+}
+
+impl FromStr for BankedAddress {
+    type Err = ParseIntError;
+
+    /// Parse a banked address in the `BB:AAAA` notation.
+    /// A bare `AAAA` is taken to be in bank 0.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((bank, addr)) => Ok(Self {
+                bank: u16::from_str_radix(bank, 16)?,
+                addr: u16::from_str_radix(addr, 16)?,
+            }),
+
+            None => Ok(Self {
+                bank: 0,
+                addr: u16::from_str_radix(s, 16)?,
+            }),
+        }
+    }
+}
+
+/// Receives a line of trace for every executed instruction.
+pub type TraceCallback = Box<dyn FnMut(&str)>;
+
+/// What the CPU does after a PC hook ran.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HookAction {
+    /// Execute the instruction at PC as usual. If the hook
+    /// changed PC, that is the instruction at the new PC.
+    Continue,
+
+    /// Return from the hooked routine as if it executed `RET`,
+    /// skipping the original code entirely.
+    Return,
+}
+
+/// Called when the CPU is about to execute the instruction at a hooked
+/// address. Hooks can read and change anything through the instance.
+pub type PcHook = Box<dyn FnMut(&mut Argentum) -> HookAction>;
+
+/// Keeps track of breakpoints, tracing and profiling.
+pub(crate) struct Debugger {
+    /// All the breakpoints that are set.
+    breakpoints: BTreeSet<BankedAddress>,
+
+    /// Set after stopping at a breakpoint so that
+    /// execution can resume past it.
+    skip_breakpoint: bool,
+
+    /// Called with a line of trace for every executed instruction.
+    trace_callback: Option<TraceCallback>,
+
+    /// Which instructions are traced.
+    trace_filter: TraceFilter,
+
+    /// Set if tight loops are deduplicated in the trace.
+    loop_deduplicator: Option<LoopDeduplicator>,
+
+    /// Amount of times each instruction was executed.
+    profile: Option<BTreeMap<BankedAddress, u64>>,
+
+    /// Amount of times each opcode was executed.
+    histogram: Option<OpcodeHistogram>,
+}
+
+impl Debugger {
+    /// Create a new `Debugger` instance.
+    pub fn new() -> Self {
+        Self {
+            breakpoints: BTreeSet::new(),
+            skip_breakpoint: false,
+            trace_callback: None,
+            trace_filter: TraceFilter::default(),
+            loop_deduplicator: None,
+            profile: None,
+            histogram: None,
+        }
+    }
+
+    /// Check if any debugging facility is in use.
+    pub fn is_active(&self) -> bool {
+        !self.breakpoints.is_empty()
+            || self.trace_callback.is_some()
+            || self.profile.is_some()
+            || self.histogram.is_some()
+    }
+
+    pub fn add_breakpoint(&mut self, addr: BankedAddress) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: BankedAddress) {
+        self.breakpoints.remove(&addr);
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn set_trace_callback(&mut self, callback: Option<TraceCallback>) {
+        self.trace_callback = callback;
+    }
+
+    pub fn set_trace_filter(&mut self, filter: TraceFilter) {
+        self.loop_deduplicator = if filter.deduplicate_loops {
+            Some(LoopDeduplicator::default())
+        } else {
+            None
+        };
+
+        self.trace_filter = filter;
+    }
+
+    /// Enable or disable the profiler, this discards the
+    /// collected profile.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = if enabled { Some(BTreeMap::new()) } else { None };
+    }
+
+    /// Get the amount of times each instruction was executed,
+    /// sorted with the hottest instruction first.
+    pub fn profile_report(&self) -> Vec<(BankedAddress, u64)> {
+        let mut report = self
+            .profile
+            .iter()
+            .flatten()
+            .map(|(&addr, &count)| (addr, count))
+            .collect::<Vec<_>>();
+
+        report.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        report
+    }
+
+    /// Enable or disable counting opcodes, this discards
+    /// the collected counts.
+    pub fn set_opcode_histogram(&mut self, enabled: bool) {
+        self.histogram = if enabled {
+            Some(OpcodeHistogram::new())
+        } else {
+            None
+        };
+    }
+
+    pub fn opcode_histogram(&self) -> Option<&OpcodeHistogram> {
+        self.histogram.as_ref()
+    }
+
+    /// Called before the CPU executes an instruction.
+    /// Returns the breakpoint if execution should stop at one.
+    pub fn before_instruction(&mut self, cpu: &Cpu, bus: &Bus) -> Option<BankedAddress> {
+        let pc = BankedAddress::new(bus.bank_at(cpu.reg.pc), cpu.reg.pc);
+
+        if self.skip_breakpoint {
+            self.skip_breakpoint = false;
+        } else if self.breakpoints.contains(&pc) {
+            self.skip_breakpoint = true;
+
+            return Some(pc);
+        }
+
+        if let Some(profile) = self.profile.as_mut() {
+            *profile.entry(pc).or_insert(0) += 1;
+        }
+
+        if let Some(histogram) = self.histogram.as_mut() {
+            histogram.record(
+                bus.peek_byte(cpu.reg.pc),
+                bus.peek_byte(cpu.reg.pc.wrapping_add(1)),
+            );
+        }
+
+        let traced = self.trace_filter.matches(pc);
+
+        if let Some(callback) = self.trace_callback.as_mut().filter(|_| traced) {
+            let line = format!(
+                "{} PC: {} ({:02X} {:02X} {:02X} {:02X})",
+                TraceRegisters(cpu),
+                pc,
+                bus.peek_byte(cpu.reg.pc),
+                bus.peek_byte(cpu.reg.pc.wrapping_add(1)),
+                bus.peek_byte(cpu.reg.pc.wrapping_add(2)),
+                bus.peek_byte(cpu.reg.pc.wrapping_add(3)),
+            );
+
+            match self.loop_deduplicator.as_mut() {
+                Some(deduplicator) => {
+                    for line in deduplicator.process(pc, line) {
+                        callback(&line);
+                    }
+                }
+
+                None => callback(&line),
+            }
+        }
+
+        None
+    }
+}
+
+/// Formats the registers like Peach's (wheremyfoodat) logs,
+/// minus the PC which is formatted as a banked address.
+struct TraceRegisters<'a>(&'a Cpu);
+
+impl Display for TraceRegisters<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let reg = &self.0.reg;
+
+        write!(
+            f,
+            "A: {:02X} F: {:02X} B: {:02X} C: {:02X} D: {:02X} E: {:02X} H: {:02X} L: {:02X} SP: {:04X}",
+            reg.a,
+            reg.f.bits(),
+            reg.b,
+            reg.c,
+            reg.d,
+            reg.e,
+            reg.h,
+            reg.l,
+            reg.sp
+        )
+    }
+}
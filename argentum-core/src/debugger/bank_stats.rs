@@ -0,0 +1,55 @@
+//! ROM bank access statistics.
+
+use alloc::{boxed::Box, vec::Vec};
+
+/// The most ROM banks a cartridge can have, with MBC5.
+const MAX_ROM_BANKS: usize = 512;
+
+/// How many times each ROM bank was read from, instruction
+/// fetches and DMA transfers included.
+#[derive(Clone)]
+pub struct RomBankStats {
+    reads: Box<[u64; MAX_ROM_BANKS]>,
+}
+
+impl RomBankStats {
+    /// Create a new `RomBankStats` instance.
+    pub(crate) fn new() -> Self {
+        Self {
+            reads: Box::new([0; MAX_ROM_BANKS]),
+        }
+    }
+
+    /// Count a read from the given bank.
+    pub(crate) fn record(&mut self, bank: u16) {
+        if let Some(reads) = self.reads.get_mut(bank as usize) {
+            *reads += 1;
+        }
+    }
+
+    /// Get how many times a bank was read from.
+    pub fn reads(&self, bank: u16) -> u64 {
+        self.reads.get(bank as usize).copied().unwrap_or(0)
+    }
+
+    /// Get the total amount of reads from ROM.
+    pub fn total(&self) -> u64 {
+        self.reads.iter().sum()
+    }
+
+    /// Get all the banks that were read from at least once, hottest
+    /// first. Banks that are never read from are dead weight, or
+    /// point to mistakes in the bank switching code.
+    pub fn sorted(&self) -> Vec<(u16, u64)> {
+        let mut sorted = self
+            .reads
+            .iter()
+            .enumerate()
+            .map(|(bank, &n)| (bank as u16, n))
+            .filter(|&(_, n)| n != 0)
+            .collect::<Vec<_>>();
+
+        sorted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        sorted
+    }
+}
@@ -0,0 +1,178 @@
+//! SM83 disassembler.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{self, Display, Formatter};
+
+use super::BankedAddress;
+use crate::bus::Bus;
+
+/// 8-bit register operands, indexed like the opcode encoding.
+const R8: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+
+/// 16-bit register operands (group 1).
+const R16: [&str; 4] = ["BC", "DE", "HL", "SP"];
+
+/// 16-bit register operands used as pointers (group 2).
+const R16_MEM: [&str; 4] = ["(BC)", "(DE)", "(HL+)", "(HL-)"];
+
+/// 16-bit register operands used by PUSH and POP (group 3).
+const R16_STK: [&str; 4] = ["BC", "DE", "HL", "AF"];
+
+/// Branch conditions.
+const CONDITIONS: [&str; 4] = ["NZ", "Z", "NC", "C"];
+
+/// ALU operations on the accumulator.
+const ALU: [&str; 8] = [
+    "ADD A,", "ADC A,", "SUB A,", "SBC A,", "AND A,", "XOR A,", "OR A,", "CP A,",
+];
+
+/// Operations performed by the accumulator rotate group.
+const ACC_OPS: [&str; 8] = ["RLCA", "RRCA", "RLA", "RRA", "DAA", "CPL", "SCF", "CCF"];
+
+/// Rotate and shift operations in the CB table.
+const SHIFTS: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+
+/// A single decoded instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisassembledInstruction {
+    /// The address the instruction was decoded at.
+    pub address: BankedAddress,
+
+    /// The raw bytes making up the instruction.
+    pub bytes: Vec<u8>,
+
+    /// The instruction in assembly form, jump targets
+    /// into ROM are shown as banked addresses.
+    pub mnemonic: String,
+}
+
+impl Display for DisassembledInstruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let bytes = self
+            .bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        write!(f, "{}  {:<8}  {}", self.address, bytes, self.mnemonic)
+    }
+}
+
+/// Format a 16-bit target address, addresses in ROM are
+/// qualified with the currently mapped bank.
+fn target(bus: &Bus, addr: u16) -> String {
+    if addr < 0x8000 {
+        BankedAddress::new(bus.bank_at(addr), addr).to_string()
+    } else {
+        format!("${:04X}", addr)
+    }
+}
+
+/// Disassemble the instruction at the given address.
+pub(crate) fn disassemble(bus: &Bus, addr: u16) -> DisassembledInstruction {
+    let byte = |offset: u16| bus.peek_byte(addr.wrapping_add(offset));
+
+    let opcode = byte(0);
+    let u8_imm = byte(1);
+    let u16_imm = u16::from_le_bytes([byte(1), byte(2)]);
+
+    let x = (opcode >> 6) as usize;
+    let y = ((opcode >> 3) & 0x7) as usize;
+    let z = (opcode & 0x7) as usize;
+    let p = y >> 1;
+
+    let (mnemonic, len) = match opcode {
+        0x00 => ("NOP".to_string(), 1),
+        0x08 => (format!("LD (${:04X}), SP", u16_imm), 3),
+        0x10 => ("STOP".to_string(), 2),
+        0x76 => ("HALT".to_string(), 1),
+        0xCB => (disassemble_cb(u8_imm), 2),
+
+        0x18 | 0x20 | 0x28 | 0x30 | 0x38 => {
+            let jump = addr.wrapping_add(2).wrapping_add(u8_imm as i8 as u16);
+
+            if opcode == 0x18 {
+                (format!("JR {}", target(bus, jump)), 2)
+            } else {
+                (
+                    format!("JR {}, {}", CONDITIONS[y - 4], target(bus, jump)),
+                    2,
+                )
+            }
+        }
+
+        0xC3 => (format!("JP {}", target(bus, u16_imm)), 3),
+        0xCD => (format!("CALL {}", target(bus, u16_imm)), 3),
+        0xC9 => ("RET".to_string(), 1),
+        0xD9 => ("RETI".to_string(), 1),
+        0xE9 => ("JP HL".to_string(), 1),
+        0xF9 => ("LD SP, HL".to_string(), 1),
+        0xE0 => (format!("LD ($FF00+${:02X}), A", u8_imm), 2),
+        0xF0 => (format!("LD A, ($FF00+${:02X})", u8_imm), 2),
+        0xE2 => ("LD ($FF00+C), A".to_string(), 1),
+        0xF2 => ("LD A, ($FF00+C)".to_string(), 1),
+        0xEA => (format!("LD (${:04X}), A", u16_imm), 3),
+        0xFA => (format!("LD A, (${:04X})", u16_imm), 3),
+        0xE8 => (format!("ADD SP, {}", u8_imm as i8), 2),
+        0xF8 => (format!("LD HL, SP{:+}", u8_imm as i8), 2),
+        0xF3 => ("DI".to_string(), 1),
+        0xFB => ("EI".to_string(), 1),
+
+        0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD => {
+            (format!("ILLEGAL ${:02X}", opcode), 1)
+        }
+
+        _ => match (x, z) {
+            (0, 1) if y & 1 == 0 => (format!("LD {}, ${:04X}", R16[p], u16_imm), 3),
+            (0, 1) => (format!("ADD HL, {}", R16[p]), 1),
+            (0, 2) if y & 1 == 0 => (format!("LD {}, A", R16_MEM[p]), 1),
+            (0, 2) => (format!("LD A, {}", R16_MEM[p]), 1),
+            (0, 3) if y & 1 == 0 => (format!("INC {}", R16[p]), 1),
+            (0, 3) => (format!("DEC {}", R16[p]), 1),
+            (0, 4) => (format!("INC {}", R8[y]), 1),
+            (0, 5) => (format!("DEC {}", R8[y]), 1),
+            (0, 6) => (format!("LD {}, ${:02X}", R8[y], u8_imm), 2),
+            (0, 7) => (ACC_OPS[y].to_string(), 1),
+            (1, _) => (format!("LD {}, {}", R8[y], R8[z]), 1),
+            (2, _) => (format!("{} {}", ALU[y], R8[z]), 1),
+            (3, 0) => (format!("RET {}", CONDITIONS[y]), 1),
+            (3, 1) => (format!("POP {}", R16_STK[p]), 1),
+            (3, 2) => (format!("JP {}, {}", CONDITIONS[y], target(bus, u16_imm)), 3),
+            (3, 4) => (
+                format!("CALL {}, {}", CONDITIONS[y], target(bus, u16_imm)),
+                3,
+            ),
+            (3, 5) => (format!("PUSH {}", R16_STK[p]), 1),
+            (3, 6) => (format!("{} ${:02X}", ALU[y], u8_imm), 2),
+            (3, 7) => (format!("RST ${:02X}", y * 8), 1),
+
+            _ => unreachable!(),
+        },
+    };
+
+    DisassembledInstruction {
+        address: BankedAddress::new(bus.bank_at(addr), addr),
+        bytes: (0..len).map(byte).collect(),
+        mnemonic,
+    }
+}
+
+/// Disassemble an opcode from the CB table.
+fn disassemble_cb(opcode: u8) -> String {
+    let r8 = R8[(opcode & 0x7) as usize];
+    let y = ((opcode >> 3) & 0x7) as usize;
+
+    match opcode >> 6 {
+        0 => format!("{} {}", SHIFTS[y], r8),
+        1 => format!("BIT {}, {}", y, r8),
+        2 => format!("RES {}, {}", y, r8),
+        3 => format!("SET {}, {}", y, r8),
+
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,64 @@
+//! Opcode usage statistics.
+
+/// How many times each opcode was executed.
+use alloc::{boxed::Box, vec::Vec};
+
+#[derive(Clone)]
+pub struct OpcodeHistogram {
+    /// Counts for the base opcode table.
+    base: Box<[u64; 256]>,
+
+    /// Counts for the opcodes prefixed by 0xCB.
+    cb: Box<[u64; 256]>,
+}
+
+impl OpcodeHistogram {
+    /// Create a new `OpcodeHistogram` instance.
+    pub(crate) fn new() -> Self {
+        Self {
+            base: Box::new([0; 256]),
+            cb: Box::new([0; 256]),
+        }
+    }
+
+    /// Count an executed instruction, `cb_opcode` is only
+    /// looked at if `opcode` is the 0xCB prefix.
+    pub(crate) fn record(&mut self, opcode: u8, cb_opcode: u8) {
+        self.base[opcode as usize] += 1;
+
+        if opcode == 0xCB {
+            self.cb[cb_opcode as usize] += 1;
+        }
+    }
+
+    /// Get how many times an opcode was executed.
+    pub fn count(&self, opcode: u8) -> u64 {
+        self.base[opcode as usize]
+    }
+
+    /// Get how many times a 0xCB prefixed opcode was executed.
+    pub fn cb_count(&self, opcode: u8) -> u64 {
+        self.cb[opcode as usize]
+    }
+
+    /// Get the total amount of instructions executed.
+    pub fn total(&self) -> u64 {
+        self.base.iter().sum()
+    }
+
+    /// Get all the opcodes that were executed at least once, most
+    /// used first. CB opcodes are returned as `0xCB00 | opcode`.
+    pub fn sorted(&self) -> Vec<(u16, u64)> {
+        let base = self.base.iter().enumerate().map(|(i, &n)| (i as u16, n));
+        let cb = self
+            .cb
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| (0xCB00 | i as u16, n));
+
+        let mut sorted = base.chain(cb).filter(|&(_, n)| n != 0).collect::<Vec<_>>();
+
+        sorted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        sorted
+    }
+}
@@ -0,0 +1,101 @@
+//! Typed views of the CPU's registers.
+
+use crate::cpu::Cpu;
+
+/// The flag register, one bool per flag.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Flags {
+    /// Z - Set if the result of an operation is zero.
+    pub zero: bool,
+
+    /// N - Set if the last operation was a subtraction.
+    pub subtract: bool,
+
+    /// H - Set on a carry out of the lower nibble.
+    pub half_carry: bool,
+
+    /// C - Set on a carry out of the result.
+    pub carry: bool,
+}
+
+impl From<u8> for Flags {
+    fn from(value: u8) -> Self {
+        Self {
+            zero: (value & 0x80) != 0,
+            subtract: (value & 0x40) != 0,
+            half_carry: (value & 0x20) != 0,
+            carry: (value & 0x10) != 0,
+        }
+    }
+}
+
+impl From<Flags> for u8 {
+    fn from(flags: Flags) -> Self {
+        ((flags.zero as u8) << 7)
+            | ((flags.subtract as u8) << 6)
+            | ((flags.half_carry as u8) << 5)
+            | ((flags.carry as u8) << 4)
+    }
+}
+
+/// A copy of all the CPU's registers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CpuRegisters {
+    pub a: u8,
+    pub f: Flags,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+}
+
+impl CpuRegisters {
+    /// Copy the registers out of the CPU.
+    pub(crate) fn capture(cpu: &Cpu) -> Self {
+        let reg = &cpu.reg;
+
+        Self {
+            a: reg.a,
+            f: reg.f.bits().into(),
+            b: reg.b,
+            c: reg.c,
+            d: reg.d,
+            e: reg.e,
+            h: reg.h,
+            l: reg.l,
+            sp: reg.sp,
+            pc: reg.pc,
+        }
+    }
+
+    /// Write the registers back into the CPU.
+    pub(crate) fn restore(&self, cpu: &mut Cpu) {
+        cpu.reg.set_af(self.af());
+        cpu.reg.set_bc(self.bc());
+        cpu.reg.set_de(self.de());
+        cpu.reg.set_hl(self.hl());
+
+        cpu.reg.sp = self.sp;
+        cpu.reg.pc = self.pc;
+    }
+
+    pub fn af(&self) -> u16 {
+        u16::from_be_bytes([self.a, self.f.into()])
+    }
+
+    pub fn bc(&self) -> u16 {
+        u16::from_be_bytes([self.b, self.c])
+    }
+
+    pub fn de(&self) -> u16 {
+        u16::from_be_bytes([self.d, self.e])
+    }
+
+    pub fn hl(&self) -> u16 {
+        u16::from_be_bytes([self.h, self.l])
+    }
+}
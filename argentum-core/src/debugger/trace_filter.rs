@@ -0,0 +1,191 @@
+use alloc::{collections::VecDeque, format, string::String, vec::Vec};
+use core::{
+    fmt::{self, Display, Formatter},
+    num::ParseIntError,
+    str::FromStr,
+};
+
+use super::BankedAddress;
+
+/// Loops are only recognised if their body is at most this many instructions.
+const LOOP_WINDOW: usize = 32;
+
+/// A range of addresses, both ends included.
+///
+/// Parsed from `AAAA-AAAA`, or `AAAA` for a single address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddressRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl AddressRange {
+    pub fn contains(&self, addr: u16) -> bool {
+        (self.start..=self.end).contains(&addr)
+    }
+}
+
+impl Display for AddressRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04X}-{:04X}", self.start, self.end)
+    }
+}
+
+impl FromStr for AddressRange {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('-') {
+            Some((start, end)) => Ok(Self {
+                start: u16::from_str_radix(start, 16)?,
+                end: u16::from_str_radix(end, 16)?,
+            }),
+
+            None => {
+                let addr = u16::from_str_radix(s, 16)?;
+
+                Ok(Self {
+                    start: addr,
+                    end: addr,
+                })
+            }
+        }
+    }
+}
+
+/// Decides which instructions show up in the trace.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    /// Only trace instructions in these ranges, or everywhere if empty.
+    pub include: Vec<AddressRange>,
+
+    /// Never trace instructions in these ranges,
+    /// for example a wait for VBlank loop.
+    pub exclude: Vec<AddressRange>,
+
+    /// Only trace instructions in these banks, or in any bank if empty.
+    /// The bank is the one of the instruction's `BankedAddress`.
+    pub banks: Vec<u16>,
+
+    /// Trace one iteration of a tight loop, and replace the
+    /// iterations that follow it with a line counting them.
+    pub deduplicate_loops: bool,
+}
+
+impl TraceFilter {
+    /// Check if the instruction at the given address is traced.
+    pub fn matches(&self, pc: BankedAddress) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|range| range.contains(pc.addr)))
+            && !self.exclude.iter().any(|range| range.contains(pc.addr))
+            && (self.banks.is_empty() || self.banks.contains(&pc.bank))
+    }
+}
+
+/// Recognises instructions that repeat the last iteration of a loop.
+///
+/// Once an instruction shows up again within `LOOP_WINDOW` instructions,
+/// the instructions since its last appearance are taken as the body of
+/// a loop. The lines of the next iteration are held back, if it repeats
+/// the body exactly they are dropped and the iterations after it are
+/// only counted.
+#[derive(Default)]
+pub(crate) struct LoopDeduplicator {
+    /// The most recently traced instructions.
+    recent: VecDeque<BankedAddress>,
+
+    /// The body of the loop being repeated, if any.
+    body: Vec<BankedAddress>,
+
+    /// The index of the instruction of the body expected next.
+    position: usize,
+
+    /// Set once a whole iteration repeated the body.
+    repeating: bool,
+
+    /// The lines of the first repetition, traced if it turns out not to be one.
+    pending: Vec<String>,
+
+    /// The instructions skipped since the loop started repeating.
+    skipped: u64,
+}
+
+impl LoopDeduplicator {
+    /// Pass the trace line of the next instruction, and get
+    /// the lines to write in its place, if any.
+    pub fn process(&mut self, pc: BankedAddress, line: String) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        if !self.body.is_empty() {
+            if self.body[self.position] == pc {
+                self.position = (self.position + 1) % self.body.len();
+
+                if self.repeating {
+                    self.skipped += 1;
+                } else {
+                    self.pending.push(line);
+
+                    if self.position == 0 {
+                        self.repeating = true;
+                        self.skipped = self.pending.len() as u64;
+                        self.pending.clear();
+                    }
+                }
+
+                return lines;
+            }
+
+            // The loop is left, or didn't repeat after all.
+            if self.repeating {
+                lines.push(format!(
+                    "... {} instructions repeating the loop at {}",
+                    self.skipped, self.body[0]
+                ));
+
+                // The loop was the last thing traced.
+                self.recent.clear();
+                self.recent.extend(self.body.iter().copied());
+            } else {
+                for pending in self.pending.drain(..) {
+                    lines.push(pending);
+                }
+
+                for i in 0..self.position {
+                    self.remember(self.body[i]);
+                }
+            }
+
+            self.body.clear();
+            self.position = 0;
+            self.repeating = false;
+            self.skipped = 0;
+        }
+
+        if let Some(start) = self.recent.iter().rposition(|&recent| recent == pc) {
+            self.body = self.recent.iter().skip(start).copied().collect();
+            self.position = 1 % self.body.len();
+            self.pending.push(line);
+
+            // A loop of a single instruction repeats right away.
+            if self.position == 0 {
+                self.repeating = true;
+                self.skipped = 1;
+                self.pending.clear();
+            }
+
+            return lines;
+        }
+
+        self.remember(pc);
+        lines.push(line);
+
+        lines
+    }
+
+    fn remember(&mut self, pc: BankedAddress) {
+        if self.recent.len() == LOOP_WINDOW {
+            self.recent.pop_front();
+        }
+
+        self.recent.push_back(pc);
+    }
+}
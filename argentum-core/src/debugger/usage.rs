@@ -0,0 +1,26 @@
+//! Emulated CPU load statistics.
+
+/// How the CPU spent the cycles of a frame, a rough measure of
+/// how much of its time a game needs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CpuUsage {
+    /// Cycles spent executing instructions.
+    pub busy_cycles: u32,
+
+    /// Cycles spent waiting in HALT or STOP, or locked up.
+    pub halted_cycles: u32,
+}
+
+impl CpuUsage {
+    /// Get the fraction of cycles spent executing instructions,
+    /// from 0.0 to 1.0.
+    pub fn load(&self) -> f64 {
+        let total = self.busy_cycles + self.halted_cycles;
+
+        if total == 0 {
+            return 0.0;
+        }
+
+        self.busy_cycles as f64 / total as f64
+    }
+}
@@ -0,0 +1,213 @@
+//! Deterministic execution, the foundation for replays and netplay.
+//!
+//! In `DeterminismMode::Strict` the emulated state after any number of
+//! frames only depends on,
+//!
+//! - The ROM, the save file and the mapper registry.
+//! - Cartridges that were swapped in, and when.
+//! - Whether the bootrom was skipped, and the overclock multiplier.
+//! - Key presses and releases, and the frame they happened on.
+//! - Save states that were loaded, and when.
+//! - The enabled cheat codes, and when they changed.
+//! - PC hooks, which can change anything.
+//! - The link partner, and the bits it sends.
+//! - Attached peripherals, and what they answer.
+//!
+//! The core never reads the host clock, all memory starts zeroed and
+//! nothing is randomized. Debugger features (tracing, breakpoints and
+//! profiling) never change the emulated state. The only host input
+//! that does is the audio rate ratio used for dynamic rate control,
+//! which is ignored in strict mode.
+
+use crate::{argentum::Argentum, joypad::ArgentumKey};
+use alloc::vec::Vec;
+
+/// How strictly the emulated state has to be reproducible.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeterminismMode {
+    /// The audio rate ratio may differ between hosts, which changes
+    /// when audio samples are generated. The default.
+    Relaxed,
+
+    /// Only the inputs listed in the module documentation affect
+    /// the emulated state. Use this for replays and netplay.
+    Strict,
+}
+
+/// A key press or release, applied before the given frame runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputEvent {
+    pub frame: u32,
+    pub key: ArgentumKey,
+    pub pressed: bool,
+}
+
+/// Two runs that should have been identical diverged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// The frame after which the differing hashes were taken.
+    pub frame: u32,
+
+    pub first_hash: u64,
+    pub second_hash: u64,
+}
+
+/// Hash a save state with 64-bit FNV-1a.
+pub(crate) fn hash_state(state: &[u8]) -> u64 {
+    let mut hash = 0xCBF2_9CE4_8422_2325u64;
+
+    for &byte in state {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+
+    hash
+}
+
+/// Run two instances of a ROM in lockstep with the same inputs, comparing
+/// the state hashes every 60 frames. Returns the hashes if both runs matched.
+///
+/// Both instances have to be set up the same way before they are
+/// passed in, for example both skipping the bootrom.
+pub fn verify_determinism(
+    mut runs: [Argentum; 2],
+    inputs: &[InputEvent],
+    frames: u32,
+) -> Result<Vec<u64>, Divergence> {
+    for gb in runs.iter_mut() {
+        gb.set_determinism_mode(DeterminismMode::Strict);
+    }
+
+    let mut hashes = Vec::new();
+
+    for frame in 0..frames {
+        for gb in runs.iter_mut() {
+            for input in inputs.iter().filter(|input| input.frame == frame) {
+                if input.pressed {
+                    gb.key_down(input.key);
+                } else {
+                    gb.key_up(input.key);
+                }
+            }
+
+            gb.execute_frame();
+        }
+
+        if (frame + 1) % 60 == 0 || frame + 1 == frames {
+            let first_hash = runs[0].state_hash();
+            let second_hash = runs[1].state_hash();
+
+            if first_hash != second_hash {
+                return Err(Divergence {
+                    frame,
+                    first_hash,
+                    second_hash,
+                });
+            }
+
+            hashes.push(first_hash);
+        }
+    }
+
+    Ok(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rom;
+    use alloc::boxed::Box;
+
+    /// Copies the buttons into BGP, so they show up in the frame,
+    /// and counts the loops in work RAM.
+    #[rustfmt::skip]
+    const PROGRAM: [u8; 14] = [
+        0x3E, 0x10,       // LD A, 0x10
+        0xE0, 0x00,       // LDH (P1), A
+        0xF0, 0x00,       // LDH A, (P1)
+        0xE0, 0x47,       // LDH (BGP), A
+        0x21, 0x00, 0xC0, // LD HL, 0xC000
+        0x34,             // INC (HL)
+        0x18, 0xF2,       // JR -14
+    ];
+
+    const INPUTS: [InputEvent; 4] = [
+        InputEvent {
+            frame: 10,
+            key: ArgentumKey::ButtonA,
+            pressed: true,
+        },
+        InputEvent {
+            frame: 25,
+            key: ArgentumKey::Start,
+            pressed: true,
+        },
+        InputEvent {
+            frame: 40,
+            key: ArgentumKey::ButtonA,
+            pressed: false,
+        },
+        InputEvent {
+            frame: 90,
+            key: ArgentumKey::Start,
+            pressed: false,
+        },
+    ];
+
+    /// Run the program with the inputs, returning
+    /// the frame hash and the save state.
+    fn run(inputs: &[InputEvent]) -> (u64, Vec<u8>) {
+        let mut gb =
+            Argentum::new(&test_rom::with_program(&PROGRAM), Box::new(|_| {}), None).unwrap();
+
+        gb.set_determinism_mode(DeterminismMode::Strict);
+        gb.skip_bootrom();
+
+        for frame in 0..120 {
+            for input in inputs.iter().filter(|input| input.frame == frame) {
+                if input.pressed {
+                    gb.key_down(input.key);
+                } else {
+                    gb.key_up(input.key);
+                }
+            }
+
+            gb.execute_frame();
+        }
+
+        (gb.frame_hash(), gb.save_state())
+    }
+
+    #[test]
+    fn same_inputs_give_the_same_state() {
+        let (first_frame, first_state) = run(&INPUTS);
+        let (second_frame, second_state) = run(&INPUTS);
+
+        assert_eq!(first_frame, second_frame);
+        assert_eq!(first_state, second_state);
+    }
+
+    #[test]
+    fn inputs_change_the_state() {
+        let (_, pressed) = run(&INPUTS[..1]);
+        let (_, released) = run(&[]);
+
+        assert_ne!(pressed, released);
+    }
+
+    #[test]
+    fn verify_determinism_matches() {
+        let rom = test_rom::with_program(&PROGRAM);
+
+        let runs = [(); 2].map(|()| {
+            let mut gb = Argentum::new(&rom, Box::new(|_| {}), None).unwrap();
+
+            gb.skip_bootrom();
+            gb
+        });
+
+        let hashes = verify_determinism(runs, &INPUTS, 120);
+
+        assert_eq!(hashes.map(|hashes| hashes.len()), Ok(2));
+    }
+}
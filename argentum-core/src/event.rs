@@ -0,0 +1,23 @@
+//! Events reported by the emulator core.
+
+use crate::debugger::BankedAddress;
+
+/// Something noteworthy that happened while executing.
+///
+/// Events are queued up by the core and can be polled
+/// using `Argentum::poll_event`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArgentumEvent {
+    /// Execution stopped before the instruction
+    /// at the given address.
+    BreakpointHit(BankedAddress),
+
+    /// The CPU executed one of the illegal opcodes and locked up.
+    /// It stays locked until the system is reset.
+    CpuLocked { opcode: u8, address: BankedAddress },
+
+    /// The boot ROM found a bad logo or header checksum and hung
+    /// instead of starting the game, see `Argentum::cartridge_info`.
+    /// Only reported when `set_header_check` enabled the check.
+    BootFailed,
+}
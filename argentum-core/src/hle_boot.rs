@@ -0,0 +1,105 @@
+//! A high level emulation of the boot sequence.
+//!
+//! Instead of executing a boot ROM, the Nintendo logo in the
+//! cartridge header is drawn and scrolled down, the chime plays
+//! and the game is started with the registers a boot ROM leaves
+//! behind. The DMG sequence is shown for every model, and the
+//! header isn't checked.
+
+use crate::bus::Bus;
+
+/// Where the logo is stored in the cartridge header.
+const LOGO_ADDRESS: u16 = 0x0104;
+
+/// The bytes of the logo, every one holds two rows of a 4x4 tile.
+const LOGO_SIZE: u16 = 48;
+
+/// The ® shown next to the logo, one byte per row.
+const REGISTERED_TILE: [u8; 8] = [0x3C, 0x42, 0xB9, 0xA5, 0xB9, 0xA5, 0x42, 0x3C];
+
+/// SCY when the logo starts scrolling in, it scrolls a line per frame.
+const SCROLL_START: u8 = 0x64;
+
+/// Frames after the scroll ends that the second note of the chime starts.
+const SECOND_NOTE_DELAY: u32 = 5;
+
+/// Frames after the scroll ends that the game is started.
+const HAND_OFF_DELAY: u32 = 60;
+
+pub(crate) struct HleBoot {
+    /// Frames shown so far.
+    frame: u32,
+}
+
+impl HleBoot {
+    /// Create a new `HleBoot` instance, drawing the logo
+    /// into VRAM and setting up the PPU and APU.
+    pub fn new(bus: &mut Bus) -> Self {
+        bus.write_byte(0xFF40, 0x00, false);
+
+        // Every pixel of the logo is doubled in both directions.
+        for i in 0..LOGO_SIZE {
+            let byte = bus.peek_byte(LOGO_ADDRESS + i);
+            let tile_address = 0x8010 + (i / 2) * 16 + (i % 2) * 8;
+
+            for (j, nibble) in [byte >> 4, byte & 0x0F].iter().enumerate() {
+                let doubled = (0..4).fold(0u8, |doubled, bit| {
+                    doubled | (((nibble >> bit) & 0x01) * 0b11) << (bit * 2)
+                });
+
+                for row in 0..2 {
+                    bus.write_byte(tile_address + (j as u16 * 2 + row) * 2, doubled, false);
+                }
+            }
+        }
+
+        for (i, &row) in REGISTERED_TILE.iter().enumerate() {
+            bus.write_byte(0x8190 + i as u16 * 2, row, false);
+        }
+
+        // The logo is 12 tiles wide, in two rows.
+        for i in 0..12 {
+            bus.write_byte(0x9904 + i, i as u8 + 1, false);
+            bus.write_byte(0x9924 + i, i as u8 + 13, false);
+        }
+
+        bus.write_byte(0x9910, 0x19, false);
+
+        bus.write_byte(0xFF42, SCROLL_START, false);
+        bus.write_byte(0xFF47, 0xFC, false);
+        bus.write_byte(0xFF40, 0x91, false);
+
+        // Square channel 1 plays the chime on both speakers.
+        bus.write_byte(0xFF26, 0x80, false);
+        bus.write_byte(0xFF11, 0x80, false);
+        bus.write_byte(0xFF12, 0xF3, false);
+        bus.write_byte(0xFF25, 0xF3, false);
+        bus.write_byte(0xFF24, 0x77, false);
+
+        Self { frame: 0 }
+    }
+
+    /// Advance the sequence after a frame was drawn.
+    /// Returns true once the game should be started.
+    pub fn next_frame(&mut self, bus: &mut Bus) -> bool {
+        self.frame += 1;
+
+        let scroll_end = SCROLL_START as u32;
+
+        if self.frame <= scroll_end {
+            bus.write_byte(0xFF42, SCROLL_START - self.frame as u8, false);
+        }
+
+        if self.frame == scroll_end {
+            bus.write_byte(0xFF13, 0x83, false);
+            bus.write_byte(0xFF14, 0x87, false);
+        }
+
+        if self.frame == scroll_end + SECOND_NOTE_DELAY {
+            bus.write_byte(0xFF13, 0xC1, false);
+            bus.write_byte(0xFF14, 0x87, false);
+        }
+
+        self.frame >= scroll_end + HAND_OFF_DELAY
+    }
+}
@@ -1,7 +1,12 @@
-use std::{cell::RefCell, rc::Rc};
+use alloc::rc::Rc;
+use core::cell::RefCell;
 
-use crate::util::set_bit;
+use crate::{
+    state::{StateError, StateReader, StateWriter},
+    util::set_bit,
+};
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum ArgentumKey {
     Right = 0x01,
@@ -14,9 +19,16 @@ pub enum ArgentumKey {
     Start = 0x80,
 }
 
+/// The most controllers that can be connected, through the SGB.
+pub const MAX_PLAYERS: usize = 4;
+
 pub struct Joypad {
-    /// Contains the current state of the buttons and DPAD.
-    joypad_state: u8,
+    /// Contains the current state of the buttons and DPAD, per player.
+    joypad_states: [u8; MAX_PLAYERS],
+
+    /// The player whose controller is read through P1. Only the SGB
+    /// can switch controllers, so this is always the first player.
+    player: usize,
 
     /// Indicates if the DPAD control bit selected.
     dpad: bool,
@@ -32,45 +44,199 @@ impl Joypad {
     /// Create a new `Joypad` instance.
     pub fn new(if_reg: Rc<RefCell<u8>>) -> Self {
         Self {
-            joypad_state: 0x00,
+            joypad_states: [0x00; MAX_PLAYERS],
+            player: 0,
             dpad: false,
             buttons: false,
             if_reg,
         }
     }
 
-    /// Register a key being pressed.
-    pub fn key_down(&mut self, key: ArgentumKey) {
-        self.joypad_state |= key as u8;
-        set_bit!(self.if_reg.borrow_mut(), 4);
+    /// Register a key being pressed on a player's controller.
+    pub fn key_down(&mut self, player: usize, key: ArgentumKey) {
+        self.set_keys(player, self.joypad_states[player] | key as u8);
     }
 
-    /// Register a key being unpressed.
-    pub fn key_up(&mut self, key: ArgentumKey) {
-        self.joypad_state &= !(key as u8);
+    /// Register a key being unpressed on a player's controller.
+    pub fn key_up(&mut self, player: usize, key: ArgentumKey) {
+        self.set_keys(player, self.joypad_states[player] & !(key as u8));
     }
 
-    /// Read a byte from the specified address.
-    pub fn read_byte(&self, _: u16) -> u8 {
-        let mut joyp = 0x00;
+    /// Replace the state of all keys on a player's controller.
+    pub fn set_keys(&mut self, player: usize, keys: u8) {
+        let lines = self.input_lines();
 
-        joyp |= (self.dpad as u8) << 4;
-        joyp |= (self.buttons as u8) << 5;
+        self.joypad_states[player] = keys;
+        self.update_interrupt(lines);
+    }
+
+    /// Get the P10 - P13 input lines, which are low while a key
+    /// in a selected group is held. Keys in both groups share the
+    /// lines when both are selected.
+    pub fn input_lines(&self) -> u8 {
+        let joypad_state = self.joypad_states[self.player];
+        let mut pressed = 0x00;
 
         if self.dpad {
-            joyp |= (self.joypad_state & 0x0F) >> 0;
+            pressed |= joypad_state & 0x0F;
         }
 
         if self.buttons {
-            joyp |= (self.joypad_state & 0xF0) >> 4;
+            pressed |= (joypad_state & 0xF0) >> 4;
+        }
+
+        !pressed & 0x0F
+    }
+
+    /// Request an interrupt if any input line went from high to low.
+    /// Keys in groups that aren't selected, or held on a controller
+    /// that isn't being read, don't pull any line low.
+    fn update_interrupt(&mut self, old_lines: u8) {
+        if old_lines & !self.input_lines() != 0 {
+            set_bit!(self.if_reg.borrow_mut(), 4);
         }
+    }
+
+    /// Get the state of all keys on a player's controller.
+    pub fn keys(&self, player: usize) -> u8 {
+        self.joypad_states[player]
+    }
+
+    /// Serialize the joypad's state.
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.joypad_states);
+        w.write_u8(self.player as u8);
+        w.write_bool(self.dpad);
+        w.write_bool(self.buttons);
+    }
+
+    /// Restore the joypad's state.
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        r.read_into(&mut self.joypad_states)?;
+        self.player = r.read_u8_in(0..=MAX_PLAYERS as u8 - 1)? as usize;
+        self.dpad = r.read_bool()?;
+        self.buttons = r.read_bool()?;
+
+        Ok(())
+    }
+
+    /// Read a byte from the specified address.
+    ///
+    /// The unused upper bits read as 1, the select bits read back
+    /// as written. For example 0xCF with both groups selected and
+    /// no key held, 0xFF with neither group selected.
+    pub fn read_byte(&self, _: u16) -> u8 {
+        let select = (!self.dpad as u8) << 4 | (!self.buttons as u8) << 5;
 
-        !joyp
+        0xC0 | select | self.input_lines()
     }
 
     /// Write a byte to the specified address.
+    ///
+    /// Selecting a group while one of its keys is held
+    /// pulls a line low, which requests an interrupt too.
     pub fn write_byte(&mut self, _: u16, value: u8) {
+        let lines = self.input_lines();
+
         self.dpad = (value & 0x10) == 0;
         self.buttons = (value & 0x20) == 0;
+
+        self.update_interrupt(lines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn joypad() -> (Joypad, Rc<RefCell<u8>>) {
+        let if_reg = Rc::new(RefCell::new(0));
+
+        (Joypad::new(if_reg.clone()), if_reg)
+    }
+
+    /// Check if a joypad interrupt was requested, and clear it.
+    fn take_interrupt(if_reg: &Rc<RefCell<u8>>) -> bool {
+        let requested = *if_reg.borrow() & 0x10 != 0;
+
+        *if_reg.borrow_mut() = 0;
+        requested
+    }
+
+    #[test]
+    fn p1_reads_with_no_keys_held() {
+        let (mut joypad, _) = joypad();
+
+        joypad.write_byte(0xFF00, 0x00);
+        assert_eq!(joypad.read_byte(0xFF00), 0xCF);
+
+        joypad.write_byte(0xFF00, 0x30);
+        assert_eq!(joypad.read_byte(0xFF00), 0xFF);
+
+        // Keys held in groups that aren't selected don't show up.
+        joypad.key_down(0, ArgentumKey::Start);
+        joypad.key_down(0, ArgentumKey::Down);
+        assert_eq!(joypad.read_byte(0xFF00), 0xFF);
+    }
+
+    #[test]
+    fn p1_reads_selected_keys() {
+        let (mut joypad, _) = joypad();
+
+        joypad.key_down(0, ArgentumKey::Start);
+        joypad.key_down(0, ArgentumKey::Left);
+
+        joypad.write_byte(0xFF00, 0x10);
+        assert_eq!(joypad.read_byte(0xFF00), 0xD7);
+
+        joypad.write_byte(0xFF00, 0x20);
+        assert_eq!(joypad.read_byte(0xFF00), 0xED);
+
+        // Both groups share the lines.
+        joypad.write_byte(0xFF00, 0x00);
+        assert_eq!(joypad.read_byte(0xFF00), 0xC5);
+    }
+
+    #[test]
+    fn interrupt_on_falling_edge_only() {
+        let (mut joypad, if_reg) = joypad();
+
+        joypad.write_byte(0xFF00, 0x10);
+
+        joypad.key_down(0, ArgentumKey::ButtonA);
+        assert!(take_interrupt(&if_reg));
+
+        // Holding a key, or releasing it, doesn't pull a line low.
+        joypad.key_down(0, ArgentumKey::ButtonA);
+        assert!(!take_interrupt(&if_reg));
+
+        joypad.key_up(0, ArgentumKey::ButtonA);
+        assert!(!take_interrupt(&if_reg));
+
+        // The DPAD isn't selected.
+        joypad.key_down(0, ArgentumKey::Right);
+        assert!(!take_interrupt(&if_reg));
+
+        // Selecting it with Right held pulls P10 low.
+        joypad.write_byte(0xFF00, 0x00);
+        assert!(take_interrupt(&if_reg));
+
+        // P10 is already low from Right.
+        joypad.key_down(0, ArgentumKey::ButtonA);
+        assert!(!take_interrupt(&if_reg));
+
+        joypad.key_down(0, ArgentumKey::ButtonB);
+        assert!(take_interrupt(&if_reg));
+    }
+
+    #[test]
+    fn other_players_do_not_interrupt() {
+        let (mut joypad, if_reg) = joypad();
+
+        joypad.write_byte(0xFF00, 0x00);
+        joypad.key_down(1, ArgentumKey::Start);
+
+        assert!(!take_interrupt(&if_reg));
+        assert_eq!(joypad.read_byte(0xFF00), 0xCF);
     }
 }
@@ -1,11 +1,65 @@
+#![no_std]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+extern crate std;
+
 mod argentum;
 mod audio;
+mod audio_log;
 mod bus;
+mod bus_script;
 mod cartridge;
+mod cheats;
+mod clock;
+mod compat_palette;
 mod cpu;
+mod debugger;
+mod determinism;
+mod event;
+mod hle_boot;
 mod joypad;
+#[cfg(feature = "perf-counters")]
+mod perf;
+mod peripheral;
 mod ppu;
+mod ram_diff;
+mod serial;
+mod state;
+#[cfg(test)]
+mod test_rom;
 mod timer;
 mod util;
 
-pub use {argentum::Argentum, joypad::ArgentumKey};
+pub use {
+    argentum::Argentum,
+    audio::{AudioCallback, AudioFormat, AudioSamples},
+    audio_log::{AudioLog, AudioRegisterWrite},
+    bus::{InvalidBootRom, MemoryBus},
+    bus_script::{BusScript, BusScriptFailure, ParseBusScriptError},
+    cartridge::{
+        CartridgeInfo, Mapper, MapperRegistry, RamError, Rom, RomSource, UnsupportedCartridge,
+    },
+    cheats::{CheatCode, ParseCheatError},
+    compat_palette::DmgPalettes,
+    cpu::{Cpu, CpuState},
+    debugger::{
+        AddressRange, BankedAddress, CpuRegisters, CpuUsage, DisassembledInstruction, Flags,
+        HookAction, OpcodeHistogram, PcHook, RomBankStats, TraceCallback, TraceFilter,
+    },
+    determinism::{verify_determinism, DeterminismMode, Divergence, InputEvent},
+    event::ArgentumEvent,
+    joypad::{ArgentumKey, MAX_PLAYERS},
+    peripheral::Peripheral,
+    ppu::{FrameInfo, IndexedImage, RawFrame, ScanlineRegisters},
+    ram_diff::RamChange,
+    serial::{
+        AdapterPort, BarcodeReader, BarcodeReaderKind, FourPlayerAdapter, InvalidBarcode,
+        LinkPartner,
+    },
+    state::{Snapshot, StateError},
+};
+
+#[cfg(feature = "perf-counters")]
+pub use perf::PerfCounters;
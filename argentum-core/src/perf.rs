@@ -0,0 +1,69 @@
+//! Host time spent in every subsystem, so reports of the emulator being
+//! slow on some machine come with numbers pointing at the culprit.
+
+use core::{
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+
+/// Counts where the time running the emulation went, since the
+/// counters were last reset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PerfCounters {
+    /// Frames completed with `Argentum::execute_frame`.
+    pub frames: u64,
+
+    /// Instructions executed. While halted or stopped,
+    /// every M-cycle counts as an instruction.
+    pub instructions: u64,
+
+    /// Time spent executing instructions, the time
+    /// the components were ticked for included.
+    pub total: Duration,
+
+    /// Time spent ticking the PPU, rendering scanlines included.
+    pub ppu: Duration,
+
+    /// Time spent ticking the APU, mixing samples and
+    /// calling the audio callback included.
+    pub apu: Duration,
+}
+
+impl PerfCounters {
+    /// Time spent dispatching instructions and in everything
+    /// else, like the timer, memory accesses and DMA.
+    pub fn cpu(&self) -> Duration {
+        self.total.saturating_sub(self.ppu + self.apu)
+    }
+}
+
+impl Display for PerfCounters {
+    /// Summarise the counters on one line, per frame where it makes sense.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let frames = self.frames.max(1) as f64;
+
+        let per_frame = |duration: Duration| duration.as_secs_f64() * 1000.0 / frames;
+
+        let share = |duration: Duration| {
+            if self.total.as_nanos() == 0 {
+                0.0
+            } else {
+                duration.as_secs_f64() / self.total.as_secs_f64() * 100.0
+            }
+        };
+
+        write!(
+            f,
+            "{} frames, {} instructions, {:.3} ms per frame: CPU {:.3} ms ({:.0}%), PPU {:.3} ms ({:.0}%), APU {:.3} ms ({:.0}%)",
+            self.frames,
+            self.instructions,
+            per_frame(self.total),
+            per_frame(self.cpu()),
+            share(self.cpu()),
+            per_frame(self.ppu),
+            share(self.ppu),
+            per_frame(self.apu),
+            share(self.apu),
+        )
+    }
+}
@@ -0,0 +1,25 @@
+//! Devices attached to the bus from outside the core.
+//!
+//! A peripheral sees every access to the addresses it was attached
+//! to. It can claim addresses nothing answers to, such as the unused
+//! I/O registers, for a debug port homebrew writes to. It can also
+//! wrap existing registers, watching or changing what goes through.
+
+/// A device attached to the bus, see `Argentum::attach_peripheral`.
+pub trait Peripheral {
+    /// Called when the CPU or a DMA reads one of the peripheral's
+    /// addresses. `value` is what the bus would return, 0xFF for
+    /// unused addresses. Return the value the read sees instead.
+    fn read(&mut self, addr: u16, value: u8) -> u8;
+
+    /// Called when the CPU or a DMA writes one of the peripheral's
+    /// addresses. Return the value passed on to the bus, or `None`
+    /// to keep the write from reaching it.
+    fn write(&mut self, addr: u16, value: u8) -> Option<u8>;
+
+    /// Called every M-cycle. Return the interrupts to request,
+    /// as the bits of IF.
+    fn tick(&mut self) -> u8 {
+        0
+    }
+}
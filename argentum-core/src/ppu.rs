@@ -1,16 +1,164 @@
-use std::{cell::RefCell, rc::Rc};
+mod fetcher;
 
-use crate::util::{get_bit, res_bit, set_bit};
+use alloc::{boxed::Box, rc::Rc, vec, vec::Vec};
+use core::cell::RefCell;
 
-/// The colour palette used in DMG mode.
+use self::fetcher::{BgPixel, Fetcher, FetcherStep, ObjPixel};
+
+use crate::{
+    compat_palette::DmgPalettes,
+    state::{StateError, StateReader, StateWriter},
+    util::{get_bit, res_bit, set_bit},
+};
+
+/// Width of the LCD in pixels.
+pub const SCREEN_WIDTH: usize = 160;
+
+/// Height of the LCD in pixels.
+pub const SCREEN_HEIGHT: usize = 144;
+
+/// Describes the layout of a RGB24 framebuffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameInfo {
+    /// Width in pixels.
+    pub width: usize,
+
+    /// Height in pixels.
+    pub height: usize,
+
+    /// Bytes from the start of one row to the start of the next.
+    pub pitch: usize,
+}
+
+/// An image with one palette index per pixel, used to export VRAM.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexedImage {
+    pub width: usize,
+    pub height: usize,
+
+    /// Palette indices, row by row.
+    pub pixels: Vec<u8>,
+
+    /// The colours as 0xRRGGBB values.
+    pub palette: Vec<u32>,
+}
+
+/// Raw pixels with this bit set hold a CGB RGB555 colour.
+const RAW_CGB_COLOUR: u16 = 0x8000;
+
+/// A frame without the colours applied yet, so a frontend can apply
+/// them on another thread. See `Argentum::set_deferred_colours`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawFrame {
+    /// The pixels, row by row. Pixels with the top bit set hold a CGB
+    /// RGB555 colour in the other bits, the others a DMG shade, 0 - 3
+    /// for the background and 4 - 7 and 8 - 11 for either object palette.
+    pub pixels: Vec<u16>,
+
+    /// The colours of the DMG shades.
+    pub palettes: DmgPalettes,
+
+    /// The layout of the RGB24 framebuffer the frame is written to.
+    pub info: FrameInfo,
+}
+
+impl RawFrame {
+    /// Apply the colours, writing the frame to an RGB24
+    /// framebuffer laid out as described by `info`.
+    pub fn write_rgb(&self, framebuffer: &mut [u8]) {
+        let rows = self.pixels.chunks(self.info.width);
+
+        for (pixels, row) in rows.zip(framebuffer.chunks_mut(self.info.pitch)) {
+            for (&raw, rgb) in pixels.iter().zip(row.chunks_exact_mut(3)) {
+                let colour = raw_colour(raw, &self.palettes);
+
+                rgb[0] = (colour >> 16) as u8;
+                rgb[1] = (colour >> 8) as u8;
+                rgb[2] = colour as u8;
+            }
+        }
+    }
+}
+
+/// Get the 0xRRGGBB colour of a raw pixel, see `RawFrame`.
+fn raw_colour(raw: u16, palettes: &DmgPalettes) -> u32 {
+    if raw & RAW_CGB_COLOUR != 0 {
+        return scale_rgb(raw);
+    }
+
+    let shade = (raw & 0x03) as usize;
+
+    match raw >> 2 {
+        0 => palettes.bg[shade],
+        1 => palettes.obj0[shade],
+        _ => palettes.obj1[shade],
+    }
+}
+
+/// The registers that affect rendering, as they were when a
+/// scanline was drawn. Games change these mid-frame for raster
+/// effects like status bars and parallax scrolling.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScanlineRegisters {
+    pub lcdc: u8,
+    pub scx: u8,
+    pub scy: u8,
+    pub wx: u8,
+    pub wy: u8,
+    pub bgp: u8,
+}
+
+/// T-cycles the PPU takes to draw a frame, 154 lines of 456 cycles.
+const CYCLES_PER_FRAME: u32 = 70224;
+
+/// The amount of tiles in a VRAM bank.
+const TILES_PER_BANK: usize = 384;
+
+/// Tiles per row of the tile sheet.
+const TILE_SHEET_COLUMNS: usize = 16;
+
+/// How much the heat of a tile drops every frame,
+/// a tile fades out about half a second after a write.
+const VRAM_HEAT_DECAY: u8 = 8;
+
+/// Dots a sprite fetch holds up the pixel pipeline for, once
+/// the background fetcher is done with the tile it's on.
+const SPRITE_FETCH_DOTS: u8 = 6;
+
+/// The default colour palette used in DMG mode.
 /// 0 - White
 /// 1 - Light Gray
 /// 2 - Dark Gray
 /// 3 - Black
 const DMG_MODE_PALETTE: [u32; 4] = [0xFED018, 0xD35600, 0x5E1210, 0x0D0405];
 
+/// Scale the CGB 5 bit RGB to standard 8 bit RGB.
+/// Colour Correction Algorithm taken from Byuu's (Near) blog.
+/// https://near.sh/articles/video/color-emulation
+pub(crate) fn scale_rgb(cgb_colour: u16) -> u32 {
+    let mut scaled = 0x000000;
+
+    let red = cgb_colour & 0x1F;
+    let green = (cgb_colour >> 5) & 0x1F;
+    let blue = (cgb_colour >> 10) & 0x1F;
+
+    let mut new_red = red * 26 + green * 4 + blue * 2;
+    let mut new_green = green * 24 + blue * 8;
+    let mut new_blue = red * 6 + green * 4 + blue * 22;
+
+    new_red = new_red.min(960) >> 2;
+    new_green = new_green.min(960) >> 2;
+    new_blue = new_blue.min(960) >> 2;
+
+    scaled |= (new_red as u32) << 16;
+    scaled |= (new_green as u32) << 8;
+    scaled |= new_blue as u32;
+
+    scaled
+}
+
 /// Represents sprite data as stored in OAM.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default)]
 struct Sprite {
     /// The Y coordinate of the sprite.
     y: u8,
@@ -26,7 +174,7 @@ struct Sprite {
 }
 
 /// Enumerates all the different modes the PPU can be in.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub(crate) enum PpuMode {
     HBlank = 0,
@@ -122,29 +270,65 @@ pub(crate) struct Ppu {
     /// Sprite Colour Palettes (CGB Mode Only).
     obj_palettes: [u8; 0x40],
 
-    /// State of background rendering for the
-    /// current LY position.
-    bgd_line: [(u8, bool); 160],
-
     /// 0xFF4F - VBK
     ///
     /// Indicates whether VRAM is switched to the 2nd bank.
     vram_banked: bool,
 
+    /// 0xFF6C - OPRI
+    ///
+    /// Sprites overlap by X coordinate like on the DMG,
+    /// instead of by OAM position (CGB Mode Only).
+    opri: bool,
+
     /// The current mode the PPU is in.
     current_mode: PpuMode,
 
-    /// Total cycles ticked under the current mode.
-    total_cycles: u32,
+    /// Dots ticked on the current line.
+    line_dots: u32,
+
+    /// Set once LY matched WY this frame, the
+    /// window is only drawn from then on.
+    wy_triggered: bool,
+
+    /// The state of drawing the current line.
+    fetcher: Fetcher,
+
+    /// Cycles ticked since the LCD was switched off,
+    /// used to keep frames going while it is.
+    lcd_off_cycles: u32,
 
-    /// RGB24 framebuffer, this is the back buffer.
-    back_framebuffer: Box<[u8; 160 * 144 * 3]>,
+    /// Set when a frame is completed, until it's taken.
+    frame_complete: bool,
 
-    /// RGB24 framebuffer, this is the front buffer.
-    pub front_framebuffer: Box<[u8; 160 * 144 * 3]>,
+    /// RGB24 framebuffers, drawn to in turns. The front
+    /// buffer holds the last complete frame.
+    framebuffers: [Box<[u8; 160 * 144 * 3]>; 2],
+
+    /// Raw pixels, see `RawFrame`, drawn to in turns like the framebuffers.
+    /// Only allocated if the frontend applies the colours, the raw
+    /// pixels are drawn instead of the RGB24 framebuffers then.
+    raw_frames: Option<[Box<[u16; 160 * 144]>; 2]>,
+
+    /// The index of the front buffer, the other one is the back buffer.
+    /// They swap places when a frame is completed.
+    pub front_buffer: usize,
 
     /// Shared reference to IF register.
     if_reg: Rc<RefCell<u8>>,
+
+    /// The colours the four DMG shades are drawn with.
+    pub dmg_palettes: DmgPalettes,
+
+    /// How recently every tile was written to, 255 for this frame.
+    /// Bank 1 tiles follow bank 0 tiles, as in the tile sheet.
+    vram_heat: Option<Box<[u8; TILES_PER_BANK * 2]>>,
+
+    /// The registers every scanline of the frame being drawn used.
+    back_scanline_log: [ScanlineRegisters; SCREEN_HEIGHT],
+
+    /// The registers every scanline of the last complete frame used.
+    pub front_scanline_log: [ScanlineRegisters; SCREEN_HEIGHT],
 }
 
 impl Ppu {
@@ -170,14 +354,295 @@ impl Ppu {
             bgd_palettes: [0; 0x40],
             ocps: 0,
             obj_palettes: [0; 0x40],
-            bgd_line: [(0, false); 160],
             vram_banked: false,
+            opri: false,
             current_mode: PpuMode::OamSearch,
-            total_cycles: 0,
-            back_framebuffer: Box::new([0; 160 * 144 * 3]),
-            front_framebuffer: Box::new([0; 160 * 144 * 3]),
+            line_dots: 0,
+            wy_triggered: false,
+            fetcher: Fetcher::new(),
+            lcd_off_cycles: 0,
+            frame_complete: false,
+            framebuffers: [Box::new([0; 160 * 144 * 3]), Box::new([0; 160 * 144 * 3])],
+            raw_frames: None,
+            front_buffer: 0,
             if_reg,
+            dmg_palettes: DmgPalettes::uniform(DMG_MODE_PALETTE),
+            vram_heat: None,
+            back_scanline_log: [ScanlineRegisters::default(); SCREEN_HEIGHT],
+            front_scanline_log: [ScanlineRegisters::default(); SCREEN_HEIGHT],
+        }
+    }
+
+    /// Get the last complete frame without the colours applied.
+    pub fn raw_frame(&self) -> RawFrame {
+        RawFrame {
+            pixels: self
+                .raw_frames
+                .as_ref()
+                .map_or_else(Vec::new, |raw_frames| {
+                    raw_frames[self.front_buffer].to_vec()
+                }),
+            palettes: self.dmg_palettes,
+            info: self.frame_info(),
+        }
+    }
+
+    /// Get the RGB24 front buffer, holding the last complete frame.
+    pub fn front_framebuffer(&self) -> &[u8] {
+        self.framebuffers[self.front_buffer].as_ref()
+    }
+
+    /// Get the layout of the front framebuffer.
+    pub fn frame_info(&self) -> FrameInfo {
+        FrameInfo {
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+            pitch: SCREEN_WIDTH * 3,
+        }
+    }
+
+    /// Get the colours of a background palette, always
+    /// palette 0 in DMG mode, which maps through BGP.
+    fn bg_palette(&self, index: usize) -> [u32; 4] {
+        let mut colours = [0; 4];
+
+        for (i, colour) in colours.iter_mut().enumerate() {
+            *colour = if self.cgb_mode {
+                let offset = (index << 3) + (i << 1);
+
+                scale_rgb(u16::from_le_bytes([
+                    self.bgd_palettes[offset],
+                    self.bgd_palettes[offset + 1],
+                ]))
+            } else {
+                self.dmg_palettes.bg[((self.bgp >> (i << 1)) & 0x03) as usize]
+            };
         }
+
+        colours
+    }
+
+    /// Get the colour of a pixel in the tile at the given VRAM offset.
+    fn tile_pixel(&self, tile_address: usize, x: usize, y: usize) -> u8 {
+        let lsb = self.vram[tile_address + (y << 1)];
+        let msb = self.vram[tile_address + (y << 1) + 1];
+
+        (((msb >> (7 - x)) & 0x01) << 1) | ((lsb >> (7 - x)) & 0x01)
+    }
+
+    /// Draw every tile in VRAM, 16 per row, with the first
+    /// background palette. In CGB mode bank 1 follows bank 0.
+    pub fn tile_sheet(&self) -> IndexedImage {
+        let banks = if self.cgb_mode { 2 } else { 1 };
+        let tiles = TILES_PER_BANK * banks;
+
+        let width = TILE_SHEET_COLUMNS * 8;
+        let height = tiles / TILE_SHEET_COLUMNS * 8;
+
+        let mut pixels = vec![0; width * height];
+
+        for tile in 0..tiles {
+            let tile_address = (tile / TILES_PER_BANK) * 0x2000 + (tile % TILES_PER_BANK) * 16;
+
+            let left = (tile % TILE_SHEET_COLUMNS) * 8;
+            let top = (tile / TILE_SHEET_COLUMNS) * 8;
+
+            for y in 0..8 {
+                for x in 0..8 {
+                    pixels[(top + y) * width + left + x] = self.tile_pixel(tile_address, x, y);
+                }
+            }
+        }
+
+        IndexedImage {
+            width,
+            height,
+            pixels,
+            palette: self.bg_palette(0).to_vec(),
+        }
+    }
+
+    /// Draw the whole 256 x 256 background map selected by LCDC,
+    /// ignoring scrolling. In CGB mode all 8 background palettes
+    /// are included, the tile attributes pick one.
+    pub fn bg_map(&self) -> IndexedImage {
+        let bgd_map = if get_bit!(self.lcdc, 3) {
+            0x1C00
+        } else {
+            0x1800
+        };
+
+        let mut pixels = vec![0; 256 * 256];
+
+        for map_y in 0..32 {
+            for map_x in 0..32 {
+                let index = bgd_map + (map_y << 5) + map_x;
+                let tile_number = self.vram[index];
+
+                let attrs = if self.cgb_mode {
+                    self.vram[index + 0x2000]
+                } else {
+                    0
+                };
+
+                // Same addressing modes as when rendering.
+                let mut tile_address = if get_bit!(self.lcdc, 4) {
+                    (tile_number as usize) << 4
+                } else {
+                    (0x1000 + ((tile_number as i8 as isize) << 4)) as usize
+                };
+
+                if get_bit!(attrs, 3) {
+                    tile_address += 0x2000;
+                }
+
+                let palette = (attrs & 0x07) << 2;
+
+                for y in 0..8 {
+                    for x in 0..8 {
+                        let tile_x = if get_bit!(attrs, 5) { 7 - x } else { x };
+                        let tile_y = if get_bit!(attrs, 6) { 7 - y } else { y };
+
+                        pixels[((map_y << 3) + y) * 256 + (map_x << 3) + x] =
+                            palette | self.tile_pixel(tile_address, tile_x, tile_y);
+                    }
+                }
+            }
+        }
+
+        let palettes = if self.cgb_mode { 8 } else { 1 };
+
+        IndexedImage {
+            width: 256,
+            height: 256,
+            pixels,
+            palette: (0..palettes)
+                .flat_map(|index| self.bg_palette(index).to_vec())
+                .collect(),
+        }
+    }
+
+    /// Enable or disable tracking which tiles are written to.
+    pub fn set_vram_heatmap(&mut self, enabled: bool) {
+        self.vram_heat = if enabled {
+            Some(Box::new([0; TILES_PER_BANK * 2]))
+        } else {
+            None
+        };
+    }
+
+    /// Enable or disable drawing raw pixels instead of RGB24 colours.
+    pub fn set_deferred_colours(&mut self, enabled: bool) {
+        self.raw_frames = if enabled {
+            Some([Box::new([0; 160 * 144]), Box::new([0; 160 * 144])])
+        } else {
+            None
+        };
+    }
+
+    /// Get how recently every tile was written to, if tracked.
+    pub fn vram_heatmap(&self) -> Option<&[u8]> {
+        self.vram_heat.as_deref().map(|heat| &heat[..])
+    }
+
+    /// Let every tile cool down by a frame.
+    pub fn decay_vram_heat(&mut self) {
+        if let Some(heat) = self.vram_heat.as_mut() {
+            for tile in heat.iter_mut() {
+                *tile = tile.saturating_sub(VRAM_HEAT_DECAY);
+            }
+        }
+    }
+
+    /// Reset the PPU to its power on state, keeping the DMG palettes.
+    pub fn reset(&mut self, if_reg: Rc<RefCell<u8>>) {
+        let dmg_palettes = self.dmg_palettes;
+        let heatmap = self.vram_heat.is_some();
+        let deferred_colours = self.raw_frames.is_some();
+
+        *self = Self::new(if_reg, self.cgb_mode);
+        self.dmg_palettes = dmg_palettes;
+        self.set_vram_heatmap(heatmap);
+        self.set_deferred_colours(deferred_colours);
+    }
+
+    /// Serialize the PPU's state.
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_bytes(&self.vram);
+        w.write_bytes(&self.oam_ram);
+
+        w.write_u8(self.lcdc);
+        w.write_u8(self.stat);
+        w.write_u8(self.scy);
+        w.write_u8(self.scx);
+        w.write_u8(self.ly);
+        w.write_u8(self.lyc);
+        w.write_u8(self.bgp);
+        w.write_u8(self.obp0);
+        w.write_u8(self.obp1);
+        w.write_u8(self.wy);
+        w.write_u8(self.wx);
+        w.write_u8(self.window_line_counter);
+
+        w.write_u8(self.bcps);
+        w.write_bytes(&self.bgd_palettes);
+        w.write_u8(self.ocps);
+        w.write_bytes(&self.obj_palettes);
+        w.write_bool(self.vram_banked);
+        w.write_bool(self.opri);
+
+        w.write_u8(self.current_mode as u8);
+        w.write_u32(self.line_dots);
+        w.write_bool(self.wy_triggered);
+        self.fetcher.save_state(w);
+        w.write_u32(self.lcd_off_cycles);
+        w.write_bool(self.frame_complete);
+
+        w.write_bytes(self.framebuffers[self.front_buffer ^ 1].as_ref());
+        w.write_bytes(self.framebuffers[self.front_buffer].as_ref());
+    }
+
+    /// Restore the PPU's state.
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        r.read_into(&mut self.vram)?;
+        r.read_into(&mut self.oam_ram)?;
+
+        self.lcdc = r.read_u8()?;
+        self.stat = r.read_u8()?;
+        self.scy = r.read_u8()?;
+        self.scx = r.read_u8()?;
+        self.ly = r.read_u8_in(0..=153)?;
+        self.lyc = r.read_u8()?;
+        self.bgp = r.read_u8()?;
+        self.obp0 = r.read_u8()?;
+        self.obp1 = r.read_u8()?;
+        self.wy = r.read_u8()?;
+        self.wx = r.read_u8()?;
+        self.window_line_counter = r.read_u8()?;
+
+        self.bcps = r.read_u8()?;
+        r.read_into(&mut self.bgd_palettes)?;
+        self.ocps = r.read_u8()?;
+        r.read_into(&mut self.obj_palettes)?;
+        self.vram_banked = r.read_bool()?;
+        self.opri = r.read_bool()?;
+
+        self.current_mode = match r.read_u8()? & 0x03 {
+            0 => PpuMode::HBlank,
+            1 => PpuMode::VBlank,
+            2 => PpuMode::OamSearch,
+            _ => PpuMode::Drawing,
+        };
+        self.line_dots = r.read_u32()?;
+        self.wy_triggered = r.read_bool()?;
+        self.fetcher.load_state(r)?;
+        self.lcd_off_cycles = r.read_u32()?;
+        self.frame_complete = r.read_bool()?;
+
+        r.read_into(self.framebuffers[self.front_buffer ^ 1].as_mut())?;
+        r.read_into(self.framebuffers[self.front_buffer].as_mut())?;
+
+        Ok(())
     }
 
     /// Read a byte from the specified address.
@@ -209,10 +674,16 @@ impl Ppu {
 
             0xFF4F => (self.vram_banked as u8) | 0xFE,
             0xFF68 => self.bcps | 0x40,
-            0xFF69 => self.bgd_palettes[(self.bcps & 0x3F) as usize],
             0xFF6A => self.ocps | 0x40,
+
+            // Palette RAM is in use while drawing.
+            0xFF69 | 0xFF6B if self.current_mode == PpuMode::Drawing => 0xFF,
+
+            0xFF69 => self.bgd_palettes[(self.bcps & 0x3F) as usize],
             0xFF6B => self.obj_palettes[(self.ocps & 0x3F) as usize],
 
+            0xFF6C => (self.opri as u8) | 0xFE,
+
             _ => unreachable!(),
         }
     }
@@ -221,18 +692,26 @@ impl Ppu {
     pub fn write_byte(&mut self, addr: u16, value: u8) {
         match addr {
             0x8000..=0x9FFF => {
-                let offset = (addr - 0x8000) as usize;
+                let mut offset = (addr - 0x8000) as usize;
 
                 if self.cgb_mode && self.vram_banked {
-                    self.vram[offset + 0x2000] = value;
-                } else {
-                    self.vram[offset] = value;
+                    offset += 0x2000;
+                }
+
+                self.vram[offset] = value;
+
+                if let Some(heat) = self.vram_heat.as_mut() {
+                    // Tile data ends where the tile maps start.
+                    if offset % 0x2000 < 0x1800 {
+                        heat[(offset / 0x2000) * TILES_PER_BANK + (offset % 0x2000) / 16] = 0xFF;
+                    }
                 }
             }
 
             0xFE00..=0xFE9F => self.oam_ram[(addr - 0xFE00) as usize] = value,
 
             0xFF40 => self.lcdc = value,
+
             0xFF41 => self.stat = value & 0x78,
             0xFF42 => self.scy = value,
             0xFF43 => self.scx = value,
@@ -249,7 +728,10 @@ impl Ppu {
             0xFF69 => {
                 let index = self.bcps & 0x3F;
 
-                self.bgd_palettes[index as usize] = value;
+                // Writes while drawing are lost, but still increment the index.
+                if self.current_mode != PpuMode::Drawing {
+                    self.bgd_palettes[index as usize] = value;
+                }
 
                 if get_bit!(self.bcps, 7) {
                     self.bcps &= 0xC0;
@@ -260,25 +742,33 @@ impl Ppu {
             0xFF6B => {
                 let index = self.ocps & 0x3F;
 
-                self.obj_palettes[index as usize] = value;
+                if self.current_mode != PpuMode::Drawing {
+                    self.obj_palettes[index as usize] = value;
+                }
 
                 if get_bit!(self.ocps, 7) {
                     self.ocps &= 0xC0;
                     self.ocps |= index.wrapping_add(1);
                 }
             }
+            0xFF6C => self.opri = get_bit!(value, 0),
 
             _ => unreachable!(),
         }
     }
 
+    /// Get the VRAM bank currently mapped to 0x8000 - 0x9FFF.
+    pub fn vram_bank(&self) -> u16 {
+        (self.cgb_mode && self.vram_banked) as u16
+    }
+
     /// Change the PPU's current mode.
     fn change_mode(&mut self, mode: PpuMode) {
         self.current_mode = mode;
 
         match mode {
             PpuMode::HBlank => {
-                self.render_scanline();
+                self.finish_line();
 
                 // Request STAT interrupt if HBlank bit
                 // in LCD STAT is set.
@@ -299,6 +789,12 @@ impl Ppu {
             }
 
             PpuMode::OamSearch => {
+                // The window can start on any line
+                // once LY matched WY this frame.
+                if self.ly == self.wy {
+                    self.wy_triggered = true;
+                }
+
                 // Request STAT interrupt if OamSearch bit
                 // in LCD STAT is set.
                 if get_bit!(self.stat, 5) {
@@ -306,7 +802,10 @@ impl Ppu {
                 }
             }
 
-            _ => {}
+            PpuMode::Drawing => {
+                self.search_sprites();
+                self.fetcher.start_line(self.scx);
+            }
         }
     }
 
@@ -328,34 +827,55 @@ impl Ppu {
     /// Tick the PPU by 1 M cycle, and return a bool
     /// that tells if we have entered HBlank.
     pub fn tick(&mut self, cycles: u32) -> bool {
+        // Nothing is drawn while the LCD is off, but frames still
+        // complete at the usual rate so that emulation keeps pace.
         if !get_bit!(self.lcdc, 7) {
+            self.lcd_off_cycles += cycles;
+
+            if self.lcd_off_cycles >= CYCLES_PER_FRAME {
+                self.lcd_off_cycles -= CYCLES_PER_FRAME;
+                self.frame_complete = true;
+            }
+
             return false;
         }
 
-        self.total_cycles += cycles;
+        self.lcd_off_cycles = 0;
 
         let mut entered_hblank = false;
 
-        // The actual PPU timings are not fixed.
-        // They vary depending upon the number of sprites
-        // on the screen, if the window is being drawn etc..
+        for _ in 0..cycles {
+            entered_hblank |= self.tick_dot();
+        }
+
+        entered_hblank
+    }
+
+    /// Tick the PPU by a single dot, returns true if HBlank was
+    /// entered in CGB mode, where it's used for HDMA.
+    fn tick_dot(&mut self) -> bool {
+        self.line_dots += 1;
+
+        // Drawing takes 172 dots at the least, longer with fine
+        // scrolling, the window and sprites. HBlank makes up
+        // the rest of the 456 dots of a line.
         match self.current_mode {
-            PpuMode::OamSearch if self.total_cycles >= 80 => {
-                self.total_cycles -= 80;
+            PpuMode::OamSearch if self.line_dots == 80 => {
                 self.change_mode(PpuMode::Drawing);
             }
 
-            PpuMode::Drawing if self.total_cycles >= 172 => {
-                self.total_cycles -= 172;
+            PpuMode::Drawing => {
+                if !self.draw_dot() {
+                    return false;
+                }
+
                 self.change_mode(PpuMode::HBlank);
 
-                if self.cgb_mode {
-                    entered_hblank = true;
-                }
+                return self.cgb_mode;
             }
 
-            PpuMode::HBlank if self.total_cycles >= 204 => {
-                self.total_cycles -= 204;
+            PpuMode::HBlank if self.line_dots >= 456 => {
+                self.line_dots = 0;
                 self.ly += 1;
 
                 // LY 0x90 (144) signals end of one complete frame.
@@ -368,19 +888,22 @@ impl Ppu {
                 self.compare_lyc();
             }
 
-            PpuMode::VBlank if self.total_cycles >= 456 => {
-                self.total_cycles -= 456;
+            PpuMode::VBlank if self.line_dots >= 456 => {
+                self.line_dots = 0;
                 self.ly += 1;
 
                 // The PPU actually has 154 lines instead of 144.
                 // These 10 lines are `psuedo lines` of sorts.
                 if self.ly == 154 {
-                    // Swap the copy the back buffer to the front buffer.
-                    self.front_framebuffer
-                        .copy_from_slice(self.back_framebuffer.as_ref());
+                    // Swap the back buffer and the front buffer.
+                    self.front_buffer ^= 1;
+
+                    self.front_scanline_log = self.back_scanline_log;
+                    self.frame_complete = true;
 
                     self.ly = 0;
                     self.window_line_counter = 0;
+                    self.wy_triggered = false;
                     self.change_mode(PpuMode::OamSearch);
                 }
 
@@ -390,370 +913,446 @@ impl Ppu {
             _ => {}
         }
 
-        entered_hblank
+        false
     }
 
-    /// Render the current scanline.
-    fn render_scanline(&mut self) {
-        self.render_background();
-        self.render_sprites();
+    /// Check if a frame was completed since the last call.
+    pub fn take_frame_complete(&mut self) -> bool {
+        core::mem::take(&mut self.frame_complete)
     }
 
-    /// Set a pixel in the framebuffer at the given `x` and `y`
-    /// coordinates.
-    fn set_pixel(&mut self, x: u8, y: u8, colour: u32) {
-        let offset = (((y as usize) << 5) * 15) + (x as usize * 3);
+    /// Wrap up the line that was drawn, as HBlank starts.
+    fn finish_line(&mut self) {
+        self.back_scanline_log[self.ly as usize] = ScanlineRegisters {
+            lcdc: self.lcdc,
+            scx: self.scx,
+            scy: self.scy,
+            wx: self.wx,
+            wy: self.wy,
+            bgp: self.bgp,
+        };
 
-        self.back_framebuffer[offset] = ((colour & 0xFF0000) >> 16) as u8;
-        self.back_framebuffer[offset + 1] = ((colour & 0x00FF00) >> 8) as u8;
-        self.back_framebuffer[offset + 2] = (colour & 0x0000FF) as u8;
+        // The window picks up where it left off on the next line
+        // it's drawn on, even if it was hidden on the lines between.
+        if self.fetcher.in_window {
+            self.window_line_counter += 1;
+        }
     }
 
-    /// Scale the CGB 5 bit RGB to standard 8 bit RGB.
-    /// Colour Correction Algorithm taken from Byuu's (Near) blog.
-    /// https://near.sh/articles/video/color-emulation
-    fn scale_rgb(&self, cgb_colour: u16) -> u32 {
-        let mut scaled = 0x000000;
+    /// Set a pixel in the framebuffer at the given `x` and `y`
+    /// coordinates to a raw pixel, see `RawFrame`.
+    fn set_pixel(&mut self, x: u8, y: u8, raw: u16) {
+        let back_buffer = self.front_buffer ^ 1;
+
+        if let Some(raw_frames) = self.raw_frames.as_mut() {
+            raw_frames[back_buffer][y as usize * SCREEN_WIDTH + x as usize] = raw;
+
+            return;
+        }
 
-        let red = (cgb_colour >> 0) & 0x1F;
-        let green = (cgb_colour >> 5) & 0x1F;
-        let blue = (cgb_colour >> 10) & 0x1F;
+        let colour = raw_colour(raw, &self.dmg_palettes);
+        let offset = (((y as usize) << 5) * 15) + (x as usize * 3);
+        let framebuffer = &mut self.framebuffers[back_buffer];
+
+        framebuffer[offset] = ((colour & 0xFF0000) >> 16) as u8;
+        framebuffer[offset + 1] = ((colour & 0x00FF00) >> 8) as u8;
+        framebuffer[offset + 2] = (colour & 0x0000FF) as u8;
+    }
 
-        let mut new_red = red * 26 + green * 4 + blue * 2;
-        let mut new_green = green * 24 + blue * 8;
-        let mut new_blue = red * 6 + green * 4 + blue * 22;
+    /// Pick the sprites on this line, like the PPU does in OAM search
+    /// mode. The size of the sprites is taken from LCDC as it is now.
+    ///
+    /// The requirements for a sprite to be on the line are,
+    /// 1. Y COORD <= LY
+    /// 2. Y COORD + SPRITE SIZE > LY
+    fn search_sprites(&mut self) {
+        let search_size = if get_bit!(self.lcdc, 2) { 16 } else { 8 };
+        let mut count = 0;
+
+        for entry in self.oam_ram.chunks_exact(4) {
+            if let [y, x, tile_number, flags] = *entry {
+                let y = y.wrapping_sub(16);
+                let x = x.wrapping_sub(8);
+
+                if y <= self.ly && self.ly < y.wrapping_add(search_size) {
+                    self.fetcher.sprites[count] = Sprite {
+                        y,
+                        x,
+                        tile_number,
+                        flags,
+                    };
 
-        new_red = new_red.min(960) >> 2;
-        new_green = new_green.min(960) >> 2;
-        new_blue = new_blue.min(960) >> 2;
+                    count += 1;
 
-        scaled |= (new_red as u32) << 16;
-        scaled |= (new_green as u32) << 8;
-        scaled |= new_blue as u32;
+                    if count == self.fetcher.sprites.len() {
+                        break;
+                    }
+                }
+            }
+        }
 
-        scaled
+        self.fetcher.sprite_count = count as u8;
     }
 
-    /// Render the background map and the window map for this scanline.
-    fn render_background(&mut self) {
-        // The 0th bit of the LCDC in DMG mode when zero disables all forms
-        // of background and window rendering.
-        if !get_bit!(self.lcdc, 0) && !self.cgb_mode {
-            return;
+    /// Draw for one dot of mode 3, returns true once
+    /// the line is done and HBlank starts.
+    fn draw_dot(&mut self) -> bool {
+        if self.fetcher.lcd_x == SCREEN_WIDTH as u8 {
+            return true;
         }
 
-        // The window tile map that is to be rendered.
-        let win_map = if get_bit!(self.lcdc, 6) {
-            0x1C00
-        } else {
-            0x1800
-        };
+        // Both the background fetcher and the LCD
+        // wait while a sprite is fetched.
+        if self.fetcher.sprite_fetch_dots > 0 {
+            self.fetcher.sprite_fetch_dots -= 1;
 
-        // The background tile map that is to be rendered.
-        let bgd_map = if get_bit!(self.lcdc, 3) {
-            0x1C00
-        } else {
-            0x1800
-        };
+            if self.fetcher.sprite_fetch_dots == 0 {
+                self.fetch_sprite(self.fetcher.sprite_fetch as usize);
+            }
 
-        // The tile data that is going to be used for rendering
-        // the above tile maps.
-        let tile_data = if get_bit!(self.lcdc, 4) {
-            0x0000u16
-        } else {
-            0x1000u16
-        };
+            return false;
+        }
 
-        // If the window is enabled this line, we increment
-        // the internal line counter.
-        let mut increment_window_counter = false;
+        if get_bit!(self.lcdc, 5)
+            && self.wy_triggered
+            && !self.fetcher.in_window
+            && self.fetcher.lcd_x + 7 >= self.wx
+        {
+            self.fetcher.start_window(self.wx);
+        }
 
-        for x in 0u8..160u8 {
-            // Extract the absolute X and Y coordinates of the pixel in
-            // the respective 256 x 256 tile map.
-            let (map_x, map_y, tile_map) =
-                if get_bit!(self.lcdc, 5) && self.wy <= self.ly && self.wx <= x + 7 {
-                    let map_x = x.wrapping_add(7).wrapping_sub(self.wx);
-                    let map_y = self.window_line_counter;
+        if let Some(sprite) = self.next_sprite() {
+            // Sprites are fetched once the background fetcher has
+            // a tile ready, until then it keeps going on its own.
+            if self.fetcher.step == FetcherStep::Push && !self.fetcher.bg_fifo.is_empty() {
+                self.fetcher.fetched_sprites |= 1 << sprite;
+                self.fetcher.sprite_fetch = sprite as u8;
+                self.fetcher.sprite_fetch_dots = SPRITE_FETCH_DOTS - 1;
+            } else {
+                self.step_fetcher();
+            }
 
-                    increment_window_counter = true;
+            return false;
+        }
 
-                    (map_x, map_y, win_map)
-                } else {
-                    let map_x = x.wrapping_add(self.scx);
-                    let map_y = self.ly.wrapping_add(self.scy);
+        self.step_fetcher();
+        self.shift_pixel();
 
-                    (map_x, map_y, bgd_map)
-                };
+        false
+    }
 
-            // Extract the X and Y coordinates of the pixel inside the
-            // respective tile.
-            let mut tile_x = map_x & 0x07;
-            let mut tile_y = map_y & 0x07;
+    /// Find the sprite the LCD reached that wasn't fetched yet, the
+    /// one furthest left first and the one earlier in OAM if they are
+    /// at the same X coordinate. Sprites reached while they are
+    /// switched off in LCDC are skipped.
+    fn next_sprite(&mut self) -> Option<usize> {
+        let lcd_x = self.fetcher.lcd_x as u16;
+        let sprites = &self.fetcher.sprites[..self.fetcher.sprite_count as usize];
 
-            // Calculate the index for the tile number.
-            let tile_number_index =
-                tile_map + (((map_y as u16 >> 3) << 5) & 0x3FF) + ((map_x as u16 >> 3) & 0x1F);
+        let sprite = sprites
+            .iter()
+            .enumerate()
+            .filter(|&(index, sprite)| {
+                self.fetcher.fetched_sprites & (1 << index) == 0
+                    && sprite.x.wrapping_add(8) as u16 <= lcd_x + 8
+            })
+            .min_by_key(|&(index, sprite)| (sprite.x.wrapping_add(8), index))
+            .map(|(index, _)| index)?;
 
-            // Extract the tile number.
-            let tile_number = self.vram[tile_number_index as usize];
+        if !get_bit!(self.lcdc, 1) {
+            self.fetcher.fetched_sprites |= 1 << sprite;
 
-            // Extract CGB background attributes.
-            let cgb_bgd_attrs = self.vram[tile_number_index as usize + 0x2000];
+            return None;
+        }
 
-            // If we are in CGB mode, check if we need to flip
-            // the tile over the Y axis.
-            if self.cgb_mode && get_bit!(cgb_bgd_attrs, 6) {
-                tile_y = 7 - tile_y;
-            }
+        Some(sprite)
+    }
 
-            // If we are in CGB mode, check if we need to flip
-            // the tile over the X axis.
-            if self.cgb_mode && !get_bit!(cgb_bgd_attrs, 5) {
-                tile_x = 7 - tile_x;
+    /// Run the background fetcher for a dot.
+    fn step_fetcher(&mut self) {
+        if self.fetcher.step != FetcherStep::Push {
+            if !self.fetcher.step_started {
+                self.fetcher.step_started = true;
+
+                return;
             }
 
-            // Extract the address of the row we are rendering in the concerned tile.
-            // There are two addressing modes,
-            //
-            // 1. 0x8000: (TILE_NUMBER as u8 * 16) + 0x8000.
-            // 2. 0x8800: (TILE_NUMBER as i8 * 16) + 0x9000.
-            let tile_address = if tile_data == 0x0000 {
-                tile_data
-                    .wrapping_add((tile_number as u16) << 4)
-                    .wrapping_add((tile_y << 1) as u16)
-            } else {
-                tile_data
-                    .wrapping_add(((tile_number as i8 as i16) as u16) << 4)
-                    .wrapping_add((tile_y << 1) as u16)
-            } as usize;
+            self.fetcher.step_started = false;
 
-            if !self.cgb_mode {
-                // Extract the colour data pertaining to the row.
-                let lsb = self.vram[tile_address];
-                let msb = self.vram[tile_address + 1];
+            match self.fetcher.step {
+                FetcherStep::Tile => {
+                    self.fetch_tile();
+                    self.fetcher.step = FetcherStep::DataLow;
+                }
 
-                // Extract the colour pertaining to the pixel.
-                let pixel_colour =
-                    (((msb >> (7 - tile_x)) & 0x01) << 1) | ((lsb >> (7 - tile_x)) & 0x01);
+                FetcherStep::DataLow => {
+                    self.fetcher.data_low = self.fetch_tile_data(0);
+                    self.fetcher.step = FetcherStep::DataHigh;
+                }
 
-                // Store the BG colour, and priority bit for later use.
-                self.bgd_line[x as usize] = (pixel_colour, false);
+                _ => {
+                    self.fetcher.data_high = self.fetch_tile_data(1);
+                    self.fetcher.step = FetcherStep::Push;
+                }
+            }
+        }
 
-                // Extract the actual pixel colour.
-                let actual_pixel_colour =
-                    DMG_MODE_PALETTE[((self.bgp >> (pixel_colour << 1)) & 0x03) as usize];
+        // The row is pushed as soon as the FIFO is empty,
+        // which can be the dot the fetch finished.
+        if self.fetcher.step == FetcherStep::Push && self.fetcher.bg_fifo.is_empty() {
+            self.fetcher.step = FetcherStep::Tile;
 
-                self.set_pixel(x, self.ly, actual_pixel_colour);
+            if self.fetcher.dummy_fetch {
+                self.fetcher.dummy_fetch = false;
             } else {
-                // Extract the index of the colour palette we are
-                // going to use to render the tile.
-                let palette = (cgb_bgd_attrs & 0x07) as usize;
+                self.push_tile_row();
+                self.fetcher.tile_x = self.fetcher.tile_x.wrapping_add(1);
+            }
+        }
+    }
 
-                // Extract which VRAM bank to take tile data from.
-                let bank_offset = if get_bit!(cgb_bgd_attrs, 3) {
-                    0x2000
-                } else {
-                    0x0000
-                };
+    /// Fetch the number and attributes of the next tile, with the
+    /// scroll registers and LCDC as they are on this dot.
+    fn fetch_tile(&mut self) {
+        // Extract the absolute X and Y coordinates of the tile in
+        // the respective 32 x 32 tile map.
+        let (tile_map, map_x, map_y) = if self.fetcher.in_window {
+            let tile_map = if get_bit!(self.lcdc, 6) {
+                0x1C00
+            } else {
+                0x1800
+            };
 
-                // Extract BG to OAM priority, and store it later for sprite
-                // rendering.
-                let bg_oam_priority = get_bit!(cgb_bgd_attrs, 7);
+            (tile_map, self.fetcher.tile_x, self.window_line_counter)
+        } else {
+            let tile_map = if get_bit!(self.lcdc, 3) {
+                0x1C00
+            } else {
+                0x1800
+            };
+
+            let map_x = (self.scx >> 3).wrapping_add(self.fetcher.tile_x);
 
-                // Extract the colour data pertaining to the row.
-                let lsb = self.vram[tile_address + bank_offset];
-                let msb = self.vram[tile_address + bank_offset + 1];
+            (tile_map, map_x, self.ly.wrapping_add(self.scy))
+        };
 
-                // Extract the colour pertaining to the pixel.
-                let pixel_colour = (((msb >> tile_x) & 0x01) << 1) | ((lsb >> tile_x) & 0x01);
+        // Calculate the index for the tile number.
+        let tile_number_index = tile_map + ((map_y as usize >> 3) << 5) + (map_x as usize & 0x1F);
 
-                // Store the BG colour, and priority bit for later use.
-                self.bgd_line[x as usize] = (pixel_colour, bg_oam_priority);
+        self.fetcher.tile_number = self.vram[tile_number_index];
+
+        // Extract CGB background attributes.
+        self.fetcher.tile_attrs = if self.cgb_mode {
+            self.vram[tile_number_index + 0x2000]
+        } else {
+            0
+        };
+    }
 
-                // The offset to the palette we are going to use.
-                let palette_offset = (palette << 3) + ((pixel_colour as usize) << 1);
+    /// Fetch the low (0) or high (1) byte of the row
+    /// of the tile that is drawn on this line.
+    fn fetch_tile_data(&self, byte: usize) -> u8 {
+        let attrs = self.fetcher.tile_attrs;
 
-                // Extract the actual pixel colour.
-                let actual_colour = ((self.bgd_palettes[palette_offset + 1] as u16) << 8)
-                    | (self.bgd_palettes[palette_offset] as u16);
+        let mut tile_y = if self.fetcher.in_window {
+            self.window_line_counter & 0x07
+        } else {
+            self.ly.wrapping_add(self.scy) & 0x07
+        };
 
-                self.set_pixel(x, self.ly, self.scale_rgb(actual_colour));
-            }
+        // If we are in CGB mode, check if we need to flip
+        // the tile over the Y axis.
+        if get_bit!(attrs, 6) {
+            tile_y = 7 - tile_y;
         }
 
-        self.window_line_counter += increment_window_counter as u8;
+        // Extract the address of the row we are rendering in the concerned tile.
+        // There are two addressing modes,
+        //
+        // 1. 0x8000: (TILE_NUMBER as u8 * 16) + 0x8000.
+        // 2. 0x8800: (TILE_NUMBER as i8 * 16) + 0x9000.
+        let tile_number = self.fetcher.tile_number;
+
+        let tile_address = if get_bit!(self.lcdc, 4) {
+            (tile_number as u16) << 4
+        } else {
+            0x1000u16.wrapping_add(((tile_number as i8 as i16) as u16) << 4)
+        } as usize;
+
+        // Extract which VRAM bank to take tile data from.
+        let bank_offset = if get_bit!(attrs, 3) { 0x2000 } else { 0x0000 };
+
+        self.vram[tile_address + bank_offset + ((tile_y as usize) << 1) + byte]
     }
 
-    /// Render the sprites present on this scanline.
-    fn render_sprites(&mut self) {
-        // The 1st bit of LCDC controls whether OBJs (sprites)
-        // are enabled or not.
-        if !get_bit!(self.lcdc, 1) {
-            return;
+    /// Push the fetched row into the empty background FIFO.
+    fn push_tile_row(&mut self) {
+        let attrs = self.fetcher.tile_attrs;
+        let mut pixels = [BgPixel::default(); 8];
+
+        for (x, pixel) in pixels.iter_mut().enumerate() {
+            // If we are in CGB mode, check if we need to flip
+            // the tile over the X axis.
+            let bit = if get_bit!(attrs, 5) { x } else { 7 - x };
+
+            *pixel = BgPixel {
+                colour: (((self.fetcher.data_high >> bit) & 0x01) << 1)
+                    | ((self.fetcher.data_low >> bit) & 0x01),
+                palette: attrs & 0x07,
+                priority: get_bit!(attrs, 7),
+            };
         }
 
-        // If the 2nd bit of LCDC is zero the sprite's size is taken to
-        // be 8 x 8 else it's 8 x 16.
+        self.fetcher.bg_fifo.push_row(pixels);
+    }
+
+    /// Fetch the row of a sprite on this line and merge it
+    /// into the sprite FIFO, with LCDC as it is now.
+    fn fetch_sprite(&mut self, index: usize) {
+        let sprite = self.fetcher.sprites[index];
         let sprite_size = if get_bit!(self.lcdc, 2) { 16 } else { 8 };
 
-        // Go through the OAM ram and search for all the sprites
-        // that are visible in this scanline.
-        // This is similar to what the PPU does in OAM search mode.
-        //
-        // The requirements for a sprite to be visible are,
-        // 1. Y COORD <= LY
-        // 2. Y COORD + SPRITE SIZE > LY
-        let mut sprites = self
-            .oam_ram
-            .chunks_exact(4)
-            .filter_map(|entry| {
-                if let [y, x, tile_number, flags] = *entry {
-                    let y = y.wrapping_sub(16);
-                    let x = x.wrapping_sub(8);
-
-                    // In 8 x 16 sprite mode, the 0th bit of the tile number
-                    // is ignored.
-                    let tile_number = if sprite_size == 16 {
-                        tile_number & 0xFE
-                    } else {
-                        tile_number
-                    };
+        // In 8 x 16 sprite mode, the 0th bit of the tile number
+        // is ignored.
+        let tile_number = if sprite_size == 16 {
+            sprite.tile_number & 0xFE
+        } else {
+            sprite.tile_number
+        };
 
-                    if y <= self.ly && self.ly < y.wrapping_add(sprite_size) {
-                        Some(Sprite {
-                            y,
-                            x,
-                            tile_number,
-                            flags,
-                        })
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            })
-            .take(10)
-            .enumerate()
-            .collect::<Vec<(usize, Sprite)>>();
+        // Extract sprite attributes.
+        let sprite_attr = sprite.flags;
 
-        // Sort the sprites in a way that,
-        //
-        // 1. The sprite that has the lower X coordinate will draw
-        //    over the sprite that has a higher X coordinate.
-        // 2. The sprite that appeared earlier in the OAM RAM will draw
-        //    over the sprite with same X coordinates.
-        if !self.cgb_mode {
-            sprites.sort_by(|&a, &b| {
-                use core::cmp::Ordering;
-
-                let res = a.1.x.cmp(&b.1.x);
-
-                if let Ordering::Equal = res {
-                    // X coordinates are equal,
-                    // therefore the one that appeared earlier wins.
-                    // BUT we reverse the order since we have to draw the sprite
-                    // over the other.
-                    a.0.cmp(&b.0).reverse()
-                } else {
-                    // Here the lower X wins.
-                    // BUT we reverse the order since we have to draw the sprite
-                    // over the other.
-                    res.reverse()
-                }
-            });
-        } else {
-            sprites.reverse();
-        }
+        // Is the sprite flipped over the Y axis.
+        let y_flip = get_bit!(sprite_attr, 6);
 
-        for (_, sprite) in sprites {
-            // Extract sprite attributes.
-            let sprite_attr = sprite.flags;
+        // Is the sprite flipped over the X axis.
+        let x_flip = get_bit!(sprite_attr, 5);
 
-            // Is the sprite flipped over the Y axis.
-            let y_flip = get_bit!(sprite_attr, 6);
+        // The row in the tile of the sprite. A sprite picked as 8 x 16
+        // that is fetched as 8 x 8 wraps around to its first row.
+        let row = (self.ly - sprite.y) & (sprite_size - 1);
 
-            // Is the sprite flipped over the X axis.
-            let x_flip = get_bit!(sprite_attr, 5);
+        let tile_y = if y_flip { sprite_size - 1 - row } else { row };
 
-            // The palette used to render the sprite. (DMG)
-            let palette = if get_bit!(sprite_attr, 4) {
-                self.obp1
-            } else {
-                self.obp0
-            };
+        // The VRAM bank to use for getting the sprite tile in
+        // CGB mode.
+        let vram_offset = if self.cgb_mode && get_bit!(sprite_attr, 3) {
+            0x2000
+        } else {
+            0x0000
+        };
 
-            // The palette used to render the sprite. (CGB)
-            let colour_palette = (sprite_attr & 0x07) as usize;
+        // The address of the sprite tile.
+        let tile_address =
+            (((tile_number as u16) << 4) + ((tile_y as u16) << 1)) as usize + vram_offset;
 
-            // The VRAM bank to use for getting the sprite tile in
-            // CGB mode.
-            let vram_offset = if self.cgb_mode && get_bit!(sprite_attr, 3) {
-                0x2000
-            } else {
-                0x0000
-            };
+        // Extract the colour data pertaining to the row.
+        let lsb = self.vram[tile_address];
+        let msb = self.vram[tile_address + 1];
 
-            // Should the sprite be drawn over the background layer.
-            // If this is false, the sprite will only be drawn
-            // if the colour of BG is NOT 1-3.
-            let sprite_over_bg = !get_bit!(sprite_attr, 7);
+        let palette = if self.cgb_mode {
+            sprite_attr & 0x07
+        } else {
+            get_bit!(sprite_attr, 4) as u8
+        };
 
-            // The row in the tile of the sprite.
-            let tile_y = if y_flip {
-                sprite_size - (self.ly - sprite.y + 1)
-            } else {
-                self.ly - sprite.y
-            };
+        // On the CGB, the sprite earlier in OAM draws over the others,
+        // unless OPRI asks for the DMG's order. On the DMG the sprite
+        // fetched first, the one further left, draws over the others.
+        let by_oam_position = self.cgb_mode && !self.opri;
 
-            // The address of the sprite tile.
-            let tile_address =
-                (((sprite.tile_number as u16) << 4) + ((tile_y as u16) << 1)) as usize;
+        for x in 0..8u8 {
+            // Pixels left of the LCD, for sprites partially
+            // off the screen, are cut off.
+            let offset = sprite.x.wrapping_add(8) as i16 - 8 + x as i16 - self.fetcher.lcd_x as i16;
 
-            // Extract the colour data pertaining to the row.
-            let lsb = self.vram[tile_address + vram_offset];
-            let msb = self.vram[tile_address + vram_offset + 1];
+            if offset < 0 {
+                continue;
+            }
 
-            for x in 0..8 {
-                let actual_x = sprite.x.wrapping_add(x);
+            let bit = if x_flip { x } else { 7 - x };
+            let colour = (((msb >> bit) & 0x01) << 1) | ((lsb >> bit) & 0x01);
 
-                if actual_x < 160 {
-                    // Get the index of the colour.
-                    // 0 - Is always transparent for sprites.
-                    let colour_index = if x_flip {
-                        ((msb >> x & 0x01) << 1) | (lsb >> x & 0x01)
-                    } else {
-                        ((msb >> (7 - x) & 0x01) << 1) | (lsb >> (7 - x) & 0x01)
-                    };
+            // We don't merge pixels that are transparent.
+            if colour != 0 {
+                let pixel = ObjPixel {
+                    colour,
+                    palette,
+                    behind_bg: get_bit!(sprite_attr, 7),
+                    sprite: index as u8,
+                };
 
-                    // Extract the actual RGB colour.
-                    let colour = if self.cgb_mode {
-                        let palette_offset = (colour_palette * 8) + (colour_index as usize * 2);
+                self.fetcher
+                    .obj_fifo
+                    .merge(offset as usize, pixel, by_oam_position);
+            }
+        }
+    }
 
-                        let cgb_colour = ((self.obj_palettes[palette_offset + 1] as u16) << 8)
-                            | (self.obj_palettes[palette_offset] as u16);
+    /// Shift a pixel out to the LCD, if the background FIFO has one.
+    fn shift_pixel(&mut self) {
+        let bg = match self.fetcher.bg_fifo.pop() {
+            Some(pixel) => pixel,
+            None => return,
+        };
 
-                        self.scale_rgb(cgb_colour)
-                    } else {
-                        DMG_MODE_PALETTE[((palette >> (colour_index << 1)) & 0x03) as usize]
-                    };
+        if self.fetcher.discard > 0 {
+            self.fetcher.discard -= 1;
 
-                    // We don't draw pixels that are transparent.
-                    if colour_index != 0 {
-                        if self.cgb_mode {
-                            if !get_bit!(self.lcdc, 0)
-                                || (self.bgd_line[actual_x as usize].0 == 0)
-                                || (!self.bgd_line[actual_x as usize].1 && sprite_over_bg)
-                            {
-                                self.set_pixel(actual_x, self.ly, colour);
-                            }
-                        } else if sprite_over_bg || self.bgd_line[actual_x as usize].0 == 0 {
-                            self.set_pixel(actual_x, self.ly, colour);
-                        }
-                    }
-                }
-            }
+            return;
         }
+
+        let obj = self.fetcher.obj_fifo.pop();
+
+        // The 0th bit of the LCDC in DMG mode when zero disables all forms
+        // of background and window rendering, the pixel shows up white.
+        // In CGB mode it puts sprites over the background instead.
+        let (bg_colour, bg_raw) = if self.cgb_mode {
+            let palette_offset = ((bg.palette as usize) << 3) + ((bg.colour as usize) << 1);
+
+            let colour = ((self.bgd_palettes[palette_offset + 1] as u16) << 8)
+                | (self.bgd_palettes[palette_offset] as u16);
+
+            (bg.colour, RAW_CGB_COLOUR | colour)
+        } else if get_bit!(self.lcdc, 0) {
+            (bg.colour, ((self.bgp >> (bg.colour << 1)) & 0x03) as u16)
+        } else {
+            (0, 0)
+        };
+
+        // Should the sprite be drawn over the background layer. If the
+        // sprite or the background tile asks for it, the sprite will
+        // only be drawn if the colour of BG is NOT 1-3.
+        let obj_visible = obj.colour != 0
+            && get_bit!(self.lcdc, 1)
+            && if self.cgb_mode {
+                !get_bit!(self.lcdc, 0) || bg_colour == 0 || (!bg.priority && !obj.behind_bg)
+            } else {
+                !obj.behind_bg || bg_colour == 0
+            };
+
+        let raw = if !obj_visible {
+            bg_raw
+        } else if self.cgb_mode {
+            let palette_offset = ((obj.palette as usize) << 3) + ((obj.colour as usize) << 1);
+
+            let colour = ((self.obj_palettes[palette_offset + 1] as u16) << 8)
+                | (self.obj_palettes[palette_offset] as u16);
+
+            RAW_CGB_COLOUR | colour
+        } else {
+            // Where the shades of the palette start in the raw pixels.
+            let (palette, first_shade) = if obj.palette == 1 {
+                (self.obp1, 8)
+            } else {
+                (self.obp0, 4)
+            };
+
+            first_shade + ((palette >> (obj.colour << 1)) & 0x03) as u16
+        };
+
+        self.set_pixel(self.fetcher.lcd_x, self.ly, raw);
+        self.fetcher.lcd_x += 1;
     }
 }
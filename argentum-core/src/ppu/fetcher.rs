@@ -0,0 +1,336 @@
+//! The pixel fetcher and the FIFOs mode 3 draws a line through.
+//!
+//! The fetcher reads a row of a background or window tile every
+//! 6 dots and pushes it into the background FIFO once that runs
+//! empty. Every dot a pixel is shifted out to the LCD, mixed with
+//! the sprite FIFO, which sprite rows are merged into as the LCD
+//! reaches them. Registers are read at the dot they are used,
+//! so writes in the middle of a line affect the rest of it.
+
+use crate::state::{StateError, StateReader, StateWriter};
+
+use super::Sprite;
+
+/// The steps of fetching a row of a tile, all but
+/// `Push` take 2 dots.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub(super) enum FetcherStep {
+    Tile = 0,
+    DataLow = 1,
+    DataHigh = 2,
+
+    /// Waiting for the background FIFO to run empty.
+    Push = 3,
+}
+
+/// A background or window pixel waiting to be shifted out.
+#[derive(Clone, Copy, Default)]
+pub(super) struct BgPixel {
+    /// The colour in the tile, 0 - 3.
+    pub colour: u8,
+
+    /// The colour palette (CGB Mode Only).
+    pub palette: u8,
+
+    /// Draw the pixel over sprites (CGB Mode Only).
+    pub priority: bool,
+}
+
+/// A sprite pixel waiting to be shifted out.
+#[derive(Clone, Copy, Default)]
+pub(super) struct ObjPixel {
+    /// The colour in the tile, 0 is transparent.
+    pub colour: u8,
+
+    /// 1 for OBP1 in DMG mode, the colour palette in CGB mode.
+    pub palette: u8,
+
+    /// Only draw the pixel over background colour 0.
+    pub behind_bg: bool,
+
+    /// The position of the sprite among the ones on the line,
+    /// which is their order in OAM.
+    pub sprite: u8,
+}
+
+/// Holds the pixels of the last fetched tile row.
+#[derive(Default)]
+pub(super) struct BgFifo {
+    pixels: [BgPixel; 8],
+
+    /// How many pixels are left, the last ones in `pixels`.
+    len: u8,
+}
+
+impl BgFifo {
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Fill the empty FIFO with a tile row, leftmost pixel first.
+    pub fn push_row(&mut self, pixels: [BgPixel; 8]) {
+        self.pixels = pixels;
+        self.len = 8;
+    }
+
+    pub fn pop(&mut self) -> Option<BgPixel> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.len -= 1;
+
+        Some(self.pixels[7 - self.len as usize])
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        for pixel in self.pixels.iter() {
+            w.write_u8(pixel.colour);
+            w.write_u8(pixel.palette);
+            w.write_bool(pixel.priority);
+        }
+
+        w.write_u8(self.len);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        for pixel in self.pixels.iter_mut() {
+            pixel.colour = r.read_u8_in(0..=3)?;
+            pixel.palette = r.read_u8_in(0..=7)?;
+            pixel.priority = r.read_bool()?;
+        }
+
+        self.len = r.read_u8_in(0..=8)?;
+
+        Ok(())
+    }
+}
+
+/// Holds the sprite pixels of the next 8 dots, transparent
+/// where no sprite was merged in.
+#[derive(Default)]
+pub(super) struct ObjFifo {
+    pixels: [ObjPixel; 8],
+
+    /// Where the next pixel to shift out is.
+    head: u8,
+}
+
+impl ObjFifo {
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn pop(&mut self) -> ObjPixel {
+        let pixel = core::mem::take(&mut self.pixels[self.head as usize]);
+
+        self.head = (self.head + 1) & 0x07;
+
+        pixel
+    }
+
+    /// Merge a pixel in, `offset` pixels from the next one shifted out.
+    /// It only replaces a transparent pixel, or with `by_oam_position`
+    /// one of a sprite that comes after its own in OAM.
+    pub fn merge(&mut self, offset: usize, pixel: ObjPixel, by_oam_position: bool) {
+        let slot = &mut self.pixels[(self.head as usize + offset) & 0x07];
+
+        if slot.colour == 0 || (by_oam_position && pixel.sprite < slot.sprite) {
+            *slot = pixel;
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        for pixel in self.pixels.iter() {
+            w.write_u8(pixel.colour);
+            w.write_u8(pixel.palette);
+            w.write_bool(pixel.behind_bg);
+            w.write_u8(pixel.sprite);
+        }
+
+        w.write_u8(self.head);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        for pixel in self.pixels.iter_mut() {
+            pixel.colour = r.read_u8_in(0..=3)?;
+            pixel.palette = r.read_u8_in(0..=7)?;
+            pixel.behind_bg = r.read_bool()?;
+            pixel.sprite = r.read_u8()?;
+        }
+
+        self.head = r.read_u8_in(0..=7)?;
+
+        Ok(())
+    }
+}
+
+/// The state of drawing the current line.
+pub(super) struct Fetcher {
+    pub step: FetcherStep,
+
+    /// Set after the first dot of a 2 dot step.
+    pub step_started: bool,
+
+    /// The first tile of a line is fetched twice, the first time
+    /// it is thrown away instead of pushed.
+    pub dummy_fetch: bool,
+
+    /// The column of the next tile to fetch, from the left
+    /// of the screen or the left of the window.
+    pub tile_x: u8,
+
+    /// The tile number, attributes and row data being fetched.
+    pub tile_number: u8,
+    pub tile_attrs: u8,
+    pub data_low: u8,
+    pub data_high: u8,
+
+    /// Set once the window started on this line, tiles
+    /// are fetched from the window from then on.
+    pub in_window: bool,
+
+    /// Background pixels left to throw away instead of shifting
+    /// them out, for the fine scroll of SCX or WX below 7.
+    pub discard: u8,
+
+    /// The X coordinate of the next pixel shifted out.
+    pub lcd_x: u8,
+
+    pub bg_fifo: BgFifo,
+    pub obj_fifo: ObjFifo,
+
+    /// The sprites on this line in OAM order,
+    /// at most 10 like on hardware.
+    pub sprites: [Sprite; 10],
+    pub sprite_count: u8,
+
+    /// A bit per sprite that was fetched, or skipped.
+    pub fetched_sprites: u16,
+
+    /// The sprite being fetched, and the dots the fetch has left.
+    pub sprite_fetch: u8,
+    pub sprite_fetch_dots: u8,
+}
+
+impl Fetcher {
+    /// Create a new `Fetcher` instance.
+    pub fn new() -> Self {
+        Self {
+            step: FetcherStep::Tile,
+            step_started: false,
+            dummy_fetch: true,
+            tile_x: 0,
+            tile_number: 0,
+            tile_attrs: 0,
+            data_low: 0,
+            data_high: 0,
+            in_window: false,
+            discard: 0,
+            lcd_x: 0,
+            bg_fifo: BgFifo::default(),
+            obj_fifo: ObjFifo::default(),
+            sprites: [Sprite::default(); 10],
+            sprite_count: 0,
+            fetched_sprites: 0,
+            sprite_fetch: 0,
+            sprite_fetch_dots: 0,
+        }
+    }
+
+    /// Get ready to draw a new line, with the fine scroll of SCX.
+    pub fn start_line(&mut self, scx: u8) {
+        self.step = FetcherStep::Tile;
+        self.step_started = false;
+        self.dummy_fetch = true;
+        self.tile_x = 0;
+        self.in_window = false;
+        self.discard = scx & 0x07;
+        self.lcd_x = 0;
+        self.bg_fifo.clear();
+        self.obj_fifo.clear();
+        self.fetched_sprites = 0;
+        self.sprite_fetch_dots = 0;
+    }
+
+    /// Start fetching the window, with the pixels left of
+    /// the screen thrown away if WX is below 7.
+    pub fn start_window(&mut self, wx: u8) {
+        self.step = FetcherStep::Tile;
+        self.step_started = false;
+        self.tile_x = 0;
+        self.in_window = true;
+        self.discard = 7u8.saturating_sub(wx);
+        self.bg_fifo.clear();
+    }
+
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.step as u8);
+        w.write_bool(self.step_started);
+        w.write_bool(self.dummy_fetch);
+        w.write_u8(self.tile_x);
+        w.write_u8(self.tile_number);
+        w.write_u8(self.tile_attrs);
+        w.write_u8(self.data_low);
+        w.write_u8(self.data_high);
+        w.write_bool(self.in_window);
+        w.write_u8(self.discard);
+        w.write_u8(self.lcd_x);
+
+        self.bg_fifo.save_state(w);
+        self.obj_fifo.save_state(w);
+
+        for sprite in self.sprites.iter() {
+            w.write_u8(sprite.y);
+            w.write_u8(sprite.x);
+            w.write_u8(sprite.tile_number);
+            w.write_u8(sprite.flags);
+        }
+
+        w.write_u8(self.sprite_count);
+        w.write_u16(self.fetched_sprites);
+        w.write_u8(self.sprite_fetch);
+        w.write_u8(self.sprite_fetch_dots);
+    }
+
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.step = match r.read_u8()? & 0x03 {
+            0 => FetcherStep::Tile,
+            1 => FetcherStep::DataLow,
+            2 => FetcherStep::DataHigh,
+            _ => FetcherStep::Push,
+        };
+        self.step_started = r.read_bool()?;
+        self.dummy_fetch = r.read_bool()?;
+        self.tile_x = r.read_u8()?;
+        self.tile_number = r.read_u8()?;
+        self.tile_attrs = r.read_u8()?;
+        self.data_low = r.read_u8()?;
+        self.data_high = r.read_u8()?;
+        self.in_window = r.read_bool()?;
+        self.discard = r.read_u8()?;
+        self.lcd_x = r.read_u8_in(0..=160)?;
+
+        self.bg_fifo.load_state(r)?;
+        self.obj_fifo.load_state(r)?;
+
+        for sprite in self.sprites.iter_mut() {
+            sprite.y = r.read_u8()?;
+            sprite.x = r.read_u8()?;
+            sprite.tile_number = r.read_u8()?;
+            sprite.flags = r.read_u8()?;
+        }
+
+        self.sprite_count = r.read_u8_in(0..=10)?;
+        self.fetched_sprites = r.read_u16()?;
+        self.sprite_fetch = r.read_u8_in(0..=9)?;
+        self.sprite_fetch_dots = r.read_u8()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+//! Finding out which RAM a game changes, a common ROM hacking workflow.
+//!
+//! A snapshot of all RAM is taken first, after that every write that
+//! changes a byte is counted along with the instruction that did it.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+
+use crate::debugger::BankedAddress;
+
+/// A byte of RAM that changed since the snapshot was taken.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RamChange {
+    pub address: BankedAddress,
+
+    /// The value when the snapshot was taken.
+    pub before: u8,
+
+    /// The current value.
+    pub after: u8,
+
+    /// How many writes changed the value.
+    pub changes: u32,
+
+    /// The instructions that changed the value, lowest address first.
+    pub writers: Vec<BankedAddress>,
+}
+
+/// How often a byte changed, and which instructions changed it.
+#[derive(Default)]
+struct Writes {
+    changes: u32,
+    writers: BTreeSet<BankedAddress>,
+}
+
+/// Tracks writes to RAM since a snapshot was taken.
+pub(crate) struct RamDiff {
+    /// The contents of RAM when tracking started.
+    snapshot: Vec<(BankedAddress, u8)>,
+
+    writes: BTreeMap<BankedAddress, Writes>,
+
+    /// The instruction that is executing.
+    pub pc: BankedAddress,
+}
+
+impl RamDiff {
+    /// Create a new `RamDiff` instance, starting from the given RAM contents.
+    pub fn new(snapshot: Vec<(BankedAddress, u8)>) -> Self {
+        Self {
+            snapshot,
+            writes: BTreeMap::new(),
+            pc: BankedAddress::new(0, 0),
+        }
+    }
+
+    /// Record a write that changed a byte.
+    pub fn record(&mut self, address: BankedAddress) {
+        let writes = self.writes.entry(address).or_default();
+
+        writes.changes += 1;
+        writes.writers.insert(self.pc);
+    }
+
+    /// Compare the snapshot with the current RAM contents, which have
+    /// to be laid out the same way. Bytes that changed and then changed
+    /// back are reported too.
+    pub fn report(&self, current: &[(BankedAddress, u8)]) -> Vec<RamChange> {
+        self.snapshot
+            .iter()
+            .zip(current.iter())
+            .filter_map(|(&(address, before), &(_, after))| {
+                let writes = self.writes.get(&address);
+
+                if before == after && writes.is_none() {
+                    return None;
+                }
+
+                Some(RamChange {
+                    address,
+                    before,
+                    after,
+                    changes: writes.map_or(0, |writes| writes.changes),
+                    writers: writes
+                        .map(|writes| writes.writers.iter().copied().collect())
+                        .unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,346 @@
+//! The serial port, which talks to another Game Boy over a link cable.
+//!
+//! Every transfer shifts the 8 bits of SB out, most significant bit
+//! first, while shifting in the bits of the partner. The Game Boy
+//! either drives the clock itself, or waits for the partner to do so.
+
+mod barcode;
+mod four_player;
+
+use alloc::{boxed::Box, rc::Rc};
+use core::cell::RefCell;
+
+pub use self::{
+    barcode::{BarcodeReader, BarcodeReaderKind, InvalidBarcode},
+    four_player::{AdapterPort, FourPlayerAdapter},
+};
+use crate::{
+    state::{StateError, StateReader, StateWriter},
+    util::set_bit,
+};
+
+/// M-cycles per bit with the internal clock, 8192 Hz.
+const SLOW_BIT_CYCLES: u16 = 128;
+
+/// M-cycles per bit with the fast internal clock (CGB Mode Only), 262144 Hz.
+const FAST_BIT_CYCLES: u16 = 4;
+
+/// The other end of the link cable.
+///
+/// Bytes are exchanged whole, the way link protocols and peripherals
+/// deal in them. With the internal clock the received byte is still
+/// shifted in bit by bit, at the speed of the clock.
+pub trait LinkPartner {
+    /// Exchange a byte when the Game Boy starts a transfer with its
+    /// own clock. The partner receives `byte` and returns the byte
+    /// it sends back.
+    fn exchange(&mut self, byte: u8) -> u8;
+
+    /// Called every M-cycle while a transfer waits for the partner's
+    /// clock. Clock the transfer by returning the byte sent back for
+    /// `byte`, or return `None` to keep the Game Boy waiting.
+    fn external_clock(&mut self, byte: u8) -> Option<u8>;
+}
+
+pub(crate) struct Serial {
+    /// 0xFF01 - Serial Transfer Data.
+    sb: u8,
+
+    /// 0xFF02 - Serial Transfer Control.
+    ///
+    /// Bit 7 starts a transfer, bit 1 selects the fast clock
+    /// and bit 0 selects the internal clock.
+    sc: u8,
+
+    /// The bits left to shift in the current transfer.
+    bits_left: u8,
+
+    /// The byte the partner sent, shifted into SB as the
+    /// internal clock ticks.
+    incoming: u8,
+
+    /// M-cycles since the last bit was shifted, internal clock only.
+    cycles: u16,
+
+    /// Indicates whether the fast clock can be selected.
+    cgb_mode: bool,
+
+    /// The connected Game Boy, transfers with the internal clock
+    /// receive 0xFF and ones with the external clock never complete
+    /// without one, as on an unplugged link port.
+    pub partner: Option<Box<dyn LinkPartner>>,
+
+    /// Shared reference to IF register.
+    if_reg: Rc<RefCell<u8>>,
+}
+
+impl Serial {
+    /// Create a new `Serial` instance.
+    pub fn new(if_reg: Rc<RefCell<u8>>, cgb_mode: bool) -> Self {
+        Self {
+            sb: 0,
+            sc: 0,
+            bits_left: 0,
+            incoming: 0,
+            cycles: 0,
+            cgb_mode,
+            partner: None,
+            if_reg,
+        }
+    }
+
+    /// Reset the serial port to its power on state,
+    /// keeping the link partner connected.
+    pub fn reset(&mut self, if_reg: Rc<RefCell<u8>>) {
+        let partner = self.partner.take();
+
+        *self = Self::new(if_reg, self.cgb_mode);
+        self.partner = partner;
+    }
+
+    /// Serialize the serial port's state.
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_u8(self.sb);
+        w.write_u8(self.sc);
+        w.write_u8(self.bits_left);
+        w.write_u8(self.incoming);
+        w.write_u16(self.cycles);
+    }
+
+    /// Restore the serial port's state.
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.sb = r.read_u8()?;
+        self.sc = r.read_u8()?;
+        self.bits_left = r.read_u8_in(0..=8)?;
+        self.incoming = r.read_u8()?;
+        self.cycles = r.read_u16()?;
+
+        Ok(())
+    }
+
+    /// Read a byte from the specified address.
+    pub fn read_byte(&self, addr: u16) -> u8 {
+        match addr {
+            0xFF01 => self.sb,
+
+            // Unused bits read as 1.
+            0xFF02 if self.cgb_mode => self.sc | 0x7C,
+            0xFF02 => self.sc | 0x7E,
+
+            _ => unreachable!(),
+        }
+    }
+
+    /// Write a byte to the specified address.
+    pub fn write_byte(&mut self, addr: u16, value: u8) {
+        match addr {
+            0xFF01 => self.sb = value,
+
+            0xFF02 => {
+                self.sc = value;
+
+                if value & 0x80 != 0 {
+                    self.bits_left = 8;
+                    self.cycles = 0;
+
+                    // Nothing drives the line without a partner, so it reads high.
+                    if value & 0x01 != 0 {
+                        let sb = self.sb;
+
+                        self.incoming = self
+                            .partner
+                            .as_mut()
+                            .map_or(0xFF, |partner| partner.exchange(sb));
+                    }
+                } else {
+                    self.bits_left = 0;
+                }
+            }
+
+            _ => unreachable!(),
+        }
+    }
+
+    /// Tick the serial port by 1 M-cycle.
+    pub fn tick(&mut self) {
+        if self.bits_left == 0 {
+            return;
+        }
+
+        if self.sc & 0x01 != 0 {
+            let bit_cycles = if self.cgb_mode && self.sc & 0x02 != 0 {
+                FAST_BIT_CYCLES
+            } else {
+                SLOW_BIT_CYCLES
+            };
+
+            self.cycles += 1;
+
+            if self.cycles < bit_cycles {
+                return;
+            }
+
+            self.cycles = 0;
+
+            self.sb = (self.sb << 1) | (self.incoming >> 7);
+            self.incoming <<= 1;
+            self.bits_left -= 1;
+        } else {
+            let sb = self.sb;
+
+            match self
+                .partner
+                .as_mut()
+                .and_then(|partner| partner.external_clock(sb))
+            {
+                Some(received) => {
+                    self.sb = received;
+                    self.bits_left = 0;
+                }
+
+                None => return,
+            }
+        }
+
+        // Request a serial interrupt once all bits are shifted.
+        if self.bits_left == 0 {
+            self.sc &= 0x7F;
+            set_bit!(self.if_reg.borrow_mut(), 3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// A partner that keeps the Game Boy waiting for `delay`
+    /// M-cycles before clocking, recording what it receives.
+    struct TestPartner {
+        reply: u8,
+        delay: u32,
+        received: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl LinkPartner for TestPartner {
+        fn exchange(&mut self, byte: u8) -> u8 {
+            self.received.borrow_mut().push(byte);
+
+            self.reply
+        }
+
+        fn external_clock(&mut self, byte: u8) -> Option<u8> {
+            if self.delay > 0 {
+                self.delay -= 1;
+
+                return None;
+            }
+
+            self.received.borrow_mut().push(byte);
+
+            Some(self.reply)
+        }
+    }
+
+    fn serial(cgb_mode: bool) -> (Serial, Rc<RefCell<u8>>) {
+        let if_reg = Rc::new(RefCell::new(0));
+
+        (Serial::new(Rc::clone(&if_reg), cgb_mode), if_reg)
+    }
+
+    fn plug_in(serial: &mut Serial, reply: u8, delay: u32) -> Rc<RefCell<Vec<u8>>> {
+        let received = Rc::new(RefCell::new(Vec::new()));
+
+        serial.partner = Some(Box::new(TestPartner {
+            reply,
+            delay,
+            received: Rc::clone(&received),
+        }));
+
+        received
+    }
+
+    #[test]
+    fn internal_clock_without_partner_receives_ff() {
+        let (mut serial, if_reg) = serial(false);
+
+        serial.write_byte(0xFF01, 0x42);
+        serial.write_byte(0xFF02, 0x81);
+
+        for _ in 0..8 * SLOW_BIT_CYCLES - 1 {
+            serial.tick();
+        }
+
+        assert_eq!(*if_reg.borrow(), 0);
+
+        serial.tick();
+
+        assert_eq!(serial.read_byte(0xFF01), 0xFF);
+        assert_eq!(serial.read_byte(0xFF02), 0x7F);
+        assert_eq!(*if_reg.borrow(), 0x08);
+    }
+
+    #[test]
+    fn internal_clock_shifts_the_partners_byte_in() {
+        let (mut serial, if_reg) = serial(true);
+        let received = plug_in(&mut serial, 0x0F, 0);
+
+        serial.write_byte(0xFF01, 0x42);
+        serial.write_byte(0xFF02, 0x83);
+
+        assert_eq!(*received.borrow(), [0x42]);
+
+        // Half way the upper bits of SB are shifted out.
+        for _ in 0..4 * FAST_BIT_CYCLES {
+            serial.tick();
+        }
+
+        assert_eq!(serial.read_byte(0xFF01), 0x20);
+
+        for _ in 0..4 * FAST_BIT_CYCLES {
+            serial.tick();
+        }
+
+        assert_eq!(serial.read_byte(0xFF01), 0x0F);
+        assert_eq!(*if_reg.borrow(), 0x08);
+    }
+
+    #[test]
+    fn external_clock_without_partner_never_completes() {
+        let (mut serial, if_reg) = serial(false);
+
+        serial.write_byte(0xFF01, 0x42);
+        serial.write_byte(0xFF02, 0x80);
+
+        for _ in 0..100_000 {
+            serial.tick();
+        }
+
+        assert_eq!(serial.read_byte(0xFF01), 0x42);
+        assert_eq!(serial.read_byte(0xFF02), 0xFE);
+        assert_eq!(*if_reg.borrow(), 0);
+    }
+
+    #[test]
+    fn external_clock_completes_when_the_partner_clocks() {
+        let (mut serial, if_reg) = serial(false);
+        let received = plug_in(&mut serial, 0x5A, 1000);
+
+        serial.write_byte(0xFF01, 0x42);
+        serial.write_byte(0xFF02, 0x80);
+
+        for _ in 0..1000 {
+            serial.tick();
+        }
+
+        assert_eq!(*if_reg.borrow(), 0);
+
+        serial.tick();
+
+        assert_eq!(*received.borrow(), [0x42]);
+        assert_eq!(serial.read_byte(0xFF01), 0x5A);
+        assert_eq!(serial.read_byte(0xFF02), 0x7E);
+        assert_eq!(*if_reg.borrow(), 0x08);
+    }
+}
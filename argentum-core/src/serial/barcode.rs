@@ -0,0 +1,242 @@
+//! Barcode readers, which plug into the link port.
+//!
+//! The Barcode Boy answers a handshake the game starts, after that it
+//! clocks every scanned barcode in as ASCII digits between 0x02 and
+//! 0x03, twice. The Barcode Taisen Bardigun scanner leaves decoding
+//! to the game, it clocks in the bars of the barcode, one byte per
+//! module, 0x00 for a black one and 0xFF for a white one.
+
+use alloc::{collections::VecDeque, rc::Rc, vec::Vec};
+use core::{
+    cell::RefCell,
+    fmt::{self, Display, Formatter},
+};
+
+use super::LinkPartner;
+
+/// What the game sends to start the Barcode Boy handshake.
+const HANDSHAKE: [u8; 4] = [0x10, 0x07, 0x10, 0x07];
+
+/// What the Barcode Boy answers every byte of the handshake with.
+const HANDSHAKE_REPLY: [u8; 4] = [0xFF, 0xFF, 0x10, 0x07];
+
+/// EAN-13 digit patterns of the left half with odd parity, 1 is a
+/// black module. Even parity digits are these reversed and inverted,
+/// digits of the right half are these inverted.
+const L_CODES: [u8; 10] = [0x0D, 0x19, 0x13, 0x3D, 0x23, 0x31, 0x2F, 0x3B, 0x37, 0x0B];
+
+/// Which digits of the left half have even parity, decided
+/// by the first digit. Bit 5 is the second digit.
+const PARITY: [u8; 10] = [0x00, 0x0B, 0x0D, 0x0E, 0x13, 0x19, 0x1C, 0x15, 0x16, 0x1A];
+
+/// The kinds of barcode readers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BarcodeReaderKind {
+    /// The Barcode Boy, used by Battle Space, Famista 3 and others.
+    BarcodeBoy,
+
+    /// The scanner of Barcode Taisen Bardigun.
+    Bardigun,
+}
+
+/// An error which can be returned when scanning a barcode that isn't
+/// made up of the 13 digits of an EAN-13 (JAN) barcode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidBarcode;
+
+impl Display for InvalidBarcode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "not a 13 digit barcode")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidBarcode {}
+
+#[derive(Default)]
+struct ReaderState {
+    /// How many bytes of the handshake were received.
+    handshake: usize,
+
+    /// The bytes of scanned barcodes that weren't clocked in yet.
+    pending: VecDeque<u8>,
+}
+
+/// A barcode reader plugged into the link port.
+///
+/// Clones share the reader, so that one can be plugged into the
+/// Game Boy while the other is used to scan barcodes.
+#[derive(Clone)]
+pub struct BarcodeReader {
+    kind: BarcodeReaderKind,
+    state: Rc<RefCell<ReaderState>>,
+}
+
+impl BarcodeReader {
+    /// Create a new `BarcodeReader` instance.
+    pub fn new(kind: BarcodeReaderKind) -> Self {
+        Self {
+            kind,
+            state: Rc::new(RefCell::new(ReaderState::default())),
+        }
+    }
+
+    /// Scan a barcode, given as its 13 digits. It is sent to
+    /// the game once the game waits for it.
+    pub fn scan(&self, barcode: &str) -> Result<(), InvalidBarcode> {
+        let digits = barcode
+            .chars()
+            .map(|c| c.to_digit(10).map(|digit| digit as u8))
+            .collect::<Option<Vec<u8>>>()
+            .filter(|digits| digits.len() == 13)
+            .ok_or(InvalidBarcode)?;
+
+        let mut state = self.state.borrow_mut();
+
+        match self.kind {
+            BarcodeReaderKind::BarcodeBoy => {
+                for _ in 0..2 {
+                    state.pending.push_back(0x02);
+                    state.pending.extend(barcode.bytes());
+                    state.pending.push_back(0x03);
+                }
+            }
+
+            BarcodeReaderKind::Bardigun => {
+                let modules = ean13_modules(&digits);
+
+                state
+                    .pending
+                    .extend(modules.iter().map(|&black| if black { 0x00 } else { 0xFF }));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get the 95 modules of an EAN-13 barcode from left to right,
+/// true for black ones.
+fn ean13_modules(digits: &[u8]) -> Vec<bool> {
+    let mut modules = Vec::with_capacity(95);
+
+    let mut push = |pattern: u8, width: usize| {
+        for i in (0..width).rev() {
+            modules.push((pattern >> i) & 0x01 != 0);
+        }
+    };
+
+    // Start guard.
+    push(0b101, 3);
+
+    for (i, &digit) in digits[1..7].iter().enumerate() {
+        let code = L_CODES[digit as usize];
+
+        if (PARITY[digits[0] as usize] >> (5 - i)) & 0x01 != 0 {
+            push((!code).reverse_bits() >> 1, 7);
+        } else {
+            push(code, 7);
+        }
+    }
+
+    // Middle guard.
+    push(0b01010, 5);
+
+    for &digit in digits[7..].iter() {
+        push(!L_CODES[digit as usize], 7);
+    }
+
+    // End guard.
+    push(0b101, 3);
+
+    modules
+}
+
+impl LinkPartner for BarcodeReader {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        let mut state = self.state.borrow_mut();
+
+        if self.kind != BarcodeReaderKind::BarcodeBoy {
+            return 0xFF;
+        }
+
+        // Anything but the next byte of the handshake starts it over.
+        if byte != HANDSHAKE[state.handshake % HANDSHAKE.len()] {
+            state.handshake = 0;
+
+            return 0xFF;
+        }
+
+        let reply = HANDSHAKE_REPLY[state.handshake % HANDSHAKE.len()];
+
+        state.handshake += 1;
+
+        reply
+    }
+
+    fn external_clock(&mut self, _: u8) -> Option<u8> {
+        self.state.borrow_mut().pending.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn barcode_boy_handshake_and_scan() {
+        let mut reader = BarcodeReader::new(BarcodeReaderKind::BarcodeBoy);
+
+        // Nothing to clock in before a barcode is scanned.
+        assert_eq!(reader.external_clock(0x00), None);
+
+        // A wrong byte starts the handshake over.
+        assert_eq!(reader.exchange(0x10), 0xFF);
+        assert_eq!(reader.exchange(0x10), 0xFF);
+
+        let replies: Vec<u8> = HANDSHAKE
+            .iter()
+            .map(|&byte| reader.exchange(byte))
+            .collect();
+
+        assert_eq!(replies, HANDSHAKE_REPLY);
+
+        assert_eq!(reader.scan("49012345678"), Err(InvalidBarcode));
+        assert_eq!(reader.scan("490123456789x"), Err(InvalidBarcode));
+
+        reader.scan("4901234567894").unwrap();
+
+        let mut expected = Vec::new();
+
+        for _ in 0..2 {
+            expected.push(0x02);
+            expected.extend_from_slice(b"4901234567894");
+            expected.push(0x03);
+        }
+
+        let clocked: Vec<u8> = core::iter::from_fn(|| reader.external_clock(0x00)).collect();
+
+        assert_eq!(clocked, expected);
+    }
+
+    #[test]
+    fn bardigun_clocks_the_modules_in() {
+        let mut reader = BarcodeReader::new(BarcodeReaderKind::Bardigun);
+
+        // The game drives nothing, the scanner only clocks.
+        assert_eq!(reader.exchange(0x10), 0xFF);
+
+        reader.scan("5901234123457").unwrap();
+
+        let clocked: Vec<u8> = core::iter::from_fn(|| reader.external_clock(0x00)).collect();
+        let expected: Vec<u8> = concat!(
+            "10100010110100111011001100100110111101001110101010110011",
+            "011011001000010101110010011101000100101"
+        )
+        .bytes()
+        .map(|module| if module == b'1' { 0x00 } else { 0xFF })
+        .collect();
+
+        assert_eq!(clocked, expected);
+    }
+}
@@ -0,0 +1,380 @@
+//! The four player adapter (DMG-07), which links up to four Game Boys.
+//!
+//! The adapter clocks every transfer, the games only wait for it.
+//! While pinging, it sends every Game Boy 0xFE followed by three
+//! status bytes, its player number with a bit per connected player
+//! in the upper nibble. The games answer with two acknowledgements,
+//! the transfer rate and the packet size, player 1's packet size
+//! is used. Once player 1 answers a whole ping with 0xAA, the adapter
+//! sends 0xCC four times and starts transmitting.
+//!
+//! While transmitting, the adapter runs in rounds. In every round each
+//! Game Boy sends a packet, and receives the packets all four players
+//! sent the round before, player 1's first. Players that aren't
+//! connected send zeros. A Game Boy that gets ahead of the others
+//! waits until their packets are in, so the games stay in lockstep.
+//! The transfer rate is ignored.
+
+use alloc::{collections::VecDeque, rc::Rc, vec::Vec};
+use core::cell::RefCell;
+
+use super::LinkPartner;
+
+/// Players the adapter can link.
+const PLAYERS: usize = 4;
+
+/// The first byte of a ping.
+const PING_HEADER: u8 = 0xFE;
+
+/// Player 1's answer to a ping that starts the transmission.
+const START_REQUEST: u8 = 0xAA;
+
+/// Sent four times once the transmission starts.
+const START_ACK: u8 = 0xCC;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Ping,
+    Transmission,
+}
+
+/// Where a single player is in the protocol.
+#[derive(Clone, Copy, Default)]
+struct PlayerState {
+    /// The index of the next byte in the ping or round.
+    position: usize,
+
+    /// The `START_ACK`s left to send.
+    start_acks: u8,
+
+    /// The round the player is in.
+    round: u64,
+}
+
+struct AdapterState {
+    phase: Phase,
+    connected: [bool; PLAYERS],
+    players: [PlayerState; PLAYERS],
+
+    /// The bytes in a packet.
+    packet_size: usize,
+
+    /// The answers player 1 gave to the ping being sent.
+    ping_answers: [u8; 4],
+
+    /// The packets every player sent, per round.
+    rounds: VecDeque<[Vec<u8>; PLAYERS]>,
+
+    /// The round at the front of `rounds`.
+    first_round: u64,
+}
+
+impl AdapterState {
+    /// The status byte sent to a player while pinging.
+    fn status(&self, player: usize) -> u8 {
+        let connected = self
+            .connected
+            .iter()
+            .enumerate()
+            .filter(|(_, &connected)| connected)
+            .fold(0, |mask, (i, _)| mask | (0x10 << i));
+
+        connected | (player as u8 + 1)
+    }
+
+    /// Get the packets of a round, creating it if it's new.
+    fn round_mut(&mut self, round: u64) -> &mut [Vec<u8>; PLAYERS] {
+        let index = (round - self.first_round) as usize;
+
+        while self.rounds.len() <= index {
+            self.rounds.push_back(Default::default());
+        }
+
+        &mut self.rounds[index]
+    }
+
+    /// Check if every connected player sent its packet of a round.
+    fn is_round_complete(&self, round: u64) -> bool {
+        let index = match round.checked_sub(self.first_round) {
+            Some(index) => index as usize,
+            None => return true,
+        };
+
+        match self.rounds.get(index) {
+            Some(packets) => {
+                (0..PLAYERS).all(|i| !self.connected[i] || packets[i].len() == self.packet_size)
+            }
+
+            None => false,
+        }
+    }
+
+    /// Forget the rounds every player received already.
+    fn prune_rounds(&mut self) {
+        let oldest = (0..PLAYERS)
+            .filter(|&i| self.connected[i])
+            .map(|i| self.players[i].round)
+            .min()
+            .unwrap_or(0);
+
+        while self.first_round + 1 < oldest && !self.rounds.is_empty() {
+            self.rounds.pop_front();
+            self.first_round += 1;
+        }
+    }
+
+    /// Clock a byte to a player while pinging.
+    fn ping(&mut self, player: usize, byte: u8) -> u8 {
+        let position = self.players[player].position;
+
+        let sent = match position {
+            0 => PING_HEADER,
+            _ => self.status(player),
+        };
+
+        self.players[player].position = (position + 1) % 4;
+
+        if player == 0 {
+            self.ping_answers[position] = byte;
+
+            if position == 3 {
+                if self.ping_answers == [START_REQUEST; 4] {
+                    self.start_transmission();
+                } else if self.ping_answers[3] != 0 {
+                    self.packet_size = self.ping_answers[3] as usize;
+                }
+            }
+        }
+
+        sent
+    }
+
+    fn start_transmission(&mut self) {
+        self.phase = Phase::Transmission;
+
+        for player in self.players.iter_mut() {
+            *player = PlayerState {
+                start_acks: 4,
+                ..PlayerState::default()
+            };
+        }
+
+        self.rounds.clear();
+        self.first_round = 0;
+    }
+
+    /// Clock a byte to a player while transmitting, `None`
+    /// if the other players have to catch up first.
+    fn transmit(&mut self, player: usize, byte: u8) -> Option<u8> {
+        let state = self.players[player];
+
+        if state.start_acks > 0 {
+            self.players[player].start_acks -= 1;
+
+            return Some(START_ACK);
+        }
+
+        // The first round has nothing to pass on.
+        let sent = if state.round == 0 {
+            0x00
+        } else {
+            if !self.is_round_complete(state.round - 1) {
+                return None;
+            }
+
+            let sender = state.position / self.packet_size;
+            let index = (state.round - 1 - self.first_round) as usize;
+
+            self.rounds[index][sender]
+                .get(state.position % self.packet_size)
+                .copied()
+                .unwrap_or(0x00)
+        };
+
+        if state.position < self.packet_size {
+            self.round_mut(state.round)[player].push(byte);
+        }
+
+        let position = state.position + 1;
+
+        if position == PLAYERS * self.packet_size {
+            self.players[player].position = 0;
+            self.players[player].round += 1;
+            self.prune_rounds();
+        } else {
+            self.players[player].position = position;
+        }
+
+        Some(sent)
+    }
+}
+
+/// The four player adapter, hand out its ports to the Game Boys
+/// that are linked. Everything runs on the thread driving them.
+#[derive(Clone)]
+pub struct FourPlayerAdapter {
+    state: Rc<RefCell<AdapterState>>,
+}
+
+impl FourPlayerAdapter {
+    /// Create a new `FourPlayerAdapter` instance.
+    pub fn new() -> Self {
+        Self {
+            state: Rc::new(RefCell::new(AdapterState {
+                phase: Phase::Ping,
+                connected: [false; PLAYERS],
+                players: [PlayerState::default(); PLAYERS],
+                packet_size: 1,
+                ping_answers: [0; 4],
+                rounds: VecDeque::new(),
+                first_round: 0,
+            })),
+        }
+    }
+
+    /// Plug a Game Boy into the port of a player, from 0 to 3.
+    /// The returned port is the Game Boy's link partner.
+    pub fn port(&self, player: usize) -> AdapterPort {
+        assert!(player < PLAYERS, "the adapter only has four ports");
+
+        self.state.borrow_mut().connected[player] = true;
+
+        AdapterPort {
+            player,
+            state: Rc::clone(&self.state),
+        }
+    }
+}
+
+impl Default for FourPlayerAdapter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A port of the four player adapter.
+pub struct AdapterPort {
+    player: usize,
+    state: Rc<RefCell<AdapterState>>,
+}
+
+impl LinkPartner for AdapterPort {
+    /// The adapter drives the clock, nothing answers a Game Boy that does.
+    fn exchange(&mut self, _: u8) -> u8 {
+        0xFF
+    }
+
+    fn external_clock(&mut self, byte: u8) -> Option<u8> {
+        let mut state = self.state.borrow_mut();
+
+        match state.phase {
+            Phase::Ping => Some(state.ping(self.player, byte)),
+            Phase::Transmission => state.transmit(self.player, byte),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clock a byte to every player, each sending its byte of `bytes`.
+    /// Player 1 goes last, so what it answers to a ping takes effect
+    /// for everyone at once, as the adapter clocks them all together.
+    fn clock_all(ports: &mut [AdapterPort], bytes: [u8; PLAYERS]) -> [u8; PLAYERS] {
+        let mut received = [0; PLAYERS];
+
+        for (i, port) in ports.iter_mut().enumerate().rev() {
+            received[i] = port.external_clock(bytes[i]).expect("the adapter stalled");
+        }
+
+        received
+    }
+
+    #[test]
+    fn ping_and_first_round() {
+        let adapter = FourPlayerAdapter::new();
+        let mut ports: Vec<AdapterPort> = (0..PLAYERS).map(|i| adapter.port(i)).collect();
+
+        // Player 1 acknowledges the ping and asks for 2 byte packets.
+        for (position, &answer) in [0x88, 0x88, 0x00, 0x02].iter().enumerate() {
+            let received = clock_all(&mut ports, [answer, 0x88, 0x88, 0x88]);
+
+            if position == 0 {
+                assert_eq!(received, [PING_HEADER; PLAYERS]);
+            } else {
+                assert_eq!(received, [0xF1, 0xF2, 0xF3, 0xF4]);
+            }
+        }
+
+        for _ in 0..4 {
+            clock_all(&mut ports, [START_REQUEST; PLAYERS]);
+        }
+
+        for _ in 0..4 {
+            assert_eq!(clock_all(&mut ports, [0; PLAYERS]), [START_ACK; PLAYERS]);
+        }
+
+        // Every player sends its packet, the first round passes nothing on.
+        let packet = |player: usize, byte: usize| (0x10 * (player + 1) + byte) as u8;
+
+        for position in 0..PLAYERS * 2 {
+            let bytes = [0, 1, 2, 3].map(|player| packet(player, position % 2));
+
+            assert_eq!(clock_all(&mut ports, bytes), [0; PLAYERS]);
+        }
+
+        // Player 1 can't go on before the others are done with the second round.
+        let mut round = Vec::new();
+
+        for _ in 0..PLAYERS * 2 {
+            round.push(ports[0].external_clock(0).unwrap());
+        }
+
+        assert_eq!(ports[0].external_clock(0), None);
+
+        // The second round hands everyone the packets of the first.
+        let expected: Vec<u8> = (0..PLAYERS * 2).map(|i| packet(i / 2, i % 2)).collect();
+
+        assert_eq!(round, expected);
+
+        for port in ports[1..].iter_mut() {
+            let round: Vec<u8> = (0..PLAYERS * 2)
+                .map(|_| port.external_clock(0).unwrap())
+                .collect();
+
+            assert_eq!(round, expected);
+        }
+
+        assert_eq!(ports[0].external_clock(0), Some(0));
+    }
+
+    #[test]
+    fn unplugged_players_send_zeros() {
+        let adapter = FourPlayerAdapter::new();
+        let mut port = adapter.port(0);
+
+        for answer in [0x88, 0x88, 0x00, 0x01] {
+            port.external_clock(answer);
+        }
+
+        assert_eq!(port.external_clock(START_REQUEST), Some(PING_HEADER));
+        assert_eq!(port.external_clock(START_REQUEST), Some(0x11));
+
+        for _ in 0..2 {
+            port.external_clock(START_REQUEST);
+        }
+
+        for _ in 0..4 {
+            assert_eq!(port.external_clock(0), Some(START_ACK));
+        }
+
+        for byte in [0x42, 0, 0, 0] {
+            assert_eq!(port.external_clock(byte), Some(0));
+        }
+
+        let round: Vec<u8> = (0..4).map(|_| port.external_clock(0).unwrap()).collect();
+
+        assert_eq!(round, [0x42, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,229 @@
+//! Save state serialization.
+//!
+//! Save states are a flat little endian byte stream, every component
+//! writes its fields in a fixed order and reads them back in the
+//! same order. The stream starts with `AGST` and the version of the
+//! format as a `u16`.
+
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Display, Formatter},
+    ops::RangeInclusive,
+};
+
+/// Identifies save states.
+const MAGIC: &[u8; 4] = b"AGST";
+
+/// The version of the save state format. Bump it whenever a component
+/// changes what it writes, states of other versions are rejected.
+const VERSION: u16 = 7;
+
+/// A snapshot of the whole system, for rewinding and run-ahead.
+///
+/// Snapshots are the save state stream without the header. They are
+/// only meant to be restored into the system that took them, by the
+/// same build, and are never written to disk, so they aren't versioned.
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    pub(crate) buffer: Vec<u8>,
+}
+
+/// Errors that can occur while loading a save state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateError {
+    /// The save state ended before all fields were read.
+    Truncated,
+
+    /// The data doesn't start like a save state.
+    NotAState,
+
+    /// The save state was written by a version of the core that
+    /// serializes the system differently.
+    UnsupportedVersion(u16),
+
+    /// A bank number or an index is out of range, the state is corrupt.
+    OutOfRange,
+
+    /// The cartridge's state doesn't fit the loaded cartridge,
+    /// as when the state was saved by another game.
+    CartridgeMismatch,
+}
+
+impl Display for StateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "the save state is truncated"),
+            Self::NotAState => write!(f, "the file is not a save state"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "the save state has version {}, only version {} is supported",
+                version, VERSION
+            ),
+            Self::OutOfRange => write!(f, "the save state is corrupt, a value is out of range"),
+            Self::CartridgeMismatch => write!(f, "the save state is for another cartridge"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StateError {}
+
+/// Serializes the state of the components.
+pub(crate) struct StateWriter {
+    buffer: Vec<u8>,
+}
+
+impl StateWriter {
+    /// Create a new `StateWriter` instance.
+    pub fn new() -> Self {
+        Self { buffer: Vec::new() }
+    }
+
+    /// Create a new `StateWriter` instance that writes into the
+    /// given buffer, reusing its allocation. The buffer is cleared.
+    pub fn with_buffer(mut buffer: Vec<u8>) -> Self {
+        buffer.clear();
+
+        Self { buffer }
+    }
+
+    /// Write the magic and version the state starts with.
+    pub fn write_header(&mut self) {
+        self.write_bytes(MAGIC);
+        self.write_u16(VERSION);
+    }
+
+    /// Get the serialized state.
+    pub fn finish(self) -> Vec<u8> {
+        self.buffer
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_f64(&mut self, value: f64) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Write a buffer whose length is known when reading it back.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Write what `write` appends to the buffer, prefixed by its
+    /// length, without collecting it in a buffer of its own first.
+    pub fn write_blob(&mut self, write: impl FnOnce(&mut Vec<u8>)) {
+        let start = self.buffer.len();
+
+        self.write_u32(0);
+        write(&mut self.buffer);
+
+        let len = (self.buffer.len() - start - 4) as u32;
+
+        self.buffer[start..start + 4].copy_from_slice(&len.to_le_bytes());
+    }
+}
+
+/// Deserializes the state of the components.
+pub(crate) struct StateReader<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    /// Create a new `StateReader` instance.
+    pub fn new(buffer: &'a [u8]) -> Self {
+        Self { buffer }
+    }
+
+    /// Check the magic and version the state starts with.
+    pub fn read_header(&mut self) -> Result<(), StateError> {
+        if self.read_bytes(MAGIC.len()) != Ok(MAGIC.as_ref()) {
+            return Err(StateError::NotAState);
+        }
+
+        match self.read_u16()? {
+            VERSION => Ok(()),
+            version => Err(StateError::UnsupportedVersion(version)),
+        }
+    }
+
+    /// Read a buffer of the given length.
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.buffer.len() < len {
+            return Err(StateError::Truncated);
+        }
+
+        let (bytes, rest) = self.buffer.split_at(len);
+        self.buffer = rest;
+
+        Ok(bytes)
+    }
+
+    /// Read a buffer into the given slice.
+    pub fn read_into(&mut self, dst: &mut [u8]) -> Result<(), StateError> {
+        dst.copy_from_slice(self.read_bytes(dst.len())?);
+
+        Ok(())
+    }
+
+    /// Read a buffer prefixed by its length.
+    pub fn read_blob(&mut self) -> Result<&'a [u8], StateError> {
+        let len = self.read_u32()? as usize;
+
+        self.read_bytes(len)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    /// Read a byte that has to lie in `range`, like bank numbers and
+    /// indices, so a corrupt state can't index out of bounds.
+    pub fn read_u8_in(&mut self, range: RangeInclusive<u8>) -> Result<u8, StateError> {
+        let value = self.read_u8()?;
+
+        if range.contains(&value) {
+            Ok(value)
+        } else {
+            Err(StateError::OutOfRange)
+        }
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, StateError> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, StateError> {
+        let mut bytes = [0; 2];
+        self.read_into(&mut bytes)?;
+
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, StateError> {
+        let mut bytes = [0; 4];
+        self.read_into(&mut bytes)?;
+
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn read_f64(&mut self) -> Result<f64, StateError> {
+        let mut bytes = [0; 8];
+        self.read_into(&mut bytes)?;
+
+        Ok(f64::from_le_bytes(bytes))
+    }
+}
@@ -0,0 +1,15 @@
+//! Synthetic ROMs for the unit tests.
+
+use alloc::{vec, vec::Vec};
+
+/// Build a 32 KB ROM only cartridge that jumps to `program`,
+/// placed right after the header at 0x0150.
+pub(crate) fn with_program(program: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+
+    // NOP, JP 0x0150
+    rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x0150..0x0150 + program.len()].copy_from_slice(program);
+
+    rom
+}
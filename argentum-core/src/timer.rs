@@ -1,6 +1,11 @@
-use std::{cell::RefCell, hint::unreachable_unchecked, rc::Rc};
+use alloc::rc::Rc;
+use core::{cell::RefCell, hint::unreachable_unchecked};
 
-use crate::util::set_bit;
+use crate::{
+    clock::Speed,
+    state::{StateError, StateReader, StateWriter},
+    util::set_bit,
+};
 
 #[derive(Default)]
 pub(crate) struct Timer {
@@ -49,8 +54,11 @@ impl Timer {
         }
     }
 
-    /// Tick the timers and divider by 4 T-cycles.
-    pub fn tick(&mut self) {
+    /// Tick the timers and divider by 4 T-cycles of the CPU clock.
+    /// Returns true if the APU's frame sequencer should be clocked.
+    pub fn tick(&mut self, speed: Speed) -> bool {
+        let div_apu_bit = self.div_apu_bit(speed);
+
         if let Some(ref mut cycles) = self.tima_reload {
             if *cycles == 0 {
                 self.tima_reload = None;
@@ -66,6 +74,41 @@ impl Timer {
 
         self.div = self.div.wrapping_add(4);
         self.check_falling_edge();
+
+        div_apu_bit && !self.div_apu_bit(speed)
+    }
+
+    /// Get the bit of DIV whose falling edge clocks the APU's frame
+    /// sequencer. This is bit 4 of DIV, or bit 5 in double speed mode.
+    pub fn div_apu_bit(&self, speed: Speed) -> bool {
+        (self.div >> speed.div_apu_bit()) & 0x01 != 0
+    }
+
+    /// Serialize the timer's state.
+    pub fn save_state(&self, w: &mut StateWriter) {
+        w.write_u16(self.div);
+        w.write_u8(self.tima);
+        w.write_u8(self.tma);
+        w.write_u8(self.tac);
+        w.write_u8(self.last_and_result);
+        w.write_bool(self.tima_reload.is_some());
+        w.write_u8(self.tima_reload.unwrap_or(0));
+    }
+
+    /// Restore the timer's state.
+    pub fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.div = r.read_u16()?;
+        self.tima = r.read_u8()?;
+        self.tma = r.read_u8()?;
+        self.tac = r.read_u8()?;
+        self.last_and_result = r.read_u8()?;
+
+        let reload_pending = r.read_bool()?;
+        let reload = r.read_u8()?;
+
+        self.tima_reload = if reload_pending { Some(reload) } else { None };
+
+        Ok(())
     }
 
     /// Check for a falling edge on the selected bit of DIV.
@@ -134,3 +177,38 @@ impl Timer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Count how often the frame sequencer is clocked while
+    /// the given T-cycles of the system clock pass.
+    fn frame_sequencer_clocks(speed: Speed, system_cycles: u32) -> u32 {
+        let mut timer = Timer::new(Rc::new(RefCell::new(0)));
+
+        (0..system_cycles / speed.system_cycles(1))
+            .filter(|_| timer.tick(speed))
+            .count() as u32
+    }
+
+    #[test]
+    fn frame_sequencer_runs_at_512_hz_in_both_speeds() {
+        // 8192 T-cycles of the 4 MiHz system clock per clock.
+        assert_eq!(frame_sequencer_clocks(Speed::Normal, 8192 * 8), 8);
+        assert_eq!(frame_sequencer_clocks(Speed::Double, 8192 * 8), 8);
+    }
+
+    #[test]
+    fn div_apu_bit_reads_the_selected_bit() {
+        let mut timer = Timer::new(Rc::new(RefCell::new(0)));
+
+        timer.div = 1 << 12;
+        assert!(timer.div_apu_bit(Speed::Normal));
+        assert!(!timer.div_apu_bit(Speed::Double));
+
+        timer.div = 1 << 13;
+        assert!(!timer.div_apu_bit(Speed::Normal));
+        assert!(timer.div_apu_bit(Speed::Double));
+    }
+}
@@ -19,4 +19,22 @@ macro_rules! res_bit {
     };
 }
 
-pub(crate) use {get_bit, res_bit, set_bit};
+/// Evaluate an expression, adding the time it took to a `Duration`
+/// when the `perf-counters` feature is enabled.
+macro_rules! timed {
+    ($counter:expr, $body:expr) => {{
+        #[cfg(feature = "perf-counters")]
+        let start = std::time::Instant::now();
+
+        let result = $body;
+
+        #[cfg(feature = "perf-counters")]
+        {
+            $counter += start.elapsed();
+        }
+
+        result
+    }};
+}
+
+pub(crate) use {get_bit, res_bit, set_bit, timed};
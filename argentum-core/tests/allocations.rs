@@ -0,0 +1,87 @@
+//! Checks that the core doesn't allocate while emulating, once it is
+//! set up. Frontends on targets without much heap depend on this.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use argentum_core::Argentum;
+
+/// Hands allocations to the system allocator, counting them.
+struct CountingAllocator {
+    allocations: AtomicUsize,
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator {
+    allocations: AtomicUsize::new(0),
+};
+
+/// A ROM only cartridge that turns on the sound, and keeps
+/// triggering channel 1 and writing to work RAM.
+fn test_rom() -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+
+    // NOP, JP 0x0150
+    rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+
+    #[rustfmt::skip]
+    let program = [
+        0x3E, 0x80, 0xE0, 0x26, // LD A, 0x80; LDH (NR52), A
+        0x3E, 0x77, 0xE0, 0x24, // LD A, 0x77; LDH (NR50), A
+        0x3E, 0xFF, 0xE0, 0x25, // LD A, 0xFF; LDH (NR51), A
+        0x3E, 0xF0, 0xE0, 0x12, // LD A, 0xF0; LDH (NR12), A
+        0x3E, 0x87, 0xE0, 0x14, // LD A, 0x87; LDH (NR14), A
+        0x04,                   // INC B
+        0x78,                   // LD A, B
+        0xEA, 0x00, 0xC0,       // LD (0xC000), A
+        0x18, 0xF5,             // JR -11
+    ];
+
+    rom[0x0150..0x0150 + program.len()].copy_from_slice(&program);
+    rom
+}
+
+#[test]
+fn execute_frame_does_not_allocate() {
+    let mut gb = Argentum::new(&test_rom(), Box::new(|_| {}), None).unwrap();
+
+    gb.skip_bootrom();
+
+    let before = ALLOCATOR.allocations.load(Ordering::Relaxed);
+
+    for _ in 0..120 {
+        gb.execute_frame();
+
+        while gb.poll_event().is_some() {}
+    }
+
+    let allocations = ALLOCATOR.allocations.load(Ordering::Relaxed) - before;
+
+    assert_eq!(allocations, 0, "the core allocated while emulating");
+}
@@ -0,0 +1,114 @@
+/*
+ * Thin JNI wrappers around argentum.h for MainActivity. The session is
+ * passed to Kotlin as a long.
+ */
+
+#include <android/bitmap.h>
+#include <jni.h>
+
+#include "argentum.h"
+
+#define JNI_FN(name) Java_com_github_nightshade256_argentum_Native_##name
+
+JNIEXPORT jlong JNICALL JNI_FN(create)(JNIEnv *env, jclass cls, jbyteArray rom, jbyteArray save) {
+    jsize rom_len = (*env)->GetArrayLength(env, rom);
+    jbyte *rom_bytes = (*env)->GetByteArrayElements(env, rom, NULL);
+
+    jsize save_len = 0;
+    jbyte *save_bytes = NULL;
+
+    if (save != NULL) {
+        save_len = (*env)->GetArrayLength(env, save);
+        save_bytes = (*env)->GetByteArrayElements(env, save, NULL);
+    }
+
+    ArgentumSession *session = argentum_new((const uint8_t *)rom_bytes, rom_len,
+                                            (const uint8_t *)save_bytes, save_len);
+
+    (*env)->ReleaseByteArrayElements(env, rom, rom_bytes, JNI_ABORT);
+
+    if (save_bytes != NULL) {
+        (*env)->ReleaseByteArrayElements(env, save, save_bytes, JNI_ABORT);
+    }
+
+    return (jlong)(intptr_t)session;
+}
+
+JNIEXPORT void JNICALL JNI_FN(free)(JNIEnv *env, jclass cls, jlong session) {
+    argentum_free((ArgentumSession *)(intptr_t)session);
+}
+
+JNIEXPORT void JNICALL JNI_FN(runFrame)(JNIEnv *env, jclass cls, jlong session) {
+    argentum_run_frame((ArgentumSession *)(intptr_t)session);
+}
+
+/* Copy the last frame into an RGB_565 bitmap of the frame's size. */
+JNIEXPORT void JNICALL JNI_FN(drawFrame)(JNIEnv *env, jclass cls, jlong session, jobject bitmap) {
+    AndroidBitmapInfo info;
+    void *pixels;
+
+    if (AndroidBitmap_getInfo(env, bitmap, &info) != ANDROID_BITMAP_RESULT_SUCCESS ||
+        info.format != ANDROID_BITMAP_FORMAT_RGB_565) {
+        return;
+    }
+
+    if (AndroidBitmap_lockPixels(env, bitmap, &pixels) != ANDROID_BITMAP_RESULT_SUCCESS) {
+        return;
+    }
+
+    argentum_frame_rgb565((ArgentumSession *)(intptr_t)session, pixels, info.stride / 2);
+    AndroidBitmap_unlockPixels(env, bitmap);
+}
+
+JNIEXPORT void JNICALL JNI_FN(touchDown)(JNIEnv *env, jclass cls, jlong session, jlong id,
+                                         jfloat x, jfloat y) {
+    argentum_touch_down((ArgentumSession *)(intptr_t)session, id, x, y);
+}
+
+JNIEXPORT void JNICALL JNI_FN(touchMove)(JNIEnv *env, jclass cls, jlong session, jlong id,
+                                         jfloat x, jfloat y) {
+    argentum_touch_move((ArgentumSession *)(intptr_t)session, id, x, y);
+}
+
+JNIEXPORT void JNICALL JNI_FN(touchUp)(JNIEnv *env, jclass cls, jlong session, jlong id) {
+    argentum_touch_up((ArgentumSession *)(intptr_t)session, id);
+}
+
+JNIEXPORT void JNICALL JNI_FN(touchReleaseAll)(JNIEnv *env, jclass cls, jlong session) {
+    argentum_touch_release_all((ArgentumSession *)(intptr_t)session);
+}
+
+/* The buttons as x, y, width and height, four floats per button. */
+JNIEXPORT jfloatArray JNICALL JNI_FN(buttons)(JNIEnv *env, jclass cls, jlong session) {
+    ArgentumSession *s = (ArgentumSession *)(intptr_t)session;
+    size_t count = argentum_pad_button_count(s);
+    jfloatArray rects = (*env)->NewFloatArray(env, (jsize)(count * 4));
+
+    for (size_t i = 0; i < count; i++) {
+        ArgentumVirtualButton button;
+
+        argentum_pad_button(s, i, &button);
+
+        jfloat rect[4] = {button.x, button.y, button.width, button.height};
+        (*env)->SetFloatArrayRegion(env, rects, (jsize)(i * 4), 4, rect);
+    }
+
+    return rects;
+}
+
+JNIEXPORT jbyteArray JNICALL JNI_FN(exportRam)(JNIEnv *env, jclass cls, jlong session) {
+    ArgentumSession *s = (ArgentumSession *)(intptr_t)session;
+    size_t len = argentum_export_ram(s, NULL, 0);
+
+    if (len == 0) {
+        return NULL;
+    }
+
+    jbyteArray ram = (*env)->NewByteArray(env, (jsize)len);
+    jbyte *bytes = (*env)->GetByteArrayElements(env, ram, NULL);
+
+    argentum_export_ram(s, (uint8_t *)bytes, len);
+    (*env)->ReleaseByteArrayElements(env, ram, bytes, 0);
+
+    return ram;
+}
@@ -0,0 +1,164 @@
+package com.github.nightshade256.argentum
+
+import android.app.Activity
+import android.content.Context
+import android.graphics.Bitmap
+import android.graphics.Canvas
+import android.graphics.Color
+import android.graphics.Paint
+import android.graphics.Rect
+import android.graphics.RectF
+import android.os.Bundle
+import android.view.Choreographer
+import android.view.MotionEvent
+import android.view.View
+import java.io.File
+
+/** The functions in argentum_jni.c. */
+object Native {
+    init {
+        System.loadLibrary("argentum_jni")
+    }
+
+    @JvmStatic external fun create(rom: ByteArray, save: ByteArray?): Long
+    @JvmStatic external fun free(session: Long)
+    @JvmStatic external fun runFrame(session: Long)
+    @JvmStatic external fun drawFrame(session: Long, bitmap: Bitmap)
+    @JvmStatic external fun touchDown(session: Long, id: Long, x: Float, y: Float)
+    @JvmStatic external fun touchMove(session: Long, id: Long, x: Float, y: Float)
+    @JvmStatic external fun touchUp(session: Long, id: Long)
+    @JvmStatic external fun touchReleaseAll(session: Long)
+    @JvmStatic external fun buttons(session: Long): FloatArray
+    @JvmStatic external fun exportRam(session: Long): ByteArray?
+}
+
+/** Runs the game in assets/rom.gb, saving its RAM to the app's files. */
+class MainActivity : Activity() {
+    private var session = 0L
+    private lateinit var view: GameView
+
+    private val saveFile by lazy { File(filesDir, "rom.sav") }
+
+    override fun onCreate(savedInstanceState: Bundle?) {
+        super.onCreate(savedInstanceState)
+
+        val rom = assets.open("rom.gb").use { it.readBytes() }
+        val save = if (saveFile.exists()) saveFile.readBytes() else null
+
+        session = Native.create(rom, save)
+
+        if (session == 0L) {
+            throw IllegalStateException("unsupported cartridge type")
+        }
+
+        view = GameView(this, session)
+        setContentView(view)
+    }
+
+    override fun onResume() {
+        super.onResume()
+        view.start()
+    }
+
+    override fun onPause() {
+        view.stop()
+        Native.touchReleaseAll(session)
+        Native.exportRam(session)?.let { saveFile.writeBytes(it) }
+        super.onPause()
+    }
+
+    override fun onDestroy() {
+        Native.free(session)
+        super.onDestroy()
+    }
+}
+
+/** Shows the frames with the virtual buttons drawn over the bottom half. */
+class GameView(context: Context, private val session: Long) : View(context),
+    Choreographer.FrameCallback {
+
+    private val frame = Bitmap.createBitmap(160, 144, Bitmap.Config.RGB_565)
+    private val buttons = Native.buttons(session)
+
+    private val framePaint = Paint().apply { isFilterBitmap = false }
+    private val buttonPaint = Paint().apply { color = Color.argb(80, 255, 255, 255) }
+
+    private var running = false
+
+    fun start() {
+        running = true
+        Choreographer.getInstance().postFrameCallback(this)
+    }
+
+    fun stop() {
+        running = false
+        Choreographer.getInstance().removeFrameCallback(this)
+    }
+
+    // Displays refreshing faster than 60 Hz run the game faster too.
+    override fun doFrame(frameTimeNanos: Long) {
+        if (!running) {
+            return
+        }
+
+        Native.runFrame(session)
+        Native.drawFrame(session, frame)
+        invalidate()
+
+        Choreographer.getInstance().postFrameCallback(this)
+    }
+
+    override fun onDraw(canvas: Canvas) {
+        canvas.drawColor(Color.BLACK)
+
+        // The largest integer scale that fits the top half of the view.
+        val scale = maxOf(1, minOf(width / 160, height / 2 / 144))
+        val left = (width - 160 * scale) / 2
+        val top = (height / 2 - 144 * scale) / 2
+
+        canvas.drawBitmap(
+            frame,
+            Rect(0, 0, 160, 144),
+            Rect(left, top, left + 160 * scale, top + 144 * scale),
+            framePaint
+        )
+
+        for (i in buttons.indices step 4) {
+            val x = buttons[i] * width
+            val y = buttons[i + 1] * height
+
+            canvas.drawRoundRect(
+                RectF(x, y, x + buttons[i + 2] * width, y + buttons[i + 3] * height),
+                16f,
+                16f,
+                buttonPaint
+            )
+        }
+    }
+
+    override fun onTouchEvent(event: MotionEvent): Boolean {
+        val index = event.actionIndex
+        val id = event.getPointerId(index).toLong()
+
+        when (event.actionMasked) {
+            MotionEvent.ACTION_DOWN, MotionEvent.ACTION_POINTER_DOWN ->
+                Native.touchDown(session, id, event.getX(index) / width, event.getY(index) / height)
+
+            MotionEvent.ACTION_MOVE -> for (i in 0 until event.pointerCount) {
+                Native.touchMove(
+                    session,
+                    event.getPointerId(i).toLong(),
+                    event.getX(i) / width,
+                    event.getY(i) / height
+                )
+            }
+
+            MotionEvent.ACTION_UP, MotionEvent.ACTION_POINTER_UP ->
+                Native.touchUp(session, id)
+
+            MotionEvent.ACTION_CANCEL -> Native.touchReleaseAll(session)
+        }
+
+        return true
+    }
+}
@@ -0,0 +1,72 @@
+/*
+ * C bindings to the Argentum core, see argentum-ffi/src/lib.rs for the
+ * details of every function. Works from Swift through a bridging header.
+ */
+
+#ifndef ARGENTUM_H
+#define ARGENTUM_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Keys, OR'd together where functions take several. */
+#define ARGENTUM_KEY_RIGHT 0x01
+#define ARGENTUM_KEY_LEFT 0x02
+#define ARGENTUM_KEY_UP 0x04
+#define ARGENTUM_KEY_DOWN 0x08
+#define ARGENTUM_KEY_A 0x10
+#define ARGENTUM_KEY_B 0x20
+#define ARGENTUM_KEY_SELECT 0x40
+#define ARGENTUM_KEY_START 0x80
+
+/* The most fingers tracked at once. */
+#define ARGENTUM_MAX_TOUCHES 10
+
+typedef struct ArgentumSession ArgentumSession;
+
+/* A rectangle holding keys down while touched, normalized to the view. */
+typedef struct ArgentumVirtualButton {
+    uint8_t keys;
+    float x;
+    float y;
+    float width;
+    float height;
+} ArgentumVirtualButton;
+
+/* Returns NULL if the cartridge type isn't supported. `save` may be NULL. */
+ArgentumSession *argentum_new(const uint8_t *rom, size_t rom_len, const uint8_t *save,
+                              size_t save_len);
+void argentum_free(ArgentumSession *session);
+
+void argentum_run_frame(ArgentumSession *session);
+size_t argentum_frame_width(const ArgentumSession *session);
+size_t argentum_frame_height(const ArgentumSession *session);
+void argentum_frame_rgb565(const ArgentumSession *session, uint16_t *pixels, size_t stride);
+
+void argentum_key_down(ArgentumSession *session, uint8_t keys);
+void argentum_key_up(ArgentumSession *session, uint8_t keys);
+
+void argentum_touch_down(ArgentumSession *session, int64_t id, float x, float y);
+void argentum_touch_move(ArgentumSession *session, int64_t id, float x, float y);
+void argentum_touch_up(ArgentumSession *session, int64_t id);
+void argentum_touch_release_all(ArgentumSession *session);
+
+void argentum_pad_clear(ArgentumSession *session);
+void argentum_pad_add_button(ArgentumSession *session, ArgentumVirtualButton button);
+size_t argentum_pad_button_count(const ArgentumSession *session);
+bool argentum_pad_button(const ArgentumSession *session, size_t index,
+                         ArgentumVirtualButton *button);
+
+/* Returns the size of the RAM, only copied if it fits in `len` bytes. */
+size_t argentum_export_ram(const ArgentumSession *session, uint8_t *out, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
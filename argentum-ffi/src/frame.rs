@@ -0,0 +1,19 @@
+//! Converting frames to the pixel formats of mobile platforms.
+
+use argentum_core::FrameInfo;
+
+/// Convert an RGB24 frame to native endian RGB565, the format of
+/// Android's `RGB_565` bitmaps, `stride` pixels apart per row.
+pub fn write_rgb565(framebuffer: &[u8], info: &FrameInfo, pixels: &mut [u16], stride: usize) {
+    let rows = framebuffer.chunks(info.pitch).take(info.height);
+
+    for (row, line) in rows.zip(pixels.chunks_mut(stride)) {
+        let row = row.chunks_exact(3).take(info.width);
+
+        for (rgb, pixel) in row.zip(line.iter_mut()) {
+            *pixel = ((rgb[0] as u16 & 0xF8) << 8)
+                | ((rgb[1] as u16 & 0xFC) << 3)
+                | (rgb[2] as u16 >> 3);
+        }
+    }
+}
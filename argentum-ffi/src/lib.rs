@@ -0,0 +1,279 @@
+//! C bindings to the core for frontends on mobile platforms.
+//!
+//! The library builds as a shared library for Android, loaded from
+//! JNI, and as a static library for iOS, called from Swift through a
+//! bridging header. The functions are declared in `include/argentum.h`.
+//!
+//! Besides running frames, the bindings turn touches into key presses
+//! with a `VirtualPad` and convert frames to RGB565 for the platform's
+//! bitmaps. Sound isn't exposed yet.
+
+mod frame;
+mod touch;
+
+use std::{ptr, slice};
+
+use argentum_core::Argentum;
+
+pub use {
+    frame::write_rgb565,
+    touch::{VirtualButton, VirtualPad, MAX_TOUCHES},
+};
+
+/// A running game, with the touch controls drawn over it.
+pub struct ArgentumSession {
+    gb: Argentum,
+
+    pad: VirtualPad,
+
+    /// Keys held on a physical controller or keyboard.
+    keys: u8,
+}
+
+/// Start a game, with the default touch layout.
+///
+/// Returns NULL if the cartridge type isn't supported.
+///
+/// # Safety
+///
+/// `rom` must point to `rom_len` readable bytes, and `save` to
+/// `save_len` readable bytes unless it's NULL. Both are copied.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_new(
+    rom: *const u8,
+    rom_len: usize,
+    save: *const u8,
+    save_len: usize,
+) -> *mut ArgentumSession {
+    let rom = slice::from_raw_parts(rom, rom_len);
+    let save = (!save.is_null()).then(|| slice::from_raw_parts(save, save_len).to_vec());
+
+    let mut gb = match Argentum::new(rom, Box::new(|_| {}), save) {
+        Ok(gb) => gb,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    gb.skip_bootrom();
+
+    Box::into_raw(Box::new(ArgentumSession {
+        gb,
+        pad: VirtualPad::with_default_layout(),
+        keys: 0,
+    }))
+}
+
+/// Stop a game and free the session.
+///
+/// # Safety
+///
+/// `session` must come from `argentum_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_free(session: *mut ArgentumSession) {
+    if !session.is_null() {
+        drop(Box::from_raw(session));
+    }
+}
+
+/// Emulate a frame with the keys and touches seen so far.
+///
+/// # Safety
+///
+/// `session` must be a live session from `argentum_new`.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_run_frame(session: *mut ArgentumSession) {
+    let session = &mut *session;
+
+    session.gb.set_keys(session.keys | session.pad.held_keys());
+    session.gb.execute_frame();
+}
+
+/// Get the width of the frames in pixels.
+///
+/// # Safety
+///
+/// `session` must be a live session from `argentum_new`.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_frame_width(session: *const ArgentumSession) -> usize {
+    (*session).gb.get_framebuffer().1.width
+}
+
+/// Get the height of the frames in pixels.
+///
+/// # Safety
+///
+/// `session` must be a live session from `argentum_new`.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_frame_height(session: *const ArgentumSession) -> usize {
+    (*session).gb.get_framebuffer().1.height
+}
+
+/// Copy the last complete frame as native endian RGB565,
+/// with `stride` pixels from the start of one row to the next.
+///
+/// # Safety
+///
+/// `session` must be a live session from `argentum_new`, and `pixels`
+/// must point to `stride * argentum_frame_height` writable pixels.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_frame_rgb565(
+    session: *const ArgentumSession,
+    pixels: *mut u16,
+    stride: usize,
+) {
+    let (framebuffer, info) = (*session).gb.get_framebuffer();
+    let stride = stride.max(info.width);
+    let pixels = slice::from_raw_parts_mut(pixels, stride * info.height);
+
+    write_rgb565(framebuffer, &info, pixels, stride);
+}
+
+/// Press keys on a physical controller, as `ArgentumKey` values OR'd together.
+///
+/// # Safety
+///
+/// `session` must be a live session from `argentum_new`.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_key_down(session: *mut ArgentumSession, keys: u8) {
+    (*session).keys |= keys;
+}
+
+/// Release keys on a physical controller, as `ArgentumKey` values OR'd together.
+///
+/// # Safety
+///
+/// `session` must be a live session from `argentum_new`.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_key_up(session: *mut ArgentumSession, keys: u8) {
+    (*session).keys &= !keys;
+}
+
+/// A finger touched the view at normalized coordinates.
+///
+/// # Safety
+///
+/// `session` must be a live session from `argentum_new`.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_touch_down(
+    session: *mut ArgentumSession,
+    id: i64,
+    x: f32,
+    y: f32,
+) {
+    (*session).pad.touch_down(id, x, y);
+}
+
+/// A finger moved to other normalized coordinates.
+///
+/// # Safety
+///
+/// `session` must be a live session from `argentum_new`.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_touch_move(
+    session: *mut ArgentumSession,
+    id: i64,
+    x: f32,
+    y: f32,
+) {
+    (*session).pad.touch_move(id, x, y);
+}
+
+/// A finger left the view.
+///
+/// # Safety
+///
+/// `session` must be a live session from `argentum_new`.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_touch_up(session: *mut ArgentumSession, id: i64) {
+    (*session).pad.touch_up(id);
+}
+
+/// Release every touch, for cancelled gestures and when the app is paused.
+///
+/// # Safety
+///
+/// `session` must be a live session from `argentum_new`.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_touch_release_all(session: *mut ArgentumSession) {
+    (*session).pad.release_all();
+}
+
+/// Remove every virtual button, to replace the default layout.
+///
+/// # Safety
+///
+/// `session` must be a live session from `argentum_new`.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_pad_clear(session: *mut ArgentumSession) {
+    (*session).pad.clear();
+}
+
+/// Add a virtual button on top of the others.
+///
+/// # Safety
+///
+/// `session` must be a live session from `argentum_new`.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_pad_add_button(
+    session: *mut ArgentumSession,
+    button: VirtualButton,
+) {
+    (*session).pad.add_button(button);
+}
+
+/// Get the number of virtual buttons.
+///
+/// # Safety
+///
+/// `session` must be a live session from `argentum_new`.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_pad_button_count(session: *const ArgentumSession) -> usize {
+    (*session).pad.buttons().len()
+}
+
+/// Get a virtual button to draw it, returns false if `index` is out of range.
+///
+/// # Safety
+///
+/// `session` must be a live session from `argentum_new`,
+/// and `button` must point to a writable `ArgentumVirtualButton`.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_pad_button(
+    session: *const ArgentumSession,
+    index: usize,
+    button: *mut VirtualButton,
+) -> bool {
+    match (*session).pad.buttons().get(index) {
+        Some(&found) => {
+            *button = found;
+            true
+        }
+
+        None => false,
+    }
+}
+
+/// Copy the cartridge RAM to save it, if it fits in `len` bytes.
+///
+/// Returns the size of the RAM, 0 if the cartridge has none.
+///
+/// # Safety
+///
+/// `session` must be a live session from `argentum_new`, and `out`
+/// must point to `len` writable bytes unless `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn argentum_export_ram(
+    session: *const ArgentumSession,
+    out: *mut u8,
+    len: usize,
+) -> usize {
+    let ram = match (*session).gb.export_ram() {
+        Some(ram) => ram,
+        None => return 0,
+    };
+
+    if ram.len() <= len {
+        ptr::copy_nonoverlapping(ram.as_ptr(), out, ram.len());
+    }
+
+    ram.len()
+}
@@ -0,0 +1,192 @@
+//! Virtual buttons for touch screens.
+//!
+//! Buttons are rectangles in coordinates normalized to the view, from
+//! 0.0 at the left and top to 1.0 at the right and bottom, so the same
+//! layout fits any screen size. Every finger presses the button it's
+//! on, sliding a finger from one button to another releases the first.
+
+use argentum_core::ArgentumKey;
+
+/// The most fingers tracked at once, further ones are ignored.
+pub const MAX_TOUCHES: usize = 10;
+
+/// A rectangle on the screen that holds keys down while touched.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct VirtualButton {
+    /// `ArgentumKey` values OR'd together, two directions make a diagonal.
+    pub keys: u8,
+
+    /// Left edge, normalized to the width of the view.
+    pub x: f32,
+
+    /// Top edge, normalized to the height of the view.
+    pub y: f32,
+
+    /// Width, normalized to the width of the view.
+    pub width: f32,
+
+    /// Height, normalized to the height of the view.
+    pub height: f32,
+}
+
+impl VirtualButton {
+    /// Check if the point is on the button.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+pub struct VirtualPad {
+    buttons: Vec<VirtualButton>,
+
+    /// The fingers on the screen, by the pointer ID the
+    /// platform gave them, and the keys they hold.
+    touches: [Option<(i64, u8)>; MAX_TOUCHES],
+}
+
+impl VirtualPad {
+    /// Create a new `VirtualPad` instance without any buttons.
+    pub fn new() -> Self {
+        Self {
+            buttons: Vec::new(),
+            touches: [None; MAX_TOUCHES],
+        }
+    }
+
+    /// Create a new `VirtualPad` instance with buttons laid
+    /// out for a phone held upright, below the picture.
+    pub fn with_default_layout() -> Self {
+        let mut pad = Self::new();
+
+        // A 3 x 3 grid for the D-pad, the corners press both directions.
+        let size = 0.11;
+        let (left, top) = (0.22 - size * 1.5, 0.72 - size * 1.5);
+
+        let horizontal = [ArgentumKey::Left as u8, 0, ArgentumKey::Right as u8];
+        let vertical = [ArgentumKey::Up as u8, 0, ArgentumKey::Down as u8];
+
+        for (row, &vertical) in vertical.iter().enumerate() {
+            for (column, &horizontal) in horizontal.iter().enumerate() {
+                let keys = vertical | horizontal;
+
+                if keys != 0 {
+                    pad.add_button(VirtualButton {
+                        keys,
+                        x: left + size * column as f32,
+                        y: top + size * row as f32,
+                        width: size,
+                        height: size,
+                    });
+                }
+            }
+        }
+
+        pad.add_button(VirtualButton {
+            keys: ArgentumKey::ButtonB as u8,
+            x: 0.58,
+            y: 0.70,
+            width: 0.16,
+            height: 0.10,
+        });
+
+        pad.add_button(VirtualButton {
+            keys: ArgentumKey::ButtonA as u8,
+            x: 0.78,
+            y: 0.64,
+            width: 0.16,
+            height: 0.10,
+        });
+
+        pad.add_button(VirtualButton {
+            keys: ArgentumKey::Select as u8,
+            x: 0.30,
+            y: 0.90,
+            width: 0.18,
+            height: 0.06,
+        });
+
+        pad.add_button(VirtualButton {
+            keys: ArgentumKey::Start as u8,
+            x: 0.52,
+            y: 0.90,
+            width: 0.18,
+            height: 0.06,
+        });
+
+        pad
+    }
+
+    /// Get the buttons, to draw them over the picture.
+    pub fn buttons(&self) -> &[VirtualButton] {
+        &self.buttons
+    }
+
+    /// Add a button, it goes on top of the ones added before.
+    pub fn add_button(&mut self, button: VirtualButton) {
+        self.buttons.push(button);
+    }
+
+    /// Remove every button, releasing the keys they hold.
+    pub fn clear(&mut self) {
+        self.buttons.clear();
+        self.release_all();
+    }
+
+    /// A finger touched the screen.
+    pub fn touch_down(&mut self, id: i64, x: f32, y: f32) {
+        let keys = self.keys_at(x, y);
+
+        if let Some(touch) = self.touches.iter_mut().find(|t| t.is_none()) {
+            *touch = Some((id, keys));
+        }
+    }
+
+    /// A finger moved, possibly onto another button.
+    pub fn touch_move(&mut self, id: i64, x: f32, y: f32) {
+        let keys = self.keys_at(x, y);
+
+        for (touch_id, touch_keys) in self.touches.iter_mut().flatten() {
+            if *touch_id == id {
+                *touch_keys = keys;
+            }
+        }
+    }
+
+    /// A finger left the screen.
+    pub fn touch_up(&mut self, id: i64) {
+        for touch in self.touches.iter_mut() {
+            if matches!(touch, Some((touch_id, _)) if *touch_id == id) {
+                *touch = None;
+            }
+        }
+    }
+
+    /// Forget every finger, for when the platform cancels a gesture.
+    pub fn release_all(&mut self) {
+        self.touches = [None; MAX_TOUCHES];
+    }
+
+    /// Get the keys held by all fingers, as `ArgentumKey` values OR'd together.
+    pub fn held_keys(&self) -> u8 {
+        self.touches
+            .iter()
+            .flatten()
+            .fold(0, |held, &(_, keys)| held | keys)
+    }
+
+    /// Get the keys of the topmost button under the point.
+    fn keys_at(&self, x: f32, y: f32) -> u8 {
+        self.buttons
+            .iter()
+            .rev()
+            .find(|button| button.contains(x, y))
+            .map_or(0, |button| button.keys)
+    }
+}
+
+impl Default for VirtualPad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,56 @@
+//! Frame rate measurement for on screen display.
+
+use std::time::{Duration, Instant};
+
+/// How often the displayed frame rate is updated.
+const UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Counts presented frames to display the frame rate.
+///
+/// The value only changes twice a second, so it stays
+/// readable when drawn on screen every frame.
+pub struct FpsCounter {
+    /// When the current measurement started.
+    start: Instant,
+
+    /// Frames presented since `start`.
+    frames: u32,
+
+    /// The frame rate of the last finished measurement.
+    fps: f64,
+}
+
+impl FpsCounter {
+    /// Create a new `FpsCounter` instance.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            frames: 0,
+            fps: 0.0,
+        }
+    }
+
+    /// Record that a frame was presented.
+    pub fn tick(&mut self) {
+        self.frames += 1;
+
+        let elapsed = self.start.elapsed();
+
+        if elapsed >= UPDATE_INTERVAL {
+            self.fps = self.frames as f64 / elapsed.as_secs_f64();
+            self.start = Instant::now();
+            self.frames = 0;
+        }
+    }
+
+    /// Get the most recently measured frame rate.
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+}
+
+impl Default for FpsCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
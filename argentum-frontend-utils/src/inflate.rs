@@ -0,0 +1,350 @@
+//! A small decoder for zlib streams (RFC 1950 and 1951), enough
+//! to read the PNGs test suites ship their expected screenshots as.
+
+/// Base lengths of the length symbols 257 - 285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+
+/// Extra bits of the length symbols 257 - 285.
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// Base distances of the distance symbols.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+
+/// Extra bits of the distance symbols.
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// The order the code length code lengths are stored in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Reads bits from the least significant end of every byte first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    bit: u8,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, count: u8) -> Option<u32> {
+        let mut value = 0;
+
+        for i in 0..count {
+            let byte = *self.data.get(self.position)?;
+
+            value |= (((byte >> self.bit) & 0x01) as u32) << i;
+
+            self.bit += 1;
+
+            if self.bit == 8 {
+                self.bit = 0;
+                self.position += 1;
+            }
+        }
+
+        Some(value)
+    }
+
+    /// Skip to the start of the next byte.
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.position += 1;
+        }
+    }
+}
+
+/// A canonical Huffman code.
+struct Huffman {
+    /// How many codes there are of every length.
+    counts: [u16; 16],
+
+    /// The symbols, ordered by their codes.
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; 16];
+
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+
+        counts[0] = 0;
+
+        let mut offsets = [0; 16];
+
+        for length in 1..16 {
+            offsets[length] = offsets[length - 1] + counts[length - 1];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Option<u16> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+
+        for length in 1..16 {
+            code |= reader.bits(1)? as i32;
+
+            let count = self.counts[length] as i32;
+
+            if code - first < count {
+                return self.symbols.get((index + code - first) as usize).copied();
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        None
+    }
+}
+
+/// The fixed codes of block type 1.
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0; 288];
+
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+/// Read the codes of a block of type 2.
+fn dynamic_codes(reader: &mut BitReader) -> Option<(Huffman, Huffman)> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0; 19];
+
+    for &index in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+
+    let code_length_code = Huffman::new(&code_lengths);
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+
+    while lengths.len() < literal_count + distance_count {
+        let symbol = code_length_code.decode(reader)?;
+
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last()?, reader.bits(2)? + 3),
+            17 => (0, reader.bits(3)? + 3),
+            18 => (0, reader.bits(7)? + 11),
+            _ => return None,
+        };
+
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+
+    if lengths.len() != literal_count + distance_count {
+        return None;
+    }
+
+    Some((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+/// Decode the symbols of a compressed block.
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Option<()> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+
+            256 => return Some(()),
+
+            _ => {
+                let index = symbol - 257;
+
+                let length = *LENGTH_BASE.get(index)? as usize
+                    + reader.bits(*LENGTH_EXTRA.get(index)?)? as usize;
+
+                let index = distances.decode(reader)? as usize;
+
+                let distance = *DISTANCE_BASE.get(index)? as usize
+                    + reader.bits(*DISTANCE_EXTRA.get(index)?)? as usize;
+
+                if distance > output.len() {
+                    return None;
+                }
+
+                // The copy can overlap what it writes, byte by byte it is.
+                for _ in 0..length {
+                    output.push(output[output.len() - distance]);
+                }
+            }
+        }
+    }
+}
+
+/// Decompress a zlib stream, `None` if it is malformed.
+pub fn inflate_zlib(data: &[u8]) -> Option<Vec<u8>> {
+    // Compression method 8 is deflate, preset dictionaries aren't used by PNG.
+    if data.len() < 2 || data[0] & 0x0F != 8 || data[1] & 0x20 != 0 {
+        return None;
+    }
+
+    let mut reader = BitReader {
+        data: &data[2..],
+        position: 0,
+        bit: 0,
+    };
+
+    let mut output = Vec::new();
+
+    loop {
+        let last = reader.bits(1)? == 1;
+
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+
+                let start = reader.position;
+                let header = reader.data.get(start..start + 4)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                let nlen = u16::from_le_bytes([header[2], header[3]]);
+
+                if len != !nlen {
+                    return None;
+                }
+
+                let block = reader.data.get(start + 4..start + 4 + len as usize)?;
+
+                output.extend_from_slice(block);
+                reader.position = start + 4 + len as usize;
+            }
+
+            1 => {
+                let (literals, distances) = fixed_codes();
+
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+
+            _ => return None,
+        }
+
+        if last {
+            return Some(output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The text both fixtures were compressed from, by zlib.
+    fn text() -> Vec<u8> {
+        (0..20)
+            .flat_map(|i| format!("line {} of the test\n", i).into_bytes())
+            .collect()
+    }
+
+    /// Level 9 with the `Z_FIXED` strategy, a single fixed Huffman block.
+    const FIXED: &[u8] = &[
+        0x78, 0x01, 0xCB, 0xC9, 0xCC, 0x4B, 0x55, 0x30, 0x50, 0xC8, 0x4F, 0x53, 0x28, 0xC9, 0x48,
+        0x55, 0x28, 0x49, 0x2D, 0x2E, 0xE1, 0xCA, 0x01, 0x09, 0x19, 0x62, 0x0A, 0x19, 0x61, 0x0A,
+        0x19, 0x63, 0x0A, 0x99, 0x60, 0x0A, 0x99, 0x62, 0x0A, 0x99, 0x61, 0x0A, 0x99, 0x63, 0x0A,
+        0x59, 0x60, 0x0A, 0x59, 0x62, 0x71, 0x2A, 0x36, 0xE7, 0x63, 0x71, 0xBF, 0x21, 0x16, 0x0F,
+        0x18, 0x62, 0xF1, 0x81, 0x21, 0x16, 0x2F, 0x18, 0x62, 0xF1, 0x83, 0x21, 0x16, 0x4F, 0x18,
+        0x62, 0xF1, 0x85, 0x21, 0x16, 0x6F, 0x18, 0xA2, 0xFA, 0x03, 0x00, 0x3E, 0xD3, 0x7E, 0xA5,
+    ];
+
+    /// Level 9 with the default strategy, a single dynamic Huffman block.
+    const DYNAMIC: &[u8] = &[
+        0x78, 0xDA, 0x6D, 0xCF, 0xBB, 0x0D, 0x80, 0x30, 0x10, 0x04, 0xD1, 0x9C, 0x2A, 0xAE, 0x04,
+        0x96, 0x3F, 0x05, 0x19, 0x61, 0xC9, 0x82, 0x80, 0xEB, 0x5F, 0x88, 0x10, 0xED, 0xA4, 0x2F,
+        0x9A, 0x69, 0xF5, 0x2A, 0xD1, 0xC7, 0x7D, 0x44, 0x9E, 0x25, 0xB2, 0x3C, 0xD9, 0xB5, 0x8F,
+        0xE4, 0x34, 0x38, 0x8D, 0x4E, 0x93, 0xD3, 0xEC, 0xB4, 0x38, 0xAD, 0x4E, 0x9B, 0xD3, 0x0E,
+        0xA9, 0x94, 0x0F, 0xFD, 0x82, 0x01, 0xC1, 0x81, 0x60, 0x41, 0xF0, 0x20, 0x98, 0x10, 0x5C,
+        0x08, 0x36, 0xF4, 0xFF, 0x78, 0x01, 0x3E, 0xD3, 0x7E, 0xA5,
+    ];
+
+    #[test]
+    fn fixed_huffman() {
+        assert_eq!((FIXED[2] >> 1) & 0x03, 1);
+        assert_eq!(inflate_zlib(FIXED), Some(text()));
+    }
+
+    #[test]
+    fn dynamic_huffman() {
+        assert_eq!((DYNAMIC[2] >> 1) & 0x03, 2);
+        assert_eq!(inflate_zlib(DYNAMIC), Some(text()));
+    }
+
+    #[test]
+    fn stored_blocks() {
+        // "ab" in a block that isn't the last, then "c".
+        let data = [
+            0x78, 0x01, 0x00, 0x02, 0x00, 0xFD, 0xFF, b'a', b'b', 0x01, 0x01, 0x00, 0xFE, 0xFF,
+            b'c',
+        ];
+
+        assert_eq!(inflate_zlib(&data), Some(b"abc".to_vec()));
+    }
+
+    #[test]
+    fn malformed_streams() {
+        // Truncated, a preset dictionary, a mismatched stored length and block type 3.
+        assert_eq!(inflate_zlib(&FIXED[..FIXED.len() / 2]), None);
+        assert_eq!(
+            inflate_zlib(&[0x78, 0x20, 0x01, 0x00, 0x00, 0xFF, 0xFF]),
+            None
+        );
+        assert_eq!(
+            inflate_zlib(&[0x78, 0x01, 0x01, 0x01, 0x00, 0xFF, 0xFF, 0x00]),
+            None
+        );
+        assert_eq!(inflate_zlib(&[0x78, 0x01, 0x07]), None);
+    }
+}
@@ -0,0 +1,25 @@
+//! Helpers shared between Argentum frontends.
+//!
+//! The core never sleeps or otherwise paces itself, `execute_frame`
+//! always returns as soon as a frame is done. Frontends that need
+//! to run in real time can use the helpers in here, while frontends
+//! that are paced by their host (wasm, libretro) can skip them.
+
+mod fps_counter;
+mod inflate;
+mod limiter;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod png;
+mod rate_control;
+mod rewind;
+mod state_stack;
+
+pub use fps_counter::FpsCounter;
+pub use limiter::{FpsLimiter, FRAME_RATE};
+#[cfg(feature = "metrics")]
+pub use metrics::{serve_metrics, Metrics};
+pub use png::{crc32, decode_png, encode_indexed_png, encode_rgb_png, InvalidPng, RgbImage};
+pub use rate_control::RateControl;
+pub use rewind::RewindBuffer;
+pub use state_stack::StateStack;
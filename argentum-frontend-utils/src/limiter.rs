@@ -0,0 +1,93 @@
+//! Frame pacing.
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// T-cycles in a frame.
+const CYCLES_PER_FRAME: u128 = 70224;
+
+/// T-cycles per second.
+const CPU_CLOCK: u128 = 4194304;
+
+/// The frame rate of the Game Boy, roughly 59.7275 Hz.
+pub const FRAME_RATE: f64 = CPU_CLOCK as f64 / CYCLES_PER_FRAME as f64;
+
+/// Sleeping is only accurate to a millisecond or two on most
+/// platforms, the last stretch before a deadline is spun instead.
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+/// If we fall behind by more than this many frames, give up on
+/// catching up and start pacing from the current time again.
+const MAX_LAG_FRAMES: u128 = 4;
+
+/// Paces a frontend to the frame rate of the Game Boy.
+///
+/// Deadlines are computed from the time the limiter was started
+/// rather than from the previous frame, so rounding errors and
+/// late wakeups don't accumulate into drift.
+pub struct FpsLimiter {
+    /// The point in time frame 0 started at.
+    start: Instant,
+
+    /// Frames waited for since `start`.
+    frames: u128,
+}
+
+impl FpsLimiter {
+    /// Create a new `FpsLimiter` instance.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            frames: 0,
+        }
+    }
+
+    /// Start pacing from the current time again, for example
+    /// after the emulator was paused.
+    pub fn reset(&mut self) {
+        self.start = Instant::now();
+        self.frames = 0;
+    }
+
+    /// Get the point in time the next frame is due at.
+    pub fn next_deadline(&self) -> Instant {
+        let nanos = (self.frames + 1) * CYCLES_PER_FRAME * 1_000_000_000 / CPU_CLOCK;
+
+        self.start + Duration::from_nanos(nanos as u64)
+    }
+
+    /// Block until the next frame is due.
+    pub fn wait(&mut self) {
+        let deadline = self.next_deadline();
+        let now = Instant::now();
+
+        if now > deadline {
+            let lag = (now - deadline).as_nanos() * CPU_CLOCK / (CYCLES_PER_FRAME * 1_000_000_000);
+
+            if lag >= MAX_LAG_FRAMES {
+                self.reset();
+                return;
+            }
+        } else {
+            let remaining = deadline - now;
+
+            if remaining > SPIN_THRESHOLD {
+                thread::sleep(remaining - SPIN_THRESHOLD);
+            }
+
+            while Instant::now() < deadline {
+                std::hint::spin_loop();
+            }
+        }
+
+        self.frames += 1;
+    }
+}
+
+impl Default for FpsLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
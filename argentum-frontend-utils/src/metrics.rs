@@ -0,0 +1,214 @@
+//! Runtime metrics, served in the Prometheus text format.
+
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    io::{self, Read, Write as _},
+    net::{TcpListener, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::FRAME_RATE;
+
+/// Frame times are kept for roughly the last ten seconds.
+const WINDOW_FRAMES: usize = 600;
+
+/// Collects performance data of a running frontend.
+pub struct Metrics {
+    /// Time between the most recent frames.
+    frame_times: VecDeque<Duration>,
+
+    /// When the previous frame finished.
+    last_frame: Option<Instant>,
+
+    /// Frames presented since startup.
+    frames_total: u64,
+
+    /// Times the audio queue ran dry.
+    audio_underruns_total: u64,
+}
+
+impl Metrics {
+    /// Create a new `Metrics` instance.
+    pub fn new() -> Self {
+        Self {
+            frame_times: VecDeque::with_capacity(WINDOW_FRAMES),
+            last_frame: None,
+            frames_total: 0,
+            audio_underruns_total: 0,
+        }
+    }
+
+    /// Record that a frame was presented.
+    pub fn record_frame(&mut self) {
+        let now = Instant::now();
+
+        if let Some(last_frame) = self.last_frame {
+            if self.frame_times.len() == WINDOW_FRAMES {
+                self.frame_times.pop_front();
+            }
+
+            self.frame_times.push_back(now - last_frame);
+        }
+
+        self.last_frame = Some(now);
+        self.frames_total += 1;
+    }
+
+    /// Forget the time of the previous frame, for example after
+    /// being paused, so the pause doesn't count as a long frame.
+    pub fn reset_frame_timer(&mut self) {
+        self.last_frame = None;
+    }
+
+    /// Record that the audio queue ran dry. The queue is expected to be
+    /// empty right after startup or a pause, so those aren't counted.
+    pub fn record_audio_underrun(&mut self) {
+        if self.last_frame.is_some() {
+            self.audio_underruns_total += 1;
+        }
+    }
+
+    /// Frames per second over the recent frames.
+    pub fn fps(&self) -> f64 {
+        let total: Duration = self.frame_times.iter().sum();
+
+        if total == Duration::from_secs(0) {
+            0.0
+        } else {
+            self.frame_times.len() as f64 / total.as_secs_f64()
+        }
+    }
+
+    /// Emulation speed relative to real hardware, 1.0 is full speed.
+    pub fn speed(&self) -> f64 {
+        self.fps() / FRAME_RATE
+    }
+
+    /// Get the frame time below which the given fraction of recent frames fall.
+    pub fn frame_time_quantile(&self, quantile: f64) -> Duration {
+        let mut times: Vec<Duration> = self.frame_times.iter().copied().collect();
+
+        if times.is_empty() {
+            return Duration::from_secs(0);
+        }
+
+        times.sort_unstable();
+
+        let index = ((times.len() - 1) as f64 * quantile).round() as usize;
+
+        times[index]
+    }
+
+    /// Render the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let total: Duration = self.frame_times.iter().sum();
+
+        write_metric(&mut out, "frames_total", "counter", "Frames presented.");
+        let _ = writeln!(out, "argentum_frames_total {}", self.frames_total);
+
+        write_metric(
+            &mut out,
+            "audio_underruns_total",
+            "counter",
+            "Times the audio queue ran dry.",
+        );
+        let _ = writeln!(
+            out,
+            "argentum_audio_underruns_total {}",
+            self.audio_underruns_total
+        );
+
+        write_metric(&mut out, "fps", "gauge", "Frames per second.");
+        let _ = writeln!(out, "argentum_fps {:.3}", self.fps());
+
+        write_metric(
+            &mut out,
+            "emulation_speed",
+            "gauge",
+            "Speed relative to real hardware.",
+        );
+        let _ = writeln!(out, "argentum_emulation_speed {:.4}", self.speed());
+
+        write_metric(
+            &mut out,
+            "frame_time_seconds",
+            "summary",
+            "Time between frames.",
+        );
+
+        for quantile in [0.5, 0.9, 0.99].iter() {
+            let time = self.frame_time_quantile(*quantile).as_secs_f64();
+
+            let _ = writeln!(
+                out,
+                "argentum_frame_time_seconds{{quantile=\"{}\"}} {:.6}",
+                quantile, time
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "argentum_frame_time_seconds_sum {:.6}",
+            total.as_secs_f64()
+        );
+        let _ = writeln!(
+            out,
+            "argentum_frame_time_seconds_count {}",
+            self.frame_times.len()
+        );
+
+        out
+    }
+}
+
+/// Write the help and type lines of a metric.
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP argentum_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE argentum_{} {}", name, kind);
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Serve the metrics over HTTP on a background thread.
+///
+/// Every request is answered with the metrics, regardless of its path.
+pub fn serve_metrics<A: ToSocketAddrs>(address: A, metrics: Arc<Mutex<Metrics>>) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+
+            // The request itself doesn't matter, but it has to be read
+            // or some clients see the connection reset.
+            let mut request = [0; 1024];
+
+            let _ = stream.set_read_timeout(Some(Duration::from_millis(100)));
+            let _ = stream.read(&mut request);
+
+            let body = metrics.lock().unwrap().render();
+
+            let mut response = String::from("HTTP/1.1 200 OK\r\n");
+
+            response.push_str("Content-Type: text/plain; version=0.0.4\r\n");
+            response.push_str("Connection: close\r\n");
+            let _ = write!(response, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    Ok(())
+}
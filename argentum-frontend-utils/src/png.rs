@@ -0,0 +1,469 @@
+//! A minimal PNG encoder for palette based and RGB images, and a
+//! decoder for the expected screenshots of test suites.
+//!
+//! Image data is stored without compression, which keeps the encoder
+//! tiny. The images frontends write (screenshots, tile sheets) are
+//! small enough that this doesn't matter.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::inflate::inflate_zlib;
+
+/// The largest amount of data a stored deflate block can hold.
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+/// Calculate the CRC-32 (IEEE) of the data, as used by PNG and ZIP.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+
+    for &byte in data {
+        crc ^= byte as u32;
+
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a = 1u32;
+    let mut b = 0u32;
+
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+/// Append a chunk, its length and CRC included.
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = png.len();
+
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+
+    let crc = crc32(&png[start..]);
+
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Start a PNG with its signature and header chunk, for
+/// an image with a bit depth of 8 and the given colour type.
+fn start_png(width: usize, height: usize, colour_type: u8) -> Vec<u8> {
+    let mut png = b"\x89PNG\r\n\x1A\n".to_vec();
+
+    // No interlacing.
+    let mut header = Vec::new();
+
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, colour_type, 0, 0, 0]);
+
+    write_chunk(&mut png, b"IHDR", &header);
+
+    png
+}
+
+/// Append the image data and the end of the PNG. Every
+/// scanline of `raw` starts with its filter type.
+fn finish_png(png: &mut Vec<u8>, raw: &[u8]) {
+    // A zlib stream made of stored deflate blocks.
+    let mut zlib = vec![0x78, 0x01];
+    let block_count = raw.chunks(MAX_STORED_BLOCK).count();
+
+    for (i, block) in raw.chunks(MAX_STORED_BLOCK).enumerate() {
+        let len = block.len() as u16;
+
+        zlib.push((i + 1 == block_count) as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+
+    zlib.extend_from_slice(&adler32(raw).to_be_bytes());
+
+    write_chunk(png, b"IDAT", &zlib);
+    write_chunk(png, b"IEND", &[]);
+}
+
+/// Encode an image with one byte per pixel, each an index into
+/// `palette`, which holds up to 256 colours as 0xRRGGBB values.
+pub fn encode_indexed_png(width: usize, height: usize, pixels: &[u8], palette: &[u32]) -> Vec<u8> {
+    assert_eq!(
+        pixels.len(),
+        width * height,
+        "pixel count doesn't match the size"
+    );
+    assert!(palette.len() <= 256, "too many palette colours");
+
+    // Colour type 3 is indexed.
+    let mut png = start_png(width, height, 3);
+
+    let colours: Vec<u8> = palette
+        .iter()
+        .flat_map(|colour| colour.to_be_bytes()[1..].to_vec())
+        .collect();
+
+    write_chunk(&mut png, b"PLTE", &colours);
+
+    // Every scanline starts with its filter type, 0 is none.
+    let mut raw = Vec::with_capacity((width + 1) * height);
+
+    for row in pixels.chunks(width) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    finish_png(&mut png, &raw);
+
+    png
+}
+
+/// Encode an image with three bytes per pixel, red, green and blue,
+/// like the core's framebuffer. Rows start `pitch` bytes apart.
+pub fn encode_rgb_png(width: usize, height: usize, pitch: usize, pixels: &[u8]) -> Vec<u8> {
+    assert!(pitch >= width * 3, "pitch is shorter than a row");
+    assert!(
+        pixels.len() >= pitch * height,
+        "pixel count doesn't match the size"
+    );
+
+    // Colour type 2 is RGB.
+    let mut png = start_png(width, height, 2);
+
+    // Every scanline starts with its filter type, 0 is none.
+    let mut raw = Vec::with_capacity((width * 3 + 1) * height);
+
+    for row in pixels.chunks(pitch).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(&row[..width * 3]);
+    }
+
+    finish_png(&mut png, &raw);
+
+    png
+}
+
+/// An image with three bytes per pixel, red, green and blue.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RgbImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+/// The error returned for PNGs that are malformed, interlaced
+/// or otherwise not supported by `decode_png`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidPng;
+
+impl Display for InvalidPng {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "the PNG is malformed or not supported")
+    }
+}
+
+impl std::error::Error for InvalidPng {}
+
+/// Predict a byte with the Paeth filter.
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+
+    let distance_left = (estimate - left as i16).abs();
+    let distance_up = (estimate - up as i16).abs();
+    let distance_up_left = (estimate - up_left as i16).abs();
+
+    if distance_left <= distance_up && distance_left <= distance_up_left {
+        left
+    } else if distance_up <= distance_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// Undo the filters of every scanline, removing the filter bytes.
+fn unfilter(raw: &[u8], stride: usize, height: usize, bytes_per_pixel: usize) -> Option<Vec<u8>> {
+    let mut data = vec![0u8; stride * height];
+
+    for y in 0..height {
+        let line = raw.get(y * (stride + 1)..(y + 1) * (stride + 1))?;
+        let filter = line[0];
+
+        for x in 0..stride {
+            let left = if x >= bytes_per_pixel {
+                data[y * stride + x - bytes_per_pixel]
+            } else {
+                0
+            };
+
+            let up = if y > 0 { data[(y - 1) * stride + x] } else { 0 };
+
+            let up_left = if y > 0 && x >= bytes_per_pixel {
+                data[(y - 1) * stride + x - bytes_per_pixel]
+            } else {
+                0
+            };
+
+            let prediction = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return None,
+            };
+
+            data[y * stride + x] = line[x + 1].wrapping_add(prediction);
+        }
+    }
+
+    Some(data)
+}
+
+/// Decode a non-interlaced PNG of any colour type, dropping the alpha.
+pub fn decode_png(png: &[u8]) -> Result<RgbImage, InvalidPng> {
+    if png.get(..8) != Some(&b"\x89PNG\r\n\x1A\n"[..]) {
+        return Err(InvalidPng);
+    }
+
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut compressed = Vec::new();
+    let mut offset = 8;
+
+    while let Some(length) = png.get(offset..offset + 4) {
+        let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize;
+        let kind = png.get(offset + 4..offset + 8).ok_or(InvalidPng)?;
+        let data = png.get(offset + 8..offset + 8 + length).ok_or(InvalidPng)?;
+
+        match kind {
+            b"IHDR" if data.len() == 13 => header = Some(data),
+            b"PLTE" => palette = data.to_vec(),
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+
+        // Skip the data and the CRC.
+        offset += 12 + length;
+    }
+
+    let header = header.ok_or(InvalidPng)?;
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let bit_depth = header[8] as usize;
+    let colour_type = header[9];
+
+    // Only the interlacing is checked, the rest has a single valid value.
+    if header[12] != 0 {
+        return Err(InvalidPng);
+    }
+
+    let channels = match colour_type {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => return Err(InvalidPng),
+    };
+
+    if ![1, 2, 4, 8, 16].contains(&bit_depth) {
+        return Err(InvalidPng);
+    }
+
+    let bits_per_pixel = channels * bit_depth;
+    let stride = (width * bits_per_pixel).div_ceil(8);
+
+    let raw = inflate_zlib(&compressed).ok_or(InvalidPng)?;
+    let data =
+        unfilter(&raw, stride, height, bits_per_pixel.div_ceil(8).max(1)).ok_or(InvalidPng)?;
+
+    // Read a sample, scaled to 8 bits except for palette indices.
+    let sample = |y: usize, index: usize| -> u8 {
+        let line = &data[y * stride..(y + 1) * stride];
+
+        match bit_depth {
+            16 => line[index * 2],
+            8 => line[index],
+
+            _ => {
+                let bit = index * bit_depth;
+                let max = (1 << bit_depth) - 1;
+                let value = (line[bit / 8] >> (8 - bit_depth - bit % 8)) & max;
+
+                if colour_type == 3 {
+                    value
+                } else {
+                    (value as u16 * 255 / max as u16) as u8
+                }
+            }
+        }
+    };
+
+    let mut pixels = Vec::with_capacity(width * height * 3);
+
+    for y in 0..height {
+        for x in 0..width {
+            match colour_type {
+                0 | 4 => {
+                    let gray = sample(y, x * channels);
+
+                    pixels.extend_from_slice(&[gray, gray, gray]);
+                }
+
+                3 => {
+                    let index = sample(y, x) as usize * 3;
+                    let colour = palette.get(index..index + 3).ok_or(InvalidPng)?;
+
+                    pixels.extend_from_slice(colour);
+                }
+
+                _ => {
+                    for channel in 0..3 {
+                        pixels.push(sample(y, x * channels + channel));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(RgbImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The colour of every pixel of the fixtures.
+    fn pixel(x: usize, y: usize) -> [u8; 3] {
+        [
+            ((x * 40 + y * 7) % 256) as u8,
+            ((y * 50 + x * 3) % 256) as u8,
+            ((x * y * 13) % 256) as u8,
+        ]
+    }
+
+    /// An 8x10 RGB image whose rows use the filter types 0 - 4 in turn,
+    /// made by zlib with the `Z_FIXED` strategy.
+    const FIXED_PNG: &[u8] = &[
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x0A, 0x08, 0x02, 0x00, 0x00, 0x00, 0x06,
+        0xA5, 0x88, 0xD7, 0x00, 0x00, 0x00, 0xAB, 0x49, 0x44, 0x41, 0x54, 0x78, 0x01, 0x63, 0x60,
+        0x60, 0x60, 0xD0, 0x60, 0x66, 0x08, 0x60, 0x63, 0xA8, 0xE0, 0x64, 0x58, 0xC0, 0xC3, 0x70,
+        0x82, 0x9F, 0xE1, 0x83, 0x10, 0x83, 0x84, 0x28, 0x03, 0x23, 0xBB, 0x11, 0x50, 0x82, 0x17,
+        0x13, 0x31, 0x01, 0x25, 0xD8, 0x8D, 0x78, 0xD9, 0x8D, 0xA4, 0xD8, 0x8D, 0xD4, 0xD9, 0x8D,
+        0x4C, 0xD8, 0x8D, 0x1C, 0xD9, 0x8D, 0xFC, 0xD8, 0x8D, 0xA2, 0x99, 0xF9, 0x52, 0x18, 0x24,
+        0xA4, 0xA5, 0x24, 0xA4, 0x15, 0x25, 0xA4, 0xD5, 0x25, 0xA4, 0xF5, 0x24, 0xA4, 0x4D, 0x24,
+        0xA4, 0xAD, 0x25, 0xA4, 0x1D, 0x59, 0x40, 0x3A, 0x98, 0x79, 0xD9, 0x99, 0xA5, 0xD8, 0x99,
+        0xD5, 0xD9, 0x99, 0x4D, 0x60, 0x28, 0x9A, 0x41, 0xF9, 0x17, 0x83, 0xF7, 0x5F, 0xC7, 0x62,
+        0x86, 0xA6, 0xD9, 0xCC, 0x87, 0x0F, 0xB3, 0xB1, 0xBC, 0xE6, 0x74, 0x15, 0xE6, 0x69, 0xB3,
+        0xE6, 0x3F, 0xCE, 0xA8, 0xA5, 0x03, 0xB4, 0xDC, 0x0F, 0x13, 0xE1, 0xB6, 0x5C, 0x21, 0x91,
+        0x01, 0x62, 0x9B, 0x84, 0xF4, 0x09, 0x09, 0x69, 0x3F, 0x09, 0xE9, 0x50, 0x09, 0xE9, 0x68,
+        0x09, 0xE9, 0x24, 0x4C, 0xCB, 0x1D, 0xD9, 0x99, 0xFD, 0x80, 0x96, 0x03, 0x00, 0xAE, 0x49,
+        0x23, 0x9F, 0x2F, 0x5B, 0x97, 0x47, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE,
+        0x42, 0x60, 0x82,
+    ];
+
+    /// The same image compressed with dynamic Huffman codes.
+    const DYNAMIC_PNG: &[u8] = &[
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x0A, 0x08, 0x02, 0x00, 0x00, 0x00, 0x06,
+        0xA5, 0x88, 0xD7, 0x00, 0x00, 0x00, 0xAA, 0x49, 0x44, 0x41, 0x54, 0x78, 0xDA, 0x75, 0xCD,
+        0xA1, 0x0A, 0xC2, 0x50, 0x14, 0xC6, 0xF1, 0x6F, 0xDE, 0xB1, 0xC3, 0x60, 0x73, 0xA0, 0x08,
+        0x87, 0x9D, 0xA2, 0x18, 0x26, 0x22, 0x86, 0xC3, 0x9A, 0xE9, 0x06, 0x93, 0x70, 0xB1, 0x98,
+        0x96, 0x14, 0xDF, 0xC0, 0x62, 0x36, 0xF8, 0x12, 0x16, 0xDF, 0x65, 0xC1, 0xBD, 0x8B, 0xC5,
+        0x2C, 0x08, 0x5E, 0x11, 0xD3, 0x10, 0xFE, 0xED, 0xE3, 0xE3, 0x07, 0x00, 0x13, 0x83, 0x75,
+        0x84, 0x63, 0x8C, 0x6B, 0x82, 0x26, 0xC3, 0xA3, 0x07, 0x1E, 0x20, 0x20, 0xF5, 0x43, 0xDA,
+        0xAE, 0xE3, 0x07, 0xD2, 0x94, 0x34, 0x27, 0x2D, 0x48, 0x4B, 0x52, 0x4B, 0xEA, 0x48, 0x2B,
+        0xD3, 0xDD, 0x83, 0x25, 0x67, 0x19, 0xB1, 0x14, 0x2C, 0x73, 0x96, 0x92, 0x65, 0xC1, 0x62,
+        0xC3, 0xCF, 0xC3, 0xA4, 0x64, 0x72, 0x32, 0x05, 0x99, 0xF2, 0x57, 0x85, 0xF1, 0x13, 0xAB,
+        0x97, 0x3D, 0xE0, 0x74, 0x31, 0x75, 0x1D, 0x85, 0xF7, 0x78, 0xD9, 0x4F, 0xCE, 0x8B, 0xEC,
+        0x16, 0x4C, 0x67, 0x1E, 0x77, 0xED, 0xFE, 0xE3, 0xC3, 0x2D, 0xBE, 0x1A, 0x4B, 0xC3, 0xE2,
+        0x58, 0x36, 0x2C, 0x15, 0xCB, 0xAE, 0x8D, 0x5B, 0x32, 0xCE, 0xE3, 0x6F, 0xAE, 0x49, 0x23,
+        0x9F, 0xE0, 0xA8, 0x4C, 0xD8, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42,
+        0x60, 0x82,
+    ];
+
+    fn expected() -> RgbImage {
+        RgbImage {
+            width: 8,
+            height: 10,
+            pixels: (0..10)
+                .flat_map(|y| (0..8).flat_map(move |x| pixel(x, y)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn decodes_every_filter() {
+        assert_eq!(decode_png(FIXED_PNG), Ok(expected()));
+        assert_eq!(decode_png(DYNAMIC_PNG), Ok(expected()));
+    }
+
+    #[test]
+    fn rgb_round_trip() {
+        // Two bytes of padding at the end of every row.
+        let pitch = 5 * 3 + 2;
+        let mut pixels = vec![0xEE; pitch * 4];
+
+        for y in 0..4 {
+            for x in 0..5 {
+                pixels[y * pitch + x * 3..][..3].copy_from_slice(&pixel(x, y));
+            }
+        }
+
+        let image = decode_png(&encode_rgb_png(5, 4, pitch, &pixels)).unwrap();
+
+        assert_eq!((image.width, image.height), (5, 4));
+
+        for y in 0..4 {
+            assert_eq!(
+                image.pixels[y * 15..][..15],
+                pixels[y * pitch..][..15],
+                "row {}",
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn indexed_round_trip() {
+        let palette = [0xE0F8D0, 0x88C070, 0x346856, 0x081820];
+        let pixels: Vec<u8> = (0..12).map(|i| (i % 4) as u8).collect();
+
+        let image = decode_png(&encode_indexed_png(4, 3, &pixels, &palette)).unwrap();
+        let expected: Vec<u8> = pixels
+            .iter()
+            .flat_map(|&i| palette[i as usize].to_be_bytes()[1..].to_vec())
+            .collect();
+
+        assert_eq!(image.pixels, expected);
+    }
+
+    #[test]
+    fn rejects_malformed_pngs() {
+        assert_eq!(decode_png(b"not a png"), Err(InvalidPng));
+        assert_eq!(decode_png(&FIXED_PNG[..40]), Err(InvalidPng));
+
+        // An unknown filter type on the only row, after the zlib
+        // header and the header of the stored block.
+        let mut png = encode_rgb_png(1, 1, 3, &[1, 2, 3]);
+        let idat = png.windows(4).position(|kind| kind == b"IDAT").unwrap();
+
+        png[idat + 4 + 7] = 5;
+
+        assert_eq!(decode_png(&png), Err(InvalidPng));
+    }
+}
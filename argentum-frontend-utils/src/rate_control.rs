@@ -0,0 +1,36 @@
+//! Dynamic rate control for audio.
+
+/// Keeps an audio queue at a steady fill level.
+///
+/// The emulator and the audio device are driven by different
+/// clocks, so even with a perfect frame limiter the queue slowly
+/// drains or grows. This computes a resample ratio that nudges
+/// the amount of generated samples up when the queue is running
+/// low, and down when it is filling up.
+pub struct RateControl {
+    /// The fill level to aim for, in any unit.
+    target: f64,
+
+    /// The largest deviation from a ratio of 1.0.
+    max_delta: f64,
+}
+
+impl RateControl {
+    /// Create a new `RateControl` instance.
+    ///
+    /// A `max_delta` of 0.005 is a good default,
+    /// bigger values become audible as pitch changes.
+    pub fn new(target: usize, max_delta: f64) -> Self {
+        Self {
+            target: target as f64,
+            max_delta,
+        }
+    }
+
+    /// Get the ratio to resample by for the current fill level.
+    pub fn ratio(&self, fill: usize) -> f64 {
+        let error = ((self.target - fill as f64) / self.target).clamp(-1.0, 1.0);
+
+        1.0 + self.max_delta * error
+    }
+}
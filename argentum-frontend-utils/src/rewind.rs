@@ -0,0 +1,374 @@
+//! Rewind history within a memory budget.
+//!
+//! Every frame's state is pushed, but only every `KEYFRAME_INTERVAL`th
+//! one is stored whole. The states in between are stored as the runs
+//! of bytes that differ from the keyframe before them, which are small
+//! as most of the state doesn't change from frame to frame.
+//!
+//! Once the history outgrows its budget, every other state in its
+//! older half is dropped. The older a state, the more often it went
+//! through this, so recent frames can be rewound one by one while
+//! older ones are kept further and further apart, and the history
+//! reaches back much longer than it would if the oldest were dropped.
+
+use std::collections::VecDeque;
+
+/// How many states a keyframe covers, itself included.
+const KEYFRAME_INTERVAL: usize = 60;
+
+enum Entry {
+    /// A whole state.
+    Keyframe(Vec<u8>),
+
+    /// The changes from the closest keyframe before it.
+    Delta(Vec<u8>),
+}
+
+impl Entry {
+    fn size(&self) -> usize {
+        match self {
+            Entry::Keyframe(data) | Entry::Delta(data) => data.len(),
+        }
+    }
+}
+
+/// A history of states to rewind through, newest last.
+pub struct RewindBuffer {
+    entries: VecDeque<Entry>,
+
+    /// The most memory the states may take up, in bytes.
+    budget: usize,
+
+    /// The memory the states take up, in bytes.
+    size: usize,
+}
+
+impl RewindBuffer {
+    /// Create a new `RewindBuffer` instance, keeping
+    /// as many states as fit in `budget` bytes.
+    pub fn new(budget: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            budget,
+            size: 0,
+        }
+    }
+
+    /// Push the current state, usually once per frame.
+    pub fn push(&mut self, state: &[u8]) {
+        let entry = match self.last_keyframe() {
+            // Keyframes of states of another size, of a swapped
+            // cartridge for example, can't be compared against.
+            Some((index, keyframe))
+                if self.entries.len() - index < KEYFRAME_INTERVAL
+                    && keyframe.len() == state.len() =>
+            {
+                let mut delta = Vec::new();
+
+                encode_delta(keyframe, state, &mut delta);
+                Entry::Delta(delta)
+            }
+
+            _ => Entry::Keyframe(state.to_vec()),
+        };
+
+        self.size += entry.size();
+        self.entries.push_back(entry);
+
+        while self.size > self.budget && !self.entries.is_empty() {
+            if !self.thin_out() {
+                self.drop_oldest_keyframe();
+            }
+        }
+    }
+
+    /// Take the newest state out of the history, writing it into
+    /// `state`. Returns false if there are no states left.
+    pub fn pop(&mut self, state: &mut Vec<u8>) -> bool {
+        let entry = match self.entries.pop_back() {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        self.size -= entry.size();
+
+        match entry {
+            Entry::Keyframe(data) => *state = data,
+
+            Entry::Delta(delta) => {
+                let (_, keyframe) = self
+                    .last_keyframe()
+                    .expect("rewind delta without a keyframe");
+
+                state.clear();
+                state.extend_from_slice(keyframe);
+
+                apply_delta(state, &delta);
+            }
+        }
+
+        true
+    }
+
+    /// Get the memory the states take up, in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.size = 0;
+    }
+
+    /// Find the newest keyframe, and where it is.
+    fn last_keyframe(&self) -> Option<(usize, &[u8])> {
+        self.entries
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(index, entry)| match entry {
+                Entry::Keyframe(data) => Some((index, data.as_slice())),
+                Entry::Delta(_) => None,
+            })
+    }
+
+    /// Drop every other state that can go in the older half of the
+    /// history. Returns false if there weren't two to choose from.
+    fn thin_out(&mut self) -> bool {
+        let half = self.entries.len() / 2;
+        let mut kept = VecDeque::with_capacity(self.entries.len());
+        let mut drop_next = false;
+        let mut removed = false;
+
+        let mut entries = std::mem::take(&mut self.entries)
+            .into_iter()
+            .enumerate()
+            .peekable();
+
+        while let Some((index, entry)) = entries.next() {
+            // Keyframes are needed until their deltas are gone.
+            let removable = match entry {
+                Entry::Delta(_) => true,
+                Entry::Keyframe(_) => !matches!(entries.peek(), Some((_, Entry::Delta(_)))),
+            };
+
+            if index < half && removable {
+                if drop_next {
+                    self.size -= entry.size();
+                    removed = true;
+                    drop_next = false;
+                    continue;
+                }
+
+                drop_next = true;
+            }
+
+            kept.push_back(entry);
+        }
+
+        self.entries = kept;
+        removed
+    }
+
+    /// Drop the oldest keyframe along with its deltas.
+    fn drop_oldest_keyframe(&mut self) {
+        if let Some(entry) = self.entries.pop_front() {
+            self.size -= entry.size();
+        }
+
+        while let Some(Entry::Delta(_)) = self.entries.front() {
+            if let Some(entry) = self.entries.pop_front() {
+                self.size -= entry.size();
+            }
+        }
+    }
+}
+
+/// Encode the bytes of `state` that differ from `base`, which is as
+/// long, as runs of a `u32` offset from the end of the previous run,
+/// a `u32` length and the changed bytes.
+fn encode_delta(base: &[u8], state: &[u8], delta: &mut Vec<u8>) {
+    let mut position = 0;
+    let mut last_end = 0;
+
+    while position < state.len() {
+        if base[position] == state[position] {
+            position += 1;
+            continue;
+        }
+
+        let start = position;
+
+        // Runs end at the first 8 unchanged bytes, shorter
+        // gaps take less space as part of the run.
+        let mut unchanged = 0;
+
+        while position < state.len() && unchanged < 8 {
+            if base[position] == state[position] {
+                unchanged += 1;
+            } else {
+                unchanged = 0;
+            }
+
+            position += 1;
+        }
+
+        let end = position - unchanged;
+
+        delta.extend_from_slice(&((start - last_end) as u32).to_le_bytes());
+        delta.extend_from_slice(&((end - start) as u32).to_le_bytes());
+        delta.extend_from_slice(&state[start..end]);
+
+        last_end = end;
+    }
+}
+
+/// Apply the runs written by `encode_delta` to a copy of its base.
+fn apply_delta(state: &mut [u8], mut delta: &[u8]) {
+    let mut position = 0;
+
+    while delta.len() >= 8 {
+        let (header, rest) = delta.split_at(8);
+
+        let offset = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+
+        position += offset;
+        state[position..position + len].copy_from_slice(&rest[..len]);
+        position += len;
+
+        delta = &rest[len..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Make `count` states of `len` bytes that change a little from one
+    /// to the next, in scattered bytes and runs, with the number of
+    /// the state in the first four bytes.
+    fn states(count: usize, len: usize) -> Vec<Vec<u8>> {
+        let mut seed = 0x1234_5678u32;
+        let mut state = vec![0xAAu8; len];
+
+        (0..count)
+            .map(|n| {
+                for _ in 0..8 {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+
+                    let position = (seed >> 8) as usize % len;
+                    let run = (seed & 0x1F) as usize;
+
+                    for byte in &mut state[position..(position + run).min(len)] {
+                        *byte = byte.wrapping_add(seed as u8 | 1);
+                    }
+                }
+
+                state[len - 1] = n as u8;
+                state[..4].copy_from_slice(&(n as u32).to_le_bytes());
+                state.clone()
+            })
+            .collect()
+    }
+
+    fn number(state: &[u8]) -> usize {
+        u32::from_le_bytes([state[0], state[1], state[2], state[3]]) as usize
+    }
+
+    #[test]
+    fn delta_round_trip() {
+        let states = states(16, 300);
+
+        for base in &states {
+            for state in &states {
+                let mut delta = Vec::new();
+                let mut decoded = base.clone();
+
+                encode_delta(base, state, &mut delta);
+                apply_delta(&mut decoded, &delta);
+
+                assert_eq!(&decoded, state);
+            }
+        }
+    }
+
+    #[test]
+    fn pops_every_state_across_keyframes() {
+        let states = states(KEYFRAME_INTERVAL * 2 + 30, 1000);
+        let mut rewind = RewindBuffer::new(usize::MAX);
+
+        for state in &states {
+            rewind.push(state);
+        }
+
+        assert_eq!(rewind.len(), states.len());
+
+        let mut popped = Vec::new();
+
+        for state in states.iter().rev() {
+            assert!(rewind.pop(&mut popped));
+            assert_eq!(&popped, state);
+        }
+
+        assert!(!rewind.pop(&mut popped));
+        assert_eq!(rewind.size(), 0);
+    }
+
+    #[test]
+    fn states_of_another_size_start_a_keyframe() {
+        let small = states(10, 500);
+        let large = states(10, 800);
+        let mut rewind = RewindBuffer::new(usize::MAX);
+
+        for state in small.iter().chain(&large) {
+            rewind.push(state);
+        }
+
+        let mut popped = Vec::new();
+
+        for state in small.iter().chain(&large).rev() {
+            assert!(rewind.pop(&mut popped));
+            assert_eq!(&popped, state);
+        }
+    }
+
+    #[test]
+    fn pops_pushed_states_after_thinning() {
+        let states = states(KEYFRAME_INTERVAL * 10, 1000);
+        let budget = 20_000;
+        let mut rewind = RewindBuffer::new(budget);
+
+        for state in &states {
+            rewind.push(state);
+
+            assert!(rewind.size() <= budget);
+        }
+
+        assert!(rewind.len() < states.len());
+
+        let mut popped = Vec::new();
+        let mut numbers = Vec::new();
+
+        while rewind.pop(&mut popped) {
+            let n = number(&popped);
+
+            assert_eq!(popped, states[n]);
+            numbers.push(n);
+        }
+
+        // The newest states are all kept, and older
+        // ones are further apart, newest first.
+        assert_eq!(numbers[..4], [599, 598, 597, 596]);
+        assert!(numbers.windows(2).all(|pair| pair[0] > pair[1]));
+        assert!(numbers.windows(2).any(|pair| pair[0] - pair[1] > 1));
+    }
+}
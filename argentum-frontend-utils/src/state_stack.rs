@@ -0,0 +1,54 @@
+//! Undo history for save states.
+
+use std::collections::VecDeque;
+
+/// A bounded stack of save states.
+///
+/// Frontends push the current state before overwriting it, for
+/// example before loading a save state, so that a mispressed
+/// hotkey can be undone. The oldest state is dropped once the
+/// stack is full.
+pub struct StateStack {
+    states: VecDeque<Vec<u8>>,
+    capacity: usize,
+}
+
+impl StateStack {
+    /// Create a new `StateStack` instance.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            states: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Push a state onto the stack.
+    pub fn push(&mut self, state: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.states.len() == self.capacity {
+            self.states.pop_front();
+        }
+
+        self.states.push_back(state);
+    }
+
+    /// Pop the most recently pushed state.
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        self.states.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.states.clear();
+    }
+}
@@ -0,0 +1,13 @@
+//! Put `memory.x` where the linker script of `cortex-m-rt` finds it.
+
+use std::{env, fs, path::PathBuf};
+
+fn main() {
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+
+    fs::write(out.join("memory.x"), include_bytes!("memory.x")).unwrap();
+
+    println!("cargo:rustc-link-search={}", out.display());
+    println!("cargo:rerun-if-changed=memory.x");
+    println!("cargo:rerun-if-env-changed=ARGENTUM_ROM");
+}
@@ -0,0 +1,30 @@
+//! Buttons wired between a GPIO pin and ground, with the pin's pull-up
+//! enabled, so a pressed button reads low.
+
+use argentum_core::ArgentumKey;
+use embedded_hal::digital::InputPin;
+
+pub struct Buttons<P> {
+    /// Every button's pin, with the key it presses.
+    pins: [(P, ArgentumKey); 8],
+}
+
+impl<P: InputPin> Buttons<P> {
+    /// Create a new `Buttons` instance.
+    pub fn new(pins: [(P, ArgentumKey); 8]) -> Self {
+        Self { pins }
+    }
+
+    /// Get the keys held, as `ArgentumKey` values OR'd together.
+    pub fn held_keys(&mut self) -> u8 {
+        let mut keys = 0;
+
+        for (pin, key) in self.pins.iter_mut() {
+            if pin.is_low().unwrap_or(false) {
+                keys |= *key as u8;
+            }
+        }
+
+        keys
+    }
+}
@@ -0,0 +1,163 @@
+//! A minimal driver for the ST7789 240 x 240 SPI LCDs, written against
+//! the `embedded-hal` traits so other boards can reuse it.
+//!
+//! Errors of the SPI bus and pins are ignored, a failed transfer
+//! only garbles a frame and the next one is drawn over it anyway.
+
+use argentum_core::FrameInfo;
+use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiBus};
+
+/// Width of the LCD in pixels.
+const LCD_WIDTH: usize = 240;
+
+/// Height of the LCD in pixels.
+const LCD_HEIGHT: usize = 240;
+
+const SWRESET: u8 = 0x01;
+const SLPOUT: u8 = 0x11;
+const NORON: u8 = 0x13;
+const INVON: u8 = 0x21;
+const DISPON: u8 = 0x29;
+const CASET: u8 = 0x2A;
+const RASET: u8 = 0x2B;
+const RAMWR: u8 = 0x2C;
+const MADCTL: u8 = 0x36;
+const COLMOD: u8 = 0x3A;
+
+pub struct Lcd<SPI, DC, CS> {
+    spi: SPI,
+
+    /// Low while a command is sent, high while its parameters are.
+    dc: DC,
+
+    /// Chip select, low while talking to the LCD.
+    cs: CS,
+
+    /// A line of the frame, converted to big endian RGB565.
+    line: [u8; LCD_WIDTH * 2],
+}
+
+impl<SPI, DC, CS> Lcd<SPI, DC, CS>
+where
+    SPI: SpiBus,
+    DC: OutputPin,
+    CS: OutputPin,
+{
+    /// Create a new `Lcd` instance, resetting the
+    /// LCD and clearing it to black.
+    pub fn new(
+        spi: SPI,
+        dc: DC,
+        cs: CS,
+        rst: &mut impl OutputPin,
+        delay: &mut impl DelayNs,
+    ) -> Self {
+        let mut lcd = Self {
+            spi,
+            dc,
+            cs,
+            line: [0; LCD_WIDTH * 2],
+        };
+
+        let _ = lcd.cs.set_high();
+        let _ = rst.set_low();
+        delay.delay_ms(10);
+        let _ = rst.set_high();
+        delay.delay_ms(120);
+
+        lcd.command(SWRESET, &[]);
+        delay.delay_ms(150);
+
+        lcd.command(SLPOUT, &[]);
+        delay.delay_ms(10);
+
+        // 16 bits per pixel, RGB565.
+        lcd.command(COLMOD, &[0x55]);
+        lcd.command(MADCTL, &[0x00]);
+
+        // The IPS panels these are sold with show inverted colours otherwise.
+        lcd.command(INVON, &[]);
+        lcd.command(NORON, &[]);
+        lcd.command(DISPON, &[]);
+        delay.delay_ms(10);
+
+        lcd.set_window(0, 0, LCD_WIDTH, LCD_HEIGHT);
+        lcd.begin(RAMWR);
+
+        for _ in 0..LCD_HEIGHT {
+            let _ = lcd.spi.write(&[0; LCD_WIDTH * 2]);
+        }
+
+        lcd.end();
+        lcd
+    }
+
+    /// Draw an RGB24 frame in the middle of the LCD.
+    pub fn draw_frame(&mut self, framebuffer: &[u8], info: &FrameInfo) {
+        let width = info.width.min(LCD_WIDTH);
+        let height = info.height.min(LCD_HEIGHT);
+
+        self.set_window(
+            (LCD_WIDTH - width) / 2,
+            (LCD_HEIGHT - height) / 2,
+            width,
+            height,
+        );
+
+        self.begin(RAMWR);
+
+        for row in framebuffer.chunks(info.pitch).take(height) {
+            let pixels = row.chunks_exact(3).take(width);
+
+            for (rgb, rgb565) in pixels.zip(self.line.chunks_exact_mut(2)) {
+                let colour = ((rgb[0] as u16 & 0xF8) << 8)
+                    | ((rgb[1] as u16 & 0xFC) << 3)
+                    | (rgb[2] as u16 >> 3);
+
+                rgb565.copy_from_slice(&colour.to_be_bytes());
+            }
+
+            let _ = self.spi.write(&self.line[..width * 2]);
+        }
+
+        self.end();
+    }
+
+    /// Set the area written to by `RAMWR`.
+    fn set_window(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        let range = |start: usize, length: usize| {
+            let [start_high, start_low] = (start as u16).to_be_bytes();
+            let [end_high, end_low] = ((start + length - 1) as u16).to_be_bytes();
+
+            [start_high, start_low, end_high, end_low]
+        };
+
+        self.command(CASET, &range(x, width));
+        self.command(RASET, &range(y, height));
+    }
+
+    /// Send a command with its parameters.
+    fn command(&mut self, command: u8, params: &[u8]) {
+        self.begin(command);
+
+        let _ = self.spi.write(params);
+
+        self.end();
+    }
+
+    /// Send a command, leaving the LCD selected for its parameters.
+    fn begin(&mut self, command: u8) {
+        let _ = self.cs.set_low();
+        let _ = self.dc.set_low();
+        let _ = self.spi.write(&[command]);
+
+        // DC may only change once the command is out.
+        let _ = self.spi.flush();
+        let _ = self.dc.set_high();
+    }
+
+    fn end(&mut self) {
+        let _ = self.spi.flush();
+        let _ = self.cs.set_high();
+    }
+}
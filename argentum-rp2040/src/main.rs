@@ -0,0 +1,175 @@
+//! A reference frontend for the RP2040, showing how to drive the core
+//! without `std`. It runs a single ROM built into the firmware, draws
+//! to an ST7789 240 x 240 LCD over SPI and reads eight buttons.
+//!
+//! Wiring, by GPIO number:
+//!
+//! | LCD  | GPIO |    | Button | GPIO |
+//! |------|------|----|--------|------|
+//! | DC   | 16   |    | Right  | 2    |
+//! | CS   | 17   |    | Left   | 3    |
+//! | SCK  | 18   |    | Up     | 4    |
+//! | MOSI | 19   |    | Down   | 5    |
+//! | RST  | 20   |    | A      | 6    |
+//! |      |      |    | B      | 7    |
+//! |      |      |    | Select | 8    |
+//! |      |      |    | Start  | 9    |
+//!
+//! The buttons connect their pin to ground.
+//!
+//! Build it from this directory with the ROM to run in `ARGENTUM_ROM`,
+//! `ARGENTUM_ROM=/path/to/game.gb cargo run --release` flashes a Pico
+//! in BOOTSEL mode. The ROM is read in place from flash, so any game
+//! that fits next to the firmware in the Pico's 2 MB runs, with up to
+//! 32 KB of cartridge RAM. There is no sound, and the RP2040 doesn't
+//! keep up with 60 frames a second.
+//!
+//! Only the setup in this file is specific to the RP2040, the LCD and
+//! the buttons are driven through the `embedded-hal` traits. Boards
+//! like the ESP32 need their HAL's version of `main` and a heap.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+mod buttons;
+mod lcd;
+
+use core::{mem::MaybeUninit, ptr::addr_of_mut};
+
+use alloc::boxed::Box;
+use argentum_core::{Argentum, ArgentumKey, Rom};
+use linked_list_allocator::LockedHeap;
+use panic_halt as _;
+use rp2040_hal::{
+    self as hal, clocks::init_clocks_and_plls, fugit::RateExtU32, gpio::FunctionSpi, pac, spi::Spi,
+    Clock, Sio, Timer, Watchdog,
+};
+
+use buttons::Buttons;
+use lcd::Lcd;
+
+/// The second stage bootloader, for the W25Q080 flash of the Pico.
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2_FIRMWARE: [u8; 256] = rp2040_boot2::BOOT_LOADER_W25Q080;
+
+/// The frequency of the Pico's crystal.
+const XTAL_FREQ_HZ: u32 = 12_000_000;
+
+/// The core allocates around 170 KB plus the cartridge RAM, most of
+/// it the framebuffers. Everything is allocated when it's created.
+const HEAP_SIZE: usize = 232 * 1024;
+
+/// The ROM to run, built into the firmware.
+static ROM: &[u8] = include_bytes!(env!("ARGENTUM_ROM"));
+
+#[global_allocator]
+static ALLOCATOR: LockedHeap = LockedHeap::empty();
+
+static mut HEAP: [MaybeUninit<u8>; HEAP_SIZE] = [MaybeUninit::uninit(); HEAP_SIZE];
+
+#[hal::entry]
+fn main() -> ! {
+    // Safety: nothing is allocated before the heap is set up.
+    unsafe {
+        ALLOCATOR
+            .lock()
+            .init(addr_of_mut!(HEAP) as *mut u8, HEAP_SIZE);
+    }
+
+    let mut pac = pac::Peripherals::take().unwrap();
+    let mut watchdog = Watchdog::new(pac.WATCHDOG);
+
+    let clocks = init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    let mut timer = Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+
+    let sio = Sio::new(pac.SIO);
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    let sck = pins.gpio18.into_function::<FunctionSpi>();
+    let mosi = pins.gpio19.into_function::<FunctionSpi>();
+
+    let spi = Spi::<_, _, _, 8>::new(pac.SPI0, (mosi, sck)).init(
+        &mut pac.RESETS,
+        clocks.peripheral_clock.freq(),
+        62_500_000u32.Hz(),
+        embedded_hal::spi::MODE_0,
+    );
+
+    let mut lcd = Lcd::new(
+        spi,
+        pins.gpio16.into_push_pull_output(),
+        pins.gpio17.into_push_pull_output(),
+        &mut pins.gpio20.into_push_pull_output(),
+        &mut timer,
+    );
+
+    let mut buttons = Buttons::new([
+        (
+            pins.gpio2.into_pull_up_input().into_dyn_pin(),
+            ArgentumKey::Right,
+        ),
+        (
+            pins.gpio3.into_pull_up_input().into_dyn_pin(),
+            ArgentumKey::Left,
+        ),
+        (
+            pins.gpio4.into_pull_up_input().into_dyn_pin(),
+            ArgentumKey::Up,
+        ),
+        (
+            pins.gpio5.into_pull_up_input().into_dyn_pin(),
+            ArgentumKey::Down,
+        ),
+        (
+            pins.gpio6.into_pull_up_input().into_dyn_pin(),
+            ArgentumKey::ButtonA,
+        ),
+        (
+            pins.gpio7.into_pull_up_input().into_dyn_pin(),
+            ArgentumKey::ButtonB,
+        ),
+        (
+            pins.gpio8.into_pull_up_input().into_dyn_pin(),
+            ArgentumKey::Select,
+        ),
+        (
+            pins.gpio9.into_pull_up_input().into_dyn_pin(),
+            ArgentumKey::Start,
+        ),
+    ]);
+
+    // Nothing plays the samples, and the ROM stays in flash. An
+    // unsupported ROM halts the board before anything is shown.
+    let mut argentum = Argentum::from_rom(Rom::new(ROM), Box::new(|_| {}), None).unwrap();
+
+    argentum.skip_bootrom();
+
+    // Nothing is allocated from here on.
+    loop {
+        argentum.set_keys(buttons.held_keys());
+        argentum.execute_frame();
+
+        let (framebuffer, info) = argentum.get_framebuffer();
+
+        lcd.draw_frame(framebuffer, &info);
+    }
+}
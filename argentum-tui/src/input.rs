@@ -0,0 +1,106 @@
+//! Keyboard input from the terminal.
+//!
+//! Most terminals only report key presses, not releases. Each press
+//! holds its button down for a few frames, and the terminal's key
+//! repeat keeps it held for as long as the key is. Terminals that
+//! support the kitty keyboard protocol report releases as well, and
+//! are used that way when available.
+
+use argentum_core::ArgentumKey;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+/// Frames a button stays held after a press when releases aren't
+/// reported, long enough to bridge the delay before key repeat.
+const HOLD_FRAMES: u8 = 15;
+
+/// What a key press asks the frontend to do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    None,
+    Quit,
+}
+
+pub struct Keyboard {
+    /// Whether the terminal reports key releases.
+    releases: bool,
+
+    /// Frames left until each button is released, by its bit.
+    held: [u8; 8],
+}
+
+impl Keyboard {
+    /// Create a new `Keyboard` instance, `releases` tells if
+    /// the terminal reports key releases.
+    pub fn new(releases: bool) -> Self {
+        Self {
+            releases,
+            held: [0; 8],
+        }
+    }
+
+    /// Handle a key event.
+    pub fn handle(&mut self, event: KeyEvent) -> Action {
+        let quit = event.code == KeyCode::Esc
+            || (event.code == KeyCode::Char('c')
+                && event.modifiers.contains(KeyModifiers::CONTROL));
+
+        if quit {
+            return Action::Quit;
+        }
+
+        let key = match bound_key(event.code) {
+            Some(key) => key,
+            None => return Action::None,
+        };
+
+        let index = (key as u8).trailing_zeros() as usize;
+
+        self.held[index] = match event.kind {
+            KeyEventKind::Release => 0,
+
+            // Held until released.
+            _ if self.releases => u8::MAX,
+
+            _ => HOLD_FRAMES,
+        };
+
+        Action::None
+    }
+
+    /// Get the held buttons as `ArgentumKey` values OR'd together,
+    /// counting down the buttons held by presses alone.
+    pub fn next_frame(&mut self) -> u8 {
+        let mut keys = 0;
+
+        for (index, frames) in self.held.iter_mut().enumerate() {
+            if *frames > 0 {
+                keys |= 1 << index;
+
+                if *frames != u8::MAX {
+                    *frames -= 1;
+                }
+            }
+        }
+
+        keys
+    }
+}
+
+/// Get the button bound to a key, the arrow keys or WASD for the
+/// D-pad, Z and X for A and B, Enter for Start and Space for Select.
+fn bound_key(code: KeyCode) -> Option<ArgentumKey> {
+    let key = match code {
+        KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('W') => ArgentumKey::Up,
+        KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('S') => ArgentumKey::Down,
+        KeyCode::Left | KeyCode::Char('a') | KeyCode::Char('A') => ArgentumKey::Left,
+        KeyCode::Right | KeyCode::Char('d') | KeyCode::Char('D') => ArgentumKey::Right,
+        KeyCode::Char('z') | KeyCode::Char('Z') => ArgentumKey::ButtonA,
+        KeyCode::Char('x') | KeyCode::Char('X') => ArgentumKey::ButtonB,
+        KeyCode::Enter => ArgentumKey::Start,
+        KeyCode::Char(' ') | KeyCode::Backspace => ArgentumKey::Select,
+
+        _ => return None,
+    };
+
+    Some(key)
+}
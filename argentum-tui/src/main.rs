@@ -0,0 +1,171 @@
+//! A frontend that plays games in a terminal, for servers, demos over
+//! SSH and trying the core out without a GPU. There is no sound.
+
+mod input;
+mod render;
+
+use std::{
+    io::{self, BufWriter, Write},
+    panic,
+    path::PathBuf,
+    time::Duration,
+};
+
+use argentum_core::Argentum;
+use argentum_frontend_utils::FpsLimiter;
+use clap::Clap;
+use crossterm::{
+    cursor::{Hide, Show},
+    event::{
+        self, Event, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
+    },
+    execute,
+    style::ResetColor,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+
+use input::{Action, Keyboard};
+use render::{Mode, Screen};
+
+/// The version of the frontend.
+const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Clap)]
+#[clap(name = "Argentum GB (terminal)")]
+#[clap(version = PKG_VERSION, about = "Play Game Boy games in a terminal.")]
+struct Opt {
+    /// The Game Boy ROM file to execute.
+    #[clap(parse(from_os_str))]
+    rom_file: PathBuf,
+
+    /// Skip the bootrom (Optix's custom bootrom Bootix).
+    #[clap(short, long)]
+    skip_bootrom: bool,
+
+    /// Draw with braille patterns instead of coloured half blocks,
+    /// for small terminals and ones without 24-bit colour.
+    #[clap(short, long)]
+    braille: bool,
+}
+
+fn main() {
+    let opts: Opt = Opt::parse();
+
+    let rom = std::fs::read(&opts.rom_file).unwrap_or_else(|err| {
+        eprintln!("Failed to read the ROM file: {}", err);
+        std::process::exit(1);
+    });
+
+    // Check if there is a save file.
+    let save_path = opts.rom_file.with_extension("sav");
+
+    let save_file = std::fs::read(&save_path).ok();
+
+    let mut argentum = Argentum::new(&rom, Box::new(|_| {}), save_file).unwrap_or_else(|err| {
+        eprintln!("Failed to load the ROM: {}", err);
+        std::process::exit(1);
+    });
+
+    if opts.skip_bootrom {
+        argentum.skip_bootrom();
+    }
+
+    let mode = if opts.braille {
+        Mode::Braille
+    } else {
+        Mode::HalfBlocks
+    };
+
+    if let Err(err) = run(&mut argentum, mode) {
+        eprintln!("Terminal error: {}", err);
+    }
+
+    if let Some(ram_save) = argentum.get_ram_dump() {
+        std::fs::write(&save_path, &ram_save).expect("Failed to write save file.");
+    }
+}
+
+/// Play until Escape or Ctrl+C is pressed.
+fn run(argentum: &mut Argentum, mode: Mode) -> io::Result<()> {
+    let mut out = BufWriter::with_capacity(1 << 16, io::stdout());
+
+    // Kitty protocol terminals report key releases.
+    let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+
+    terminal::enable_raw_mode()?;
+    execute!(out, EnterAlternateScreen, Hide)?;
+
+    if releases {
+        execute!(
+            out,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+
+    // Give the terminal back before a panic message is printed.
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        restore_terminal(releases);
+        default_hook(info);
+    }));
+
+    let result = play(argentum, mode, releases, &mut out);
+
+    drop(out);
+    restore_terminal(releases);
+
+    result
+}
+
+fn play(
+    argentum: &mut Argentum,
+    mode: Mode,
+    releases: bool,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let (columns, rows) = terminal::size()?;
+
+    let mut screen = Screen::new(mode, columns, rows);
+    let mut keyboard = Keyboard::new(releases);
+    let mut limiter = FpsLimiter::new();
+
+    loop {
+        while event::poll(Duration::ZERO)? {
+            let action = match event::read()? {
+                Event::Key(key) => keyboard.handle(key),
+
+                Event::Resize(columns, rows) => {
+                    screen.resize(columns, rows);
+                    Action::None
+                }
+
+                _ => Action::None,
+            };
+
+            if action == Action::Quit {
+                return Ok(());
+            }
+        }
+
+        argentum.set_keys(keyboard.next_frame());
+        argentum.execute_frame();
+
+        let (framebuffer, info) = argentum.get_framebuffer();
+
+        screen.draw(out, framebuffer, &info)?;
+        limiter.wait();
+    }
+}
+
+fn restore_terminal(releases: bool) {
+    let mut stdout = io::stdout();
+
+    if releases {
+        let _ = execute!(stdout, PopKeyboardEnhancementFlags);
+    }
+
+    let _ = execute!(stdout, ResetColor, Show, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+}
@@ -0,0 +1,208 @@
+//! Drawing frames with text.
+//!
+//! Only the rows of text that changed since the last frame are sent
+//! to the terminal, which keeps mostly still screens cheap over SSH.
+
+use std::io::{self, Write};
+
+use argentum_core::FrameInfo;
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{Clear, ClearType},
+};
+
+/// How pixels are mapped to characters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// An upper half block per two pixels, stacked, in 24-bit colour.
+    HalfBlocks,
+
+    /// A braille pattern per 2 x 4 pixels, a dot for every light pixel.
+    /// Fits smaller terminals and needs no colour support.
+    Braille,
+}
+
+impl Mode {
+    /// Get the size of a frame in characters.
+    pub fn size(self, info: &FrameInfo) -> (usize, usize) {
+        match self {
+            Mode::HalfBlocks => (info.width, info.height.div_ceil(2)),
+            Mode::Braille => (info.width.div_ceil(2), info.height.div_ceil(4)),
+        }
+    }
+}
+
+pub struct Screen {
+    mode: Mode,
+
+    /// The size of the terminal in characters.
+    columns: u16,
+    rows: u16,
+
+    /// The escape sequences of every row of text last drawn.
+    lines: Vec<Vec<u8>>,
+}
+
+impl Screen {
+    /// Create a new `Screen` instance for a terminal of the given size.
+    pub fn new(mode: Mode, columns: u16, rows: u16) -> Self {
+        Self {
+            mode,
+            columns,
+            rows,
+            lines: Vec::new(),
+        }
+    }
+
+    /// The terminal was resized, everything is drawn again.
+    pub fn resize(&mut self, columns: u16, rows: u16) {
+        self.columns = columns;
+        self.rows = rows;
+        self.lines.clear();
+    }
+
+    /// Draw an RGB24 frame in the middle of the terminal.
+    pub fn draw(
+        &mut self,
+        out: &mut impl Write,
+        framebuffer: &[u8],
+        info: &FrameInfo,
+    ) -> io::Result<()> {
+        let (width, height) = self.mode.size(info);
+
+        if self.lines.is_empty() {
+            queue!(out, ResetColor, Clear(ClearType::All))?;
+        }
+
+        if width > self.columns as usize || height > self.rows as usize {
+            if self.lines.is_empty() {
+                let message = format!(
+                    "Make the terminal at least {} x {} characters.",
+                    width, height
+                );
+
+                queue!(out, MoveTo(0, 0), Print(message))?;
+
+                // Only show the message once.
+                self.lines.push(Vec::new());
+            }
+
+            return out.flush();
+        }
+
+        self.lines.resize(height, Vec::new());
+
+        let left = (self.columns as usize - width) / 2;
+        let top = (self.rows as usize - height) / 2;
+
+        for row in 0..height {
+            let mut line = Vec::with_capacity(self.lines[row].len());
+
+            match self.mode {
+                Mode::HalfBlocks => half_block_row(&mut line, framebuffer, info, row)?,
+                Mode::Braille => braille_row(&mut line, framebuffer, info, row)?,
+            }
+
+            if line != self.lines[row] {
+                queue!(out, MoveTo(left as u16, (top + row) as u16))?;
+                out.write_all(&line)?;
+
+                self.lines[row] = line;
+            }
+        }
+
+        queue!(out, ResetColor)?;
+        out.flush()
+    }
+}
+
+/// Get the colour of a pixel, black past the bottom of the frame.
+fn pixel(framebuffer: &[u8], info: &FrameInfo, x: usize, y: usize) -> (u8, u8, u8) {
+    if y >= info.height {
+        return (0, 0, 0);
+    }
+
+    let offset = y * info.pitch + x * 3;
+
+    (
+        framebuffer[offset],
+        framebuffer[offset + 1],
+        framebuffer[offset + 2],
+    )
+}
+
+/// Write a row of half blocks, for two rows of pixels. Colours
+/// are only set when they differ from the previous character's.
+fn half_block_row(
+    line: &mut Vec<u8>,
+    framebuffer: &[u8],
+    info: &FrameInfo,
+    row: usize,
+) -> io::Result<()> {
+    let mut colours = None;
+
+    for x in 0..info.width {
+        let (r, g, b) = pixel(framebuffer, info, x, row * 2);
+        let top = Color::Rgb { r, g, b };
+
+        let (r, g, b) = pixel(framebuffer, info, x, row * 2 + 1);
+        let bottom = Color::Rgb { r, g, b };
+
+        match colours {
+            Some((fg, bg)) if fg == top && bg == bottom => {}
+
+            Some((fg, _)) if fg == top => queue!(line, SetBackgroundColor(bottom))?,
+            Some((_, bg)) if bg == bottom => queue!(line, SetForegroundColor(top))?,
+
+            _ => queue!(line, SetForegroundColor(top), SetBackgroundColor(bottom))?,
+        }
+
+        colours = Some((top, bottom));
+        line.extend_from_slice("▀".as_bytes());
+    }
+
+    Ok(())
+}
+
+/// Write a row of braille patterns, for four rows of pixels.
+fn braille_row(
+    line: &mut Vec<u8>,
+    framebuffer: &[u8],
+    info: &FrameInfo,
+    row: usize,
+) -> io::Result<()> {
+    // The dot for each pixel of a 2 x 4 block, by row and column.
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+    for column in 0..info.width.div_ceil(2) {
+        let mut pattern = 0;
+
+        for (dy, dots) in DOTS.iter().enumerate() {
+            for (dx, dot) in dots.iter().enumerate() {
+                let x = column * 2 + dx;
+
+                if x >= info.width {
+                    continue;
+                }
+
+                let (r, g, b) = pixel(framebuffer, info, x, row * 4 + dy);
+
+                // Perceived brightness, with integer weights.
+                let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+
+                if luma >= 128 {
+                    pattern |= dot;
+                }
+            }
+        }
+
+        let mut encoded = [0; 4];
+        let character = char::from_u32(0x2800 + pattern).unwrap_or(' ');
+
+        line.extend_from_slice(character.encode_utf8(&mut encoded).as_bytes());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+//! Feeding barcodes to a barcode reader plugged into the link port.
+//!
+//! The barcodes are listed in a file, one per line, and F4 scans
+//! the next one. Empty lines and lines starting with `#` are ignored.
+
+use std::path::Path;
+
+use argentum_core::{BarcodeReader, BarcodeReaderKind};
+
+/// Scans the barcodes of a file in order.
+pub struct BarcodeScanner {
+    reader: BarcodeReader,
+    barcodes: Vec<String>,
+
+    /// The barcode scanned next.
+    next: usize,
+}
+
+impl BarcodeScanner {
+    /// Create a new `BarcodeScanner` instance, loading
+    /// the barcodes listed in the given file.
+    pub fn load(kind: BarcodeReaderKind, path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read the barcodes: {}", err))?;
+
+        let barcodes = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect::<Vec<_>>();
+
+        if barcodes.is_empty() {
+            return Err(String::from("The barcode file lists no barcodes."));
+        }
+
+        Ok(Self {
+            reader: BarcodeReader::new(kind),
+            barcodes,
+            next: 0,
+        })
+    }
+
+    /// Get the reader to plug into the link port.
+    pub fn reader(&self) -> BarcodeReader {
+        self.reader.clone()
+    }
+
+    /// Scan the next barcode, starting over after the last one.
+    pub fn scan_next(&mut self) {
+        let barcode = &self.barcodes[self.next];
+
+        self.next = (self.next + 1) % self.barcodes.len();
+
+        match self.reader.scan(barcode) {
+            Ok(()) => println!("Scanned barcode {}.", barcode),
+            Err(err) => eprintln!("Can't scan \"{}\": {}.", barcode, err),
+        }
+    }
+}
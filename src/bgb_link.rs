@@ -0,0 +1,297 @@
+//! The BGB link protocol (version 1.4), which links Argentum to BGB
+//! and other emulators that speak it over TCP.
+//!
+//! Every packet is 8 bytes, a command, three bytes of arguments
+//! and a little endian `u32` timestamp. The Game Boy that clocks a
+//! transfer sends its byte with `sync1`, the other side answers with
+//! `sync2` if the game is waiting for a transfer and `sync3` if not.
+//!
+//! Argentum doesn't keep its clock in step with the partner's. It
+//! answers with the partner's own timestamps, so BGB never waits on it.
+
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicU16, AtomicU32, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use argentum_core::LinkPartner;
+
+const CMD_VERSION: u8 = 1;
+const CMD_JOYPAD: u8 = 101;
+const CMD_SYNC1: u8 = 104;
+const CMD_SYNC2: u8 = 105;
+const CMD_SYNC3: u8 = 106;
+const CMD_STATUS: u8 = 108;
+const CMD_WANT_DISCONNECT: u8 = 109;
+
+/// Sent with `sync1`, a transfer with the internal clock.
+const SYNC1_CONTROL: u8 = 0x81;
+
+/// Sent with `status`, the emulator is running.
+const STATUS_RUNNING: u8 = 0x01;
+
+/// How long to wait for the partner to answer a transfer,
+/// after that it is treated as unplugged.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Marks a byte stored in one of the `AtomicU16`s as present.
+const PRESENT: u16 = 0x100;
+
+#[derive(Clone, Copy)]
+struct Packet {
+    command: u8,
+    args: [u8; 3],
+    timestamp: u32,
+}
+
+impl Packet {
+    fn new(command: u8, args: [u8; 3], timestamp: u32) -> Self {
+        Self {
+            command,
+            args,
+            timestamp,
+        }
+    }
+
+    fn read(stream: &mut impl Read) -> io::Result<Self> {
+        let mut bytes = [0; 8];
+
+        stream.read_exact(&mut bytes)?;
+
+        Ok(Self {
+            command: bytes[0],
+            args: [bytes[1], bytes[2], bytes[3]],
+            timestamp: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        })
+    }
+
+    fn write(&self, stream: &mut impl Write) -> io::Result<()> {
+        let mut bytes = [
+            self.command,
+            self.args[0],
+            self.args[1],
+            self.args[2],
+            0,
+            0,
+            0,
+            0,
+        ];
+
+        bytes[4..].copy_from_slice(&self.timestamp.to_le_bytes());
+
+        stream.write_all(&bytes)
+    }
+}
+
+/// State shared with the thread reading packets.
+struct Shared {
+    /// Packets are written by both threads.
+    writer: Mutex<TcpStream>,
+
+    /// SB while the game waits for the partner's clock, with `PRESENT` set.
+    waiting: AtomicU16,
+
+    /// The byte the partner clocked in, with `PRESENT` set.
+    received: AtomicU16,
+
+    /// The last timestamp the partner sent.
+    timestamp: AtomicU32,
+}
+
+impl Shared {
+    fn send(&self, command: u8, args: [u8; 3]) -> io::Result<()> {
+        let timestamp = self.timestamp.load(Ordering::Relaxed);
+        let mut writer = self.writer.lock().unwrap();
+
+        Packet::new(command, args, timestamp).write(&mut *writer)
+    }
+}
+
+/// A link cable to an emulator speaking the BGB protocol.
+pub struct BgbLink {
+    shared: Arc<Shared>,
+
+    /// Answers to the transfers this side clocks.
+    replies: Receiver<u8>,
+}
+
+impl BgbLink {
+    /// Connect to an emulator listening on the address.
+    pub fn connect(address: &str) -> io::Result<Self> {
+        Self::start(TcpStream::connect(address)?)
+    }
+
+    /// Wait for an emulator to connect to the address.
+    pub fn listen(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+
+        println!("Waiting for a BGB link connection on {}.", address);
+
+        Self::accept(&listener)
+    }
+
+    fn accept(listener: &TcpListener) -> io::Result<Self> {
+        let (stream, _) = listener.accept()?;
+
+        Self::start(stream)
+    }
+
+    /// Exchange versions, and start reading packets.
+    fn start(mut stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+
+        Packet::new(CMD_VERSION, [1, 4, 0], 0).write(&mut stream)?;
+
+        let version = Packet::read(&mut stream)?;
+
+        if version.command != CMD_VERSION || version.args != [1, 4, 0] {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the partner doesn't speak BGB link protocol 1.4",
+            ));
+        }
+
+        Packet::new(CMD_STATUS, [STATUS_RUNNING, 0, 0], 0).write(&mut stream)?;
+
+        let reader = stream.try_clone()?;
+
+        let shared = Arc::new(Shared {
+            writer: Mutex::new(stream),
+            waiting: AtomicU16::new(0),
+            received: AtomicU16::new(0),
+            timestamp: AtomicU32::new(0),
+        });
+
+        let (sender, replies) = mpsc::channel();
+        let thread_shared = Arc::clone(&shared);
+
+        thread::spawn(move || {
+            if let Err(err) = read_packets(reader, &thread_shared, &sender) {
+                eprintln!("The BGB link failed: {}", err);
+            } else {
+                eprintln!("The BGB link partner disconnected.");
+            }
+        });
+
+        Ok(Self { shared, replies })
+    }
+}
+
+/// Answer the partner's packets until it disconnects.
+fn read_packets(mut reader: TcpStream, shared: &Shared, replies: &Sender<u8>) -> io::Result<()> {
+    loop {
+        let packet = Packet::read(&mut reader)?;
+
+        shared.timestamp.store(packet.timestamp, Ordering::Relaxed);
+
+        match packet.command {
+            // The partner clocked a transfer.
+            CMD_SYNC1 => match shared.waiting.swap(0, Ordering::AcqRel) {
+                0 => shared.send(CMD_SYNC3, [1, 0, 0])?,
+
+                waiting => {
+                    shared
+                        .received
+                        .store(PRESENT | packet.args[0] as u16, Ordering::Release);
+
+                    shared.send(CMD_SYNC2, [waiting as u8, 0x80, 1])?;
+                }
+            },
+
+            // Answers to a transfer this side clocked. The partner
+            // not waiting for one is the same as nobody being there.
+            CMD_SYNC2 => {
+                let _ = replies.send(packet.args[0]);
+            }
+
+            CMD_SYNC3 if packet.args[0] == 1 => {
+                let _ = replies.send(0xFF);
+            }
+
+            // Timestamp syncs are echoed, Argentum is always in step.
+            CMD_SYNC3 => shared.send(CMD_SYNC3, [0, 0, 0])?,
+
+            CMD_WANT_DISCONNECT => return Ok(()),
+
+            CMD_VERSION | CMD_JOYPAD | CMD_STATUS => {}
+
+            command => eprintln!("Ignoring unknown BGB link command {}.", command),
+        }
+    }
+}
+
+impl LinkPartner for BgbLink {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        // Drop answers that came in after their transfer timed out.
+        while self.replies.try_recv().is_ok() {}
+
+        if self
+            .shared
+            .send(CMD_SYNC1, [byte, SYNC1_CONTROL, 0])
+            .is_err()
+        {
+            return 0xFF;
+        }
+
+        self.replies.recv_timeout(REPLY_TIMEOUT).unwrap_or(0xFF)
+    }
+
+    fn external_clock(&mut self, byte: u8) -> Option<u8> {
+        let received = self.shared.received.swap(0, Ordering::AcqRel);
+
+        if received != 0 {
+            return Some(received as u8);
+        }
+
+        self.shared
+            .waiting
+            .store(PRESENT | byte as u16, Ordering::Release);
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Link two instances over a local connection.
+    fn linked_pair() -> (BgbLink, BgbLink) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let listening = thread::spawn(move || BgbLink::accept(&listener).unwrap());
+        let connected = BgbLink::connect(&address).unwrap();
+
+        (listening.join().unwrap(), connected)
+    }
+
+    #[test]
+    fn transfers_reach_the_waiting_side() {
+        let (mut master, mut slave) = linked_pair();
+
+        // Nobody waits for a transfer yet.
+        assert_eq!(master.exchange(0x11), 0xFF);
+
+        assert_eq!(slave.external_clock(0x42), None);
+        assert_eq!(master.exchange(0x99), 0x42);
+
+        // The byte is only there once the reading thread got it.
+        let received = loop {
+            if let Some(byte) = slave.external_clock(0x42) {
+                break byte;
+            }
+
+            thread::yield_now();
+        };
+
+        assert_eq!(received, 0x99);
+    }
+}
@@ -0,0 +1,74 @@
+//! The options for how the emulator boots and runs, shared by playing
+//! a ROM and the headless checks, so they all start the same way.
+
+use std::path::PathBuf;
+
+use argentum_core::Argentum;
+use clap::Clap;
+
+#[derive(Clap)]
+pub struct BootOptions {
+    /// Skip the bootrom (Optix's custom bootrom Bootix).
+    #[clap(short, long)]
+    pub skip_bootrom: bool,
+
+    /// Show the logo scrolling down and play the chime
+    /// without running a bootrom.
+    #[clap(long, conflicts_with = "skip-bootrom")]
+    pub hle_boot: bool,
+
+    /// Run a dump of a real boot ROM instead of the built in one,
+    /// the DMG's for DMG games and the CGB's for CGB games.
+    #[clap(long, parse(from_os_str), conflicts_with_all = &["skip-bootrom", "hle-boot"])]
+    pub bootrom: Option<PathBuf>,
+
+    /// Hang in the bootrom if the cartridge has a bad logo or
+    /// header checksum, like a real Game Boy.
+    #[clap(long)]
+    pub check_header: bool,
+
+    /// Run the CPU this many times faster than the rest of the
+    /// system to reduce slowdown. Not accurate! Movies and the
+    /// headless checks always run at the stock speed.
+    #[clap(
+        long,
+        possible_values = &["1", "2", "4"],
+        conflicts_with_all = &["movie", "verify-determinism", "print-frame-hash", "verify-against"]
+    )]
+    pub overclock: Option<u8>,
+}
+
+impl BootOptions {
+    /// Set up a new emulator as the options ask for and start it,
+    /// exiting with status 1 if the boot ROM can't be loaded.
+    pub fn apply(&self, gb: &mut Argentum) {
+        gb.set_header_check(self.check_header);
+
+        if let Some(path) = &self.bootrom {
+            let result = std::fs::read(path)
+                .map_err(|err| err.to_string())
+                .and_then(|boot_rom| gb.set_boot_rom(&boot_rom).map_err(|err| err.to_string()));
+
+            if let Err(err) = result {
+                eprintln!("Failed to load the boot ROM: {}", err);
+                std::process::exit(1);
+            }
+        }
+
+        self.start(gb);
+
+        if let Some(overclock) = self.overclock {
+            gb.set_overclock(overclock);
+        }
+    }
+
+    /// Start the emulator from power on, skipping the
+    /// bootrom or showing the logo if asked to.
+    pub fn start(&self, gb: &mut Argentum) {
+        if self.skip_bootrom {
+            gb.skip_bootrom();
+        } else if self.hle_boot {
+            gb.hle_boot();
+        }
+    }
+}
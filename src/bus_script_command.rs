@@ -0,0 +1,47 @@
+//! The `bus-script` subcommand, which replays bus scripts
+//! against a fresh system and reports which of them fail.
+//!
+//! `argentum bus-script <FILE>...` exits with status 1 if any
+//! script fails, so it can run alongside the other checks.
+
+use std::path::{Path, PathBuf};
+
+use argentum_core::BusScript;
+use clap::Clap;
+
+#[derive(Clap)]
+pub struct BusScriptCommand {
+    /// The bus scripts to run.
+    #[clap(parse(from_os_str), required = true)]
+    scripts: Vec<PathBuf>,
+}
+
+fn run_script(path: &Path) -> Result<(), String> {
+    let script = std::fs::read_to_string(path)
+        .map_err(|err| format!("failed to read the script: {}", err))?;
+
+    let script: BusScript = script.parse().map_err(|err| format!("{}", err))?;
+
+    script.run().map_err(|failure| format!("{}", failure))
+}
+
+/// Run every script, exiting with status 1 if any of them failed.
+pub fn run(command: BusScriptCommand) {
+    let mut failed = 0;
+
+    for path in &command.scripts {
+        match run_script(path) {
+            Ok(()) => println!("{}: ok", path.display()),
+
+            Err(err) => {
+                println!("{}: {}", path.display(), err);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed != 0 {
+        eprintln!("{} of {} scripts failed.", failed, command.scripts.len());
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,129 @@
+//! Per game cheat lists, stored next to the ROM in `.cht` files.
+//!
+//! Every line holds `on` or `off`, a GameShark or Game Genie code and
+//! an optional description, for example `on 010947C1 Infinite health`.
+//! Empty lines and lines starting with `#` are ignored, and aren't
+//! kept when toggling a cheat in the menu rewrites the file.
+
+use std::path::{Path, PathBuf};
+
+use argentum_core::CheatCode;
+
+/// A cheat from the cheat file.
+pub struct Cheat {
+    pub enabled: bool,
+
+    /// The code as written in the file.
+    pub code: String,
+
+    pub description: String,
+
+    /// The decoded code, `None` if it isn't valid.
+    pub parsed: Option<CheatCode>,
+}
+
+impl Cheat {
+    /// The name shown in the menu.
+    pub fn label(&self) -> &str {
+        if self.description.is_empty() {
+            &self.code
+        } else {
+            &self.description
+        }
+    }
+}
+
+/// The cheats of a single game.
+pub struct CheatList {
+    /// The file the cheats are stored in.
+    path: PathBuf,
+
+    cheats: Vec<Cheat>,
+}
+
+impl CheatList {
+    /// Create a new `CheatList` instance, loading the cheats stored at
+    /// the given path. Invalid codes are kept, but never applied.
+    pub fn load(path: PathBuf) -> Self {
+        let contents = std::fs::read_to_string(&path).unwrap_or_default();
+
+        let mut cheats = Vec::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+
+            let enabled = match fields.next() {
+                Some("on") => true,
+                Some("off") => false,
+
+                _ => {
+                    eprintln!("Ignoring cheat without on or off on line {}.", number + 1);
+                    continue;
+                }
+            };
+
+            let code = fields.next().unwrap_or_default().to_owned();
+            let description = fields.collect::<Vec<_>>().join(" ");
+
+            let parsed = code.parse().ok();
+
+            if parsed.is_none() {
+                eprintln!("Invalid cheat code \"{}\" on line {}.", code, number + 1);
+            }
+
+            cheats.push(Cheat {
+                enabled,
+                code,
+                description,
+                parsed,
+            });
+        }
+
+        Self { path, cheats }
+    }
+
+    /// Write the cheats back to their file.
+    pub fn save(&self) -> std::io::Result<()> {
+        let mut contents = String::new();
+
+        for cheat in self.cheats.iter() {
+            let enabled = if cheat.enabled { "on" } else { "off" };
+
+            let line = format!("{} {} {}", enabled, cheat.code, cheat.description);
+
+            contents.push_str(line.trim_end());
+            contents.push('\n');
+        }
+
+        std::fs::write(&self.path, contents)
+    }
+
+    /// Get the path the cheats are stored at for the given ROM.
+    pub fn path_for_rom(rom_path: &Path) -> PathBuf {
+        rom_path.with_extension("cht")
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    /// Enable or disable a cheat.
+    pub fn toggle(&mut self, index: usize) {
+        self.cheats[index].enabled = !self.cheats[index].enabled;
+    }
+
+    /// Get the valid codes of all enabled cheats.
+    pub fn enabled_codes(&self) -> Vec<CheatCode> {
+        self.cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .filter_map(|cheat| cheat.parsed)
+            .collect()
+    }
+}
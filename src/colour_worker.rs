@@ -0,0 +1,78 @@
+//! Applies the colours to frames on a thread of its own, so the
+//! emulation thread can go on with the next frame in the meantime.
+//!
+//! This is an experiment to push the speed past what a single core
+//! manages without skipping frames. Frames are shown one frame late,
+//! the one being coloured while the next is emulated.
+
+use std::{
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread,
+};
+
+use argentum_core::{FrameInfo, RawFrame};
+
+pub struct ColourWorker {
+    /// Raw frames waiting to be coloured.
+    jobs: SyncSender<RawFrame>,
+
+    /// Coloured frames and their layout.
+    frames: Receiver<(Vec<u8>, FrameInfo)>,
+
+    /// Buffers to colour the next frames into.
+    spare_buffers: SyncSender<Vec<u8>>,
+
+    /// The most recent coloured frame, if any.
+    latest: Option<(Vec<u8>, FrameInfo)>,
+}
+
+impl ColourWorker {
+    /// Create a new `ColourWorker` instance, starting its thread.
+    pub fn new() -> Self {
+        // A single raw frame can wait while another is coloured,
+        // beyond that emulation waits for the worker to catch up.
+        let (jobs, job_receiver) = mpsc::sync_channel::<RawFrame>(1);
+        let (frame_sender, frames) = mpsc::channel();
+        let (spare_buffers, spare_receiver) = mpsc::sync_channel::<Vec<u8>>(2);
+
+        thread::spawn(move || {
+            for raw_frame in job_receiver {
+                let info = raw_frame.info;
+                let mut framebuffer = spare_receiver.try_recv().unwrap_or_default();
+
+                framebuffer.resize(info.pitch * info.height, 0);
+                raw_frame.write_rgb(&mut framebuffer);
+
+                if frame_sender.send((framebuffer, info)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            jobs,
+            frames,
+            spare_buffers,
+            latest: None,
+        }
+    }
+
+    /// Queue a frame to be coloured, waiting if the worker is behind.
+    pub fn submit(&mut self, raw_frame: RawFrame) {
+        // The thread only stops once the worker is dropped.
+        let _ = self.jobs.send(raw_frame);
+    }
+
+    /// Get the most recent coloured frame, `None` until the first is done.
+    pub fn latest(&mut self) -> Option<(&[u8], FrameInfo)> {
+        for frame in self.frames.try_iter() {
+            if let Some((buffer, _)) = self.latest.replace(frame) {
+                let _ = self.spare_buffers.try_send(buffer);
+            }
+        }
+
+        self.latest
+            .as_ref()
+            .map(|(framebuffer, info)| (framebuffer.as_slice(), *info))
+    }
+}
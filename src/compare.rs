@@ -0,0 +1,251 @@
+//! The `compare` subcommand, which replays an input script from a save
+//! state and finds the first frame whose screen differs from a
+//! reference run, to compare speedrun routes and verify glitches.
+//!
+//! `argentum compare <ROM> <STATE> <INPUTS> --reference <INPUTS>` runs
+//! both scripts from the state. A run can also be recorded with
+//! `--record <FILE>`, a hash of the screen per line, and compared
+//! against later with `--against <FILE>`, for example to check a route
+//! still plays out the same way in a newer build. The frames of input
+//! scripts count from the frame after the state was saved.
+//!
+//! The exit status is 1 if the screens differ.
+
+use std::path::{Path, PathBuf};
+
+use argentum_core::{Argentum, DeterminismMode, InputEvent};
+use argentum_frontend_utils::encode_rgb_png;
+use clap::Clap;
+
+use crate::input_script::load_input_script;
+
+/// Frames run past the last input by default, for
+/// the screen to catch up with the last key press.
+const TRAILING_FRAMES: u32 = 60;
+
+#[derive(Clap)]
+pub struct Compare {
+    /// The Game Boy ROM file the save state belongs to.
+    #[clap(parse(from_os_str))]
+    rom_file: PathBuf,
+
+    /// The save state both runs start from.
+    #[clap(parse(from_os_str))]
+    state_file: PathBuf,
+
+    /// The input script to replay.
+    #[clap(parse(from_os_str))]
+    input_script: PathBuf,
+
+    /// The input script of the reference run.
+    #[clap(long, parse(from_os_str), conflicts_with = "against")]
+    reference: Option<PathBuf>,
+
+    /// Screen hashes recorded with `--record` to compare against.
+    #[clap(long, parse(from_os_str))]
+    against: Option<PathBuf>,
+
+    /// Write the screen hash of every frame to a file.
+    #[clap(long, parse(from_os_str))]
+    record: Option<PathBuf>,
+
+    /// How many frames to run, by default 60 past the last input.
+    #[clap(long)]
+    frames: Option<u32>,
+
+    /// Write the screens of the first differing frame to this directory.
+    #[clap(long, parse(from_os_str))]
+    screenshots: Option<PathBuf>,
+}
+
+/// Replays an input script from a save state, one frame at a time.
+struct Replay {
+    gb: Argentum,
+    inputs: Vec<InputEvent>,
+    frame: u32,
+}
+
+impl Replay {
+    fn new(rom: &[u8], state: &[u8], inputs: Vec<InputEvent>) -> Result<Self, String> {
+        let mut gb = Argentum::new(rom, Box::new(|_| {}), None)
+            .map_err(|err| format!("Failed to load the ROM: {}", err))?;
+
+        gb.load_state(state)
+            .map_err(|err| format!("Failed to load the save state: {}", err))?;
+
+        // The host clock would make the runs differ.
+        gb.set_determinism_mode(DeterminismMode::Strict);
+
+        Ok(Self {
+            gb,
+            inputs,
+            frame: 0,
+        })
+    }
+
+    /// Run the next frame, returning the hash of its screen.
+    fn next_frame(&mut self) -> u64 {
+        let frame = self.frame;
+
+        for input in self.inputs.iter().filter(|input| input.frame == frame) {
+            if input.pressed {
+                self.gb.key_down(input.key);
+            } else {
+                self.gb.key_up(input.key);
+            }
+        }
+
+        self.gb.execute_frame();
+        self.frame += 1;
+
+        self.gb.frame_hash()
+    }
+
+    fn last_input_frame(&self) -> u32 {
+        self.inputs
+            .iter()
+            .map(|input| input.frame)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn write_screenshot(&self, path: &Path) -> Result<(), String> {
+        let (framebuffer, info) = self.gb.get_framebuffer();
+        let png = encode_rgb_png(info.width, info.height, info.pitch, framebuffer);
+
+        std::fs::write(path, png)
+            .map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+    }
+}
+
+/// Read hashes written by `--record`, one per line in hexadecimal.
+fn load_hashes(path: &Path) -> Result<Vec<u64>, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read the recorded hashes: {}", err))?;
+
+    contents
+        .lines()
+        .enumerate()
+        .map(|(number, line)| {
+            u64::from_str_radix(line.trim(), 16)
+                .map_err(|_| format!("Line {} of the recorded hashes is malformed.", number + 1))
+        })
+        .collect()
+}
+
+/// Run the frames, returning the first one whose screen differs
+/// and how many frames had something to compare against.
+fn compare(command: &Compare) -> Result<(Option<u32>, u32), String> {
+    let rom = std::fs::read(&command.rom_file)
+        .map_err(|err| format!("Failed to read the ROM: {}", err))?;
+
+    let state = std::fs::read(&command.state_file)
+        .map_err(|err| format!("Failed to read the save state: {}", err))?;
+
+    let inputs = load_input_script(&command.input_script)
+        .map_err(|err| format!("Failed to load the input script: {}", err))?;
+
+    let mut run = Replay::new(&rom, &state, inputs)?;
+
+    let mut reference = match &command.reference {
+        Some(path) => {
+            let inputs = load_input_script(path)
+                .map_err(|err| format!("Failed to load the reference input script: {}", err))?;
+
+            Some(Replay::new(&rom, &state, inputs)?)
+        }
+
+        None => None,
+    };
+
+    let recorded = match &command.against {
+        Some(path) => Some(load_hashes(path)?),
+        None => None,
+    };
+
+    let last_input = reference
+        .iter()
+        .map(Replay::last_input_frame)
+        .fold(run.last_input_frame(), u32::max);
+
+    let frames = match (command.frames, &recorded) {
+        (Some(frames), _) => frames,
+        (None, Some(recorded)) => recorded.len() as u32,
+        (None, None) => last_input + TRAILING_FRAMES,
+    };
+
+    let mut hashes = Vec::with_capacity(frames as usize);
+    let mut first_difference = None;
+    let mut compared = 0;
+
+    for frame in 0..frames {
+        let hash = run.next_frame();
+
+        hashes.push(hash);
+
+        let expected = match (&mut reference, &recorded) {
+            (Some(reference), _) => Some(reference.next_frame()),
+            (None, Some(recorded)) => recorded.get(frame as usize).copied(),
+            (None, None) => None,
+        };
+
+        match expected {
+            Some(expected) if expected != hash => {
+                first_difference = Some(frame);
+                break;
+            }
+
+            Some(_) => compared += 1,
+            None => {}
+        }
+    }
+
+    if let Some(path) = &command.record {
+        let contents: String = hashes
+            .iter()
+            .map(|hash| format!("{:016X}\n", hash))
+            .collect();
+
+        std::fs::write(path, contents)
+            .map_err(|err| format!("Failed to write the hashes: {}", err))?;
+    }
+
+    if let (Some(frame), Some(dir)) = (first_difference, &command.screenshots) {
+        std::fs::create_dir_all(dir)
+            .map_err(|err| format!("Failed to create the screenshot directory: {}", err))?;
+
+        run.write_screenshot(&dir.join(format!("frame-{}.png", frame)))?;
+
+        if let Some(reference) = &reference {
+            reference.write_screenshot(&dir.join(format!("frame-{}-reference.png", frame)))?;
+        }
+    }
+
+    Ok((first_difference, compared))
+}
+
+/// Compare the runs, exiting with status 1 if the screens differ.
+pub fn run(command: Compare) {
+    if command.reference.is_none() && command.against.is_none() && command.record.is_none() {
+        eprintln!("Nothing to do, pass --reference, --against or --record.");
+        std::process::exit(1);
+    }
+
+    match compare(&command) {
+        Ok((Some(frame), _)) => {
+            println!("The screens first differ on frame {}.", frame);
+            std::process::exit(1);
+        }
+
+        Ok((None, compared)) if command.reference.is_some() || command.against.is_some() => {
+            println!("The screens matched for all {} frames.", compared);
+        }
+
+        Ok((None, _)) => {}
+
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,346 @@
+//! The `compat-scan` subcommand, which checks how well a directory of
+//! ROMs runs and writes a report that can be shared.
+//!
+//! `argentum compat-scan <DIR>` boots every ROM headlessly for a number
+//! of frames. ROMs that panic the emulator, lock up the CPU, halt with
+//! nothing left to wake them, never draw anything or have a cartridge
+//! type no mapper supports are flagged, and
+//! the CRC-32 of the last frame is recorded so runs can be compared.
+
+use std::{
+    fmt::Write as _,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use argentum_core::{Argentum, ArgentumEvent};
+use argentum_frontend_utils::crc32;
+use clap::Clap;
+
+/// A CPU that spent this many frames in a row halted is
+/// considered hung, nothing is going to wake it up.
+const HALTED_FRAMES: u32 = 60;
+
+#[derive(Clap)]
+pub struct CompatScan {
+    /// The directory with the ROMs to check.
+    #[clap(parse(from_os_str))]
+    rom_dir: PathBuf,
+
+    /// How many frames to run every ROM for.
+    #[clap(long, default_value = "600")]
+    frames: u32,
+
+    /// Where to write the CSV report.
+    #[clap(long, parse(from_os_str), default_value = "compat.csv")]
+    csv: PathBuf,
+
+    /// Where to write an HTML report, if anywhere.
+    #[clap(long, parse(from_os_str))]
+    html: Option<PathBuf>,
+}
+
+/// How a ROM fared.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+
+    /// The emulator panicked.
+    Crash,
+
+    /// The CPU locked up or halted for good.
+    Hang,
+
+    /// The ROM ran, but the screen stayed a single colour.
+    Blank,
+
+    /// No mapper handles the cartridge type.
+    Unsupported,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Crash => "crash",
+            Status::Hang => "hang",
+            Status::Blank => "blank",
+            Status::Unsupported => "unsupported",
+        }
+    }
+}
+
+/// The result of running one ROM.
+struct RomReport {
+    file: String,
+    title: String,
+    status: Status,
+
+    /// How many frames ran before the ROM crashed or hung.
+    frames: u32,
+
+    /// The CRC-32 of the last frame, if one was drawn.
+    screenshot: Option<u32>,
+
+    /// What went wrong, in words.
+    details: String,
+}
+
+/// Check if every pixel of the frame has the same colour.
+fn is_blank(framebuffer: &[u8], bytes_per_pixel: usize) -> bool {
+    let mut pixels = framebuffer.chunks(bytes_per_pixel);
+
+    match pixels.next() {
+        Some(first) => pixels.all(|pixel| pixel == first),
+        None => true,
+    }
+}
+
+/// Run the ROM for the given number of frames.
+fn run_rom(rom: &[u8], frames: u32, report: &mut RomReport) {
+    let mut gb = match Argentum::new(rom, Box::new(|_| {}), None) {
+        Ok(gb) => gb,
+
+        Err(err) => {
+            report.status = Status::Unsupported;
+            report.details = err.to_string();
+
+            return;
+        }
+    };
+
+    gb.skip_bootrom();
+    report.title = gb.cartridge_info().title.trim_end_matches('\0').to_string();
+
+    let mut halted_frames = 0;
+
+    while report.frames < frames {
+        gb.execute_frame();
+        report.frames += 1;
+
+        while let Some(event) = gb.poll_event() {
+            if let ArgentumEvent::CpuLocked { opcode, address } = event {
+                report.status = Status::Hang;
+                report.details = format!("illegal opcode {:02X} at {}", opcode, address);
+            }
+        }
+
+        if report.status == Status::Hang {
+            return;
+        }
+
+        if gb.cpu_usage().load() == 0.0 {
+            halted_frames += 1;
+        } else {
+            halted_frames = 0;
+        }
+
+        if halted_frames == HALTED_FRAMES {
+            report.status = Status::Hang;
+            report.details = "halted with nothing to wake the CPU".to_string();
+
+            return;
+        }
+    }
+
+    let (framebuffer, info) = gb.get_framebuffer();
+
+    report.screenshot = Some(crc32(framebuffer));
+
+    if is_blank(framebuffer, info.pitch / info.width) {
+        report.status = Status::Blank;
+        report.details = "the screen stayed a single colour".to_string();
+    }
+}
+
+/// Boot the ROM headlessly and see how it runs.
+fn scan_rom(path: &Path, frames: u32) -> RomReport {
+    let mut report = RomReport {
+        file: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        title: String::new(),
+        status: Status::Ok,
+        frames: 0,
+        screenshot: None,
+        details: String::new(),
+    };
+
+    let rom = match std::fs::read(path) {
+        Ok(rom) => rom,
+
+        Err(err) => {
+            report.status = Status::Crash;
+            report.details = format!("failed to read the ROM: {}", err);
+
+            return report;
+        }
+    };
+
+    // Mappers trust the header and bad headers can panic them, that
+    // is reported as a crash instead of ending the scan.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| run_rom(&rom, frames, &mut report)));
+
+    if let Err(payload) = result {
+        report.status = Status::Crash;
+
+        report.details = match payload.downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => payload
+                .downcast_ref::<String>()
+                .cloned()
+                .unwrap_or_else(|| "the emulator panicked".to_string()),
+        };
+    }
+
+    report
+}
+
+/// Quote a CSV field if it needs to be.
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn screenshot_hash(report: &RomReport) -> String {
+    report
+        .screenshot
+        .map(|crc| format!("{:08X}", crc))
+        .unwrap_or_default()
+}
+
+fn write_csv(path: &Path, reports: &[RomReport]) -> Result<(), String> {
+    let mut csv = String::from("file,title,status,frames,screenshot_crc32,details\n");
+
+    for report in reports {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{}",
+            csv_field(&report.file),
+            csv_field(&report.title),
+            report.status.name(),
+            report.frames,
+            screenshot_hash(report),
+            csv_field(&report.details)
+        );
+    }
+
+    std::fs::write(path, csv).map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+}
+
+fn write_html(path: &Path, reports: &[RomReport]) -> Result<(), String> {
+    let working = reports
+        .iter()
+        .filter(|report| report.status == Status::Ok)
+        .count();
+
+    let mut html = String::from(concat!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n",
+        "<title>Argentum GB compatibility report</title>\n<style>\n",
+        "body { font-family: sans-serif; }\n",
+        "table { border-collapse: collapse; }\n",
+        "td, th { border: 1px solid #888; padding: 2px 8px; text-align: left; }\n",
+        ".ok { background: #c8f0c8; }\n",
+        ".blank, .unsupported { background: #f0e8b0; }\n",
+        ".hang, .crash { background: #f0c0c0; }\n",
+        "</style>\n</head>\n<body>\n",
+        "<h1>Argentum GB compatibility report</h1>\n",
+    ));
+
+    let _ = writeln!(
+        html,
+        "<p>{} of {} ROMs ran without problems.</p>",
+        working,
+        reports.len()
+    );
+
+    html.push_str(
+        "<table>\n<tr><th>File</th><th>Title</th><th>Status</th>\
+         <th>Frames</th><th>Screenshot CRC-32</th><th>Details</th></tr>\n",
+    );
+
+    for report in reports {
+        let _ = writeln!(
+            html,
+            "<tr class=\"{status}\"><td>{}</td><td>{}</td><td>{status}</td>\
+             <td>{}</td><td>{}</td><td>{}</td></tr>",
+            html_escape(&report.file),
+            html_escape(&report.title),
+            report.frames,
+            screenshot_hash(report),
+            html_escape(&report.details),
+            status = report.status.name()
+        );
+    }
+
+    html.push_str("</table>\n</body>\n</html>\n");
+
+    std::fs::write(path, html).map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+}
+
+fn scan(opts: &CompatScan) -> Result<(), String> {
+    let entries = std::fs::read_dir(&opts.rom_dir)
+        .map_err(|err| format!("Failed to read the ROM directory: {}", err))?;
+
+    let mut roms: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            let extension = path.extension().and_then(|extension| extension.to_str());
+
+            match extension {
+                Some(extension) => matches!(extension.to_ascii_lowercase().as_str(), "gb" | "gbc"),
+                None => false,
+            }
+        })
+        .collect();
+
+    if roms.is_empty() {
+        return Err("The directory has no .gb or .gbc files.".to_string());
+    }
+
+    roms.sort();
+
+    // Crashes are reported per ROM, the default hook would
+    // print a backtrace for every one of them.
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut reports = Vec::with_capacity(roms.len());
+
+    for path in &roms {
+        let report = scan_rom(path, opts.frames);
+
+        println!("{}: {}", report.file, report.status.name());
+
+        reports.push(report);
+    }
+
+    let _ = panic::take_hook();
+
+    write_csv(&opts.csv, &reports)?;
+
+    if let Some(html) = &opts.html {
+        write_html(html, &reports)?;
+    }
+
+    Ok(())
+}
+
+/// Run the `compat-scan` subcommand, exiting with status 1 if it fails.
+pub fn run(opts: CompatScan) {
+    if let Err(err) = scan(&opts) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,208 @@
+//! The frontend's persistent settings, stored as TOML.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use argentum_core::ArgentumKey;
+use serde::{Deserialize, Serialize};
+
+/// The number of save state slots.
+pub const STATE_SLOTS: u8 = 10;
+
+/// Settings that persist between runs.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The code of the language the menus are shown in.
+    pub language: String,
+
+    /// The name of the DMG palette, see `palette.rs`.
+    pub palette: String,
+
+    /// Increase the separation between the shades of the palette.
+    pub high_contrast: bool,
+
+    /// Only scale the screen by whole numbers.
+    pub integer_scale: bool,
+
+    /// The colour around the screen, as `#RRGGBB`.
+    pub background_colour: String,
+
+    /// Clockwise rotation of the screen in degrees, 0, 90, 180 or 270.
+    pub rotation: u16,
+
+    /// Mirror the screen horizontally.
+    pub mirror: bool,
+
+    /// The audio volume in percent.
+    pub volume: u8,
+
+    /// The audio sample rate in Hz.
+    pub sample_rate: u32,
+
+    /// The size of the audio device's buffer in samples, a power of two.
+    /// Larger buffers add latency but stop crackling on slow machines.
+    pub audio_buffer: u16,
+
+    /// The save state slot used by the hotkeys.
+    pub state_slot: u8,
+
+    /// Save the state every this many minutes, 0 disables it. The
+    /// state is also saved on exit, `--resume` continues from it.
+    pub autosave_minutes: u32,
+
+    /// Memory for rewinding, holding Backspace, in megabytes. Older
+    /// frames are thinned out to make it last longer, 0 disables it.
+    pub rewind_megabytes: u32,
+
+    /// The keyboard bindings.
+    pub keys: KeyConfig,
+
+    /// Keys that play input macros, by the name of the macro.
+    pub macro_keys: BTreeMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            language: String::from("en"),
+            palette: String::from("original"),
+            high_contrast: false,
+            integer_scale: false,
+            background_colour: String::from("#000000"),
+            rotation: 0,
+            mirror: false,
+            volume: 100,
+            sample_rate: 48000,
+            audio_buffer: 1024,
+            state_slot: 0,
+            autosave_minutes: 0,
+            rewind_megabytes: 64,
+            keys: KeyConfig::default(),
+            macro_keys: BTreeMap::new(),
+        }
+    }
+}
+
+/// Keyboard bindings, stored as SDL scancode names.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyConfig {
+    pub up: String,
+    pub down: String,
+    pub left: String,
+    pub right: String,
+    pub a: String,
+    pub b: String,
+    pub start: String,
+    pub select: String,
+}
+
+impl Default for KeyConfig {
+    fn default() -> Self {
+        Self {
+            up: String::from("W"),
+            down: String::from("S"),
+            left: String::from("A"),
+            right: String::from("D"),
+            a: String::from("Z"),
+            b: String::from("X"),
+            start: String::from("Return"),
+            select: String::from("Space"),
+        }
+    }
+}
+
+impl KeyConfig {
+    /// Get the binding of every Game Boy button.
+    pub fn bindings(&self) -> [(ArgentumKey, &str); 8] {
+        [
+            (ArgentumKey::Up, &self.up),
+            (ArgentumKey::Down, &self.down),
+            (ArgentumKey::Left, &self.left),
+            (ArgentumKey::Right, &self.right),
+            (ArgentumKey::ButtonA, &self.a),
+            (ArgentumKey::ButtonB, &self.b),
+            (ArgentumKey::Start, &self.start),
+            (ArgentumKey::Select, &self.select),
+        ]
+    }
+
+    /// Get the binding of a single Game Boy button.
+    pub fn binding_mut(&mut self, key: ArgentumKey) -> &mut String {
+        match key {
+            ArgentumKey::Up => &mut self.up,
+            ArgentumKey::Down => &mut self.down,
+            ArgentumKey::Left => &mut self.left,
+            ArgentumKey::Right => &mut self.right,
+            ArgentumKey::ButtonA => &mut self.a,
+            ArgentumKey::ButtonB => &mut self.b,
+            ArgentumKey::Start => &mut self.start,
+            ArgentumKey::Select => &mut self.select,
+        }
+    }
+}
+
+impl Config {
+    /// Parse the background colour, falling back to black.
+    pub fn background_rgb(&self) -> u32 {
+        self.background_colour
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6)
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .unwrap_or(0x000000)
+    }
+
+    /// Get the audio sample rate, kept within what SDL can open.
+    pub fn audio_sample_rate(&self) -> u32 {
+        self.sample_rate.clamp(8000, 192000)
+    }
+
+    /// Get the audio buffer size, rounded up to a power of two.
+    pub fn audio_buffer_samples(&self) -> u16 {
+        self.audio_buffer.clamp(256, 8192).next_power_of_two()
+    }
+
+    /// Rotate the screen by another 90 degrees clockwise.
+    pub fn rotate(&mut self) {
+        self.rotation = match self.rotation {
+            0 => 90,
+            90 => 180,
+            180 => 270,
+            _ => 0,
+        };
+    }
+
+    /// The default location of the config file.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("argentum").join("config.toml"))
+    }
+
+    /// Load the config from the given path, a missing or
+    /// invalid file results in the default config.
+    pub fn load(path: &Path) -> Self {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+
+        toml::from_str(&contents).unwrap_or_else(|err| {
+            eprintln!("Failed to parse the config file, using defaults: {}", err);
+
+            Self::default()
+        })
+    }
+
+    /// Save the config to the given path, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let contents = toml::to_string(self).expect("Failed to serialize the config.");
+
+        std::fs::write(path, contents)
+    }
+}
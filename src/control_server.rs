@@ -0,0 +1,267 @@
+//! A WebSocket server on the local machine that lets dashboards and
+//! test tools drive the emulator. It only listens on 127.0.0.1, and
+//! turns away web pages served from elsewhere, as there is no login.
+//!
+//! Clients send text commands and get one message back for each:
+//! - `status` answers `running` or `paused`.
+//! - `pause` and `resume` stop and continue the emulation.
+//! - `save-state` answers a save state as a binary message, sending
+//!   a binary message loads the state in it.
+//! - `read ADDR [LENGTH]` answers `LENGTH` bytes from the hexadecimal
+//!   address `ADDR` as hex, `write ADDR BYTES` writes the hex bytes
+//!   from there on.
+//! - `screenshot` answers the current frame as a PNG.
+//!
+//! Failed commands are answered with `error: ` and the reason.
+//!
+//! Every client gets its own thread, which hands the commands to the
+//! emulation thread over a channel. They are handled between frames,
+//! so slow clients never hold up the emulation.
+
+use std::{
+    io::{self, BufReader},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use argentum_core::Argentum;
+use argentum_frontend_utils::encode_rgb_png;
+
+use crate::websocket::{self, Message};
+
+/// A message from a client, with where to send the answer.
+struct Request {
+    message: Message,
+    reply: Sender<Message>,
+}
+
+/// Parse hex bytes, whitespace between them is allowed.
+fn parse_hex_bytes(text: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = text
+        .bytes()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect();
+
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+fn error(text: &str) -> Message {
+    Message::Text(format!("error: {}", text))
+}
+
+/// Talk to one client until it goes away, or the emulator does.
+fn serve_client(stream: TcpStream, requests: &Sender<Request>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    websocket::accept(&mut reader, &mut writer)?;
+
+    let (reply, replies) = mpsc::channel();
+
+    while let Some(message) = websocket::read_message(&mut reader, &mut writer)? {
+        let request = Request {
+            message,
+            reply: reply.clone(),
+        };
+
+        if requests.send(request).is_err() {
+            break;
+        }
+
+        match replies.recv() {
+            Ok(message) => websocket::write_message(&mut writer, &message)?,
+            Err(_) => break,
+        }
+    }
+
+    Ok(())
+}
+
+/// Serves control commands, see the module documentation.
+pub struct ControlServer {
+    requests: Receiver<Request>,
+
+    /// Set while a client has the emulation paused.
+    paused: bool,
+}
+
+impl ControlServer {
+    /// Start listening for clients on the given port. Only programs
+    /// on this machine can connect, there is no authentication.
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        let (sender, requests) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+
+                thread::spawn(move || {
+                    // Clients that break the protocol are just dropped.
+                    let _ = serve_client(stream, &sender);
+                });
+            }
+        });
+
+        Ok(Self {
+            requests,
+            paused: false,
+        })
+    }
+
+    /// Check if a client paused the emulation.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Handle every command that is waiting, without blocking.
+    pub fn poll(&mut self, gb: &mut Argentum) {
+        while let Ok(request) = self.requests.try_recv() {
+            let reply = match request.message {
+                Message::Text(command) => self.handle(&command, gb),
+
+                Message::Binary(state) => match gb.load_state(&state) {
+                    Ok(()) => Message::Text("loaded".to_string()),
+                    Err(err) => error(&err.to_string()),
+                },
+            };
+
+            // The client may have gone away in the meantime.
+            let _ = request.reply.send(reply);
+        }
+    }
+
+    fn handle(&mut self, command: &str, gb: &mut Argentum) -> Message {
+        let mut words = command.split_whitespace();
+        let address = |word: Option<&str>| word.and_then(|addr| u16::from_str_radix(addr, 16).ok());
+
+        match words.next() {
+            Some("status") => {
+                Message::Text(if self.paused { "paused" } else { "running" }.to_string())
+            }
+
+            Some("pause") => {
+                self.paused = true;
+                Message::Text("paused".to_string())
+            }
+
+            Some("resume") => {
+                self.paused = false;
+                Message::Text("running".to_string())
+            }
+
+            Some("save-state") => Message::Binary(gb.save_state()),
+
+            Some("screenshot") => {
+                let (framebuffer, info) = gb.get_framebuffer();
+
+                Message::Binary(encode_rgb_png(
+                    info.width,
+                    info.height,
+                    info.pitch,
+                    framebuffer,
+                ))
+            }
+
+            Some("read") => {
+                let address = address(words.next());
+
+                let length = match words.next() {
+                    Some(length) => length.parse::<usize>().ok(),
+                    None => Some(1),
+                };
+
+                match (address, length) {
+                    (Some(address), Some(length)) => {
+                        // Reads stop at the end of the address space.
+                        let end = (address as usize).saturating_add(length).min(0x10000);
+
+                        let bytes: Vec<String> = (address as usize..end)
+                            .map(|addr| format!("{:02X}", gb.peek_byte(addr as u16)))
+                            .collect();
+
+                        Message::Text(bytes.join(" "))
+                    }
+
+                    _ => error("invalid address or length"),
+                }
+            }
+
+            Some("write") => {
+                let address = address(words.next());
+                let bytes = parse_hex_bytes(&words.collect::<String>());
+
+                match (address, bytes) {
+                    (Some(address), Some(bytes)) if address as usize + bytes.len() <= 0x10000 => {
+                        for (offset, &value) in bytes.iter().enumerate() {
+                            gb.poke_byte(address + offset as u16, value);
+                        }
+
+                        Message::Text("written".to_string())
+                    }
+
+                    _ => error("invalid address or bytes"),
+                }
+            }
+
+            _ => error("unknown command"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server() -> ControlServer {
+        let (_, requests) = mpsc::channel();
+
+        ControlServer {
+            requests,
+            paused: false,
+        }
+    }
+
+    fn text(message: Message) -> String {
+        match message {
+            Message::Text(text) => text,
+            Message::Binary(_) => panic!("expected a text message"),
+        }
+    }
+
+    #[test]
+    fn read_and_write_memory() {
+        let mut gb = Argentum::new(&[0; 0x8000], Box::new(|_| {}), None).unwrap();
+        let mut server = server();
+
+        assert_eq!(
+            text(server.handle("write C000 12 34ab", &mut gb)),
+            "written"
+        );
+        assert_eq!(text(server.handle("read C000 3", &mut gb)), "12 34 AB");
+        assert_eq!(text(server.handle("read C000", &mut gb)), "12");
+        assert!(text(server.handle("write FFFF 12 34", &mut gb)).starts_with("error"));
+    }
+
+    #[test]
+    fn reads_stop_at_the_end_of_memory() {
+        let mut gb = Argentum::new(&[0; 0x8000], Box::new(|_| {}), None).unwrap();
+        let mut server = server();
+
+        let bytes = text(server.handle("read FFFE 18446744073709551615", &mut gb));
+
+        assert_eq!(bytes.split(' ').count(), 2);
+
+        let bytes = text(server.handle("read 0 18446744073709551615", &mut gb));
+
+        assert_eq!(bytes.split(' ').count(), 0x10000);
+    }
+}
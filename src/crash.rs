@@ -0,0 +1,139 @@
+//! Writes a bundle of everything needed to reproduce a crash.
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt::Write as _,
+    io,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use argentum_core::Argentum;
+use argentum_frontend_utils::crc32;
+
+use crate::config::Config;
+
+/// The number of trace lines kept for crash reports.
+const TRACE_LINES: usize = 1000;
+
+/// The message of the last panic, including where it happened.
+static LAST_PANIC: Mutex<Option<String>> = Mutex::new(None);
+
+/// Remember panic messages for the crash report, on top of printing them.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        if let Ok(mut last_panic) = LAST_PANIC.lock() {
+            *last_panic = Some(info.to_string());
+        }
+
+        default_hook(info);
+    }));
+}
+
+/// The most recent trace lines, shared with the trace callback.
+#[derive(Clone, Default)]
+pub struct TraceHistory(Rc<RefCell<VecDeque<String>>>);
+
+impl TraceHistory {
+    /// Add a line, dropping the oldest one if the history is full.
+    pub fn push(&self, line: &str) {
+        let mut lines = self.0.borrow_mut();
+
+        if lines.len() == TRACE_LINES {
+            lines.pop_front();
+        }
+
+        lines.push_back(String::from(line));
+    }
+}
+
+pub struct CrashReporter {
+    /// Crash bundles are written into this directory.
+    directory: PathBuf,
+
+    /// CRC32 of the ROM.
+    rom_crc: u32,
+
+    /// The name of the ROM file.
+    rom_name: String,
+
+    /// Trace lines, if tracing is enabled.
+    trace: Option<TraceHistory>,
+}
+
+impl CrashReporter {
+    /// Create a new `CrashReporter` instance.
+    pub fn new(rom_path: &Path, rom: &[u8], trace: Option<TraceHistory>) -> Self {
+        let directory = rom_path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        let rom_name = rom_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        Self {
+            directory,
+            rom_crc: crc32(rom),
+            rom_name,
+            trace,
+        }
+    }
+
+    /// Write a crash bundle and return the directory it was written to.
+    pub fn write_bundle(&self, argentum: &Argentum, config: &Config) -> io::Result<PathBuf> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+
+        let bundle = self.directory.join(format!("argentum-crash-{}", timestamp));
+
+        std::fs::create_dir_all(&bundle)?;
+
+        let mut report = String::new();
+
+        let panic_message = LAST_PANIC.lock().ok().and_then(|last| last.clone());
+
+        let _ = writeln!(report, "Argentum {}", env!("CARGO_PKG_VERSION"));
+        let _ = writeln!(report, "ROM: {}", self.rom_name);
+        let _ = writeln!(report, "ROM CRC32: {:08X}", self.rom_crc);
+        let _ = writeln!(report);
+        let _ = writeln!(
+            report,
+            "{}",
+            panic_message.as_deref().unwrap_or("Unknown panic.")
+        );
+
+        std::fs::write(bundle.join("report.txt"), report)?;
+
+        // The emulator may be in a state that makes saving panic too,
+        // the rest of the bundle is still useful without it.
+        match panic::catch_unwind(AssertUnwindSafe(|| argentum.save_state())) {
+            Ok(state) => std::fs::write(bundle.join("state.bin"), state)?,
+            Err(_) => eprintln!("Failed to save the state for the crash report."),
+        }
+
+        if let Ok(config) = toml::to_string(config) {
+            std::fs::write(bundle.join("config.toml"), config)?;
+        }
+
+        if let Some(trace) = &self.trace {
+            let mut lines = String::new();
+
+            for line in trace.0.borrow().iter() {
+                lines.push_str(line);
+                lines.push('\n');
+            }
+
+            std::fs::write(bundle.join("trace.txt"), lines)?;
+        }
+
+        Ok(bundle)
+    }
+}
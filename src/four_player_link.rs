@@ -0,0 +1,276 @@
+//! The four player adapter (DMG-07) over TCP, so that four Argentums
+//! can play together.
+//!
+//! The emulator hosting the adapter is player 1, the others connect
+//! to it and become players 2 to 4 in the order they join. The adapter
+//! runs on a thread of the host. Every byte a player's game waits to
+//! have clocked is sent to it, and the byte the adapter clocks back
+//! is the answer. Both are single bytes on the connection.
+
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use argentum_core::{AdapterPort, FourPlayerAdapter, LinkPartner};
+
+/// Players the adapter can link.
+const PLAYERS: usize = 4;
+
+/// Where the adapter sends the bytes it clocks to a player.
+enum Output {
+    Local(Sender<u8>),
+    Remote(TcpStream),
+}
+
+impl Output {
+    fn send(&mut self, byte: u8) -> io::Result<()> {
+        match self {
+            Self::Local(sender) => sender
+                .send(byte)
+                .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe)),
+
+            Self::Remote(stream) => stream.write_all(&[byte]),
+        }
+    }
+}
+
+/// What the thread running the adapter is told.
+enum Event {
+    /// A player plugged into the port.
+    Joined(usize, Output),
+
+    /// A player waits for the adapter to clock the byte.
+    Waiting(usize, u8),
+}
+
+/// A player plugged into the adapter.
+struct Player {
+    port: AdapterPort,
+    output: Output,
+
+    /// The byte the player waits to have clocked.
+    waiting: Option<u8>,
+}
+
+/// Run the adapter until the host goes away.
+fn run_adapter(events: Receiver<Event>) {
+    let adapter = FourPlayerAdapter::new();
+    let mut players: Vec<Option<Player>> = (0..PLAYERS).map(|_| None).collect();
+
+    for event in events {
+        match event {
+            Event::Joined(player, output) => {
+                players[player] = Some(Player {
+                    port: adapter.port(player),
+                    output,
+                    waiting: None,
+                });
+            }
+
+            Event::Waiting(player, byte) => {
+                if let Some(player) = players[player].as_mut() {
+                    player.waiting = Some(byte);
+                }
+            }
+        }
+
+        // Clocking a player can let one waiting for it go on.
+        let mut clocked = true;
+
+        while clocked {
+            clocked = false;
+
+            for player in players.iter_mut().flatten() {
+                let reply = match player.waiting {
+                    Some(byte) => player.port.external_clock(byte),
+                    None => None,
+                };
+
+                if let Some(reply) = reply {
+                    player.waiting = None;
+                    clocked = true;
+
+                    // Players that left are just never clocked again.
+                    let _ = player.output.send(reply);
+                }
+            }
+        }
+    }
+}
+
+/// Where a player sends the bytes it waits for the adapter to clock.
+enum Uplink {
+    Local(Sender<Event>),
+    Remote(TcpStream),
+}
+
+/// A Game Boy's port of the four player adapter.
+pub struct FourPlayerLink {
+    uplink: Uplink,
+    replies: Receiver<u8>,
+
+    /// Whether the adapter was told about the transfer in progress.
+    waiting: bool,
+}
+
+impl FourPlayerLink {
+    /// Host the adapter as player 1, letting the
+    /// other players join on the address.
+    pub fn host(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+
+        println!(
+            "Waiting for players to join the four player adapter on {}.",
+            address
+        );
+
+        Self::host_on(listener)
+    }
+
+    fn host_on(listener: TcpListener) -> io::Result<Self> {
+        let (events, receiver) = mpsc::channel();
+        let (replies_sender, replies) = mpsc::channel();
+
+        thread::spawn(move || run_adapter(receiver));
+
+        events
+            .send(Event::Joined(0, Output::Local(replies_sender)))
+            .expect("the adapter thread stopped");
+
+        let accept_events = events.clone();
+
+        thread::spawn(move || {
+            for player in 1..PLAYERS {
+                if let Err(err) = accept_player(&listener, player, &accept_events) {
+                    eprintln!("Player {} failed to join: {}", player + 1, err);
+                }
+            }
+        });
+
+        Ok(Self {
+            uplink: Uplink::Local(events),
+            replies,
+            waiting: false,
+        })
+    }
+
+    /// Join the adapter of the emulator hosting it on the address.
+    pub fn join(address: &str) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        let mut reader = stream.try_clone()?;
+
+        stream.set_nodelay(true)?;
+
+        let (sender, replies) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut byte = [0];
+
+            while reader.read_exact(&mut byte).is_ok() && sender.send(byte[0]).is_ok() {}
+
+            eprintln!("The four player adapter was disconnected.");
+        });
+
+        Ok(Self {
+            uplink: Uplink::Remote(stream),
+            replies,
+            waiting: false,
+        })
+    }
+}
+
+/// Wait for the next player to join, and pass its bytes on to the adapter.
+fn accept_player(listener: &TcpListener, player: usize, events: &Sender<Event>) -> io::Result<()> {
+    let (mut stream, address) = listener.accept()?;
+
+    stream.set_nodelay(true)?;
+
+    println!("Player {} joined from {}.", player + 1, address);
+
+    let output = Output::Remote(stream.try_clone()?);
+    let events = events.clone();
+
+    if events.send(Event::Joined(player, output)).is_err() {
+        return Ok(());
+    }
+
+    thread::spawn(move || {
+        let mut byte = [0];
+
+        while stream.read_exact(&mut byte).is_ok() {
+            if events.send(Event::Waiting(player, byte[0])).is_err() {
+                return;
+            }
+        }
+
+        eprintln!("Player {} left the four player adapter.", player + 1);
+    });
+
+    Ok(())
+}
+
+impl LinkPartner for FourPlayerLink {
+    /// The adapter drives the clock, nothing answers a Game Boy that does.
+    fn exchange(&mut self, _: u8) -> u8 {
+        0xFF
+    }
+
+    fn external_clock(&mut self, byte: u8) -> Option<u8> {
+        if !self.waiting {
+            let sent = match &mut self.uplink {
+                Uplink::Local(events) => events.send(Event::Waiting(0, byte)).is_ok(),
+                Uplink::Remote(stream) => stream.write_all(&[byte]).is_ok(),
+            };
+
+            // Without the adapter the transfer never completes,
+            // like with an unplugged cable.
+            if !sent {
+                return None;
+            }
+
+            self.waiting = true;
+        }
+
+        let reply = self.replies.try_recv().ok()?;
+
+        self.waiting = false;
+
+        Some(reply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clock a byte, waiting for the adapter to answer.
+    fn clock(link: &mut FourPlayerLink, byte: u8) -> u8 {
+        loop {
+            if let Some(reply) = link.external_clock(byte) {
+                return reply;
+            }
+
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn players_join_and_are_pinged() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let mut host = FourPlayerLink::host_on(listener).unwrap();
+        let mut guest = FourPlayerLink::join(&address).unwrap();
+
+        // The guest's first byte can only be clocked once it joined,
+        // by then the host sees both players connected.
+        assert_eq!(clock(&mut guest, 0x88), 0xFE);
+        assert_eq!(clock(&mut guest, 0x88), 0x32);
+
+        assert_eq!(clock(&mut host, 0x88), 0xFE);
+        assert_eq!(clock(&mut host, 0x88), 0x31);
+    }
+}
@@ -0,0 +1,111 @@
+//! Running a ROM without a window, to check the emulator instead of
+//! playing. Every run exits with status 1 if its check fails.
+
+use std::{fs::File, io::BufReader, path::Path};
+
+use argentum_core::{verify_determinism, Argentum, DeterminismMode, InputEvent};
+
+use crate::boot_options::BootOptions;
+use crate::trace_compare::{format_registers, verify_against_trace};
+
+/// Create a silent instance of the ROM, exiting with
+/// status 1 if its cartridge type isn't supported.
+fn new_instance(rom: &[u8], save_file: Option<Vec<u8>>) -> Argentum {
+    Argentum::new(rom, Box::new(|_| {}), save_file).unwrap_or_else(|err| {
+        eprintln!("Failed to load the ROM: {}", err);
+        std::process::exit(1);
+    })
+}
+
+/// Run the ROM twice for `frames` frames, and check that
+/// both runs end up in the same state.
+pub fn check_determinism(
+    rom: &[u8],
+    save_file: Option<Vec<u8>>,
+    boot: &BootOptions,
+    inputs: &[InputEvent],
+    frames: u32,
+) {
+    let runs = [save_file.clone(), save_file].map(|save_file| {
+        let mut gb = new_instance(rom, save_file);
+
+        boot.apply(&mut gb);
+        gb
+    });
+
+    match verify_determinism(runs, inputs, frames) {
+        Ok(hashes) => println!(
+            "Both runs matched, the final state hash is {:016X}.",
+            hashes.last().copied().unwrap_or_default()
+        ),
+
+        Err(divergence) => {
+            eprintln!(
+                "The runs diverged by frame {}, {:016X} != {:016X}.",
+                divergence.frame, divergence.first_hash, divergence.second_hash
+            );
+
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Run the ROM for `frames` frames, then print hashes
+/// of the framebuffer and the whole state.
+pub fn print_frame_hash(
+    rom: &[u8],
+    save_file: Option<Vec<u8>>,
+    boot: &BootOptions,
+    inputs: &[InputEvent],
+    frames: u32,
+) {
+    let mut gb = new_instance(rom, save_file);
+
+    // The host clock would make the hashes differ between runs.
+    gb.set_determinism_mode(DeterminismMode::Strict);
+
+    boot.apply(&mut gb);
+
+    for frame in 0..frames {
+        for input in inputs.iter().filter(|input| input.frame == frame) {
+            if input.pressed {
+                gb.key_down(input.key);
+            } else {
+                gb.key_up(input.key);
+            }
+        }
+
+        gb.execute_frame();
+    }
+
+    println!("frame {:016X}", gb.frame_hash());
+    println!("state {:016X}", gb.state_hash());
+}
+
+/// Run the ROM, comparing the registers before every instruction
+/// with the reference trace, until the first difference.
+pub fn verify_against(rom: &[u8], save_file: Option<Vec<u8>>, boot: &BootOptions, trace: &Path) {
+    let trace = File::open(trace).unwrap_or_else(|err| {
+        eprintln!("Failed to open the reference trace: {}", err);
+        std::process::exit(1);
+    });
+
+    let mut gb = new_instance(rom, save_file);
+
+    boot.apply(&mut gb);
+
+    match verify_against_trace(&mut gb, BufReader::new(trace)) {
+        Ok(instructions) => println!("All {} instructions matched.", instructions),
+
+        Err(mismatch) => {
+            eprintln!(
+                "Line {} of the trace differs, after {} matching instructions.",
+                mismatch.line, mismatch.instructions
+            );
+            eprintln!("Expected {}", format_registers(&mismatch.expected));
+            eprintln!("Got      {}", format_registers(&mismatch.actual));
+
+            std::process::exit(1);
+        }
+    }
+}
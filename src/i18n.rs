@@ -0,0 +1,123 @@
+//! Translations of the text shown in the OSD and pause menu.
+//!
+//! Every locale is a function mapping a `Message` to its text, the
+//! match being exhaustive means a new message can't be forgotten in
+//! any of the locales. The OSD font only covers ASCII, so translations
+//! have to make do without accents.
+
+/// Every piece of text the frontend shows on screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Message {
+    Paused,
+    Resume,
+    Language,
+    Palette,
+    HighContrast,
+    IntegerScale,
+    On,
+    Off,
+    Volume,
+    StateSlot,
+    PressAKey,
+    Reset,
+    Quit,
+    Up,
+    Down,
+    Left,
+    Right,
+    ButtonA,
+    ButtonB,
+    Start,
+    Select,
+}
+
+/// A language the frontend can be displayed in.
+pub struct Locale {
+    /// The code used in the config file.
+    pub code: &'static str,
+
+    /// The name of the language, in the language itself.
+    pub name: &'static str,
+
+    /// Maps messages to their translation.
+    table: fn(Message) -> &'static str,
+}
+
+impl Locale {
+    /// Get the translation of a message.
+    pub fn get(&self, message: Message) -> &'static str {
+        (self.table)(message)
+    }
+}
+
+/// All the available locales, the first one is the default.
+pub const LOCALES: &[Locale] = &[
+    Locale {
+        code: "en",
+        name: "English",
+        table: english,
+    },
+    Locale {
+        code: "es",
+        name: "Espanol",
+        table: spanish,
+    },
+];
+
+/// Find the index of the locale with the given code,
+/// falling back to the default locale.
+pub fn find_locale(code: &str) -> usize {
+    LOCALES.iter().position(|l| l.code == code).unwrap_or(0)
+}
+
+fn english(message: Message) -> &'static str {
+    match message {
+        Message::Paused => "Paused",
+        Message::Resume => "Resume",
+        Message::Language => "Language",
+        Message::Palette => "Palette",
+        Message::HighContrast => "High contrast",
+        Message::IntegerScale => "Integer scale",
+        Message::On => "On",
+        Message::Off => "Off",
+        Message::Volume => "Volume",
+        Message::StateSlot => "State slot",
+        Message::PressAKey => "press a key",
+        Message::Reset => "Reset",
+        Message::Quit => "Quit",
+        Message::Up => "Up",
+        Message::Down => "Down",
+        Message::Left => "Left",
+        Message::Right => "Right",
+        Message::ButtonA => "A",
+        Message::ButtonB => "B",
+        Message::Start => "Start",
+        Message::Select => "Select",
+    }
+}
+
+fn spanish(message: Message) -> &'static str {
+    match message {
+        Message::Paused => "En pausa",
+        Message::Resume => "Continuar",
+        Message::Language => "Idioma",
+        Message::Palette => "Paleta",
+        Message::HighContrast => "Alto contraste",
+        Message::IntegerScale => "Escala entera",
+        Message::On => "Si",
+        Message::Off => "No",
+        Message::Volume => "Volumen",
+        Message::StateSlot => "Ranura",
+        Message::PressAKey => "pulsa tecla",
+        Message::Reset => "Reiniciar",
+        Message::Quit => "Salir",
+        Message::Up => "Arriba",
+        Message::Down => "Abajo",
+        Message::Left => "Izquierda",
+        Message::Right => "Derecha",
+        Message::ButtonA => "A",
+        Message::ButtonB => "B",
+        Message::Start => "Start",
+        Message::Select => "Select",
+    }
+}
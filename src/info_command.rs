@@ -0,0 +1,232 @@
+//! The `info` subcommand, which prints what a ROM's header says.
+//!
+//! `argentum info <ROM>` lists the title, cartridge type, sizes,
+//! checksums and the mapper Argentum would use, and `--json` writes
+//! the same as a JSON object for scripts that manage ROM collections.
+//! ROMs no mapper supports are still described, with the error, and
+//! the command exits with status 1.
+
+use std::{
+    fmt::Write as _,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+};
+
+use argentum_core::{CartridgeInfo, MapperRegistry, Rom, UnsupportedCartridge};
+use clap::Clap;
+
+#[derive(Clap)]
+pub struct InfoCommand {
+    /// The Game Boy ROM file to describe.
+    #[clap(parse(from_os_str))]
+    rom_file: PathBuf,
+
+    /// Print the header as a JSON object.
+    #[clap(long)]
+    json: bool,
+}
+
+/// Find the mapper the emulator would pick for the ROM.
+fn detect_mapper(rom: &[u8]) -> Result<&'static str, UnsupportedCartridge> {
+    // Mappers trust the header, bad headers
+    // can panic them while they are set up.
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let mapper = panic::catch_unwind(AssertUnwindSafe(|| {
+        MapperRegistry::default()
+            .create(Rom::from(rom), None)
+            .map(|mapper| mapper.name())
+    }));
+
+    panic::set_hook(hook);
+
+    mapper.ok().flatten().ok_or(UnsupportedCartridge)
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+
+    json.push('"');
+    json
+}
+
+fn json_option<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "null".to_string(), |value| value.to_string())
+}
+
+fn write_json(
+    info: &CartridgeInfo,
+    rom: &[u8],
+    title: &str,
+    mapper: Result<&str, UnsupportedCartridge>,
+) -> String {
+    let fields = [
+        ("title", json_string(title)),
+        ("cartridge_type", info.cartridge_type.to_string()),
+        (
+            "cartridge_type_name",
+            json_option(info.cartridge_type_name().map(json_string)),
+        ),
+        ("mapper", json_option(mapper.ok().map(json_string))),
+        (
+            "error",
+            json_option(mapper.err().map(|err| json_string(&err.to_string()))),
+        ),
+        ("rom_size", json_option(info.rom_size)),
+        ("ram_size", json_option(info.ram_size)),
+        ("file_size", rom.len().to_string()),
+        ("version", info.version.to_string()),
+        ("cgb_support", info.cgb_support.to_string()),
+        ("cgb_only", info.cgb_only.to_string()),
+        ("sgb_support", info.sgb_support.to_string()),
+        ("logo_valid", info.logo_valid.to_string()),
+        ("header_checksum", info.header_checksum.to_string()),
+        (
+            "header_checksum_valid",
+            info.header_checksum_valid.to_string(),
+        ),
+        ("global_checksum", info.global_checksum.to_string()),
+        (
+            "global_checksum_valid",
+            info.global_checksum_valid(rom).to_string(),
+        ),
+        ("passes_boot_checks", info.passes_boot_checks().to_string()),
+    ];
+
+    let mut json = String::from("{\n");
+
+    for (i, (name, value)) in fields.iter().enumerate() {
+        let separator = if i + 1 < fields.len() { "," } else { "" };
+
+        let _ = writeln!(json, "  \"{}\": {}{}", name, value, separator);
+    }
+
+    json.push('}');
+    json
+}
+
+fn size_text(size: Option<usize>) -> String {
+    match size {
+        Some(size) if size >= 1024 => format!("{} KiB", size / 1024),
+        Some(size) => format!("{} bytes", size),
+        None => "unknown".to_string(),
+    }
+}
+
+fn valid_text(valid: bool) -> &'static str {
+    if valid {
+        "valid"
+    } else {
+        "invalid"
+    }
+}
+
+fn write_text(
+    info: &CartridgeInfo,
+    rom: &[u8],
+    title: &str,
+    mapper: Result<&str, UnsupportedCartridge>,
+) -> String {
+    let mut text = String::new();
+
+    let _ = writeln!(text, "Title:           {}", title);
+    let _ = writeln!(
+        text,
+        "Cartridge type:  ${:02X} ({})",
+        info.cartridge_type,
+        info.cartridge_type_name().unwrap_or("unknown")
+    );
+    let _ = writeln!(text, "Mapper:          {}", mapper.unwrap_or("none"));
+    let _ = writeln!(
+        text,
+        "ROM size:        {} ({} in the file)",
+        size_text(info.rom_size),
+        size_text(Some(rom.len()))
+    );
+    let _ = writeln!(text, "RAM size:        {}", size_text(info.ram_size));
+    let _ = writeln!(text, "Version:         {}", info.version);
+    let _ = writeln!(
+        text,
+        "CGB:             {}",
+        match (info.cgb_support, info.cgb_only) {
+            (_, true) => "only",
+            (true, false) => "supported",
+            (false, false) => "no",
+        }
+    );
+    let _ = writeln!(
+        text,
+        "SGB:             {}",
+        if info.sgb_support { "supported" } else { "no" }
+    );
+    let _ = writeln!(text, "Logo:            {}", valid_text(info.logo_valid));
+    let _ = writeln!(
+        text,
+        "Header checksum: ${:02X} ({})",
+        info.header_checksum,
+        valid_text(info.header_checksum_valid)
+    );
+    let _ = write!(
+        text,
+        "Global checksum: ${:04X} ({})",
+        info.global_checksum,
+        valid_text(info.global_checksum_valid(rom))
+    );
+
+    if let Err(err) = mapper {
+        let _ = write!(text, "\nError:           {}", err);
+    }
+
+    text
+}
+
+/// Describe the ROM, and whether a mapper supports it.
+fn describe(command: &InfoCommand) -> Result<(String, bool), String> {
+    let rom = std::fs::read(&command.rom_file)
+        .map_err(|err| format!("Failed to read the ROM: {}", err))?;
+
+    let info = CartridgeInfo::parse(&rom).ok_or("The ROM is too small to have a header.")?;
+
+    let title = info.title.trim_end_matches('\0');
+    let mapper = detect_mapper(&rom);
+
+    let text = if command.json {
+        write_json(&info, &rom, title, mapper)
+    } else {
+        write_text(&info, &rom, title, mapper)
+    };
+
+    Ok((text, mapper.is_ok()))
+}
+
+/// Run the `info` subcommand, exiting with status 1 if the ROM can't
+/// be read or isn't supported. Bad headers are still described.
+pub fn run(command: InfoCommand) {
+    match describe(&command) {
+        Ok((text, supported)) => {
+            println!("{}", text);
+
+            if !supported {
+                std::process::exit(1);
+            }
+        }
+
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Scripted input, used to drive headless runs and to automate
+//! simple interactions with games.
+//!
+//! Every line holds a frame number, a key and `down` or `up`, for
+//! example `120 start down`. Empty lines and lines starting with `#`
+//! are ignored.
+
+use std::{io::Read, path::Path};
+
+use argentum_core::{ArgentumKey, InputEvent};
+
+/// Parse a key name, as used in input scripts.
+pub fn parse_key(name: &str) -> Option<ArgentumKey> {
+    match name.to_ascii_lowercase().as_str() {
+        "up" => Some(ArgentumKey::Up),
+        "down" => Some(ArgentumKey::Down),
+        "left" => Some(ArgentumKey::Left),
+        "right" => Some(ArgentumKey::Right),
+        "a" => Some(ArgentumKey::ButtonA),
+        "b" => Some(ArgentumKey::ButtonB),
+        "start" => Some(ArgentumKey::Start),
+        "select" => Some(ArgentumKey::Select),
+
+        _ => None,
+    }
+}
+
+/// Load an input script, reporting the first malformed line.
+/// The path `-` reads the script from stdin.
+pub fn load_input_script(path: &Path) -> Result<Vec<InputEvent>, String> {
+    let contents = if path == Path::new("-") {
+        let mut contents = String::new();
+
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .map_err(|err| err.to_string())?;
+
+        contents
+    } else {
+        std::fs::read_to_string(path).map_err(|err| err.to_string())?
+    };
+
+    let mut events = Vec::new();
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        let event = match fields.as_slice() {
+            [frame, key, action] => {
+                let frame = frame.parse().ok();
+                let key = parse_key(key);
+
+                let pressed = match *action {
+                    "down" => Some(true),
+                    "up" => Some(false),
+                    _ => None,
+                };
+
+                match (frame, key, pressed) {
+                    (Some(frame), Some(key), Some(pressed)) => Some(InputEvent {
+                        frame,
+                        key,
+                        pressed,
+                    }),
+
+                    _ => None,
+                }
+            }
+
+            _ => None,
+        };
+
+        match event {
+            Some(event) => events.push(event),
+            None => return Err(format!("line {} is malformed: \"{}\"", number + 1, line)),
+        }
+    }
+
+    Ok(events)
+}
@@ -0,0 +1,178 @@
+//! Input macros, short recordings of held keys that can be replayed.
+//!
+//! Macros are stored next to the ROM, one per line. Every line holds
+//! the name of the macro followed by the keys held on each frame, as
+//! hexadecimal `ArgentumKey` masks, for example `jump 10 10 10 00`.
+
+use std::path::{Path, PathBuf};
+
+/// A named recording of the keys held on every frame.
+pub struct Macro {
+    pub name: String,
+    pub frames: Vec<u8>,
+}
+
+/// Records and plays back the macros of a single game.
+pub struct Macros {
+    /// The file the macros are stored in.
+    path: PathBuf,
+
+    macros: Vec<Macro>,
+
+    /// The keys held on every frame since recording started.
+    recording: Option<Vec<u8>>,
+
+    /// The macro being played, and the next frame of it.
+    playback: Option<(usize, usize)>,
+}
+
+impl Macros {
+    /// Create a new `Macros` instance, loading the macros stored
+    /// at the given path. Malformed lines are skipped.
+    pub fn load(path: PathBuf) -> Self {
+        let contents = std::fs::read_to_string(&path).unwrap_or_default();
+
+        let mut macros = Vec::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let mut fields = line.split_whitespace();
+
+            let name = match fields.next() {
+                Some(name) => name.to_owned(),
+                None => continue,
+            };
+
+            let frames: Result<Vec<u8>, _> =
+                fields.map(|keys| u8::from_str_radix(keys, 16)).collect();
+
+            match frames {
+                Ok(frames) => macros.push(Macro { name, frames }),
+                Err(_) => eprintln!("Ignoring malformed macro on line {}.", number + 1),
+            }
+        }
+
+        Self {
+            path,
+            macros,
+            recording: None,
+            playback: None,
+        }
+    }
+
+    /// Write the macros back to their file.
+    pub fn save(&self) -> std::io::Result<()> {
+        let mut contents = String::new();
+
+        for recording in self.macros.iter() {
+            contents.push_str(&recording.name);
+
+            for keys in recording.frames.iter() {
+                contents.push_str(&format!(" {:02X}", keys));
+            }
+
+            contents.push('\n');
+        }
+
+        std::fs::write(&self.path, contents)
+    }
+
+    /// Get the path the macros are stored at for the given ROM.
+    pub fn path_for_rom(rom_path: &Path) -> PathBuf {
+        rom_path.with_extension("macros")
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// Start recording, or stop and store the recording as a new
+    /// macro. Returns the name of the stored macro.
+    ///
+    /// Nothing happens while a macro is playing.
+    pub fn toggle_recording(&mut self) -> Option<&str> {
+        if self.is_playing() {
+            return None;
+        }
+
+        match self.recording.take() {
+            None => {
+                self.recording = Some(Vec::new());
+
+                None
+            }
+
+            Some(frames) => {
+                let name = (1..)
+                    .map(|index| format!("macro{}", index))
+                    .find(|name| self.find(name).is_none())
+                    .unwrap();
+
+                self.macros.push(Macro { name, frames });
+                self.macros.last().map(|recording| recording.name.as_str())
+            }
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.macros
+            .iter()
+            .position(|recording| recording.name == name)
+    }
+
+    /// Start playing the macro with the given name, returns
+    /// false if there is no such macro or one is recording.
+    pub fn play(&mut self, name: &str) -> bool {
+        let index = self.find(name);
+
+        self.play_index(index)
+    }
+
+    /// Start playing the most recently recorded macro.
+    pub fn play_last(&mut self) -> bool {
+        let index = self.macros.len().checked_sub(1);
+
+        self.play_index(index)
+    }
+
+    fn play_index(&mut self, index: Option<usize>) -> bool {
+        match index {
+            Some(index) if !self.is_recording() => {
+                self.playback = Some((index, 0));
+
+                true
+            }
+
+            _ => false,
+        }
+    }
+
+    /// Call before every frame with the keys that are held.
+    ///
+    /// While playing, returns the keys the macro holds on this
+    /// frame, which should replace the held keys.
+    pub fn next_frame(&mut self, keys: u8) -> Option<u8> {
+        if let Some(frames) = &mut self.recording {
+            frames.push(keys);
+        }
+
+        let (index, frame) = self.playback?;
+
+        match self.macros[index].frames.get(frame) {
+            Some(&keys) => {
+                self.playback = Some((index, frame + 1));
+
+                Some(keys)
+            }
+
+            None => {
+                self.playback = None;
+
+                None
+            }
+        }
+    }
+}
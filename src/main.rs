@@ -1,47 +1,472 @@
-use std::{env, ffi::CString, path::PathBuf};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, VecDeque},
+    ffi::{CStr, CString},
+    fs::File,
+    io::{BufWriter, Write},
+    num::ParseIntError,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
-use argentum_core::{Argentum, ArgentumKey};
-use clap::Clap;
+#[cfg(feature = "metrics")]
+use std::sync::{Arc, Mutex};
+
+use argentum_core::{
+    AddressRange, Argentum, ArgentumEvent, ArgentumKey, AudioSamples, BarcodeReaderKind,
+    DeterminismMode, FrameInfo, Snapshot, TraceFilter,
+};
+#[cfg(feature = "metrics")]
+use argentum_frontend_utils::{serve_metrics, Metrics};
+use argentum_frontend_utils::{FpsCounter, FpsLimiter, RateControl, RewindBuffer, StateStack};
+use clap::{AppSettings, ArgSettings, Clap};
 use fermium::prelude::*;
 
+mod barcodes;
+mod bgb_link;
+mod boot_options;
+mod bus_script_command;
+mod cheats;
+mod colour_worker;
+mod compare;
+mod compat_scan;
+mod config;
+#[cfg(feature = "control-server")]
+mod control_server;
+mod crash;
+mod four_player_link;
+mod headless;
+mod i18n;
+mod info_command;
+mod input_script;
+mod macros;
+mod menu;
+mod movie;
+mod osd;
+mod palette;
+mod render_command;
 mod renderer;
+mod screenshot_test;
+#[cfg(feature = "shared-frame")]
+mod shared_frame;
+mod state_command;
+mod stream;
+mod tile_viewer;
+mod trace_compare;
+mod vgm;
+mod watches;
+#[cfg(feature = "control-server")]
+mod websocket;
 
+use barcodes::BarcodeScanner;
+use bgb_link::BgbLink;
+use boot_options::BootOptions;
+use bus_script_command::BusScriptCommand;
+use cheats::CheatList;
+use colour_worker::ColourWorker;
+use compare::Compare;
+use compat_scan::CompatScan;
+use config::{Config, KeyConfig, STATE_SLOTS};
+#[cfg(feature = "control-server")]
+use control_server::ControlServer;
+use crash::{CrashReporter, TraceHistory};
+use four_player_link::FourPlayerLink;
+use info_command::InfoCommand;
+use input_script::load_input_script;
+use macros::Macros;
+use menu::{Menu, MenuAction, MenuInput};
+use movie::Movie;
+use palette::resolve_palette;
+use render_command::RenderCommand;
 use renderer::Renderer;
+use screenshot_test::ScreenshotTest;
+#[cfg(feature = "shared-frame")]
+use shared_frame::SharedFrame;
+use state_command::{print_ram_changes, StateCommand};
+use stream::{FrameStream, StreamAddress, StreamCommand};
+use watches::{load_symbols, Watches};
 
 /// The version of this crate. To pass to Clap CLI.
 const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+#[derive(Clap)]
+enum Command {
+    /// Describe what a ROM's header says.
+    Info(InfoCommand),
+
+    /// Edit save files and inspect save states without running the game.
+    #[clap(setting = AppSettings::SubcommandRequiredElseHelp)]
+    State(StateCommand),
+
+    /// Check how well a directory of ROMs runs.
+    CompatScan(CompatScan),
+
+    /// Find the first frame where two runs from a save state differ.
+    Compare(Compare),
+
+    /// Replay a movie offline, writing its video and audio to files.
+    Render(RenderCommand),
+
+    /// Replay bus scripts against a fresh system, without a ROM.
+    BusScript(BusScriptCommand),
+
+    /// Run test ROMs and compare the screen with expected screenshots.
+    ScreenshotTest(ScreenshotTest),
+}
+
 #[derive(Clap)]
 #[clap(name = "Argentum GB")]
 #[clap(version = PKG_VERSION, about = "A Game Boy emulator written in Rust.")]
+#[clap(setting = AppSettings::SubcommandsNegateReqs)]
+// The options only apply to playing a ROM, subcommands
+// given after them are rejected rather than ignoring them.
+#[clap(setting = AppSettings::ArgsNegateSubcommands)]
 struct Opt {
     /// The Game Boy ROM file to execute.
-    #[clap(parse(from_os_str))]
-    rom_file: PathBuf,
+    #[clap(parse(from_os_str), setting = ArgSettings::Required)]
+    rom_file: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
 
     /// Turn on basic logging support.
     #[clap(short, long)]
     logging: bool,
 
-    /// Skip the bootrom (Optix's custom bootrom Bootix).
-    #[clap(short, long)]
-    skip_bootrom: bool,
+    #[clap(flatten)]
+    boot: BootOptions,
+
+    /// Apply the colours to frames on another thread, while the next
+    /// frame is emulated. Frames are shown a frame late. Experimental.
+    #[clap(long)]
+    colour_thread: bool,
+
+    /// Show the frame this many frames ahead of the emulation, and undo
+    /// them afterwards, so input shows up on screen sooner. Costs
+    /// that many extra frames of emulation every frame.
+    #[clap(
+        long,
+        default_value = "0",
+        possible_values = &["0", "1", "2"],
+        conflicts_with_all = &[
+            "hle-boot",
+            "trace",
+            "link",
+            "link-listen",
+            "four-player-host",
+            "four-player",
+            "barcode-boy",
+            "bardigun",
+            "vgm"
+        ]
+    )]
+    run_ahead: u8,
+
+    /// Write a trace of every executed instruction to this file.
+    #[clap(long, parse(from_os_str))]
+    trace: Option<PathBuf>,
+
+    /// Only trace instructions in this range of addresses, like
+    /// `4000-7FFF`. Can be given more than once.
+    #[clap(long, number_of_values = 1, requires = "trace")]
+    trace_include: Vec<AddressRange>,
+
+    /// Don't trace instructions in this range of addresses, like
+    /// a wait for VBlank loop. Can be given more than once.
+    #[clap(long, number_of_values = 1, requires = "trace")]
+    trace_exclude: Vec<AddressRange>,
+
+    /// Only trace instructions in this bank, in hexadecimal.
+    /// Can be given more than once.
+    #[clap(long, number_of_values = 1, requires = "trace", parse(try_from_str = parse_bank))]
+    trace_bank: Vec<u16>,
+
+    /// Trace one iteration of tight loops, and a
+    /// line counting the instructions after it.
+    #[clap(long, requires = "trace")]
+    trace_dedup_loops: bool,
+
+    /// Use this config file instead of the default one.
+    #[clap(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Continue from the state saved automatically when
+    /// the emulator was last closed.
+    #[clap(long)]
+    resume: bool,
+
+    /// Stream frames to, and accept input from, clients connecting
+    /// to this address. Either `unix:PATH` or `tcp:HOST:PORT`.
+    #[clap(long)]
+    stream: Option<StreamAddress>,
+
+    /// Plug the link cable into an emulator speaking the BGB link
+    /// protocol, listening on this address, for example `127.0.0.1:8765`.
+    #[clap(long)]
+    link: Option<String>,
+
+    /// Wait for an emulator speaking the BGB link
+    /// protocol to connect to this address.
+    #[clap(long, conflicts_with = "link")]
+    link_listen: Option<String>,
+
+    /// Host a four player adapter as player 1, the other players
+    /// join it on this address, for example `0.0.0.0:8766`.
+    #[clap(long, conflicts_with_all = &["link", "link-listen"])]
+    four_player_host: Option<String>,
+
+    /// Join the four player adapter hosted on this address.
+    #[clap(long, conflicts_with_all = &["link", "link-listen", "four-player-host"])]
+    four_player: Option<String>,
+
+    /// Plug a Barcode Boy into the link port, F4 scans the
+    /// next of the barcodes listed in this file.
+    #[clap(
+        long,
+        parse(from_os_str),
+        conflicts_with_all = &["link", "link-listen", "four-player-host", "four-player"]
+    )]
+    barcode_boy: Option<PathBuf>,
+
+    /// Plug the Barcode Taisen Bardigun scanner into the link port,
+    /// F4 scans the next of the barcodes listed in this file.
+    #[clap(
+        long,
+        parse(from_os_str),
+        conflicts_with_all = &[
+            "link",
+            "link-listen",
+            "four-player-host",
+            "four-player",
+            "barcode-boy"
+        ]
+    )]
+    bardigun: Option<PathBuf>,
+
+    /// Run the ROM twice for this many frames without a window,
+    /// and check that both runs end up in the same state.
+    #[clap(long)]
+    verify_determinism: Option<u32>,
+
+    /// Press keys as listed in this file, counting frames from the
+    /// start. Lines look like `120 start down`, `-` reads them from stdin.
+    #[clap(long, parse(from_os_str))]
+    input_script: Option<PathBuf>,
+
+    /// Record the keys held on every frame to this movie, or play it
+    /// and record on where it ends if it exists. Save states get a
+    /// bookmark of the movie, loading one cuts the movie back to it.
+    #[clap(long, parse(from_os_str))]
+    movie: Option<PathBuf>,
+
+    /// Play the movie without recording over it, loading a save
+    /// state plays on from its frame.
+    #[clap(long, requires = "movie")]
+    movie_read_only: bool,
+
+    /// Log the writes to the sound registers and save them to this
+    /// VGM file on exit, to rip the soundtrack.
+    #[clap(long, parse(from_os_str))]
+    vgm: Option<PathBuf>,
+
+    /// Print the watch expressions from the `.watch` file next
+    /// to the ROM whenever their value changes.
+    #[clap(long)]
+    log_watches: bool,
+
+    /// Run the ROM without a window, comparing the registers before
+    /// every instruction with a trace from another emulator, and
+    /// stop at the first difference.
+    #[clap(long, parse(from_os_str))]
+    verify_against: Option<PathBuf>,
+
+    /// Run the ROM for this many frames without a window, then print
+    /// hashes of the framebuffer and the whole state. Useful to find
+    /// regressions with `git bisect run`.
+    #[clap(long)]
+    print_frame_hash: Option<u32>,
+
+    /// Serve performance metrics in the Prometheus format over
+    /// HTTP on this address, for example `127.0.0.1:9477`.
+    #[cfg(feature = "metrics")]
+    #[clap(long)]
+    metrics: Option<String>,
+
+    /// Write every displayed frame to this memory mapped file, for
+    /// overlays and capture software. See `shared_frame.rs`.
+    #[cfg(feature = "shared-frame")]
+    #[clap(long, parse(from_os_str))]
+    shared_frame: Option<PathBuf>,
+
+    /// Let other programs pause, save and load states, read and write
+    /// memory and take screenshots over a WebSocket on this port of
+    /// 127.0.0.1, for example `9478`. See `control_server.rs`.
+    #[cfg(feature = "control-server")]
+    #[clap(long)]
+    control: Option<u16>,
 }
 
-/// Handle keyboard input.
-fn handle_keyboard_input(gb: &mut Argentum, input: SDL_Scancode, is_pressed: bool) {
-    let key = match input {
-        SDL_SCANCODE_W => Some(ArgentumKey::Up),
-        SDL_SCANCODE_A => Some(ArgentumKey::Left),
-        SDL_SCANCODE_S => Some(ArgentumKey::Down),
-        SDL_SCANCODE_D => Some(ArgentumKey::Right),
-        SDL_SCANCODE_RETURN => Some(ArgentumKey::Start),
-        SDL_SCANCODE_SPACE => Some(ArgentumKey::Select),
-        SDL_SCANCODE_Z => Some(ArgentumKey::ButtonA),
-        SDL_SCANCODE_X => Some(ArgentumKey::ButtonA),
+/// Get the frame to show, the one coloured last by the worker if there is one.
+fn current_frame<'a>(
+    gb: &'a Argentum,
+    colour_worker: &'a mut Option<ColourWorker>,
+) -> (&'a [u8], FrameInfo) {
+    match colour_worker.as_mut().and_then(|worker| worker.latest()) {
+        Some(frame) => frame,
+        None => gb.get_framebuffer(),
+    }
+}
+
+/// Parse a bank number, in hexadecimal like the banks in traces.
+fn parse_bank(s: &str) -> Result<u16, ParseIntError> {
+    u16::from_str_radix(s, 16)
+}
+
+/// Resolve the configured key names to SDL scancodes.
+fn build_key_bindings(keys: &KeyConfig) -> Vec<(SDL_Scancode, ArgentumKey)> {
+    let mut bindings = Vec::new();
+
+    for (key, name) in keys.bindings().iter() {
+        let c_name = CString::new(*name).unwrap_or_default();
+        let scancode = unsafe { SDL_GetScancodeFromName(c_name.as_ptr()) };
+
+        if scancode == SDL_SCANCODE_UNKNOWN {
+            eprintln!("Unknown key \"{}\" in the config, ignoring it.", name);
+        } else {
+            bindings.push((scancode, *key));
+        }
+    }
+
+    bindings
+}
+
+/// Resolve the keys that play macros to SDL scancodes.
+fn build_macro_bindings(macro_keys: &BTreeMap<String, String>) -> Vec<(SDL_Scancode, String)> {
+    let mut bindings = Vec::new();
+
+    for (name, key) in macro_keys.iter() {
+        let c_key = CString::new(key.as_str()).unwrap_or_default();
+        let scancode = unsafe { SDL_GetScancodeFromName(c_key.as_ptr()) };
+
+        if scancode == SDL_SCANCODE_UNKNOWN {
+            eprintln!(
+                "Unknown key \"{}\" for macro \"{}\", ignoring it.",
+                key, name
+            );
+        } else {
+            bindings.push((scancode, name.clone()));
+        }
+    }
+
+    bindings
+}
+
+/// Get the Game Boy keys whose bound keyboard keys are held down.
+fn held_keys(bindings: &[(SDL_Scancode, ArgentumKey)]) -> u8 {
+    let state = unsafe { SDL_GetKeyboardState(std::ptr::null_mut()) };
+
+    bindings
+        .iter()
+        .filter(|(scancode, _)| unsafe { *state.add(scancode.0 as usize) != 0 })
+        .fold(0, |keys, (_, key)| keys | *key as u8)
+}
+
+/// Check if a keyboard key is held down.
+fn is_key_held(scancode: SDL_Scancode) -> bool {
+    unsafe { *SDL_GetKeyboardState(std::ptr::null_mut()).add(scancode.0 as usize) != 0 }
+}
+
+/// Get the name SDL uses for a scancode.
+fn scancode_name(scancode: SDL_Scancode) -> String {
+    unsafe {
+        CStr::from_ptr(SDL_GetScancodeName(scancode))
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Translate a key press into menu input.
+fn menu_input(scancode: SDL_Scancode, rebinding: bool) -> Option<MenuInput> {
+    match scancode {
+        SDL_SCANCODE_ESCAPE => Some(MenuInput::Back),
+        _ if rebinding => Some(MenuInput::Key(scancode_name(scancode))),
+
+        SDL_SCANCODE_UP => Some(MenuInput::Up),
+        SDL_SCANCODE_DOWN => Some(MenuInput::Down),
+        SDL_SCANCODE_LEFT => Some(MenuInput::Left),
+        SDL_SCANCODE_RIGHT => Some(MenuInput::Right),
+        SDL_SCANCODE_RETURN => Some(MenuInput::Confirm),
 
         _ => None,
-    };
+    }
+}
+
+/// Frames the selected slot is shown for after changing it.
+const SLOT_DISPLAY_FRAMES: u32 = 120;
+
+/// Get the path of the save state for the given slot.
+fn state_path(rom_path: &Path, slot: u8) -> PathBuf {
+    if slot == 0 {
+        rom_path.with_extension("state")
+    } else {
+        rom_path.with_extension(format!("state{}", slot))
+    }
+}
+
+/// Get the path of the state saved on exit, and periodically.
+fn autosave_path(rom_path: &Path) -> PathBuf {
+    rom_path.with_extension("autosave")
+}
+
+/// Save the state to the autosave slot. The state is written to
+/// a temporary file first, so a crash while writing it doesn't
+/// destroy the previous autosave.
+fn write_autosave(gb: &Argentum, path: &Path) {
+    let temp_path = path.with_extension("autosave.tmp");
+
+    let result =
+        std::fs::write(&temp_path, gb.save_state()).and_then(|_| std::fs::rename(&temp_path, path));
+
+    if let Err(err) = result {
+        eprintln!("Failed to write the autosave: {}", err);
+    }
+}
+
+/// Apply the settings that affect the emulator.
+fn apply_config(gb: &mut Argentum, config: &Config, volume: &Cell<f32>) {
+    gb.set_dmg_palettes(resolve_palette(gb, &config.palette, config.high_contrast));
+    volume.set(f32::from(config.volume.min(100)) / 100.0);
+}
+
+/// Apply the settings that affect how frames are displayed.
+fn apply_display_config(renderer: &mut Renderer, config: &Config) {
+    renderer.set_integer_scale(config.integer_scale);
+    renderer.set_clear_colour(config.background_rgb());
+    renderer.set_transform(config.rotation, config.mirror);
+}
+
+/// Save the config, if there is somewhere to save it.
+fn save_config(config: &Config, path: Option<&Path>) {
+    if let Some(path) = path {
+        if let Err(err) = config.save(path) {
+            eprintln!("Failed to save the config: {}", err);
+        }
+    }
+}
+
+/// Handle keyboard input.
+fn handle_keyboard_input(
+    gb: &mut Argentum,
+    bindings: &[(SDL_Scancode, ArgentumKey)],
+    input: SDL_Scancode,
+    is_pressed: bool,
+) {
+    let key = bindings
+        .iter()
+        .find(|(scancode, _)| *scancode == input)
+        .map(|(_, key)| *key);
 
     if let Some(key) = key {
         if is_pressed {
@@ -52,48 +477,463 @@ fn handle_keyboard_input(gb: &mut Argentum, input: SDL_Scancode, is_pressed: boo
     }
 }
 
+fn handle_macro_hotkeys(
+    macros: &mut Macros,
+    bindings: &[(SDL_Scancode, String)],
+    input: SDL_Scancode,
+) {
+    match input {
+        SDL_SCANCODE_F10 => {
+            if let Some(name) = macros.toggle_recording() {
+                println!("Recorded macro \"{}\".", name);
+
+                if let Err(err) = macros.save() {
+                    eprintln!("Failed to save the macros: {}", err);
+                }
+            }
+        }
+
+        SDL_SCANCODE_F11 => {
+            macros.play_last();
+        }
+
+        _ => {
+            if let Some((_, name)) = bindings.iter().find(|(scancode, _)| *scancode == input) {
+                macros.play(name);
+            }
+        }
+    }
+}
+
+/// Take a RAM snapshot, or report what changed since the last one.
+fn handle_ram_diff_hotkey(gb: &mut Argentum) {
+    let changes = match gb.ram_diff() {
+        Some(changes) => changes,
+
+        None => {
+            gb.start_ram_diff();
+            println!("Took a RAM snapshot, press F12 again to see what changed.");
+
+            return;
+        }
+    };
+
+    gb.stop_ram_diff();
+
+    println!("{} bytes of RAM changed since the snapshot.", changes.len());
+
+    for change in changes.iter() {
+        let writers = change
+            .writers
+            .iter()
+            .map(|writer| writer.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        println!(
+            "{} {:02X} -> {:02X}, changed {} times by {}",
+            change.address, change.before, change.after, change.changes, writers
+        );
+    }
+}
+
+/// Handle the save state hotkeys.
+///
+/// F5 saves the state, F8 loads it and F9 undoes the last load.
+/// F1 lists the RAM that changed since the state was saved.
+///
+/// With a movie, states are saved with a bookmark of it and
+/// loading one goes back to the bookmark, see `movie.rs`.
+fn handle_state_hotkeys(
+    gb: &mut Argentum,
+    input: SDL_Scancode,
+    state_path: &Path,
+    undo_stack: &mut StateStack,
+    movie: Option<&mut Movie>,
+) {
+    match input {
+        SDL_SCANCODE_F5 => {
+            if let Err(err) = std::fs::write(state_path, gb.save_state()) {
+                eprintln!("Failed to write the save state: {}", err);
+            }
+
+            if let Some(movie) = movie {
+                if let Err(err) = movie.save_bookmark(&Movie::bookmark_path(state_path)) {
+                    eprintln!("Failed to write the movie bookmark: {}", err);
+                }
+
+                // Saving states is a good time to save the movie too.
+                if let Err(err) = movie.save() {
+                    eprintln!("Failed to save the movie: {}", err);
+                }
+            }
+        }
+
+        SDL_SCANCODE_F8 => match std::fs::read(state_path) {
+            Ok(state) => {
+                let bookmark = match &movie {
+                    Some(movie) => match movie.check_bookmark(&Movie::bookmark_path(state_path)) {
+                        Ok(frames) => Some(frames),
+
+                        Err(err) => {
+                            eprintln!("Can't load the save state during the movie, {}.", err);
+                            return;
+                        }
+                    },
+
+                    None => None,
+                };
+
+                // Keep the current state around in case the load was a mistake.
+                let current = gb.save_state();
+
+                match gb.load_state(&state) {
+                    Ok(()) => undo_stack.push(current),
+
+                    Err(err) => {
+                        eprintln!("Failed to load the save state: {}", err);
+                        return;
+                    }
+                }
+
+                if let (Some(movie), Some(frames)) = (movie, bookmark) {
+                    movie.load_bookmark(frames);
+                }
+            }
+
+            Err(err) => eprintln!("Failed to read the save state: {}", err),
+        },
+
+        // Compare RAM with the save state, to find out what changed since.
+        SDL_SCANCODE_F1 => match std::fs::read(state_path) {
+            Ok(state) => match gb.ram_changes_since_state(&state) {
+                Ok(changes) => print_ram_changes(&changes),
+                Err(err) => eprintln!("Failed to load the save state: {}", err),
+            },
+
+            Err(err) => eprintln!("Failed to read the save state: {}", err),
+        },
+
+        // The movie would be left out of step with the state.
+        SDL_SCANCODE_F9 if movie.is_some() => {
+            eprintln!("Loads can't be undone during a movie.");
+        }
+
+        SDL_SCANCODE_F9 => {
+            if let Some(state) = undo_stack.pop() {
+                gb.load_state(&state)
+                    .expect("Failed to restore the state from before loading.");
+            }
+        }
+
+        _ => {}
+    }
+}
+
 /// Start running the emulator.
 pub fn main() {
     unsafe {
         // Parse command line arguments.
         let opts: Opt = Opt::parse();
 
+        // Subcommands do their own thing instead of running a game.
+        match opts.command {
+            Some(Command::Info(command)) => return info_command::run(command),
+            Some(Command::State(command)) => return state_command::run(command),
+            Some(Command::CompatScan(command)) => return compat_scan::run(command),
+            Some(Command::Compare(command)) => return compare::run(command),
+            Some(Command::Render(command)) => return render_command::run(command),
+            Some(Command::BusScript(command)) => return bus_script_command::run(command),
+            Some(Command::ScreenshotTest(command)) => return screenshot_test::run(command),
+            None => {}
+        }
+
+        crash::install_panic_hook();
+
         // Setup logging.
         if opts.logging {
             env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
         }
 
-        // Read the ROM file into memory.
-        let mut rom_path = opts.rom_file;
+        // Load the persistent settings.
+        let config_path = opts.config.or_else(Config::default_path);
+
+        let mut config = config_path.as_deref().map(Config::load).unwrap_or_default();
+
+        // Read the ROM file into memory. Clap requires
+        // it when there is no subcommand.
+        let rom_path = opts.rom_file.expect("no ROM file given");
 
         let rom = std::fs::read(&rom_path).expect("Failed to read the ROM file.");
 
+        // States from before each load, so loads can be undone.
+        let mut undo_stack = StateStack::new(8);
+
         // Check if there is a save file.
-        rom_path.set_extension("sav");
+        let save_path = rom_path.with_extension("sav");
+
+        let save_file = std::fs::read(&save_path).ok();
 
-        let save_file = std::fs::read(&rom_path).ok();
+        let inputs = match &opts.input_script {
+            Some(path) => load_input_script(path).unwrap_or_else(|err| {
+                eprintln!("Failed to load the input script: {}", err);
+                std::process::exit(1);
+            }),
+
+            None => Vec::new(),
+        };
+
+        // Run headless to check for nondeterminism, instead of playing.
+        if let Some(frames) = opts.verify_determinism {
+            return headless::check_determinism(&rom, save_file, &opts.boot, &inputs, frames);
+        }
+
+        // Run headless to a frame and print its hashes, instead of playing.
+        if let Some(frames) = opts.print_frame_hash {
+            return headless::print_frame_hash(&rom, save_file, &opts.boot, &inputs, frames);
+        }
+
+        // Run headless against a reference trace, instead of playing.
+        if let Some(trace_path) = &opts.verify_against {
+            return headless::verify_against(&rom, save_file, &opts.boot, trace_path);
+        }
+
+        // The amount of audio we try to keep queued, in bytes, a
+        // buffer's worth of stereo 32-bit float samples.
+        let audio_queue_target = u32::from(config.audio_buffer_samples()) * 4 * 2;
+
+        // The audio callback scales samples by the volume.
+        let volume = Rc::new(Cell::new(1.0));
+        let callback_volume = Rc::clone(&volume);
+        let scaled_buffer = RefCell::new(Vec::new());
+
+        // Frames that are run ahead and undone again stay silent.
+        let audio_enabled = Rc::new(Cell::new(true));
+        let callback_audio_enabled = Rc::clone(&audio_enabled);
 
         // Create a Game Boy instance and skip the bootrom.
         let mut argentum = Argentum::new(
             &rom,
-            Box::new(|buffer| {
-                while SDL_GetQueuedAudioSize(SDL_AudioDeviceID(1)) > 1024 * 4 * 2 {
+            Box::new(move |samples| {
+                let buffer = match samples {
+                    AudioSamples::F32(buffer) if callback_audio_enabled.get() => buffer,
+                    _ => return,
+                };
+
+                // Dynamic rate control keeps the queue around the target,
+                // this only kicks in if something went very wrong.
+                while SDL_GetQueuedAudioSize(SDL_AudioDeviceID(1)) > audio_queue_target * 4 {
                     SDL_Delay(1);
                 }
 
+                let mut scaled = scaled_buffer.borrow_mut();
+                let volume = callback_volume.get();
+
+                scaled.clear();
+                scaled.extend(buffer.iter().map(|sample| sample * volume));
+
                 SDL_QueueAudio(
                     SDL_AudioDeviceID(1),
-                    buffer.as_ptr() as _,
-                    (std::mem::size_of::<f32>() * buffer.len()) as u32,
+                    scaled.as_ptr() as _,
+                    (std::mem::size_of::<f32>() * scaled.len()) as u32,
                 );
             }),
             save_file,
-        );
+        )
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to load the ROM: {}", err);
+            std::process::exit(1);
+        });
+
+        argentum.set_audio_sample_rate(config.audio_sample_rate());
+        apply_config(&mut argentum, &config, &volume);
+
+        let info = argentum.cartridge_info();
 
-        if opts.skip_bootrom {
-            argentum.skip_bootrom();
+        if !info.passes_boot_checks() {
+            eprintln!(
+                "The cartridge header is bad (logo {}, header checksum {}), a real Game Boy won't start it.",
+                if info.logo_valid { "valid" } else { "invalid" },
+                if info.header_checksum_valid { "valid" } else { "invalid" }
+            );
         }
 
+        opts.boot.apply(&mut argentum);
+
+        // Colour the frames on another thread.
+        let mut colour_worker = if opts.colour_thread {
+            argentum.set_deferred_colours(true);
+
+            Some(ColourWorker::new())
+        } else {
+            None
+        };
+
+        // Load the game's cheats.
+        let mut cheats = CheatList::load(CheatList::path_for_rom(&rom_path));
+
+        argentum.set_cheats(&cheats.enabled_codes());
+
+        // Continue where the last session left off.
+        let autosave_path = autosave_path(&rom_path);
+
+        if opts.resume {
+            match std::fs::read(&autosave_path) {
+                Ok(state) => {
+                    if let Err(err) = argentum.load_state(&state) {
+                        eprintln!("Failed to load the autosave: {}", err);
+                    }
+                }
+
+                Err(err) => eprintln!("Failed to read the autosave: {}", err),
+            }
+        }
+
+        // Record or play a movie. New movies start from the current state.
+        let mut movie = opts.movie.as_ref().map(|path| {
+            if !path.exists() && !opts.movie_read_only {
+                return Movie::new(path.clone(), argentum.save_state());
+            }
+
+            let movie = Movie::load(path.clone(), opts.movie_read_only).unwrap_or_else(|err| {
+                eprintln!("Failed to load the movie: {}", err);
+                std::process::exit(1);
+            });
+
+            if let Err(err) = argentum.load_state(movie.start_state()) {
+                eprintln!("Failed to load the state the movie starts from: {}", err);
+                std::process::exit(1);
+            }
+
+            movie
+        });
+
+        // Movies only play back the same way in strict mode.
+        if movie.is_some() {
+            argentum.set_determinism_mode(DeterminismMode::Strict);
+        }
+
+        if opts.vgm.is_some() {
+            argentum.start_audio_log();
+        }
+
+        // Save the state every few minutes, if enabled.
+        let autosave_interval = Duration::from_secs(u64::from(config.autosave_minutes) * 60);
+        let mut last_autosave = Instant::now();
+
+        // Setup instruction tracing, the most recent lines
+        // are also kept around for crash reports.
+        let trace_filter = TraceFilter {
+            include: opts.trace_include,
+            exclude: opts.trace_exclude,
+            banks: opts.trace_bank,
+            deduplicate_loops: opts.trace_dedup_loops,
+        };
+
+        let trace_history = opts.trace.map(|trace_path| {
+            let mut trace_file =
+                BufWriter::new(File::create(trace_path).expect("Failed to create the trace file."));
+
+            let trace_history = TraceHistory::default();
+            let callback_history = trace_history.clone();
+
+            argentum.set_trace_filter(trace_filter);
+
+            argentum.set_trace_callback(Some(Box::new(move |line| {
+                writeln!(trace_file, "{}", line).expect("Failed to write to the trace file.");
+                callback_history.push(line);
+            })));
+
+            trace_history
+        });
+
+        let crash_reporter = CrashReporter::new(&rom_path, &rom, trace_history);
+
+        // Plug in the link cable.
+        let link = match (&opts.link, &opts.link_listen) {
+            (Some(address), _) => Some(BgbLink::connect(address)),
+            (_, Some(address)) => Some(BgbLink::listen(address)),
+            _ => None,
+        };
+
+        if let Some(link) = link {
+            let link = link.expect("Failed to set up the BGB link.");
+
+            argentum.set_link_partner(Some(Box::new(link)));
+        }
+
+        // Or the four player adapter.
+        let four_player = match (&opts.four_player_host, &opts.four_player) {
+            (Some(address), _) => Some(FourPlayerLink::host(address)),
+            (_, Some(address)) => Some(FourPlayerLink::join(address)),
+            _ => None,
+        };
+
+        if let Some(four_player) = four_player {
+            let four_player = four_player.expect("Failed to set up the four player adapter.");
+
+            argentum.set_link_partner(Some(Box::new(four_player)));
+        }
+
+        // Or plug in a barcode reader.
+        let barcode_file = match (&opts.barcode_boy, &opts.bardigun) {
+            (Some(path), _) => Some((BarcodeReaderKind::BarcodeBoy, path)),
+            (_, Some(path)) => Some((BarcodeReaderKind::Bardigun, path)),
+            _ => None,
+        };
+
+        let mut barcode_scanner = barcode_file.map(|(kind, path)| {
+            let scanner = BarcodeScanner::load(kind, path).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            });
+
+            argentum.set_link_partner(Some(Box::new(scanner.reader())));
+
+            scanner
+        });
+
+        // Start listening for streaming clients.
+        let mut frame_stream = opts.stream.as_ref().map(|address| {
+            FrameStream::bind(address).expect("Failed to listen on the stream address.")
+        });
+
+        // Start serving performance metrics.
+        #[cfg(feature = "metrics")]
+        let metrics = opts.metrics.as_ref().map(|address| {
+            let metrics = Arc::new(Mutex::new(Metrics::new()));
+
+            serve_metrics(address.as_str(), Arc::clone(&metrics))
+                .expect("Failed to listen on the metrics address.");
+
+            metrics
+        });
+
+        // Screenshots are taken from the core's framebuffer,
+        // which isn't drawn when the colours are applied elsewhere.
+        #[cfg(feature = "control-server")]
+        if opts.control.is_some() && opts.colour_thread {
+            eprintln!("--control can't be used with --colour-thread.");
+            std::process::exit(1);
+        }
+
+        // Start listening for control requests.
+        #[cfg(feature = "control-server")]
+        let mut control_server = opts
+            .control
+            .map(|port| ControlServer::bind(port).expect("Failed to listen on the control port."));
+
+        // Map the file frames are shared through.
+        #[cfg(feature = "shared-frame")]
+        let mut shared_frame = opts.shared_frame.as_ref().map(|path| {
+            let (_, info) = argentum.get_framebuffer();
+
+            SharedFrame::create(path, &info).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            })
+        });
+
         // Initialize SDL's video and audio subsystems.
         if SDL_Init(SDL_INIT_VIDEO | SDL_INIT_AUDIO | SDL_INIT_TIMER) != 0 {
             panic!("Failed to initialize SDL.");
@@ -108,7 +948,7 @@ pub fn main() {
             SDL_WINDOWPOS_CENTERED,
             480,
             432,
-            SDL_WINDOW_OPENGL.0,
+            SDL_WINDOW_OPENGL.0 | SDL_WINDOW_RESIZABLE.0,
         );
 
         // Set the window icon.
@@ -129,13 +969,15 @@ pub fn main() {
         // Create our renderer instance, and set OpenGL viewport.
         let mut renderer = Renderer::new(window);
 
+        apply_display_config(&mut renderer, &config);
+
         // Setup SDL audio system.
         let mut audio_spec: SDL_AudioSpec = std::mem::zeroed();
 
-        audio_spec.freq = 48000;
+        audio_spec.freq = config.audio_sample_rate() as i32;
         audio_spec.format = AUDIO_F32SYS;
         audio_spec.channels = 2;
-        audio_spec.samples = 1024;
+        audio_spec.samples = config.audio_buffer_samples();
         audio_spec.callback = None;
 
         // Open audio queue with the desired spec.
@@ -147,38 +989,461 @@ pub fn main() {
         // Used to store the current polled event.
         let mut event: SDL_Event = std::mem::zeroed();
 
-        'main: loop {
-            // Poll events, quit and handle input appropriately.
-            while SDL_PollEvent(&mut event as _) != 0 {
-                match event.type_ {
-                    SDL_KEYDOWN => {
-                        handle_keyboard_input(&mut argentum, event.key.keysym.scancode, true);
+        // Paces the main loop to the Game Boy's frame rate.
+        let mut limiter = FpsLimiter::new();
+
+        // Keeps the audio queue from running dry or growing.
+        let rate_control = RateControl::new(audio_queue_target as usize, 0.005);
+
+        // Keyboard bindings, these need SDL to be initialized.
+        let mut key_bindings = build_key_bindings(&config.keys);
+
+        // Input macros, recorded with F10 and played with F11
+        // or the keys set up in the config.
+        let mut macros = Macros::load(Macros::path_for_rom(&rom_path));
+        let mut macro_bindings = build_macro_bindings(&config.macro_keys);
+
+        // Watch expressions, shown on top of the screen. They can
+        // use the labels of the `.sym` file next to the ROM.
+        let symbols = load_symbols(&rom_path.with_extension("sym"));
+        let mut watches = Watches::load(&Watches::path_for_rom(&rom_path), &symbols);
+
+        // The pause menu, and whether it is open.
+        let mut menu = Menu::new();
+        let mut paused = false;
+
+        // Tile viewer with recently written tiles highlighted, toggled with F2.
+        let mut show_tiles = false;
+
+        // Frame rate and CPU load overlay, toggled with F3.
+        let mut show_fps = false;
+        let mut fps_counter = FpsCounter::new();
+        let mut cpu_history = VecDeque::with_capacity(osd::CPU_GRAPH_FRAMES);
+
+        // Frames left to show the save state slot for, after Tab changed it.
+        let mut slot_display_frames = 0;
+
+        // Frames with something drawn on top are copied here first.
+        let mut overlay_buffer = Vec::new();
+
+        // Frames run since the start, to play the input script.
+        let mut frame_number = 0;
+
+        // The snapshot to return to after running ahead.
+        let mut run_ahead_snapshot = Snapshot::default();
+
+        // The frames to go back through while Backspace is held.
+        let mut rewind = RewindBuffer::new(config.rewind_megabytes as usize * 1024 * 1024);
+        let mut rewind_state = Vec::new();
+
+        // Catch panics, so a crash report can be written before exiting.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            'main: loop {
+                // Poll events, quit and handle input appropriately.
+                while SDL_PollEvent(&mut event as _) != 0 {
+                    match event.type_ {
+                        SDL_KEYDOWN if paused => {
+                            let input = menu_input(event.key.keysym.scancode, menu.is_rebinding());
+
+                            let action = match input {
+                                Some(input) => menu.handle_input(input, &mut config, &mut cheats),
+                                None => MenuAction::None,
+                            };
+
+                            match action {
+                                MenuAction::None => {}
+
+                                MenuAction::Resume => {
+                                    paused = false;
+                                    limiter.reset();
+                                    SDL_PauseAudio(0);
+                                }
+
+                                // Movies don't record resets.
+                                MenuAction::Reset if movie.is_some() => {
+                                    eprintln!("Resetting isn't possible during a movie.");
+                                }
+
+                                MenuAction::Reset => {
+                                    argentum.reset();
+                                    opts.boot.start(&mut argentum);
+
+                                    paused = false;
+                                    limiter.reset();
+                                    SDL_PauseAudio(0);
+                                }
+
+                                MenuAction::Quit => break 'main,
+
+                                MenuAction::ConfigChanged => {
+                                    apply_config(&mut argentum, &config, &volume);
+                                    apply_display_config(&mut renderer, &config);
+                                    key_bindings = build_key_bindings(&config.keys);
+                                    macro_bindings = build_macro_bindings(&config.macro_keys);
+
+                                    save_config(&config, config_path.as_deref());
+                                }
+
+                                MenuAction::CheatsChanged => {
+                                    argentum.set_cheats(&cheats.enabled_codes());
+
+                                    if let Err(err) = cheats.save() {
+                                        eprintln!("Failed to save the cheats: {}", err);
+                                    }
+                                }
+                            }
+                        }
+
+                        SDL_KEYDOWN => {
+                            let scancode = event.key.keysym.scancode;
+
+                            if scancode == SDL_SCANCODE_F2 && event.key.repeat == 0 {
+                                show_tiles = !show_tiles;
+                                argentum.set_vram_heatmap(show_tiles);
+                            }
+
+                            if scancode == SDL_SCANCODE_F3 && event.key.repeat == 0 {
+                                show_fps = !show_fps;
+                            }
+
+                            if scancode == SDL_SCANCODE_F4 && event.key.repeat == 0 {
+                                if let Some(scanner) = &mut barcode_scanner {
+                                    scanner.scan_next();
+                                }
+                            }
+
+                            // F6 rotates the screen and F7 mirrors it.
+                            if matches!(scancode, SDL_SCANCODE_F6 | SDL_SCANCODE_F7)
+                                && event.key.repeat == 0
+                            {
+                                if scancode == SDL_SCANCODE_F6 {
+                                    config.rotate();
+                                } else {
+                                    config.mirror = !config.mirror;
+                                }
+
+                                renderer.set_transform(config.rotation, config.mirror);
+                                save_config(&config, config_path.as_deref());
+                            }
+
+                            // Tab selects the next save state slot, for F5 and F8.
+                            if scancode == SDL_SCANCODE_TAB && event.key.repeat == 0 {
+                                config.state_slot = (config.state_slot + 1) % STATE_SLOTS;
+                                slot_display_frames = SLOT_DISPLAY_FRAMES;
+
+                                println!("Selected save state slot {}.", config.state_slot);
+                                save_config(&config, config_path.as_deref());
+                            }
+
+                            if scancode == SDL_SCANCODE_ESCAPE {
+                                paused = true;
+                                SDL_PauseAudio(1);
+
+                                // Time spent paused isn't a slow frame.
+                                #[cfg(feature = "metrics")]
+                                if let Some(metrics) = &metrics {
+                                    metrics.lock().unwrap().reset_frame_timer();
+                                }
+
+                                continue;
+                            }
+
+                            handle_keyboard_input(&mut argentum, &key_bindings, scancode, true);
+
+                            if event.key.repeat == 0 {
+                                handle_macro_hotkeys(&mut macros, &macro_bindings, scancode);
+
+                                if scancode == SDL_SCANCODE_F12 {
+                                    handle_ram_diff_hotkey(&mut argentum);
+                                }
+
+                                handle_state_hotkeys(
+                                    &mut argentum,
+                                    scancode,
+                                    &state_path(&rom_path, config.state_slot),
+                                    &mut undo_stack,
+                                    movie.as_mut(),
+                                );
+                            }
+                        }
+
+                        SDL_KEYUP => {
+                            handle_keyboard_input(
+                                &mut argentum,
+                                &key_bindings,
+                                event.key.keysym.scancode,
+                                false,
+                            );
+                        }
+
+                        SDL_QUIT => break 'main,
+
+                        _ => {}
+                    }
+                }
+
+                // Accept new streaming clients and apply their input.
+                if let Some(stream) = &mut frame_stream {
+                    for command in stream.poll() {
+                        match command {
+                            StreamCommand::KeyDown(key) => argentum.key_down(key),
+                            StreamCommand::KeyUp(key) => argentum.key_up(key),
+                        }
+                    }
+                }
+
+                // Answer control requests, and keep showing
+                // the last frame while a client has us paused.
+                #[cfg(feature = "control-server")]
+                if let Some(server) = &mut control_server {
+                    server.poll(&mut argentum);
+
+                    if server.is_paused() {
+                        let (framebuffer, info) = current_frame(&argentum, &mut colour_worker);
+
+                        renderer.update_texture(framebuffer, &info);
+                        SDL_GL_SwapWindow(window);
+
+                        limiter.wait();
+
+                        continue;
+                    }
+                }
+
+                // Draw the menu on top of the last frame while paused.
+                if paused {
+                    let (framebuffer, info) = current_frame(&argentum, &mut colour_worker);
+
+                    overlay_buffer.clear();
+                    overlay_buffer.extend_from_slice(framebuffer);
+
+                    menu.draw(&mut overlay_buffer, &info, &config, &cheats);
+                    renderer.update_texture(&overlay_buffer, &info);
+
+                    SDL_GL_SwapWindow(window);
+
+                    limiter.wait();
+
+                    continue;
+                }
+
+                // Nudge the audio sample rate to keep the queue steady.
+                let queued = SDL_GetQueuedAudioSize(SDL_AudioDeviceID(1)) as usize;
+
+                argentum.set_audio_rate_ratio(rate_control.ratio(queued));
+
+                // Record the held keys, or hold the keys of the playing macro.
+                let was_playing = macros.is_playing();
+
+                match macros.next_frame(argentum.keys()) {
+                    Some(keys) => argentum.set_keys(keys),
+                    None if was_playing => argentum.set_keys(held_keys(&key_bindings)),
+                    None => {}
+                }
+
+                // Press the keys the input script lists for this frame.
+                for input in inputs.iter().filter(|input| input.frame == frame_number) {
+                    if input.pressed {
+                        argentum.key_down(input.key);
+                    } else {
+                        argentum.key_up(input.key);
+                    }
+                }
+
+                // Record the keys now held, or hold the keys of the movie.
+                if let Some(movie) = &mut movie {
+                    let was_playing = movie.is_playing();
+
+                    match movie.next_frame(argentum.keys()) {
+                        Some(keys) => argentum.set_keys(keys),
+                        None if was_playing => argentum.set_keys(held_keys(&key_bindings)),
+                        None => {}
+                    }
+                }
+
+                // Go back a frame while Backspace is held, instead of running
+                // one. Movies would lose track of the frame they are on.
+                if movie.is_none() && is_key_held(SDL_SCANCODE_BACKSPACE) {
+                    if rewind.pop(&mut rewind_state) {
+                        argentum.rollback(&rewind_state);
+                    }
+                } else {
+                    // Execute one frame's worth of instructions.
+                    argentum.execute_frame();
+                    frame_number += 1;
+
+                    if movie.is_none() && config.rewind_megabytes != 0 {
+                        argentum.save_state_into(&mut rewind_state);
+                        rewind.push(&rewind_state);
+                    }
+                }
+
+                for line in watches.update(&argentum) {
+                    if opts.log_watches {
+                        println!("Frame {}: {}", frame_number, line);
+                    }
+                }
+
+                if cpu_history.len() == osd::CPU_GRAPH_FRAMES {
+                    cpu_history.pop_front();
+                }
+
+                cpu_history.push_back(argentum.cpu_usage().load());
+
+                // Report anything noteworthy the core ran into.
+                while let Some(event) = argentum.poll_event() {
+                    match event {
+                        ArgentumEvent::CpuLocked { opcode, address } => eprintln!(
+                            "The CPU locked up executing illegal opcode {:02X} at {}.",
+                            opcode, address
+                        ),
+
+                        ArgentumEvent::BootFailed => {
+                            eprintln!("The bootrom rejected the cartridge header and hung.")
+                        }
+
+                        ArgentumEvent::BreakpointHit(_) => {}
+                    }
+                }
+
+                // Run ahead to the frame the input held now shows up in.
+                // The state is rolled back once that frame is displayed.
+                if opts.run_ahead != 0 {
+                    argentum.take_snapshot(&mut run_ahead_snapshot);
+                    audio_enabled.set(false);
+
+                    for _ in 0..opts.run_ahead {
+                        argentum.execute_frame();
+                    }
+
+                    while argentum.poll_event().is_some() {}
+
+                    audio_enabled.set(true);
+                }
+
+                // Render the framebuffer to the backbuffer.
+                if let Some(worker) = &mut colour_worker {
+                    worker.submit(argentum.raw_frame());
+                }
+
+                let (framebuffer, info) = current_frame(&argentum, &mut colour_worker);
+
+                if show_tiles {
+                    let heat = argentum.vram_heatmap().unwrap_or_default();
+                    let info = tile_viewer::draw(&mut overlay_buffer, &argentum.tile_sheet(), heat);
+
+                    renderer.update_texture(&overlay_buffer, &info);
+                } else if show_fps
+                    || macros.is_recording()
+                    || !watches.is_empty()
+                    || slot_display_frames > 0
+                {
+                    overlay_buffer.clear();
+                    overlay_buffer.extend_from_slice(framebuffer);
+
+                    if show_fps {
+                        osd::draw_fps(
+                            &mut overlay_buffer,
+                            &info,
+                            fps_counter.fps(),
+                            renderer.render_time(),
+                        );
+
+                        osd::draw_cpu_usage(&mut overlay_buffer, &info, &cpu_history);
+                    }
+
+                    if macros.is_recording() {
+                        osd::draw_recording(&mut overlay_buffer, &info);
+                    }
+
+                    osd::draw_watches(&mut overlay_buffer, &info, &watches.lines());
+
+                    if slot_display_frames > 0 {
+                        osd::draw_state_slot(&mut overlay_buffer, &info, config.state_slot);
+                        slot_display_frames -= 1;
                     }
 
-                    SDL_KEYUP => {
-                        handle_keyboard_input(&mut argentum, event.key.keysym.scancode, false);
+                    renderer.update_texture(&overlay_buffer, &info);
+                } else {
+                    renderer.update_texture(framebuffer, &info);
+                }
+
+                fps_counter.tick();
+
+                if let Some(stream) = &mut frame_stream {
+                    stream.send_frame(framebuffer, &info);
+                }
+
+                #[cfg(feature = "shared-frame")]
+                if let Some(shared_frame) = &mut shared_frame {
+                    shared_frame.write_frame(framebuffer, &info);
+                }
+
+                if opts.run_ahead != 0 {
+                    argentum.restore_snapshot(&run_ahead_snapshot);
+                }
+
+                #[cfg(feature = "metrics")]
+                if let Some(metrics) = &metrics {
+                    let mut metrics = metrics.lock().unwrap();
+
+                    if queued == 0 {
+                        metrics.record_audio_underrun();
                     }
 
-                    SDL_QUIT => break 'main,
+                    metrics.record_frame();
+                }
+
+                // Swap front and back buffers.
+                SDL_GL_SwapWindow(window);
 
-                    _ => {}
+                if config.autosave_minutes != 0 && last_autosave.elapsed() >= autosave_interval {
+                    write_autosave(&argentum, &autosave_path);
+                    last_autosave = Instant::now();
                 }
+
+                // Wait until the next frame is due.
+                limiter.wait();
+            }
+        }));
+
+        if let Err(payload) = result {
+            match crash_reporter.write_bundle(&argentum, &config) {
+                Ok(path) => eprintln!("A crash report was written to {}.", path.display()),
+                Err(err) => eprintln!("Failed to write the crash report: {}", err),
             }
 
-            // Execute one frame's worth of instructions.
-            argentum.execute_frame();
+            panic::resume_unwind(payload);
+        }
+
+        write_autosave(&argentum, &autosave_path);
 
-            // Render the framebuffer to the backbuffer.
-            renderer.update_texture(argentum.get_framebuffer());
+        if let Some(movie) = &movie {
+            match movie.save() {
+                Ok(()) => println!(
+                    "Saved the movie, {} frames with {} rerecords.",
+                    movie.frame_count(),
+                    movie.rerecords()
+                ),
 
-            // Swap front and back buffers.
-            SDL_GL_SwapWindow(window);
+                Err(err) => eprintln!("Failed to save the movie: {}", err),
+            }
+        }
+
+        if let (Some(path), Some(log)) = (&opts.vgm, argentum.stop_audio_log()) {
+            if let Err(err) = std::fs::write(path, vgm::encode_vgm(&log)) {
+                eprintln!("Failed to write the VGM file: {}", err);
+            }
         }
 
         if let Some(ram_save) = argentum.get_ram_dump() {
-            std::fs::write(&rom_path, &ram_save).expect("Failed to write save file.");
+            std::fs::write(&save_path, &ram_save).expect("Failed to write save file.");
         }
 
+        // Where the time went, for reports of the emulator being slow.
+        #[cfg(feature = "perf-counters")]
+        eprintln!("{}", argentum.perf_counters());
+
         // De-init SDL subsystems, and return.
         SDL_CloseAudio();
         SDL_DestroyWindow(window);
@@ -0,0 +1,353 @@
+//! The pause menu, drawn with the OSD on top of the last frame.
+
+use argentum_core::{ArgentumKey, FrameInfo};
+
+use crate::{
+    cheats::CheatList,
+    config::{Config, STATE_SLOTS},
+    i18n::{find_locale, Message, LOCALES},
+    osd::{self, GLYPH_HEIGHT},
+    palette::palette_names,
+};
+
+/// Colour of unselected menu items.
+const TEXT_COLOUR: u32 = 0xC0C0C0;
+
+/// Colour of the selected menu item.
+const SELECTED_COLOUR: u32 = 0xFFD000;
+
+/// Where the first menu item is drawn, below the title.
+const MENU_TOP: usize = 12;
+
+/// Input the menu reacts to, translated from keyboard events.
+pub enum MenuInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Confirm,
+    Back,
+
+    /// Any key pressed while a key is being rebound, by its name.
+    Key(String),
+}
+
+/// What the frontend should do after the menu handled some input.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MenuAction {
+    None,
+    Resume,
+    Reset,
+    Quit,
+
+    /// The config was changed and should be applied and saved.
+    ConfigChanged,
+
+    /// A cheat was enabled or disabled.
+    CheatsChanged,
+}
+
+#[derive(Clone, Copy)]
+enum MenuItem {
+    Resume,
+    Language,
+    Palette,
+    HighContrast,
+    IntegerScale,
+    Volume,
+    StateSlot,
+    Binding(ArgentumKey, Message),
+
+    /// Toggles the cheat with the given index.
+    Cheat(usize),
+
+    Reset,
+    Quit,
+}
+
+const MENU_ITEMS: [MenuItem; 17] = [
+    MenuItem::Resume,
+    MenuItem::Language,
+    MenuItem::Palette,
+    MenuItem::HighContrast,
+    MenuItem::IntegerScale,
+    MenuItem::Volume,
+    MenuItem::StateSlot,
+    MenuItem::Binding(ArgentumKey::Up, Message::Up),
+    MenuItem::Binding(ArgentumKey::Down, Message::Down),
+    MenuItem::Binding(ArgentumKey::Left, Message::Left),
+    MenuItem::Binding(ArgentumKey::Right, Message::Right),
+    MenuItem::Binding(ArgentumKey::ButtonA, Message::ButtonA),
+    MenuItem::Binding(ArgentumKey::ButtonB, Message::ButtonB),
+    MenuItem::Binding(ArgentumKey::Start, Message::Start),
+    MenuItem::Binding(ArgentumKey::Select, Message::Select),
+    MenuItem::Reset,
+    MenuItem::Quit,
+];
+
+/// Get the menu items, with a toggle for every cheat
+/// between the settings and the last two actions.
+fn menu_items(cheats: &CheatList) -> Vec<MenuItem> {
+    let (settings, actions) = MENU_ITEMS.split_at(MENU_ITEMS.len() - 2);
+
+    settings
+        .iter()
+        .copied()
+        .chain((0..cheats.cheats().len()).map(MenuItem::Cheat))
+        .chain(actions.iter().copied())
+        .collect()
+}
+
+pub struct Menu {
+    /// Index of the selected menu item.
+    selected: usize,
+
+    /// Indicates if the next key press rebinds the selected button.
+    rebinding: bool,
+}
+
+impl Menu {
+    /// Create a new `Menu` instance.
+    pub fn new() -> Self {
+        Self {
+            selected: 0,
+            rebinding: false,
+        }
+    }
+
+    /// Indicates if the menu is waiting for a key to bind.
+    pub fn is_rebinding(&self) -> bool {
+        self.rebinding
+    }
+
+    /// Handle a single input, changing the config or cheats if needed.
+    pub fn handle_input(
+        &mut self,
+        input: MenuInput,
+        config: &mut Config,
+        cheats: &mut CheatList,
+    ) -> MenuAction {
+        let items = menu_items(cheats);
+        let item = items[self.selected];
+
+        if self.rebinding {
+            self.rebinding = false;
+
+            return match (input, item) {
+                (MenuInput::Key(name), MenuItem::Binding(key, _)) => {
+                    *config.keys.binding_mut(key) = name;
+
+                    MenuAction::ConfigChanged
+                }
+
+                _ => MenuAction::None,
+            };
+        }
+
+        match input {
+            MenuInput::Up => {
+                self.selected = (self.selected + items.len() - 1) % items.len();
+
+                MenuAction::None
+            }
+
+            MenuInput::Down => {
+                self.selected = (self.selected + 1) % items.len();
+
+                MenuAction::None
+            }
+
+            MenuInput::Left | MenuInput::Right if matches!(item, MenuItem::Cheat(_)) => {
+                Self::toggle_cheat(item, cheats)
+            }
+
+            MenuInput::Left => Self::adjust(item, config, false),
+            MenuInput::Right => Self::adjust(item, config, true),
+
+            MenuInput::Confirm => match item {
+                MenuItem::Resume => MenuAction::Resume,
+                MenuItem::Reset => MenuAction::Reset,
+                MenuItem::Quit => MenuAction::Quit,
+
+                MenuItem::Binding(..) => {
+                    self.rebinding = true;
+
+                    MenuAction::None
+                }
+
+                MenuItem::Cheat(_) => Self::toggle_cheat(item, cheats),
+
+                _ => Self::adjust(item, config, true),
+            },
+
+            MenuInput::Back => MenuAction::Resume,
+            MenuInput::Key(_) => MenuAction::None,
+        }
+    }
+
+    fn toggle_cheat(item: MenuItem, cheats: &mut CheatList) -> MenuAction {
+        match item {
+            MenuItem::Cheat(index) => {
+                cheats.toggle(index);
+
+                MenuAction::CheatsChanged
+            }
+
+            _ => MenuAction::None,
+        }
+    }
+
+    /// Change the value of a setting, in either direction.
+    fn adjust(item: MenuItem, config: &mut Config, increase: bool) -> MenuAction {
+        match item {
+            MenuItem::Language => {
+                let index = find_locale(&config.language);
+
+                let index = if increase {
+                    (index + 1) % LOCALES.len()
+                } else {
+                    (index + LOCALES.len() - 1) % LOCALES.len()
+                };
+
+                config.language = String::from(LOCALES[index].code);
+            }
+
+            MenuItem::Palette => {
+                let names = palette_names();
+
+                let index = names
+                    .iter()
+                    .position(|name| *name == config.palette)
+                    .unwrap_or(0);
+
+                let index = if increase {
+                    (index + 1) % names.len()
+                } else {
+                    (index + names.len() - 1) % names.len()
+                };
+
+                config.palette = String::from(names[index]);
+            }
+
+            MenuItem::HighContrast => config.high_contrast = !config.high_contrast,
+            MenuItem::IntegerScale => config.integer_scale = !config.integer_scale,
+
+            MenuItem::Volume => {
+                config.volume = if increase {
+                    (config.volume + 10).min(100)
+                } else {
+                    config.volume.saturating_sub(10)
+                };
+            }
+
+            MenuItem::StateSlot => {
+                config.state_slot = if increase {
+                    (config.state_slot + 1) % STATE_SLOTS
+                } else {
+                    (config.state_slot + STATE_SLOTS - 1) % STATE_SLOTS
+                };
+            }
+
+            _ => return MenuAction::None,
+        }
+
+        MenuAction::ConfigChanged
+    }
+
+    /// Draw the menu on top of a RGB24 screen buffer.
+    ///
+    /// The list scrolls to keep the selected item visible
+    /// if the items don't all fit on the screen.
+    pub fn draw(&self, buffer: &mut [u8], info: &FrameInfo, config: &Config, cheats: &CheatList) {
+        let locale = &LOCALES[find_locale(&config.language)];
+
+        let on_off = |enabled: bool| {
+            if enabled {
+                locale.get(Message::On)
+            } else {
+                locale.get(Message::Off)
+            }
+        };
+
+        osd::dim(buffer);
+        osd::draw_text(
+            buffer,
+            info,
+            8,
+            2,
+            locale.get(Message::Paused),
+            SELECTED_COLOUR,
+        );
+
+        let bindings = config.keys.bindings();
+
+        let visible = (info.height - MENU_TOP) / GLYPH_HEIGHT;
+        let first = (self.selected + 1).saturating_sub(visible);
+
+        for (i, item) in menu_items(cheats)
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(visible)
+        {
+            let text = match *item {
+                MenuItem::Resume => String::from(locale.get(Message::Resume)),
+                MenuItem::Language => format!("{}: {}", locale.get(Message::Language), locale.name),
+                MenuItem::Palette => {
+                    format!("{}: {}", locale.get(Message::Palette), config.palette)
+                }
+
+                MenuItem::HighContrast => {
+                    format!(
+                        "{}: {}",
+                        locale.get(Message::HighContrast),
+                        on_off(config.high_contrast)
+                    )
+                }
+
+                MenuItem::IntegerScale => {
+                    format!(
+                        "{}: {}",
+                        locale.get(Message::IntegerScale),
+                        on_off(config.integer_scale)
+                    )
+                }
+
+                MenuItem::Volume => format!("{}: {}%", locale.get(Message::Volume), config.volume),
+
+                MenuItem::StateSlot => {
+                    format!("{}: {}", locale.get(Message::StateSlot), config.state_slot)
+                }
+
+                MenuItem::Binding(_, label) if self.rebinding && i == self.selected => {
+                    format!("{}: {}", locale.get(label), locale.get(Message::PressAKey))
+                }
+
+                MenuItem::Binding(key, label) => {
+                    let (_, name) = bindings.iter().find(|(k, _)| *k == key).unwrap();
+
+                    format!("{}: {}", locale.get(label), name)
+                }
+
+                MenuItem::Cheat(index) => {
+                    let cheat = &cheats.cheats()[index];
+
+                    format!("{}: {}", cheat.label(), on_off(cheat.enabled))
+                }
+
+                MenuItem::Reset => String::from(locale.get(Message::Reset)),
+                MenuItem::Quit => String::from(locale.get(Message::Quit)),
+            };
+
+            let (prefix, colour) = if i == self.selected {
+                ("> ", SELECTED_COLOUR)
+            } else {
+                ("  ", TEXT_COLOUR)
+            };
+
+            let y = MENU_TOP + (i - first) * GLYPH_HEIGHT;
+
+            osd::draw_text(buffer, info, 8, y, &format!("{}{}", prefix, text), colour);
+        }
+    }
+}
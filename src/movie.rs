@@ -0,0 +1,237 @@
+//! Movies, recordings of the keys held on every frame from a starting
+//! state, the way tool-assisted speedruns are made.
+//!
+//! While a movie is active, every save state gets a bookmark next to
+//! it, `<state>.movie`, holding the movie up to the frame it was saved
+//! on. Loading the state while recording cuts the movie back to the
+//! bookmark and records on from there, counting a rerecord. Loading it
+//! read-only keeps the movie and plays on from the bookmarked frame,
+//! which has to be part of the movie.
+//!
+//! Movies are stored as `AGMV`, a version byte, the rerecord count as
+//! a little endian `u32`, the length of the starting state as a `u32`
+//! followed by the state, then the frame count as a `u32` followed by
+//! an `ArgentumKey` mask per frame.
+
+use std::{
+    convert::TryInto,
+    ffi::OsString,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Identifies movie files.
+const MAGIC: &[u8; 4] = b"AGMV";
+
+/// The current version of the movie format.
+const VERSION: u8 = 1;
+
+/// Why a bookmark couldn't be loaded.
+#[derive(Debug)]
+pub enum BookmarkError {
+    /// The state was saved without a movie, or the bookmark is unreadable.
+    Missing,
+
+    /// The bookmark belongs to a movie with another starting state.
+    OtherMovie,
+
+    /// Read-only movies can't branch off at the bookmark.
+    OtherBranch,
+}
+
+impl fmt::Display for BookmarkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BookmarkError::Missing => write!(f, "the save state has no movie bookmark"),
+            BookmarkError::OtherMovie => write!(f, "the save state belongs to another movie"),
+            BookmarkError::OtherBranch => {
+                write!(f, "the save state is on another branch of the movie")
+            }
+        }
+    }
+}
+
+pub struct Movie {
+    /// The file the movie is stored in.
+    path: PathBuf,
+
+    /// The state the movie starts from.
+    start_state: Vec<u8>,
+
+    /// The keys held on every frame.
+    frames: Vec<u8>,
+
+    /// How many times a state was loaded while recording.
+    rerecords: u32,
+
+    /// The next frame to play or record.
+    frame: usize,
+
+    /// Play the movie without recording over it.
+    read_only: bool,
+}
+
+impl Movie {
+    /// Create a new `Movie` instance, recording from the given state.
+    pub fn new(path: PathBuf, start_state: Vec<u8>) -> Self {
+        Self {
+            path,
+            start_state,
+            frames: Vec::new(),
+            rerecords: 0,
+            frame: 0,
+            read_only: false,
+        }
+    }
+
+    /// Load the movie stored at the given path, to play it from the
+    /// start. Once it ends recording continues, unless it's read-only.
+    pub fn load(path: PathBuf, read_only: bool) -> Result<Self, String> {
+        let contents = std::fs::read(&path).map_err(|err| err.to_string())?;
+        let (start_state, frames, rerecords) =
+            decode(&contents).ok_or("the movie file is malformed")?;
+
+        Ok(Self {
+            path,
+            start_state,
+            frames,
+            rerecords,
+            frame: 0,
+            read_only,
+        })
+    }
+
+    /// Write the movie back to its file.
+    pub fn save(&self) -> std::io::Result<()> {
+        std::fs::write(&self.path, self.encode(&self.frames))
+    }
+
+    /// Get the state the movie starts from.
+    pub fn start_state(&self) -> &[u8] {
+        &self.start_state
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn rerecords(&self) -> u32 {
+        self.rerecords
+    }
+
+    /// Check if the movie is playing rather than recording.
+    pub fn is_playing(&self) -> bool {
+        self.frame < self.frames.len()
+    }
+
+    /// Call before every frame with the keys that are held.
+    ///
+    /// While playing, returns the keys the movie holds on this
+    /// frame, which should replace the held keys.
+    pub fn next_frame(&mut self, keys: u8) -> Option<u8> {
+        match self.frames.get(self.frame) {
+            Some(&keys) => {
+                self.frame += 1;
+
+                Some(keys)
+            }
+
+            // Read-only movies that ended leave the input alone.
+            None if self.read_only => None,
+
+            None => {
+                self.frames.push(keys);
+                self.frame += 1;
+
+                None
+            }
+        }
+    }
+
+    /// Get the path of the bookmark of a save state.
+    pub fn bookmark_path(state_path: &Path) -> PathBuf {
+        let mut path = OsString::from(state_path);
+
+        path.push(".movie");
+        path.into()
+    }
+
+    /// Write a bookmark of the movie up to the current frame.
+    pub fn save_bookmark(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.encode(&self.frames[..self.frame]))
+    }
+
+    /// Check that a bookmark can be loaded, before the state it belongs
+    /// to is. Returns the bookmarked frames, for `load_bookmark`.
+    pub fn check_bookmark(&self, path: &Path) -> Result<Vec<u8>, BookmarkError> {
+        let contents = std::fs::read(path).map_err(|_| BookmarkError::Missing)?;
+        let (start_state, frames, _) = decode(&contents).ok_or(BookmarkError::Missing)?;
+
+        if start_state != self.start_state {
+            return Err(BookmarkError::OtherMovie);
+        }
+
+        if self.read_only && !self.frames.starts_with(&frames) {
+            return Err(BookmarkError::OtherBranch);
+        }
+
+        Ok(frames)
+    }
+
+    /// Go back to a bookmark, once its state was loaded. Recording movies
+    /// are cut back to it, counting a rerecord, read-only ones play on.
+    pub fn load_bookmark(&mut self, frames: Vec<u8>) {
+        self.frame = frames.len();
+
+        if !self.read_only {
+            self.frames = frames;
+            self.rerecords += 1;
+        }
+    }
+
+    fn encode(&self, frames: &[u8]) -> Vec<u8> {
+        let mut contents = Vec::with_capacity(17 + self.start_state.len() + frames.len());
+
+        contents.extend_from_slice(MAGIC);
+        contents.push(VERSION);
+        contents.extend_from_slice(&self.rerecords.to_le_bytes());
+        contents.extend_from_slice(&(self.start_state.len() as u32).to_le_bytes());
+        contents.extend_from_slice(&self.start_state);
+        contents.extend_from_slice(&(frames.len() as u32).to_le_bytes());
+        contents.extend_from_slice(frames);
+
+        contents
+    }
+}
+
+/// Split a movie into its starting state, frames and rerecord count.
+fn decode(contents: &[u8]) -> Option<(Vec<u8>, Vec<u8>, u32)> {
+    let mut rest = contents.strip_prefix(MAGIC.as_ref())?;
+
+    let mut take = |len: usize| -> Option<&[u8]> {
+        if rest.len() < len {
+            return None;
+        }
+
+        let (taken, remaining) = rest.split_at(len);
+
+        rest = remaining;
+        Some(taken)
+    };
+
+    let read_u32 = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap());
+
+    if take(1)? != [VERSION] {
+        return None;
+    }
+
+    let rerecords = read_u32(take(4)?);
+
+    let state_len = read_u32(take(4)?) as usize;
+    let start_state = take(state_len)?.to_vec();
+
+    let frame_count = read_u32(take(4)?) as usize;
+    let frames = take(frame_count)?.to_vec();
+
+    Some((start_state, frames, rerecords))
+}
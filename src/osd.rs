@@ -0,0 +1,205 @@
+//! On screen display, draws text on top of the framebuffer.
+
+use std::{collections::VecDeque, time::Duration};
+
+use argentum_core::FrameInfo;
+
+/// Width of a character cell, including spacing.
+pub const GLYPH_WIDTH: usize = 6;
+
+/// Height of a character cell, including spacing.
+pub const GLYPH_HEIGHT: usize = 8;
+
+/// Frames of CPU load the graph shows, one per column.
+pub const CPU_GRAPH_FRAMES: usize = 60;
+
+/// Height of the CPU load graph in pixels.
+const CPU_GRAPH_HEIGHT: usize = 16;
+
+/// 5x7 bitmap font for ASCII 0x20 - 0x5F. Each row is stored in
+/// the lower 5 bits, with bit 4 being the leftmost pixel.
+/// Lowercase letters are drawn using their uppercase glyphs.
+const FONT: [[u8; 7]; 64] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // '!'
+    [0x0A, 0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A], // '#'
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04], // '$'
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // '%'
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D], // '&'
+    [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00], // '''
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // '('
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // ')'
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00], // '*'
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08], // ','
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], // '.'
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // '/'
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // '0'
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // '1'
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // '2'
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // '3'
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // '4'
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // '5'
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // '6'
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // '7'
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // '8'
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // '9'
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00], // ':'
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08], // ';'
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // '<'
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00], // '='
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // '>'
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // '?'
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E], // '@'
+    [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11], // 'A'
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // 'B'
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // 'C'
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // 'D'
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // 'E'
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // 'F'
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // 'G'
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // 'H'
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // 'I'
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // 'J'
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // 'K'
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // 'L'
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // 'M'
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // 'N'
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // 'O'
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // 'P'
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // 'Q'
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // 'R'
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // 'S'
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // 'T'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // 'U'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // 'V'
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // 'W'
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // 'X'
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // 'Y'
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // 'Z'
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E], // '['
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // '\'
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E], // ']'
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F], // '_'
+];
+
+/// Set a single pixel of a RGB24 screen buffer, pixels
+/// outside of the screen are ignored.
+fn set_pixel(buffer: &mut [u8], info: &FrameInfo, x: usize, y: usize, colour: u32) {
+    if x >= info.width || y >= info.height {
+        return;
+    }
+
+    let offset = y * info.pitch + x * 3;
+
+    buffer[offset] = (colour >> 16) as u8;
+    buffer[offset + 1] = (colour >> 8) as u8;
+    buffer[offset + 2] = colour as u8;
+}
+
+/// Draw a line of text with its top left corner at the given position.
+pub fn draw_text(buffer: &mut [u8], info: &FrameInfo, x: usize, y: usize, text: &str, colour: u32) {
+    for (i, c) in text.chars().enumerate() {
+        let c = c.to_ascii_uppercase();
+
+        // Characters the font doesn't have are drawn as '?'.
+        let index = match c {
+            ' '..='_' => c as usize - 0x20,
+            _ => '?' as usize - 0x20,
+        };
+
+        for (row, bits) in FONT[index].iter().enumerate() {
+            for column in 0..5 {
+                if (bits >> (4 - column)) & 0x01 != 0 {
+                    set_pixel(buffer, info, x + i * GLYPH_WIDTH + column, y + row, colour);
+                }
+            }
+        }
+    }
+}
+
+/// Draw the frame rate and render time in the top left corner,
+/// with a shadow so it stays readable on any background.
+pub fn draw_fps(buffer: &mut [u8], info: &FrameInfo, fps: f64, render_time: Duration) {
+    let text = format!(
+        "{:.1} FPS {:.2} ms",
+        fps,
+        render_time.as_secs_f64() * 1000.0
+    );
+
+    draw_text(buffer, info, 3, 3, &text, 0x000000);
+    draw_text(buffer, info, 2, 2, &text, 0xFFFFFF);
+}
+
+/// Draw a graph of the emulated CPU load of the last frames in the
+/// bottom left corner, oldest first, with the latest load above it.
+pub fn draw_cpu_usage(buffer: &mut [u8], info: &FrameInfo, history: &VecDeque<f64>) {
+    let bottom = info.height - 3;
+    let top = bottom - CPU_GRAPH_HEIGHT;
+
+    let text = format!("CPU {:.0}%", history.back().unwrap_or(&0.0) * 100.0);
+
+    draw_text(buffer, info, 3, top - GLYPH_HEIGHT + 1, &text, 0x000000);
+    draw_text(buffer, info, 2, top - GLYPH_HEIGHT, &text, 0xFFFFFF);
+
+    for (x, &load) in history.iter().enumerate() {
+        let height = (load * CPU_GRAPH_HEIGHT as f64).round() as usize;
+
+        // Green while there's headroom, red once the game needs
+        // nearly the whole frame.
+        let colour = match load {
+            load if load < 0.5 => 0x00C000,
+            load if load < 0.9 => 0xE0C000,
+            _ => 0xE00000,
+        };
+
+        for y in top..bottom {
+            let colour = if bottom - y <= height {
+                colour
+            } else {
+                0x000000
+            };
+
+            set_pixel(buffer, info, 2 + x, y, colour);
+        }
+    }
+}
+
+/// Show that input is being recorded, in the top right corner.
+pub fn draw_recording(buffer: &mut [u8], info: &FrameInfo) {
+    let x = info.width - 3 * GLYPH_WIDTH - 2;
+
+    draw_text(buffer, info, x + 1, 3, "REC", 0x000000);
+    draw_text(buffer, info, x, 2, "REC", 0xFF0000);
+}
+
+/// Show the selected save state slot in the bottom right corner.
+pub fn draw_state_slot(buffer: &mut [u8], info: &FrameInfo, slot: u8) {
+    let text = format!("SLOT {}", slot);
+    let x = info.width - text.len() * GLYPH_WIDTH - 2;
+    let y = info.height - GLYPH_HEIGHT - 2;
+
+    draw_text(buffer, info, x + 1, y + 1, &text, 0x000000);
+    draw_text(buffer, info, x, y, &text, 0xFFFFFF);
+}
+
+/// Draw the values of the watch expressions below the frame rate,
+/// one per line, with a shadow like the frame rate.
+pub fn draw_watches(buffer: &mut [u8], info: &FrameInfo, lines: &[String]) {
+    for (i, line) in lines.iter().enumerate() {
+        let y = 2 + (i + 1) * GLYPH_HEIGHT;
+
+        draw_text(buffer, info, 3, y + 1, line, 0x000000);
+        draw_text(buffer, info, 2, y, line, 0xFFFF00);
+    }
+}
+
+/// Darken the whole screen, so text drawn on top stands out.
+pub fn dim(buffer: &mut [u8]) {
+    for byte in buffer.iter_mut() {
+        *byte /= 4;
+    }
+}
@@ -0,0 +1,155 @@
+//! Colour palettes selectable for DMG games.
+
+use argentum_core::{
+    Argentum,
+    ArgentumKey::{self, ButtonA, ButtonB, Down, Left, Right, Up},
+    DmgPalettes,
+};
+
+/// A named DMG palette, colours go from lightest to darkest.
+pub struct Palette {
+    /// The name used in the config file and shown in the menu.
+    pub name: &'static str,
+
+    /// The four shades as 0xRRGGBB values.
+    pub colours: [u32; 4],
+}
+
+/// All the palettes the user can pick from, the first one is the default.
+pub const PALETTES: &[Palette] = &[
+    Palette {
+        name: "original",
+        colours: [0xFED018, 0xD35600, 0x5E1210, 0x0D0405],
+    },
+    Palette {
+        name: "grayscale",
+        colours: [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000],
+    },
+    Palette {
+        name: "pocket",
+        colours: [0xC4CFA1, 0x8B956D, 0x4D533C, 0x1F1F1F],
+    },
+    Palette {
+        name: "green",
+        colours: [0x9BBC0F, 0x8BAC0F, 0x306230, 0x0F380F],
+    },
+    // The colour blind palettes avoid telling shades apart by red and
+    // green, they run from yellow to blue with evenly spaced lightness.
+    Palette {
+        name: "deuteranopia",
+        colours: [0xFFF4C2, 0xD9A93F, 0x4A6FA5, 0x10183A],
+    },
+    Palette {
+        name: "protanopia",
+        colours: [0xF5F5DC, 0xDDCC77, 0x4477AA, 0x1A1A4A],
+    },
+];
+
+/// Lightness the shades are pulled towards in high contrast mode.
+const HIGH_CONTRAST_LEVELS: [u32; 4] = [0xFF, 0xAA, 0x55, 0x00];
+
+/// Increase the separation between the shades of a palette by
+/// blending every shade halfway towards an evenly spaced gray.
+pub fn high_contrast(colours: [u32; 4]) -> [u32; 4] {
+    let mut result = [0; 4];
+
+    for (i, colour) in colours.iter().enumerate() {
+        let level = HIGH_CONTRAST_LEVELS[i];
+
+        for shift in [16, 8, 0].iter() {
+            let channel = (colour >> shift) & 0xFF;
+
+            result[i] |= ((channel + level) / 2) << shift;
+        }
+    }
+
+    result
+}
+
+/// Find the index of the palette with the given name,
+/// falling back to the default palette.
+pub fn find_palette(name: &str) -> usize {
+    PALETTES.iter().position(|p| p.name == name).unwrap_or(0)
+}
+
+/// A palette of the CGB boot ROM, colouring the background and the
+/// objects separately.
+pub struct CgbPalette {
+    /// The name used in the config file and shown in the menu.
+    pub name: &'static str,
+
+    /// The direction held while the logo is shown on a CGB to pick
+    /// the palette, `None` picks it by the game's title like the boot
+    /// ROM does.
+    pub direction: Option<ArgentumKey>,
+
+    /// The button held along with the direction.
+    pub button: Option<ArgentumKey>,
+}
+
+const fn cgb_palette(
+    name: &'static str,
+    direction: Option<ArgentumKey>,
+    button: Option<ArgentumKey>,
+) -> CgbPalette {
+    CgbPalette {
+        name,
+        direction,
+        button,
+    }
+}
+
+/// All the CGB boot ROM palettes the user can pick from.
+pub const CGB_PALETTES: &[CgbPalette] = &[
+    cgb_palette("cgb", None, None),
+    cgb_palette("cgb-up", Some(Up), None),
+    cgb_palette("cgb-up-a", Some(Up), Some(ButtonA)),
+    cgb_palette("cgb-up-b", Some(Up), Some(ButtonB)),
+    cgb_palette("cgb-left", Some(Left), None),
+    cgb_palette("cgb-left-a", Some(Left), Some(ButtonA)),
+    cgb_palette("cgb-left-b", Some(Left), Some(ButtonB)),
+    cgb_palette("cgb-down", Some(Down), None),
+    cgb_palette("cgb-down-a", Some(Down), Some(ButtonA)),
+    cgb_palette("cgb-down-b", Some(Down), Some(ButtonB)),
+    cgb_palette("cgb-right", Some(Right), None),
+    cgb_palette("cgb-right-a", Some(Right), Some(ButtonA)),
+    cgb_palette("cgb-right-b", Some(Right), Some(ButtonB)),
+];
+
+/// Get the names of every palette, in the order the menu shows them.
+pub fn palette_names() -> Vec<&'static str> {
+    PALETTES
+        .iter()
+        .map(|palette| palette.name)
+        .chain(CGB_PALETTES.iter().map(|palette| palette.name))
+        .collect()
+}
+
+/// Get the colours of the palette with the given name for the
+/// running game, falling back to the default palette.
+pub fn resolve_palette(gb: &Argentum, name: &str, contrast: bool) -> DmgPalettes {
+    let cgb_palette = CGB_PALETTES.iter().find(|palette| palette.name == name);
+
+    let palettes = match cgb_palette {
+        Some(CgbPalette {
+            direction: Some(direction),
+            button,
+            ..
+        }) => DmgPalettes::for_buttons(*direction, *button)
+            .expect("invalid CGB palette key combination"),
+
+        Some(_) => gb.cgb_compat_palettes(),
+
+        None => DmgPalettes::uniform(PALETTES[find_palette(name)].colours),
+    };
+
+    if contrast {
+        DmgPalettes {
+            bg: high_contrast(palettes.bg),
+            obj0: high_contrast(palettes.obj0),
+            obj1: high_contrast(palettes.obj1),
+        }
+    } else {
+        palettes
+    }
+}
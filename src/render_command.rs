@@ -0,0 +1,238 @@
+//! The `render` subcommand, which replays a movie offline and writes
+//! its video and audio to files, for encoding tool-assisted runs.
+//!
+//! `argentum render <ROM> <MOVIE> <VIDEO> --audio <WAV>` runs the movie
+//! as fast as possible. Every frame the Game Boy draws ends up in the
+//! video, at its exact frame rate of 4194304 / 70224 Hz, and the audio
+//! is generated at exactly 48000 samples per emulated second, so both
+//! stay in sync no matter how long the movie is.
+//!
+//! The video is YUV4MPEG2 (`.y4m`) and the audio a 16-bit stereo WAV
+//! file, which ffmpeg reads and muxes with, for example,
+//! `ffmpeg -i run.y4m -i run.wav -c:v libx264 -crf 0 run.mkv`.
+
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    rc::Rc,
+};
+
+use argentum_core::{Argentum, AudioFormat, AudioSamples, DeterminismMode, FrameInfo};
+use clap::Clap;
+
+use crate::movie::Movie;
+
+/// The audio sample rate, the core's default.
+const SAMPLE_RATE: u32 = 48000;
+
+/// The number of cycles in a frame, and per second.
+const CYCLES_PER_FRAME: u64 = 70224;
+const CYCLES_PER_SECOND: u64 = 4194304;
+
+#[derive(Clap)]
+pub struct RenderCommand {
+    /// The Game Boy ROM file the movie was recorded with.
+    #[clap(parse(from_os_str))]
+    rom_file: PathBuf,
+
+    /// The movie to replay.
+    #[clap(parse(from_os_str))]
+    movie_file: PathBuf,
+
+    /// Where to write the video, as YUV4MPEG2.
+    #[clap(parse(from_os_str))]
+    video_file: PathBuf,
+
+    /// Where to write the audio, as a WAV file.
+    #[clap(long, parse(from_os_str))]
+    audio: Option<PathBuf>,
+
+    /// Scale the screen up by this factor, video sites
+    /// blur small videos when they scale them up.
+    #[clap(long, default_value = "1")]
+    scale: usize,
+
+    /// How many frames to render, by default as many as the movie has.
+    #[clap(long)]
+    frames: Option<usize>,
+}
+
+/// Writes frames as YUV4MPEG2 with full resolution chroma,
+/// so the sharp pixel edges don't bleed colour.
+struct Y4mWriter {
+    out: BufWriter<File>,
+    scale: usize,
+
+    /// The Y, U and V planes of the frame being written.
+    planes: Vec<u8>,
+}
+
+impl Y4mWriter {
+    fn new(file: File, info: &FrameInfo, scale: usize) -> io::Result<Self> {
+        let mut out = BufWriter::new(file);
+
+        writeln!(
+            out,
+            "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C444",
+            info.width * scale,
+            info.height * scale,
+            CYCLES_PER_SECOND,
+            CYCLES_PER_FRAME
+        )?;
+
+        Ok(Self {
+            out,
+            scale,
+            planes: Vec::new(),
+        })
+    }
+
+    /// Convert an RGB24 frame to BT.601 and write it.
+    fn write_frame(&mut self, framebuffer: &[u8], info: &FrameInfo) -> io::Result<()> {
+        let width = info.width * self.scale;
+        let plane_size = width * info.height * self.scale;
+
+        self.planes.resize(plane_size * 3, 0);
+
+        for y in 0..info.height * self.scale {
+            for x in 0..width {
+                let offset = (y / self.scale) * info.pitch + (x / self.scale) * 3;
+
+                let r = framebuffer[offset] as i32;
+                let g = framebuffer[offset + 1] as i32;
+                let b = framebuffer[offset + 2] as i32;
+
+                let index = y * width + x;
+
+                self.planes[index] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+                self.planes[plane_size + index] =
+                    (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+                self.planes[plane_size * 2 + index] =
+                    (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+            }
+        }
+
+        self.out.write_all(b"FRAME\n")?;
+        self.out.write_all(&self.planes)
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Encode interleaved 16-bit stereo samples as a WAV file.
+fn encode_wav(samples: &[i16]) -> Vec<u8> {
+    let data_size = (samples.len() * 2) as u32;
+    let mut wav = Vec::with_capacity(44 + data_size as usize);
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&2u16.to_le_bytes()); // Channels
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 4).to_le_bytes()); // Bytes per second
+    wav.extend_from_slice(&4u16.to_le_bytes()); // Bytes per frame
+    wav.extend_from_slice(&16u16.to_le_bytes()); // Bits per sample
+
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    wav
+}
+
+/// Replay the movie, returning the number of frames rendered.
+fn render(command: &RenderCommand) -> Result<usize, String> {
+    if command.scale == 0 {
+        return Err(String::from("The scale has to be at least 1."));
+    }
+
+    let rom = std::fs::read(&command.rom_file)
+        .map_err(|err| format!("Failed to read the ROM: {}", err))?;
+
+    let mut movie = Movie::load(command.movie_file.clone(), true)
+        .map_err(|err| format!("Failed to load the movie: {}", err))?;
+
+    let samples = Rc::new(RefCell::new(Vec::new()));
+    let callback_samples = Rc::clone(&samples);
+
+    let mut gb = Argentum::new(
+        &rom,
+        Box::new(move |buffer| {
+            if let AudioSamples::I16(buffer) = buffer {
+                callback_samples.borrow_mut().extend_from_slice(buffer);
+            }
+        }),
+        None,
+    )
+    .map_err(|err| format!("Failed to load the ROM: {}", err))?;
+
+    gb.set_audio_format(AudioFormat::I16);
+    gb.set_audio_sample_rate(SAMPLE_RATE);
+
+    gb.load_state(movie.start_state())
+        .map_err(|err| format!("Failed to load the state the movie starts from: {}", err))?;
+
+    // The movie only plays back the same way in strict mode.
+    gb.set_determinism_mode(DeterminismMode::Strict);
+
+    let file = File::create(&command.video_file)
+        .map_err(|err| format!("Failed to create the video file: {}", err))?;
+
+    let (_, info) = gb.get_framebuffer();
+    let mut video = Y4mWriter::new(file, &info, command.scale)
+        .map_err(|err| format!("Failed to write the video: {}", err))?;
+
+    let frames = command.frames.unwrap_or_else(|| movie.frame_count());
+
+    for _ in 0..frames {
+        // Past the end of the movie no keys are held.
+        gb.set_keys(movie.next_frame(0).unwrap_or(0));
+        gb.execute_frame();
+
+        let (framebuffer, info) = gb.get_framebuffer();
+
+        video
+            .write_frame(framebuffer, &info)
+            .map_err(|err| format!("Failed to write the video: {}", err))?;
+    }
+
+    video
+        .finish()
+        .map_err(|err| format!("Failed to write the video: {}", err))?;
+
+    if let Some(path) = &command.audio {
+        let mut samples = samples.borrow_mut();
+
+        // The samples still in the core's buffer are cut off, pad
+        // with silence so the audio is exactly as long as the video.
+        let length = frames as u64 * CYCLES_PER_FRAME * SAMPLE_RATE as u64 / CYCLES_PER_SECOND;
+        samples.resize(length as usize * 2, 0);
+
+        std::fs::write(path, encode_wav(&samples))
+            .map_err(|err| format!("Failed to write the audio: {}", err))?;
+    }
+
+    Ok(frames)
+}
+
+pub fn run(command: RenderCommand) {
+    match render(&command) {
+        Ok(frames) => println!("Rendered {} frames.", frames),
+
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
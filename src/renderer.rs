@@ -1,3 +1,6 @@
+use std::time::{Duration, Instant};
+
+use argentum_core::FrameInfo;
 use fermium::prelude::*;
 
 pub struct Renderer {
@@ -6,6 +9,21 @@ pub struct Renderer {
 
     /// Texture updated every frame.
     texture: *mut SDL_Texture,
+
+    /// The size of the texture, recreated when the frame size changes.
+    texture_size: (usize, usize),
+
+    /// Time spent uploading and presenting the last frame.
+    render_time: Duration,
+
+    /// Only scale frames by whole numbers.
+    integer_scale: bool,
+
+    /// Clockwise rotation of the frame in degrees, 0, 90, 180 or 270.
+    rotation: u16,
+
+    /// Mirror the frame horizontally, after rotating it.
+    mirror: bool,
 }
 
 impl Renderer {
@@ -14,44 +32,193 @@ impl Renderer {
         unsafe {
             let renderer = SDL_CreateRenderer(window, -1, SDL_RENDERER_ACCELERATED.0);
 
-            let texture = SDL_CreateTexture(
+            Self {
                 renderer,
+                texture: std::ptr::null_mut(),
+                texture_size: (0, 0),
+                render_time: Duration::from_secs(0),
+                integer_scale: false,
+                rotation: 0,
+                mirror: false,
+            }
+        }
+    }
+
+    /// Create a texture that fits frames of the given size,
+    /// and keep its aspect ratio when scaling to the window.
+    fn resize_texture(&mut self, width: usize, height: usize) {
+        unsafe {
+            if !self.texture.is_null() {
+                SDL_DestroyTexture(self.texture);
+            }
+
+            self.texture = SDL_CreateTexture(
+                self.renderer,
                 SDL_PIXELFORMAT_RGB24.0,
                 SDL_TEXTUREACCESS_STREAMING.0,
-                160,
-                144,
+                width as i32,
+                height as i32,
             );
 
-            Self { renderer, texture }
+            self.texture_size = (width, height);
         }
+
+        self.update_logical_size();
     }
 
-    /// Update the texture and present the changes.
-    pub fn update_texture(&mut self, buffer: &[u8]) {
+    /// The size of the frame once it is rotated.
+    fn rotated_size(&self) -> (usize, usize) {
+        let (width, height) = self.texture_size;
+
+        if self.rotation % 180 == 90 {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Keep the aspect ratio of the rotated frame when scaling to the window.
+    fn update_logical_size(&mut self) {
+        let (width, height) = self.rotated_size();
+
         unsafe {
-            SDL_UpdateTexture(
-                self.texture,
-                std::ptr::null(),
-                buffer.as_ptr() as _,
-                3 * 160,
+            SDL_RenderSetLogicalSize(self.renderer, width as i32, height as i32);
+        }
+
+        self.set_integer_scale(self.integer_scale);
+    }
+
+    /// Rotate the frame clockwise by 0, 90, 180 or 270 degrees, and
+    /// optionally mirror it horizontally. Other angles are treated as 0.
+    pub fn set_transform(&mut self, rotation: u16, mirror: bool) {
+        self.rotation = match rotation {
+            90 | 180 | 270 => rotation,
+            _ => 0,
+        };
+
+        self.mirror = mirror;
+
+        if !self.texture.is_null() {
+            self.update_logical_size();
+        }
+    }
+
+    /// Only scale frames by whole numbers, so every Game Boy pixel is
+    /// the same size. Non integer scaling shimmers while scrolling.
+    pub fn set_integer_scale(&mut self, enabled: bool) {
+        self.integer_scale = enabled;
+
+        unsafe {
+            SDL_RenderSetIntegerScale(self.renderer, if enabled { SDL_TRUE } else { SDL_FALSE });
+        }
+    }
+
+    /// Set the colour of the area around the frame, as 0xRRGGBB.
+    pub fn set_clear_colour(&mut self, colour: u32) {
+        unsafe {
+            SDL_SetRenderDrawColor(
+                self.renderer,
+                (colour >> 16) as u8,
+                (colour >> 8) as u8,
+                colour as u8,
+                0xFF,
             );
+        }
+    }
+
+    /// Time spent uploading and presenting the last frame, this
+    /// includes waiting on the GPU if the driver blocks on present.
+    pub fn render_time(&self) -> Duration {
+        self.render_time
+    }
+
+    /// Copy a frame straight into the texture's memory.
+    ///
+    /// Locking a streaming texture hands out memory the driver can
+    /// upload from directly, which skips the extra copy and checks
+    /// `SDL_UpdateTexture` does on every frame.
+    fn upload(&mut self, buffer: &[u8], info: &FrameInfo) {
+        unsafe {
+            let mut pixels = std::ptr::null_mut();
+            let mut pitch = 0;
+
+            if SDL_LockTexture(self.texture, std::ptr::null(), &mut pixels, &mut pitch) != 0 {
+                return;
+            }
+
+            let row_len = info.width * 3;
+
+            for y in 0..info.height {
+                let row = &buffer[y * info.pitch..y * info.pitch + row_len];
+
+                std::ptr::copy_nonoverlapping(
+                    row.as_ptr(),
+                    (pixels as *mut u8).add(y * pitch as usize),
+                    row_len,
+                );
+            }
 
-            SDL_RenderCopy(
+            SDL_UnlockTexture(self.texture);
+        }
+    }
+
+    /// Update the texture and present the changes.
+    pub fn update_texture(&mut self, buffer: &[u8], info: &FrameInfo) {
+        let start = Instant::now();
+
+        if self.texture_size != (info.width, info.height) {
+            self.resize_texture(info.width, info.height);
+        }
+
+        self.upload(buffer, info);
+
+        unsafe {
+            SDL_RenderClear(self.renderer);
+
+            // SDL flips the texture before rotating it, mirroring the
+            // rotated frame horizontally is a vertical flip at 90 and 270.
+            let flip = match (self.mirror, self.rotation % 180 == 90) {
+                (false, _) => SDL_FLIP_NONE,
+                (true, false) => SDL_FLIP_HORIZONTAL,
+                (true, true) => SDL_FLIP_VERTICAL,
+            };
+
+            // The frame is rotated around the center of this rectangle,
+            // which is centered in the rotated logical size.
+            let (width, height) = self.texture_size;
+            let (rotated_width, rotated_height) = self.rotated_size();
+
+            let dst = SDL_Rect {
+                x: (rotated_width as i32 - width as i32) / 2,
+                y: (rotated_height as i32 - height as i32) / 2,
+                w: width as i32,
+                h: height as i32,
+            };
+
+            SDL_RenderCopyEx(
                 self.renderer,
                 self.texture,
                 std::ptr::null(),
+                &dst,
+                self.rotation as f64,
                 std::ptr::null(),
+                flip,
             );
 
             SDL_RenderPresent(self.renderer);
         }
+
+        self.render_time = start.elapsed();
     }
 }
 
 impl Drop for Renderer {
     fn drop(&mut self) {
         unsafe {
-            SDL_DestroyTexture(self.texture);
+            if !self.texture.is_null() {
+                SDL_DestroyTexture(self.texture);
+            }
+
             SDL_DestroyRenderer(self.renderer);
         }
     }
@@ -0,0 +1,378 @@
+//! The `screenshot-test` subcommand, which runs test ROMs that signal
+//! they are done with `LD B, B` and compares the screen with the
+//! expected screenshots, like the ones of the Mealybug Tearoom tests.
+//!
+//! `argentum screenshot-test <ROM_DIR> --expected <DIR>` looks for the
+//! screenshot of every ROM as `<DIR>/<ROM name>.png`. ROMs without one
+//! pass or fail by the registers they end with, like the mooneye-gb
+//! tests and Wilbert Pol's versions of them do. The results are
+//! written to a TOML report, which can be kept around and passed back
+//! with `--baseline` to find the tests that broke since. The exit
+//! status is 1 if any test that passed in the baseline fails now.
+//!
+//! Colours are matched up one to one between the screen and the
+//! screenshot, so the palette either was taken with doesn't matter.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use argentum_core::{Argentum, ArgentumEvent};
+use argentum_frontend_utils::decode_png;
+use clap::Clap;
+use serde::{Deserialize, Serialize};
+
+/// `LD B, B`, which test ROMs execute once the screen is ready.
+const LD_B_B: u8 = 0x40;
+
+/// No instruction is faster than an M-cycle, so a frame
+/// can't take more than this many instructions.
+const MAX_INSTRUCTIONS_PER_FRAME: u64 = 70224 / 4;
+
+/// B, C, D, E, H and L of a mooneye-gb test that passed.
+const REGISTERS_PASS: [u8; 6] = [3, 5, 8, 13, 21, 34];
+
+/// B, C, D, E, H and L of a mooneye-gb test that failed.
+const REGISTERS_FAIL: [u8; 6] = [0x42; 6];
+
+/// The HALT timing tests the core is known not to pass yet, by the
+/// name of the ROM, with what they are waiting on. It is written to
+/// the report when they fail, so it shows which tests the micro-op
+/// CPU rework is expected to fix.
+const BLOCKERS: &[(&str, &str)] = &[
+    (
+        "halt_ime0_nointr_timing",
+        "micro-op CPU rework, interrupts are only checked between instructions",
+    ),
+    (
+        "halt_ime1_timing2-GS",
+        "micro-op CPU rework, interrupts are only checked between instructions",
+    ),
+];
+
+#[derive(Clap)]
+pub struct ScreenshotTest {
+    /// The directory with the test ROMs.
+    #[clap(parse(from_os_str))]
+    rom_dir: PathBuf,
+
+    /// The directory with the expected screenshots.
+    #[clap(long, parse(from_os_str))]
+    expected: Option<PathBuf>,
+
+    /// Give up on ROMs that haven't finished after this many frames.
+    #[clap(long, default_value = "600")]
+    frames: u32,
+
+    /// Where to write the report.
+    #[clap(long, parse(from_os_str), default_value = "screenshot-report.toml")]
+    report: PathBuf,
+
+    /// A report of an earlier run, to compare the results with.
+    #[clap(long, parse(from_os_str))]
+    baseline: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Pass,
+
+    /// The screen doesn't match the screenshot.
+    Fail,
+
+    /// The test couldn't be run to the end.
+    Error,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct TestResult {
+    status: Status,
+
+    #[serde(default)]
+    mismatched_pixels: usize,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    details: String,
+
+    /// What a failing test is known to be waiting on, see `BLOCKERS`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    blocked_by: String,
+}
+
+impl TestResult {
+    fn new(status: Status) -> Self {
+        Self {
+            status,
+            mismatched_pixels: 0,
+            details: String::new(),
+            blocked_by: String::new(),
+        }
+    }
+
+    fn error(details: String) -> Self {
+        Self {
+            details,
+            ..Self::new(Status::Error)
+        }
+    }
+}
+
+/// The pass/fail matrix of a run, by the name of the ROM.
+#[derive(Default, Serialize, Deserialize)]
+struct Report {
+    passed: usize,
+    total: usize,
+    tests: BTreeMap<String, TestResult>,
+}
+
+/// Count the pixels that differ. Every colour of the screen stands for
+/// one colour of the screenshot, the most common pairs are matched up
+/// first and the pixels of the pairs left over differ.
+fn mismatched_pixels(actual: &[u8], expected: &[u8]) -> usize {
+    let mut pairs = HashMap::new();
+
+    for pair in actual.chunks(3).zip(expected.chunks(3)) {
+        *pairs.entry(pair).or_insert(0) += 1;
+    }
+
+    // Ties are broken by the colours, so the count doesn't change between runs.
+    let mut pairs: Vec<_> = pairs.into_iter().collect();
+    pairs.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut mapped_actual = HashSet::new();
+    let mut mapped_expected = HashSet::new();
+    let mut mismatched = 0;
+
+    for ((actual, expected), count) in pairs {
+        if !mapped_actual.contains(actual) && !mapped_expected.contains(expected) {
+            mapped_actual.insert(actual);
+            mapped_expected.insert(expected);
+        } else {
+            mismatched += count;
+        }
+    }
+
+    mismatched
+}
+
+/// Run the ROM until it executes `LD B, B`, then compare the last
+/// frame, or check the registers if there is no screenshot.
+fn run_test(rom: &[u8], screenshot: Option<&[u8]>, frames: u32) -> TestResult {
+    let mut gb = match Argentum::new(rom, Box::new(|_| {}), None) {
+        Ok(gb) => gb,
+        Err(err) => return TestResult::error(err.to_string()),
+    };
+
+    gb.skip_bootrom();
+
+    let mut finished = false;
+
+    for _ in 0..frames as u64 * MAX_INSTRUCTIONS_PER_FRAME {
+        if gb.peek_byte(gb.registers().pc) == LD_B_B {
+            finished = true;
+            break;
+        }
+
+        gb.step();
+
+        if let Some(ArgentumEvent::CpuLocked { opcode, address }) = gb.poll_event() {
+            return TestResult::error(format!("illegal opcode {:02X} at {}", opcode, address));
+        }
+    }
+
+    if !finished {
+        return TestResult::error(format!("didn't finish within {} frames", frames));
+    }
+
+    let screenshot = match screenshot {
+        Some(screenshot) => screenshot,
+
+        None => {
+            let reg = gb.registers();
+
+            return match [reg.b, reg.c, reg.d, reg.e, reg.h, reg.l] {
+                REGISTERS_PASS => TestResult::new(Status::Pass),
+                REGISTERS_FAIL => TestResult::new(Status::Fail),
+                _ => TestResult::error(
+                    "there is no expected screenshot and the registers show no result".to_string(),
+                ),
+            };
+        }
+    };
+
+    let (framebuffer, _) = gb.get_framebuffer();
+    let mismatched = mismatched_pixels(framebuffer, screenshot);
+
+    let status = if mismatched == 0 {
+        Status::Pass
+    } else {
+        Status::Fail
+    };
+
+    TestResult {
+        mismatched_pixels: mismatched,
+        ..TestResult::new(status)
+    }
+}
+
+fn check_rom(path: &Path, opts: &ScreenshotTest) -> TestResult {
+    let name = path.file_stem().unwrap_or_default();
+
+    let png = opts
+        .expected
+        .as_ref()
+        .and_then(|dir| std::fs::read(dir.join(name).with_extension("png")).ok());
+
+    let screenshot = match png.map(|png| decode_png(&png)) {
+        Some(Ok(image)) if image.width == 160 && image.height == 144 => Some(image.pixels),
+        Some(Ok(_)) => return TestResult::error("the screenshot isn't 160 x 144".to_string()),
+        Some(Err(err)) => {
+            return TestResult::error(format!("failed to read the screenshot: {}", err))
+        }
+        None => None,
+    };
+
+    let rom = match std::fs::read(path) {
+        Ok(rom) => rom,
+        Err(err) => return TestResult::error(format!("failed to read the ROM: {}", err)),
+    };
+
+    // Mappers trust the header, bad headers can panic them.
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        run_test(&rom, screenshot.as_deref(), opts.frames)
+    }))
+    .unwrap_or_else(|_| TestResult::error("the emulator panicked".to_string()))
+}
+
+fn load_report(path: &Path) -> Result<Report, String> {
+    let report = std::fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+
+    toml::from_str(&report).map_err(|err| format!("Failed to parse {}: {}", path.display(), err))
+}
+
+/// Run every test, returning how many passed in the baseline but not anymore.
+fn run_tests(opts: &ScreenshotTest) -> Result<usize, String> {
+    let baseline = match &opts.baseline {
+        Some(path) => Some(load_report(path)?),
+        None => None,
+    };
+
+    let entries = std::fs::read_dir(&opts.rom_dir)
+        .map_err(|err| format!("Failed to read the ROM directory: {}", err))?;
+
+    let mut roms: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            let extension = path.extension().and_then(|extension| extension.to_str());
+
+            matches!(extension, Some("gb") | Some("gbc"))
+        })
+        .collect();
+
+    if roms.is_empty() {
+        return Err("The directory has no .gb or .gbc files.".to_string());
+    }
+
+    roms.sort();
+
+    // Panics are reported per test, the default hook
+    // would print a backtrace for every one of them.
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut report = Report::default();
+
+    for path in &roms {
+        let name = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+
+        let mut result = check_rom(path, opts);
+
+        if result.status != Status::Pass {
+            if let Some((_, blocker)) = BLOCKERS.iter().find(|(test, _)| *test == name) {
+                result.blocked_by = blocker.to_string();
+            }
+        }
+
+        let mut outcome = match result.status {
+            Status::Pass => "pass".to_string(),
+            Status::Fail if result.mismatched_pixels != 0 => {
+                format!("fail, {} pixels differ", result.mismatched_pixels)
+            }
+            Status::Fail => "fail".to_string(),
+            Status::Error => format!("error, {}", result.details),
+        };
+
+        if !result.blocked_by.is_empty() {
+            outcome = format!("{} ({})", outcome, result.blocked_by);
+        }
+
+        println!("{}: {}", name, outcome);
+
+        report.tests.insert(name, result);
+    }
+
+    let _ = panic::take_hook();
+
+    report.total = report.tests.len();
+    report.passed = report
+        .tests
+        .values()
+        .filter(|result| result.status == Status::Pass)
+        .count();
+
+    println!("{} of {} tests passed.", report.passed, report.total);
+
+    let mut regressions = 0;
+
+    if let Some(baseline) = baseline {
+        for (name, result) in &report.tests {
+            let was_passing = baseline
+                .tests
+                .get(name)
+                .map(|result| result.status == Status::Pass);
+
+            match (was_passing, result.status == Status::Pass) {
+                (Some(true), false) => {
+                    println!("Regressed: {}", name);
+                    regressions += 1;
+                }
+
+                (Some(false), true) | (None, true) => println!("Fixed: {}", name),
+
+                _ => {}
+            }
+        }
+    }
+
+    let toml = toml::to_string(&report).map_err(|err| err.to_string())?;
+
+    std::fs::write(&opts.report, toml)
+        .map_err(|err| format!("Failed to write {}: {}", opts.report.display(), err))?;
+
+    Ok(regressions)
+}
+
+/// Run the `screenshot-test` subcommand, exiting with status 1
+/// if it fails or a test regressed since the baseline.
+pub fn run(opts: ScreenshotTest) {
+    match run_tests(&opts) {
+        Ok(0) => {}
+
+        Ok(regressions) => {
+            eprintln!("{} tests regressed since the baseline.", regressions);
+            std::process::exit(1);
+        }
+
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Exports frames through a memory mapped file, so overlays and capture
+//! software on the same machine can read them without any IPC.
+//!
+//! The file starts with a 32 byte header, all fields little endian:
+//!
+//! | Offset | Type      | Field                                          |
+//! |--------|-----------|------------------------------------------------|
+//! | 0      | `[u8; 4]` | Magic, `AGFB`                                  |
+//! | 4      | `u32`     | Version of the layout, 1                       |
+//! | 8      | `u64`     | Frame counter                                  |
+//! | 16     | `u16`     | Width in pixels                                |
+//! | 18     | `u16`     | Height in pixels                               |
+//! | 20     | `u32`     | Pitch, bytes from one row to the next          |
+//! | 24     | `u32`     | Pixel format, 0 is RGB24                       |
+//! | 28     | `u32`     | Reserved                                       |
+//!
+//! The pixels follow the header. The frame counter is odd while a frame
+//! is being written and goes up by two with every frame. Readers copy
+//! the pixels when it is even, and try again if it changed meanwhile.
+//! On Linux the file is best put in `/dev/shm`.
+
+use std::{
+    fs::OpenOptions,
+    path::Path,
+    sync::atomic::{fence, Ordering},
+};
+
+use argentum_core::FrameInfo;
+use memmap2::MmapMut;
+
+const MAGIC: &[u8; 4] = b"AGFB";
+
+const VERSION: u32 = 1;
+
+/// Size of the header in bytes, the pixels start right after it.
+const HEADER_SIZE: usize = 32;
+
+/// Offset of the frame counter in the header.
+const COUNTER_OFFSET: usize = 8;
+
+/// The pixel format of the core's framebuffer.
+const FORMAT_RGB24: u32 = 0;
+
+/// A memory mapped file the frames are written to.
+pub struct SharedFrame {
+    map: MmapMut,
+    counter: u64,
+}
+
+impl SharedFrame {
+    /// Create the file, or replace an existing one, sized
+    /// for frames with the given dimensions.
+    pub fn create(path: &Path, info: &FrameInfo) -> Result<Self, String> {
+        let frame_size = info.pitch * info.height;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|err| format!("Failed to create {}: {}", path.display(), err))?;
+
+        file.set_len((HEADER_SIZE + frame_size) as u64)
+            .map_err(|err| format!("Failed to resize {}: {}", path.display(), err))?;
+
+        // Other processes mapping the file is the point, they only read it.
+        let mut map = unsafe { MmapMut::map_mut(&file) }
+            .map_err(|err| format!("Failed to map {}: {}", path.display(), err))?;
+
+        map[0..4].copy_from_slice(MAGIC);
+        map[4..8].copy_from_slice(&VERSION.to_le_bytes());
+        map[16..18].copy_from_slice(&(info.width as u16).to_le_bytes());
+        map[18..20].copy_from_slice(&(info.height as u16).to_le_bytes());
+        map[20..24].copy_from_slice(&(info.pitch as u32).to_le_bytes());
+        map[24..28].copy_from_slice(&FORMAT_RGB24.to_le_bytes());
+
+        Ok(Self { map, counter: 0 })
+    }
+
+    fn write_counter(&mut self) {
+        self.map[COUNTER_OFFSET..COUNTER_OFFSET + 8].copy_from_slice(&self.counter.to_le_bytes());
+    }
+
+    /// Write a frame, frames of another size than the file was created for are skipped.
+    pub fn write_frame(&mut self, framebuffer: &[u8], info: &FrameInfo) {
+        let frame_size = info.pitch * info.height;
+
+        if HEADER_SIZE + frame_size != self.map.len() || framebuffer.len() < frame_size {
+            return;
+        }
+
+        self.counter += 1;
+        self.write_counter();
+        fence(Ordering::Release);
+
+        self.map[HEADER_SIZE..].copy_from_slice(&framebuffer[..frame_size]);
+
+        fence(Ordering::Release);
+        self.counter += 1;
+        self.write_counter();
+    }
+}
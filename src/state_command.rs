@@ -0,0 +1,191 @@
+//! The `state` subcommands, which edit save files without running the game.
+//!
+//! `argentum state export-ram <ROM> <OUT>` writes the cartridge RAM stored
+//! in the ROM's save file to a file a save editor can open, and
+//! `argentum state import-ram <ROM> <IN>` writes an edited dump back.
+//! `argentum state export-tiles <ROM> <STATE> <DIR>` rips the graphics
+//! in VRAM out of a save state, as `tiles.png` and `bg_map.png`.
+//! `argentum state diff-ram <ROM> <BEFORE> <AFTER>` lists the bytes of
+//! RAM that differ between two save states.
+
+use std::path::{Path, PathBuf};
+
+use argentum_core::{Argentum, IndexedImage, RamChange};
+use argentum_frontend_utils::encode_indexed_png;
+use clap::Clap;
+
+#[derive(Clap)]
+pub enum StateCommand {
+    /// Write the cartridge RAM from the ROM's save file to a file.
+    ExportRam {
+        /// The Game Boy ROM file whose save file is exported.
+        #[clap(parse(from_os_str))]
+        rom_file: PathBuf,
+
+        /// Where to write the RAM dump.
+        #[clap(parse(from_os_str))]
+        out_file: PathBuf,
+    },
+
+    /// Replace the ROM's save file with a RAM dump.
+    ImportRam {
+        /// The Game Boy ROM file whose save file is replaced.
+        #[clap(parse(from_os_str))]
+        rom_file: PathBuf,
+
+        /// The RAM dump, which must be as large as the cartridge's RAM.
+        #[clap(parse(from_os_str))]
+        in_file: PathBuf,
+    },
+
+    /// Write every tile in VRAM and the background map
+    /// in a save state to PNGs, with their palettes.
+    ExportTiles {
+        /// The Game Boy ROM file the save state belongs to.
+        #[clap(parse(from_os_str))]
+        rom_file: PathBuf,
+
+        /// The save state to take VRAM from.
+        #[clap(parse(from_os_str))]
+        state_file: PathBuf,
+
+        /// The directory to write the PNGs to.
+        #[clap(parse(from_os_str))]
+        out_dir: PathBuf,
+    },
+
+    /// List the bytes of work RAM, cartridge RAM and high RAM
+    /// that differ between two save states.
+    DiffRam {
+        /// The Game Boy ROM file the save states belong to.
+        #[clap(parse(from_os_str))]
+        rom_file: PathBuf,
+
+        /// The earlier save state.
+        #[clap(parse(from_os_str))]
+        before: PathBuf,
+
+        /// The later save state.
+        #[clap(parse(from_os_str))]
+        after: PathBuf,
+    },
+}
+
+/// Create a Game Boy instance for the ROM, without
+/// running it. The RAM starts out zeroed.
+fn load_cartridge(rom_path: &Path) -> Result<Argentum, String> {
+    let rom = std::fs::read(rom_path).map_err(|err| format!("Failed to read the ROM: {}", err))?;
+
+    Argentum::new(&rom, Box::new(|_| {}), None)
+        .map_err(|err| format!("Failed to load the ROM: {}", err))
+}
+
+fn export_ram(rom_path: &Path, out_path: &Path) -> Result<(), String> {
+    let mut gb = load_cartridge(rom_path)?;
+
+    // Loading the save file through the core checks it fits the cartridge.
+    if let Ok(save_file) = std::fs::read(rom_path.with_extension("sav")) {
+        gb.import_ram(&save_file)
+            .map_err(|err| format!("Failed to load the save file: {}", err))?;
+    }
+
+    let ram = gb.export_ram().ok_or("The cartridge has no RAM.")?;
+
+    std::fs::write(out_path, ram).map_err(|err| format!("Failed to write the RAM dump: {}", err))
+}
+
+fn import_ram(rom_path: &Path, in_path: &Path) -> Result<(), String> {
+    let mut gb = load_cartridge(rom_path)?;
+
+    let ram =
+        std::fs::read(in_path).map_err(|err| format!("Failed to read the RAM dump: {}", err))?;
+
+    gb.import_ram(&ram)
+        .map_err(|err| format!("Failed to import the RAM dump: {}", err))?;
+
+    let save_file = gb.export_ram().ok_or("The cartridge has no RAM.")?;
+
+    std::fs::write(rom_path.with_extension("sav"), save_file)
+        .map_err(|err| format!("Failed to write the save file: {}", err))
+}
+
+fn write_png(path: &Path, image: &IndexedImage) -> Result<(), String> {
+    let png = encode_indexed_png(image.width, image.height, &image.pixels, &image.palette);
+
+    std::fs::write(path, png).map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+}
+
+fn export_tiles(rom_path: &Path, state_path: &Path, out_dir: &Path) -> Result<(), String> {
+    let mut gb = load_cartridge(rom_path)?;
+
+    let state = std::fs::read(state_path)
+        .map_err(|err| format!("Failed to read the save state: {}", err))?;
+
+    gb.load_state(&state)
+        .map_err(|err| format!("Failed to load the save state: {}", err))?;
+
+    std::fs::create_dir_all(out_dir)
+        .map_err(|err| format!("Failed to create the output directory: {}", err))?;
+
+    write_png(&out_dir.join("tiles.png"), &gb.tile_sheet())?;
+    write_png(&out_dir.join("bg_map.png"), &gb.bg_map())
+}
+
+/// Print the bytes that differ, one per line.
+pub fn print_ram_changes(changes: &[RamChange]) {
+    println!("{} bytes of RAM differ.", changes.len());
+
+    for change in changes {
+        println!(
+            "{} {:02X} -> {:02X}",
+            change.address, change.before, change.after
+        );
+    }
+}
+
+fn diff_ram(rom_path: &Path, before_path: &Path, after_path: &Path) -> Result<(), String> {
+    let mut gb = load_cartridge(rom_path)?;
+
+    let read_state = |path: &Path| {
+        std::fs::read(path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))
+    };
+
+    let before = read_state(before_path)?;
+    let after = read_state(after_path)?;
+
+    gb.load_state(&after)
+        .map_err(|err| format!("Failed to load {}: {}", after_path.display(), err))?;
+
+    let changes = gb
+        .ram_changes_since_state(&before)
+        .map_err(|err| format!("Failed to load {}: {}", before_path.display(), err))?;
+
+    print_ram_changes(&changes);
+
+    Ok(())
+}
+
+/// Run a `state` subcommand, exiting with status 1 if it fails.
+pub fn run(command: StateCommand) {
+    let result = match command {
+        StateCommand::ExportRam { rom_file, out_file } => export_ram(&rom_file, &out_file),
+        StateCommand::ImportRam { rom_file, in_file } => import_ram(&rom_file, &in_file),
+
+        StateCommand::ExportTiles {
+            rom_file,
+            state_file,
+            out_dir,
+        } => export_tiles(&rom_file, &state_file, &out_dir),
+
+        StateCommand::DiffRam {
+            rom_file,
+            before,
+            after,
+        } => diff_ram(&rom_file, &before, &after),
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,305 @@
+//! Streams frames to external programs over a local socket.
+//!
+//! Every message in either direction is a little endian `u32` length,
+//! followed by that many bytes. The first byte of a message is its kind.
+//!
+//! Sent to clients:
+//! - `0x01` frame, followed by the width and height as `u16`s and
+//!   the RGB24 framebuffer, without any padding between rows.
+//!
+//! Accepted from clients:
+//! - `0x01` key down, followed by a key byte.
+//! - `0x02` key up, followed by a key byte.
+//!
+//! Key bytes are 0 - Right, 1 - Left, 2 - Up, 3 - Down, 4 - A, 5 - B,
+//! 6 - Select and 7 - Start. Clients that fall behind have frames dropped.
+
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::TcpListener,
+    str::FromStr,
+};
+
+#[cfg(unix)]
+use std::{
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+};
+
+use argentum_core::{ArgentumKey, FrameInfo};
+
+/// Message kind of a frame.
+const MSG_FRAME: u8 = 0x01;
+
+/// Message kind of a key press.
+const MSG_KEY_DOWN: u8 = 0x01;
+
+/// Message kind of a key release.
+const MSG_KEY_UP: u8 = 0x02;
+
+/// Messages from clients are tiny, anything longer is garbage.
+const MAX_CLIENT_MESSAGE: usize = 64;
+
+/// The most frames that may be waiting to be sent to a client.
+const MAX_BACKLOG_FRAMES: usize = 4;
+
+/// The keys in the order of their key bytes.
+const STREAM_KEYS: [ArgentumKey; 8] = [
+    ArgentumKey::Right,
+    ArgentumKey::Left,
+    ArgentumKey::Up,
+    ArgentumKey::Down,
+    ArgentumKey::ButtonA,
+    ArgentumKey::ButtonB,
+    ArgentumKey::Select,
+    ArgentumKey::Start,
+];
+
+/// Where to listen for clients, either `unix:PATH` or `tcp:ADDRESS`.
+#[derive(Clone, Debug)]
+pub enum StreamAddress {
+    #[cfg(unix)]
+    Unix(PathBuf),
+    Tcp(String),
+}
+
+impl FromStr for StreamAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        #[cfg(unix)]
+        if let Some(path) = s.strip_prefix("unix:") {
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+
+        if let Some(address) = s.strip_prefix("tcp:") {
+            return Ok(Self::Tcp(String::from(address)));
+        }
+
+        Err(format!("Unsupported stream address \"{}\".", s))
+    }
+}
+
+/// Input received from a client.
+pub enum StreamCommand {
+    KeyDown(ArgentumKey),
+    KeyUp(ArgentumKey),
+}
+
+/// A socket connection to a client.
+trait Connection: Read + Write {}
+
+impl<T: Read + Write> Connection for T {}
+
+enum Listener {
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+    Tcp(TcpListener),
+}
+
+impl Listener {
+    /// Accept a pending client, if there is one.
+    fn accept(&self) -> io::Result<Box<dyn Connection>> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(listener, _) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(true)?;
+
+                Ok(Box::new(stream) as Box<dyn Connection>)
+            }
+
+            Self::Tcp(listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(true)?;
+                stream.set_nodelay(true)?;
+
+                Ok(Box::new(stream) as Box<dyn Connection>)
+            }
+        }
+    }
+}
+
+struct Client {
+    connection: Box<dyn Connection>,
+
+    /// Received bytes that don't form a full message yet.
+    input: Vec<u8>,
+
+    /// Bytes that couldn't be sent yet.
+    output: Vec<u8>,
+
+    /// Cleared when the client disconnected or misbehaved.
+    alive: bool,
+}
+
+impl Client {
+    /// Send as much of the pending output as the socket accepts.
+    fn flush(&mut self) {
+        while !self.output.is_empty() {
+            match self.connection.write(&self.output) {
+                Ok(0) => self.alive = false,
+                Ok(n) => {
+                    self.output.drain(..n);
+                    continue;
+                }
+
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => self.alive = false,
+            }
+
+            break;
+        }
+    }
+
+    /// Read everything available and parse the complete messages.
+    fn receive(&mut self, commands: &mut Vec<StreamCommand>) {
+        let mut buffer = [0; 256];
+
+        loop {
+            match self.connection.read(&mut buffer) {
+                Ok(0) => self.alive = false,
+                Ok(n) => {
+                    self.input.extend_from_slice(&buffer[..n]);
+                    continue;
+                }
+
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => self.alive = false,
+            }
+
+            break;
+        }
+
+        while self.input.len() >= 4 {
+            let len =
+                u32::from_le_bytes([self.input[0], self.input[1], self.input[2], self.input[3]])
+                    as usize;
+
+            if len == 0 || len > MAX_CLIENT_MESSAGE {
+                self.alive = false;
+                return;
+            }
+
+            if self.input.len() < 4 + len {
+                break;
+            }
+
+            let message: Vec<u8> = self.input.drain(..4 + len).skip(4).collect();
+
+            let key = message
+                .get(1)
+                .and_then(|&index| STREAM_KEYS.get(index as usize))
+                .copied();
+
+            match (message[0], key) {
+                (MSG_KEY_DOWN, Some(key)) => commands.push(StreamCommand::KeyDown(key)),
+                (MSG_KEY_UP, Some(key)) => commands.push(StreamCommand::KeyUp(key)),
+
+                _ => {}
+            }
+        }
+    }
+}
+
+pub struct FrameStream {
+    listener: Listener,
+    clients: Vec<Client>,
+
+    /// Reused for every frame, to not allocate each time.
+    message: Vec<u8>,
+}
+
+impl FrameStream {
+    /// Start listening for clients on the given address.
+    pub fn bind(address: &StreamAddress) -> io::Result<Self> {
+        let listener = match address {
+            #[cfg(unix)]
+            StreamAddress::Unix(path) => {
+                // A socket file left behind by an earlier run blocks binding.
+                if UnixStream::connect(path).is_err() {
+                    let _ = std::fs::remove_file(path);
+                }
+
+                let listener = UnixListener::bind(path)?;
+                listener.set_nonblocking(true)?;
+
+                Listener::Unix(listener, path.clone())
+            }
+
+            StreamAddress::Tcp(address) => {
+                let listener = TcpListener::bind(address)?;
+                listener.set_nonblocking(true)?;
+
+                Listener::Tcp(listener)
+            }
+        };
+
+        Ok(Self {
+            listener,
+            clients: Vec::new(),
+            message: Vec::new(),
+        })
+    }
+
+    /// Accept new clients and collect the input they sent.
+    pub fn poll(&mut self) -> Vec<StreamCommand> {
+        while let Ok(connection) = self.listener.accept() {
+            self.clients.push(Client {
+                connection,
+                input: Vec::new(),
+                output: Vec::new(),
+                alive: true,
+            });
+        }
+
+        let mut commands = Vec::new();
+
+        for client in self.clients.iter_mut() {
+            client.receive(&mut commands);
+        }
+
+        self.clients.retain(|client| client.alive);
+
+        commands
+    }
+
+    /// Send a frame to every client.
+    pub fn send_frame(&mut self, framebuffer: &[u8], info: &FrameInfo) {
+        let message = &mut self.message;
+
+        message.clear();
+        message.extend_from_slice(&[0; 4]);
+        message.push(MSG_FRAME);
+        message.extend_from_slice(&(info.width as u16).to_le_bytes());
+        message.extend_from_slice(&(info.height as u16).to_le_bytes());
+
+        for row in framebuffer.chunks(info.pitch).take(info.height) {
+            message.extend_from_slice(&row[..info.width * 3]);
+        }
+
+        let len = (message.len() - 4) as u32;
+        message[..4].copy_from_slice(&len.to_le_bytes());
+
+        for client in self.clients.iter_mut() {
+            if client.output.len() < MAX_BACKLOG_FRAMES * message.len() {
+                client.output.extend_from_slice(message);
+            }
+
+            client.flush();
+        }
+
+        self.clients.retain(|client| client.alive);
+    }
+}
+
+impl Drop for FrameStream {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Listener::Unix(_, path) = &self.listener {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! A debug view of every tile in VRAM, which highlights the tiles
+//! a game wrote to recently. Handy to tell animated tiles and
+//! streamed graphics apart from static ones.
+
+use argentum_core::{FrameInfo, IndexedImage};
+
+/// The colour recently written tiles are tinted with.
+const HEAT_COLOUR: u32 = 0xFF2000;
+
+/// Mix two 0xRRGGBB colours, `amount` out of 255 of the second one.
+fn blend(colour: u32, tint: u32, amount: u32) -> u32 {
+    let mut blended = 0;
+
+    for shift in [16, 8, 0].iter() {
+        let from = (colour >> shift) & 0xFF;
+        let to = (tint >> shift) & 0xFF;
+
+        blended |= ((from * (255 - amount) + to * amount) / 255) << shift;
+    }
+
+    blended
+}
+
+/// Draw the tile sheet into a RGB24 buffer, tinting every tile by
+/// its heat. Returns the layout of the drawn frame.
+pub fn draw(buffer: &mut Vec<u8>, sheet: &IndexedImage, heat: &[u8]) -> FrameInfo {
+    let columns = sheet.width / 8;
+
+    buffer.clear();
+
+    for (i, &index) in sheet.pixels.iter().enumerate() {
+        let (x, y) = (i % sheet.width, i / sheet.width);
+        let tile = (y / 8) * columns + x / 8;
+
+        // Keep some of the tile visible even when it's hot.
+        let amount = heat.get(tile).copied().unwrap_or(0) as u32 * 3 / 4;
+
+        let colour = blend(sheet.palette[index as usize], HEAT_COLOUR, amount);
+
+        buffer.extend_from_slice(&colour.to_be_bytes()[1..]);
+    }
+
+    FrameInfo {
+        width: sheet.width,
+        height: sheet.height,
+        pitch: sheet.width * 3,
+    }
+}
@@ -0,0 +1,112 @@
+//! Comparing execution against a reference trace from another emulator.
+//!
+//! Every line of the trace holds the registers before an instruction
+//! executes. Both the format of `--trace` and Gameboy Doctor's format
+//! are understood, for example `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01
+//! L:4D SP:FFFE PC:0100`. A PC can carry a bank, as in `01:4000`.
+//! Lines without registers are skipped.
+
+use std::io::BufRead;
+
+use argentum_core::{Argentum, CpuRegisters};
+
+/// The first instruction whose registers differ from the reference.
+pub struct Mismatch {
+    /// The line of the reference trace, starting at 1.
+    pub line: usize,
+
+    /// How many instructions matched before this one.
+    pub instructions: u64,
+
+    pub expected: CpuRegisters,
+    pub actual: CpuRegisters,
+}
+
+/// Format registers the way trace lines do.
+pub fn format_registers(reg: &CpuRegisters) -> String {
+    format!(
+        "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X}",
+        reg.a,
+        u8::from(reg.f),
+        reg.b,
+        reg.c,
+        reg.d,
+        reg.e,
+        reg.h,
+        reg.l,
+        reg.sp,
+        reg.pc
+    )
+}
+
+/// Find the value following `name:`, ignoring a bank prefix.
+fn field(line: &str, name: &str) -> Option<u16> {
+    let pattern = format!("{}:", name);
+
+    // Match whole names only, `C:` also ends `PC:`.
+    let start = line.match_indices(&pattern).find_map(|(index, _)| {
+        let before = line[..index].chars().last();
+
+        match before {
+            Some(c) if c.is_ascii_alphabetic() => None,
+            _ => Some(index + pattern.len()),
+        }
+    })?;
+
+    let value = line[start..]
+        .trim_start()
+        .split(|c: char| !(c.is_ascii_hexdigit() || c == ':'))
+        .next()?;
+
+    u16::from_str_radix(value.rsplit(':').next()?, 16).ok()
+}
+
+/// Parse the registers out of a trace line.
+pub fn parse_trace_line(line: &str) -> Option<CpuRegisters> {
+    let byte = |name| field(line, name).map(|value| value as u8);
+
+    Some(CpuRegisters {
+        a: byte("A")?,
+        f: byte("F")?.into(),
+        b: byte("B")?,
+        c: byte("C")?,
+        d: byte("D")?,
+        e: byte("E")?,
+        h: byte("H")?,
+        l: byte("L")?,
+        sp: field(line, "SP")?,
+        pc: field(line, "PC")?,
+    })
+}
+
+/// Step through the reference trace one instruction at a time, and
+/// stop at the first instruction whose registers differ. Returns the
+/// number of instructions that matched if the whole trace did.
+pub fn verify_against_trace<R: BufRead>(gb: &mut Argentum, trace: R) -> Result<u64, Mismatch> {
+    let mut instructions = 0;
+
+    for (number, line) in trace.lines().enumerate() {
+        let line = line.expect("Failed to read the reference trace.");
+
+        let expected = match parse_trace_line(&line) {
+            Some(expected) => expected,
+            None => continue,
+        };
+
+        let actual = gb.registers();
+
+        if actual != expected {
+            return Err(Mismatch {
+                line: number + 1,
+                instructions,
+                expected,
+                actual,
+            });
+        }
+
+        gb.step();
+        instructions += 1;
+    }
+
+    Ok(instructions)
+}
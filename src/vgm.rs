@@ -0,0 +1,74 @@
+//! Writing logged APU register writes as VGM 1.61, which chip music
+//! tools like Furnace, vgmplay and vgm2txt read.
+
+use argentum_core::AudioLog;
+
+/// The clock of the Game Boy's APU, in Hz.
+const DMG_CLOCK: u64 = 4194304;
+
+/// VGM files count time in samples at this rate.
+const VGM_RATE: u64 = 44100;
+
+/// Where the commands start, past the header.
+const DATA_OFFSET: usize = 0x100;
+
+/// Convert a T-cycle to the VGM sample it falls on.
+fn sample_at(cycle: u64) -> u64 {
+    cycle * VGM_RATE / DMG_CLOCK
+}
+
+/// Write the commands to wait the given number of samples.
+fn write_wait(data: &mut Vec<u8>, mut samples: u64) {
+    while samples > 0 {
+        let wait = samples.min(0xFFFF);
+
+        match wait {
+            735 => data.push(0x62),
+            882 => data.push(0x63),
+            1..=16 => data.push(0x6F + wait as u8),
+
+            _ => {
+                data.push(0x61);
+                data.extend_from_slice(&(wait as u16).to_le_bytes());
+            }
+        }
+
+        samples -= wait;
+    }
+}
+
+/// Encode a log of APU register writes as a VGM file.
+pub fn encode_vgm(log: &AudioLog) -> Vec<u8> {
+    let mut data = vec![0; DATA_OFFSET];
+    let mut position = 0;
+
+    for write in &log.writes {
+        let sample = sample_at(write.cycle);
+
+        write_wait(&mut data, sample - position);
+        position = sample;
+
+        // Registers are numbered from NR10.
+        data.extend_from_slice(&[0xB3, (write.addr - 0xFF10) as u8, write.value]);
+    }
+
+    let total_samples = sample_at(log.cycles);
+
+    write_wait(&mut data, total_samples - position);
+    data.push(0x66);
+
+    let eof_offset = (data.len() - 0x04) as u32;
+
+    let mut header = |offset: usize, value: u32| {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    };
+
+    header(0x00, u32::from_le_bytes(*b"Vgm "));
+    header(0x04, eof_offset);
+    header(0x08, 0x161);
+    header(0x18, total_samples as u32);
+    header(0x34, (DATA_OFFSET - 0x34) as u32);
+    header(0x80, DMG_CLOCK as u32);
+
+    data
+}
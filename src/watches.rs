@@ -0,0 +1,237 @@
+//! Watch expressions, stored next to the ROM in `.watch` files, whose
+//! values are shown on top of the screen every frame.
+//!
+//! Every line holds an address or a label, a type and an optional name,
+//! for example `wPlayerHP u16 HP` or `C0A0 string8`. Labels are looked
+//! up in the `.sym` file next to the ROM, as written by RGBDS. The types
+//! are `u8`, `u16` (little endian), `bcd` followed by the number of
+//! bytes (most significant first, `bcd` alone is one byte) and `string`
+//! followed by the number of characters. Values are read from the banks
+//! that are mapped at the time. Empty lines and lines starting with `#`
+//! are ignored.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use argentum_core::Argentum;
+
+/// How the bytes at the address of a watch are shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchType {
+    U8,
+    U16,
+
+    /// Binary coded decimal, this many bytes long.
+    Bcd(u8),
+
+    /// ASCII text, this many characters long.
+    String(u8),
+}
+
+impl FromStr for WatchType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The length of BCD numbers and strings follows the name.
+        let length = |name: &str| -> Result<u8, ()> {
+            match &s[name.len()..] {
+                "" if name == "bcd" => Ok(1),
+                length => length.parse().ok().filter(|&len| len != 0).ok_or(()),
+            }
+        };
+
+        match s {
+            "u8" => Ok(WatchType::U8),
+            "u16" => Ok(WatchType::U16),
+            _ if s.starts_with("bcd") => Ok(WatchType::Bcd(length("bcd")?)),
+            _ if s.starts_with("string") => Ok(WatchType::String(length("string")?)),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A single watch expression.
+pub struct Watch {
+    /// The name shown next to the value.
+    pub name: String,
+
+    pub address: u16,
+
+    pub kind: WatchType,
+}
+
+impl Watch {
+    /// Read and format the current value.
+    pub fn value(&self, gb: &Argentum) -> String {
+        let byte = |offset: u16| gb.peek_byte(self.address.wrapping_add(offset));
+
+        match self.kind {
+            WatchType::U8 => format!("{} ({:02X})", byte(0), byte(0)),
+
+            WatchType::U16 => {
+                let value = u16::from_le_bytes([byte(0), byte(1)]);
+
+                format!("{} ({:04X})", value, value)
+            }
+
+            WatchType::Bcd(length) => (0..length as u16)
+                .map(|offset| format!("{:02X}", byte(offset)))
+                .collect(),
+
+            WatchType::String(length) => (0..length as u16)
+                .map(|offset| match byte(offset) {
+                    value @ 0x20..=0x7E => value as char,
+                    _ => '.',
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Read the labels of an RGBDS `.sym` file, lines like `01:4000 Label`.
+/// A missing file has no labels.
+pub fn load_symbols(path: &Path) -> HashMap<String, u16> {
+    let contents = std::fs::read_to_string(path).unwrap_or_default();
+
+    let mut symbols = HashMap::new();
+
+    for line in contents.lines() {
+        // Comments start with a semicolon.
+        let line = line.split(';').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+
+        let (location, label) = match (fields.next(), fields.next()) {
+            (Some(location), Some(label)) => (location, label),
+            _ => continue,
+        };
+
+        let address = location
+            .split_once(':')
+            .and_then(|(_, address)| u16::from_str_radix(address, 16).ok());
+
+        if let Some(address) = address {
+            symbols.insert(label.to_owned(), address);
+        }
+    }
+
+    symbols
+}
+
+/// The watch expressions of a single game.
+pub struct Watches {
+    watches: Vec<Watch>,
+
+    /// The values as of the last update.
+    values: Vec<String>,
+}
+
+impl Watches {
+    /// Create a new `Watches` instance, loading the watch expressions
+    /// stored at the given path. Labels are resolved with `symbols`.
+    pub fn load(path: &Path, symbols: &HashMap<String, u16>) -> Self {
+        let contents = std::fs::read_to_string(path).unwrap_or_default();
+
+        let mut watches = Vec::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+
+            let location = fields.next().unwrap_or_default();
+
+            let address = match symbols.get(location) {
+                Some(&address) => address,
+
+                None => match u16::from_str_radix(location, 16) {
+                    Ok(address) => address,
+
+                    Err(_) => {
+                        eprintln!(
+                            "Unknown watch address \"{}\" on line {}.",
+                            location,
+                            number + 1
+                        );
+                        continue;
+                    }
+                },
+            };
+
+            let kind = match fields.next().map(str::parse) {
+                Some(Ok(kind)) => kind,
+
+                _ => {
+                    eprintln!("Missing or invalid watch type on line {}.", number + 1);
+                    continue;
+                }
+            };
+
+            let name = fields.collect::<Vec<_>>().join(" ");
+
+            // Addresses without a name are named after their label, if they have one.
+            let name = if !name.is_empty() {
+                name
+            } else {
+                symbols
+                    .iter()
+                    .filter(|&(_, &symbol)| symbol == address)
+                    .map(|(label, _)| label.clone())
+                    .min()
+                    .unwrap_or_else(|| location.to_owned())
+            };
+
+            watches.push(Watch {
+                name,
+                address,
+                kind,
+            });
+        }
+
+        Self {
+            values: vec![String::new(); watches.len()],
+            watches,
+        }
+    }
+
+    /// Get the path the watch expressions are stored at for the given ROM.
+    pub fn path_for_rom(rom_path: &Path) -> PathBuf {
+        rom_path.with_extension("watch")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    /// Read the values again, returning the lines of
+    /// the watches whose value changed since.
+    pub fn update(&mut self, gb: &Argentum) -> Vec<String> {
+        let mut changed = Vec::new();
+
+        for (watch, value) in self.watches.iter().zip(self.values.iter_mut()) {
+            let new_value = watch.value(gb);
+
+            if *value != new_value {
+                *value = new_value;
+                changed.push(format!("{}: {}", watch.name, value));
+            }
+        }
+
+        changed
+    }
+
+    /// Get the lines to show, as of the last update.
+    pub fn lines(&self) -> Vec<String> {
+        self.watches
+            .iter()
+            .zip(self.values.iter())
+            .map(|(watch, value)| format!("{}: {}", watch.name, value))
+            .collect()
+    }
+}
@@ -0,0 +1,567 @@
+//! The parts of the WebSocket protocol (RFC 6455) the control server
+//! speaks, the opening handshake and reading and writing messages.
+//!
+//! Extensions and subprotocols aren't supported, and messages
+//! are sent unfragmented. Pings are answered while reading.
+
+use std::io::{self, BufRead, ErrorKind, Read, Write};
+
+/// Appended to the client's key to compute the accept key.
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Messages are save states at most, anything longer is garbage.
+const MAX_MESSAGE: usize = 4 * 1024 * 1024;
+
+/// Handshakes with longer headers are refused.
+const MAX_HEADER: usize = 8 * 1024;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Hash data with SHA-1, which the handshake uses.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [
+        0x6745_2301,
+        0xEFCD_AB89,
+        0x98BA_DCFE,
+        0x1032_5476,
+        0xC3D2_E1F0,
+    ];
+
+    // Pad to a multiple of 64 bytes, ending with the length in bits.
+    let mut message = data.to_vec();
+
+    message.push(0x80);
+
+    while message.len() % 64 != 56 {
+        message.push(0x00);
+    }
+
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (value, new) in state.iter_mut().zip([a, b, c, d, e].iter()) {
+            *value = value.wrapping_add(*new);
+        }
+    }
+
+    let mut hash = [0; 20];
+
+    for (bytes, value) in hash.chunks_mut(4).zip(state.iter()) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+
+    hash
+}
+
+/// Encode data as base64, with padding.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - i * 6)) as usize & 0x3F] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Get the key the server answers the client's `Sec-WebSocket-Key` with.
+fn accept_key(key: &str) -> String {
+    base64(&sha1(
+        format!("{}{}", key.trim(), HANDSHAKE_GUID).as_bytes(),
+    ))
+}
+
+/// Check if an `Origin` is a page served from this machine. Browsers
+/// send one with every handshake, scripts and tools usually don't.
+fn is_local_origin(origin: &str) -> bool {
+    let origin = origin.trim().to_ascii_lowercase();
+
+    let host = match origin.split_once("://") {
+        Some(("http", host)) | Some(("https", host)) => host,
+        _ => return false,
+    };
+
+    // Leave out the port, IPv6 addresses are in brackets.
+    let host = match host.strip_prefix('[') {
+        Some(host) => host.split(']').next().unwrap_or(""),
+        None => host.split(':').next().unwrap_or(""),
+    };
+
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+/// Read the client's opening handshake and accept it. Anything but
+/// a WebSocket upgrade request, or one from a web page served from
+/// another machine, is answered with an error.
+pub fn accept(reader: &mut impl BufRead, writer: &mut impl Write) -> io::Result<()> {
+    let mut request_line = String::new();
+    let mut key = None;
+    let mut origin = None;
+    let mut upgrade = false;
+    let mut header_len = 0;
+
+    reader.read_line(&mut request_line)?;
+
+    loop {
+        let mut line = String::new();
+
+        header_len += reader.read_line(&mut line)?;
+
+        if header_len > MAX_HEADER {
+            return Err(invalid("the handshake is too long"));
+        }
+
+        let line = line.trim_end();
+
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.trim();
+
+            if name.eq_ignore_ascii_case("upgrade") {
+                upgrade = value.trim().eq_ignore_ascii_case("websocket");
+            } else if name.eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.trim().to_string());
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    // Any page open in a browser could drive the emulator otherwise.
+    if origin.is_some_and(|origin| !is_local_origin(&origin)) {
+        write!(
+            writer,
+            "HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )?;
+
+        return Err(invalid("the handshake comes from another origin"));
+    }
+
+    match key {
+        Some(key) if upgrade && request_line.starts_with("GET ") => write!(
+            writer,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+             Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept_key(&key)
+        ),
+
+        _ => {
+            write!(
+                writer,
+                "HTTP/1.1 426 Upgrade Required\r\nUpgrade: websocket\r\n\
+                 Content-Length: 0\r\nConnection: close\r\n\r\n"
+            )?;
+
+            Err(invalid("not a WebSocket handshake"))
+        }
+    }
+}
+
+fn write_frame(writer: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut header = vec![0x80 | opcode];
+
+    match payload.len() {
+        len @ 0..=125 => header.push(len as u8),
+        len @ 126..=0xFFFF => {
+            header.push(126);
+            header.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            header.push(127);
+            header.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+
+    writer.write_all(&header)?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+/// Read a frame, returning whether it's the last of its
+/// message, its opcode and the unmasked payload.
+fn read_frame(reader: &mut impl Read) -> io::Result<(bool, u8, Vec<u8>)> {
+    let mut header = [0; 2];
+
+    reader.read_exact(&mut header)?;
+
+    let fin = (header[0] & 0x80) != 0;
+    let opcode = header[0] & 0x0F;
+
+    // Clients have to mask every frame they send.
+    if (header[1] & 0x80) == 0 {
+        return Err(invalid("the client sent an unmasked frame"));
+    }
+
+    let len = match header[1] & 0x7F {
+        126 => {
+            let mut len = [0; 2];
+
+            reader.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+
+        127 => {
+            let mut len = [0; 8];
+
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+
+        len => len as u64,
+    };
+
+    if len > MAX_MESSAGE as u64 {
+        return Err(invalid("the message is too long"));
+    }
+
+    let mut mask = [0; 4];
+    let mut payload = vec![0; len as usize];
+
+    reader.read_exact(&mut mask)?;
+    reader.read_exact(&mut payload)?;
+
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    Ok((fin, opcode, payload))
+}
+
+/// Read the next message, answering pings on the way. Returns
+/// `None` once the client closed the connection.
+pub fn read_message(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+) -> io::Result<Option<Message>> {
+    let mut kind = None;
+    let mut message = Vec::new();
+
+    loop {
+        let (fin, opcode, payload) = read_frame(reader)?;
+
+        match opcode {
+            // Echo the status code, if there is one.
+            OPCODE_CLOSE => {
+                write_frame(writer, OPCODE_CLOSE, &payload[..payload.len().min(2)])?;
+
+                return Ok(None);
+            }
+
+            OPCODE_PING => {
+                write_frame(writer, OPCODE_PONG, &payload)?;
+                continue;
+            }
+
+            OPCODE_PONG => continue,
+
+            OPCODE_TEXT | OPCODE_BINARY if kind.is_none() => {
+                kind = Some(opcode);
+                message = payload;
+            }
+
+            OPCODE_CONTINUATION if kind.is_some() => {
+                if message.len() + payload.len() > MAX_MESSAGE {
+                    return Err(invalid("the message is too long"));
+                }
+
+                message.extend_from_slice(&payload);
+            }
+
+            _ => return Err(invalid("unexpected frame")),
+        }
+
+        if fin {
+            break;
+        }
+    }
+
+    if kind == Some(OPCODE_TEXT) {
+        let text = String::from_utf8(message).map_err(|_| invalid("the text isn't UTF-8"))?;
+
+        Ok(Some(Message::Text(text)))
+    } else {
+        Ok(Some(Message::Binary(message)))
+    }
+}
+
+/// Send a message in a single frame.
+pub fn write_message(writer: &mut impl Write, message: &Message) -> io::Result<()> {
+    match message {
+        Message::Text(text) => write_frame(writer, OPCODE_TEXT, text.as_bytes()),
+        Message::Binary(bytes) => write_frame(writer, OPCODE_BINARY, bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Turn a frame the server wrote into one a client would
+    /// send, masked with the key 0x37FA213D.
+    fn masked(frame: &[u8]) -> Vec<u8> {
+        let mask = [0x37, 0xFA, 0x21, 0x3D];
+
+        let header_len = match frame[1] {
+            126 => 4,
+            127 => 10,
+            _ => 2,
+        };
+
+        let mut masked = frame[..header_len].to_vec();
+
+        masked[1] |= 0x80;
+        masked.extend_from_slice(&mask);
+        masked.extend(
+            frame[header_len..]
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask[i % 4]),
+        );
+
+        masked
+    }
+
+    fn handshake(origin: Option<&str>) -> (io::Result<()>, String) {
+        let mut request = String::from(
+            "GET /chat HTTP/1.1\r\nHost: server.example.com\r\nUpgrade: websocket\r\n\
+             Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+             Sec-WebSocket-Version: 13\r\n",
+        );
+
+        if let Some(origin) = origin {
+            request.push_str(&format!("Origin: {}\r\n", origin));
+        }
+
+        request.push_str("\r\n");
+
+        let mut response = Vec::new();
+        let result = accept(&mut request.as_bytes(), &mut response);
+
+        (result, String::from_utf8(response).unwrap())
+    }
+
+    #[test]
+    fn sha1_known_hashes() {
+        let hex = |hash: [u8; 20]| -> String {
+            hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+        };
+
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+
+        // Two blocks, the padding doesn't fit in the first.
+        assert_eq!(
+            hex(sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+    }
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    /// The example from RFC 6455, section 1.3.
+    #[test]
+    fn accept_key_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+
+        let (result, response) = handshake(None);
+
+        assert!(result.is_ok());
+        assert!(response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+    }
+
+    #[test]
+    fn accepts_local_origins_only() {
+        for origin in &[
+            "http://localhost:8080",
+            "http://127.0.0.1",
+            "https://[::1]:3000",
+        ] {
+            assert!(handshake(Some(origin)).0.is_ok(), "{}", origin);
+        }
+
+        for origin in &[
+            "https://example.com",
+            "http://localhost.example.com",
+            "http://127.0.0.1.example.com",
+            "null",
+        ] {
+            let (result, response) = handshake(Some(origin));
+
+            assert!(result.is_err(), "{}", origin);
+            assert!(response.starts_with("HTTP/1.1 403"));
+        }
+    }
+
+    #[test]
+    fn refuses_plain_http() {
+        let mut response = Vec::new();
+        let request = "GET /status HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        assert!(accept(&mut request.as_bytes(), &mut response).is_err());
+        assert!(response.starts_with(b"HTTP/1.1 426"));
+    }
+
+    /// Payloads that need the 7-bit, 16-bit and 64-bit lengths.
+    #[test]
+    fn frame_round_trip() {
+        for &len in &[0, 125, 126, 0xFFFF, 0x10000, 300_000] {
+            let payload: Vec<u8> = (0..len).map(|i| (i * 7) as u8).collect();
+            let mut frame = Vec::new();
+
+            write_message(&mut frame, &Message::Binary(payload.clone())).unwrap();
+
+            let header_len = match len {
+                0..=125 => 2,
+                126..=0xFFFF => 4,
+                _ => 10,
+            };
+
+            assert_eq!(frame.len(), header_len + len);
+
+            let mut replies = Vec::new();
+
+            match read_message(&mut masked(&frame).as_slice(), &mut replies).unwrap() {
+                Some(Message::Binary(read)) => assert_eq!(read, payload),
+                _ => panic!("expected a binary message of {} bytes", len),
+            }
+        }
+    }
+
+    #[test]
+    fn fragments_and_pings() {
+        let mut frames = Vec::new();
+        let mut sent = Vec::new();
+
+        // A text message in two frames, with a ping between them.
+        write_frame(&mut sent, OPCODE_TEXT, b"sta").unwrap();
+        sent[0] &= 0x7F;
+        frames.extend(masked(&sent));
+
+        for (opcode, payload) in &[(OPCODE_PING, &b"hi"[..]), (OPCODE_CONTINUATION, b"tus")] {
+            sent.clear();
+            write_frame(&mut sent, *opcode, payload).unwrap();
+            frames.extend(masked(&sent));
+        }
+
+        let mut reader = frames.as_slice();
+        let mut replies = Vec::new();
+
+        match read_message(&mut reader, &mut replies).unwrap() {
+            Some(Message::Text(text)) => assert_eq!(text, "status"),
+            _ => panic!("expected a text message"),
+        }
+
+        assert_eq!(replies, [0x80 | OPCODE_PONG, 2, b'h', b'i']);
+    }
+
+    #[test]
+    fn close_is_echoed() {
+        let mut sent = Vec::new();
+        let mut replies = Vec::new();
+
+        write_frame(&mut sent, OPCODE_CLOSE, &[0x03, 0xE8]).unwrap();
+
+        let message = read_message(&mut masked(&sent).as_slice(), &mut replies).unwrap();
+
+        assert!(message.is_none());
+        assert_eq!(replies, [0x80 | OPCODE_CLOSE, 2, 0x03, 0xE8]);
+    }
+
+    #[test]
+    fn refuses_unmasked_and_oversized_frames() {
+        let mut frame = Vec::new();
+        let mut replies = Vec::new();
+
+        write_frame(&mut frame, OPCODE_TEXT, b"status").unwrap();
+        assert!(read_message(&mut frame.as_slice(), &mut replies).is_err());
+
+        // A 64-bit length past the limit, without the payload.
+        let mut frame = vec![0x80 | OPCODE_BINARY, 0x80 | 127];
+
+        frame.extend_from_slice(&u64::MAX.to_be_bytes());
+        frame.extend_from_slice(&[0; 4]);
+        assert!(read_message(&mut frame.as_slice(), &mut replies).is_err());
+    }
+}